@@ -0,0 +1,93 @@
+//! Evaluates one graph on a batch of witnesses twice: with `evaluate_batch`
+//! along a compiled `EvalPlan`, and with `fill_nodes` on the graph's own node
+//! map, one witness after the other. Both produce every node's value for
+//! every witness, the results are checked to agree, and logging stays off.
+//!
+//! Run with `cargo bench --bench eval`.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use succint_graph::{Builder, EvalOrder, Node, NodeId};
+
+const INPUTS: usize = 16;
+const NODES: usize = 20_000;
+const WITNESSES: u32 = 200;
+const ROUNDS: usize = 5;
+
+fn build() -> (Builder, Vec<Node>, Node) {
+    let mut builder = Builder::new();
+    builder.set_eval_order(EvalOrder::Topological);
+    let inputs: Vec<Node> = (0..INPUTS).map(|_| builder.init()).collect();
+    let mut nodes = inputs.clone();
+    for i in 0..NODES {
+        let a = nodes[(i * 7919) % nodes.len()];
+        let b = nodes[nodes.len() - 1 - (i * 104_729) % nodes.len().min(64)];
+        let node = if i % 3 == 0 {
+            builder.mul(&a, &b)
+        } else {
+            builder.add(&a, &b)
+        };
+        nodes.push(node);
+    }
+    let last = *nodes.last().unwrap();
+    (builder, inputs, last)
+}
+
+/// Fastest of `ROUNDS` runs of `f`, with its last result.
+fn time<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        let value = black_box(f());
+        best = best.min(start.elapsed());
+        result = Some(value);
+    }
+    (best, result.unwrap())
+}
+
+fn main() {
+    let (mut graph, inputs, last) = build();
+    let batch: Vec<HashMap<NodeId, u32>> = (0..WITNESSES)
+        .map(|w| {
+            inputs
+                .iter()
+                .enumerate()
+                .map(|(i, n)| (n.id, w * 31 + i as u32))
+                .collect()
+        })
+        .collect();
+    let plan = graph.eval_plan();
+
+    let (batched, expected) = time(|| graph.evaluate_batch(&plan, &batch).unwrap());
+    let (filled, results) = time(|| {
+        batch
+            .iter()
+            .map(|inputs| {
+                graph.fill_nodes(inputs.clone());
+                plan.order()
+                    .iter()
+                    .map(|id| graph.value(*id))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(results, expected);
+    let position = plan.index_of(last.id).unwrap();
+    assert!(expected.iter().all(|values| values[position].is_some()));
+
+    eprintln!(
+        "{} nodes x {} witnesses, best of {}",
+        INPUTS + NODES,
+        WITNESSES,
+        ROUNDS
+    );
+    eprintln!("evaluate_batch: {:?}", batched);
+    eprintln!("fill_nodes:     {:?}", filled);
+    eprintln!(
+        "fill_nodes / evaluate_batch: {:.2}x",
+        filled.as_secs_f64() / batched.as_secs_f64()
+    );
+}
@@ -0,0 +1,45 @@
+//! Compares Barrett reduction in `Modulus::mul` with a plain `%` after every
+//! product.
+//!
+//! Run with `cargo bench --bench modulus`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use succint_graph::Modulus;
+
+const PRODUCTS: u32 = 20_000_000;
+
+fn time(f: impl FnOnce() -> u32) -> (Duration, u32) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed(), result)
+}
+
+fn main() {
+    let p = 4_294_967_291; // largest prime below 2^32
+    let modulus = Modulus::new(p).unwrap();
+
+    let (naive, expected) = time(|| {
+        let mut acc = 1u32;
+        for i in 1..=PRODUCTS {
+            let x = (u64::from(acc) * u64::from(black_box(i))) % u64::from(black_box(p));
+            acc = x as u32;
+        }
+        acc
+    });
+    let (barrett, result) = time(|| {
+        let mut acc = 1u32;
+        for i in 1..=PRODUCTS {
+            acc = black_box(modulus).mul(acc, black_box(i));
+        }
+        acc
+    });
+    assert_eq!(result, expected);
+    println!("{} products, % per op:  {:?}", PRODUCTS, naive);
+    println!("{} products, Barrett:   {:?}", PRODUCTS, barrett);
+    println!(
+        "speedup: {:.2}x",
+        naive.as_secs_f64() / barrett.as_secs_f64()
+    );
+}
@@ -0,0 +1,47 @@
+//! Generates code for optional front-ends: the C header
+//! `include/succint_graph.h` from `src/capi.rs` (feature `capi`), the gRPC
+//! server from `proto/graphengine.proto` (feature `grpc`), the graph and
+//! witness messages from `proto/graph.proto` (feature `protobuf`) and the
+//! UniFFI scaffolding from `uniffi/succint_graph.udl` (feature `uniffi`). The
+//! proto schemas are parsed with protox, so no `protoc` needs to be installed.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "capi")]
+    {
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        println!("cargo:rerun-if-changed=src/capi.rs");
+        // Only the C API module is parsed, so the rest of the crate's public
+        // items stay out of the header and `Builder` stays opaque.
+        cbindgen::Builder::new()
+            .with_config(cbindgen::Config::from_file("cbindgen.toml").expect("valid cbindgen.toml"))
+            .with_src("src/capi.rs")
+            .generate()
+            .expect("src/capi.rs can be read by cbindgen")
+            .write_to_file("include/succint_graph.h");
+    }
+
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/graphengine.proto");
+        let descriptors = protox::compile(["proto/graphengine.proto"], ["proto"])
+            .expect("proto/graphengine.proto is a valid schema");
+        tonic_build::configure()
+            .compile_fds(descriptors)
+            .expect("generating the gRPC service");
+    }
+
+    #[cfg(feature = "protobuf")]
+    {
+        println!("cargo:rerun-if-changed=proto/graph.proto");
+        let descriptors = protox::compile(["proto/graph.proto"], ["proto"])
+            .expect("proto/graph.proto is a valid schema");
+        prost_build::Config::new()
+            .compile_fds(descriptors)
+            .expect("generating the graph messages");
+    }
+
+    #[cfg(feature = "uniffi")]
+    uniffi::generate_scaffolding("uniffi/succint_graph.udl")
+        .expect("uniffi/succint_graph.udl is a valid interface");
+}
@@ -0,0 +1,136 @@
+/*
+ * C interface to the succint_graph engine. Build the library with the `capi`
+ * feature, e.g.
+ *
+ *     cargo rustc --release --lib --features capi --crate-type cdylib
+ */
+
+#ifndef SUCCINT_GRAPH_H
+#define SUCCINT_GRAPH_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/* Opaque graph builder. */
+typedef struct Builder Builder;
+
+/**
+ * Returned instead of a node id on invalid arguments.
+ */
+#define SG_INVALID_NODE UINT64_MAX
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an empty builder.
+ */
+Builder *sg_builder_new(void);
+
+/**
+ * Releases a builder created by `sg_builder_new`.
+ *
+ * # Safety
+ * `builder` must be null or a pointer from `sg_builder_new` not yet freed.
+ */
+void sg_builder_free(Builder *builder);
+
+/**
+ * Adds an input node.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+uint64_t sg_input(Builder *builder);
+
+/**
+ * Adds a constant node.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+uint64_t sg_constant(Builder *builder, uint32_t value);
+
+/**
+ * Adds `a + b`.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+uint64_t sg_add(Builder *builder, uint64_t a, uint64_t b);
+
+/**
+ * Adds `a * b`.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+uint64_t sg_mul(Builder *builder, uint64_t a, uint64_t b);
+
+/**
+ * Constrains `a == b`.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+int32_t sg_assert_equal(Builder *builder, uint64_t a, uint64_t b);
+
+/**
+ * Evaluates the graph with `len` input assignments `ids[i] = values[i]`.
+ *
+ * # Safety
+ * `builder` must be null or a live builder; `ids` and `values` must point to
+ * `len` readable elements (or may be null when `len` is 0).
+ */
+int32_t sg_fill(Builder *builder, const uint64_t *ids, const uint32_t *values, size_t len);
+
+/**
+ * Writes the value of node `id` to `out`. Returns `1` if the node has a
+ * value, `0` if it was not evaluated and `-1` for invalid arguments.
+ *
+ * # Safety
+ * `builder` must be null or a live builder; `out` must be null or writable.
+ */
+int32_t sg_value(const Builder *builder, uint64_t id, uint32_t *out);
+
+/**
+ * Returns `1` if every constraint holds, `0` if not and `-1` for a null builder.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+int32_t sg_check_constraints(const Builder *builder);
+
+/**
+ * The graph in Graphviz DOT format with values and constraints. Release with
+ * `sg_string_free`.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+char *sg_to_dot(const Builder *builder);
+
+/**
+ * The graph in S-expression format. Release with `sg_string_free`.
+ *
+ * # Safety
+ * `builder` must be null or a live builder.
+ */
+char *sg_to_sexpr(const Builder *builder);
+
+/**
+ * Releases a string returned by this library.
+ *
+ * # Safety
+ * `text` must be null or a string from this library not yet freed.
+ */
+void sg_string_free(char *text);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SUCCINT_GRAPH_H */
@@ -0,0 +1,399 @@
+//! AIR (algebraic intermediate representation) export, for handing unrolled
+//! iterative computations to STARK provers.
+//!
+//! ```text
+//! {
+//!   "modulus": "4294967296",
+//!   "width": 2,
+//!   "length": 11,
+//!   "trace": [[0, 1], [1, 2], ...],
+//!   "transitions": [{"degree": 1, "terms": [[4294967295, [0, 1, 0, 0]], [1, [0, 0, 1, 0]]]}, ...],
+//!   "boundary": [{"row": 0, "column": 0, "value": 0}, ...]
+//! }
+//! ```
+//!
+//! The trace is the table of states a state machine passes through: one
+//! column per state element, one row per step (`trace` maps each cell back to
+//! its node). Every transition constraint is a polynomial over the current
+//! row (`x0..x(w-1)`) and the next row (`x(w)..x(2w-1)`) that must vanish
+//! between every pair of consecutive rows; each term is a coefficient and one
+//! exponent per variable. Boundary constraints pin single cells. Coefficients
+//! are reduced modulo the graph's arithmetic: 2^32 when it wraps, or its
+//! modulus.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::json::Json;
+use crate::{Arithmetic, Builder, Node, NodeId, Op};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AirError {
+    EmptyTrace,
+    /// Row `row` has `width` cells but the first row has `expected`.
+    Ragged {
+        row: usize,
+        width: usize,
+        expected: usize,
+    },
+    /// A trace node that is not (or no longer) in the graph.
+    UnknownNode(NodeId),
+    /// A hint between trace cells; AIR constraints are polynomials only.
+    Hint(NodeId),
+    /// An input that a step reads but that is not a trace cell.
+    ExternalInput(NodeId),
+    /// An `Add` or `Mul` that neither wraps nor reduces modulo the same
+    /// modulus as the rest of the graph.
+    Arithmetic(NodeId),
+    /// The constraint `a == b` relates rows more than one step apart.
+    Span {
+        a: NodeId,
+        b: NodeId,
+    },
+    /// Step `step` computes a different transition than step 0.
+    NotUniform {
+        step: usize,
+    },
+}
+
+impl fmt::Display for AirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AirError::EmptyTrace => write!(f, "the trace has no rows"),
+            AirError::Ragged {
+                row,
+                width,
+                expected,
+            } => write!(
+                f,
+                "trace row {} has {} cells but the first row has {}",
+                row, width, expected
+            ),
+            AirError::UnknownNode(id) => write!(f, "trace node {:?} is not in the graph", id),
+            AirError::Hint(id) => write!(
+                f,
+                "hint {:?} cannot be written as a polynomial constraint",
+                id
+            ),
+            AirError::ExternalInput(id) => write!(
+                f,
+                "input {:?} is read by a step but is not a trace cell",
+                id
+            ),
+            AirError::Arithmetic(id) => write!(
+                f,
+                "{:?} does not use the same wrapping or modular arithmetic as the rest of the graph",
+                id
+            ),
+            AirError::Span { a, b } => write!(
+                f,
+                "constraint {:?} == {:?} relates rows more than one step apart",
+                a, b
+            ),
+            AirError::NotUniform { step } => write!(
+                f,
+                "step {} computes a different transition than step 0",
+                step
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AirError {}
+
+/// Sorted variable indices, one per degree, mapped to a nonzero coefficient.
+type Poly = BTreeMap<Vec<usize>, u64>;
+
+/// Polynomial arithmetic modulo the graph's modulus, with node values
+/// expanded in terms of trace cells.
+struct Expander<'a> {
+    graph: &'a Builder,
+    modulus: u64,
+}
+
+impl Expander<'_> {
+    fn constant(&self, value: u64) -> Poly {
+        let value = value % self.modulus;
+        if value == 0 {
+            Poly::new()
+        } else {
+            Poly::from([(Vec::new(), value)])
+        }
+    }
+
+    fn variable(var: usize) -> Poly {
+        Poly::from([(vec![var], 1)])
+    }
+
+    fn add(&self, a: &Poly, b: &Poly, scale: u64) -> Poly {
+        let mut sum = a.clone();
+        for (monomial, coeff) in b {
+            let entry = sum.entry(monomial.clone()).or_insert(0);
+            *entry = ((u128::from(*entry) + u128::from(*coeff) * u128::from(scale))
+                % u128::from(self.modulus)) as u64;
+        }
+        sum.retain(|_, coeff| *coeff != 0);
+        sum
+    }
+
+    fn sub(&self, a: &Poly, b: &Poly) -> Poly {
+        self.add(a, b, self.modulus - 1)
+    }
+
+    fn mul(&self, a: &Poly, b: &Poly) -> Poly {
+        let mut product = Poly::new();
+        for (ma, ca) in a {
+            for (mb, cb) in b {
+                let mut monomial = ma.clone();
+                monomial.extend(mb);
+                monomial.sort_unstable();
+                let term = Poly::from([(monomial, *ca)]);
+                product = self.add(&product, &term, *cb);
+            }
+        }
+        product
+    }
+
+    /// `root` as a polynomial in the variables `leaves` assigns to nodes.
+    fn expand(
+        &self,
+        root: NodeId,
+        leaves: &HashMap<NodeId, usize>,
+        memo: &mut HashMap<NodeId, Poly>,
+    ) -> Result<Poly, AirError> {
+        let mut stack = vec![(root, false)];
+        while let Some((id, ready)) = stack.pop() {
+            if memo.contains_key(&id) {
+                continue;
+            }
+            if let Some(&var) = leaves.get(&id) {
+                memo.insert(id, Self::variable(var));
+                continue;
+            }
+            let node = self.graph.nodes.get(&id).ok_or(AirError::UnknownNode(id))?;
+            let poly = match &node.op {
+                None => return Err(AirError::ExternalInput(id)),
+                Some(Op::Hint(..)) => return Err(AirError::Hint(id)),
+                Some(Op::Const(v)) => self.constant(u64::from(*v)),
+                Some(Op::Add(a, b, _) | Op::Mul(a, b, _)) if !ready => {
+                    stack.extend([(id, true), (*a, false), (*b, false)]);
+                    continue;
+                }
+                Some(Op::Add(a, b, _)) => self.add(&memo[a], &memo[b], 1),
+                Some(Op::Mul(a, b, _)) => self.mul(&memo[a], &memo[b]),
+            };
+            memo.insert(id, poly);
+        }
+        Ok(memo[&root].clone())
+    }
+
+    /// `(var, value)` if `poly` only says that one variable equals a
+    /// constant.
+    fn as_boundary(&self, poly: &Poly) -> Option<(usize, u64)> {
+        let mut terms = poly.iter().filter(|(m, _)| !m.is_empty());
+        let (monomial, coeff) = terms.next()?;
+        if terms.next().is_some() || monomial.len() != 1 {
+            return None;
+        }
+        let constant = poly.get(&Vec::new()).copied().unwrap_or(0);
+        let value = if *coeff == 1 {
+            (self.modulus - constant) % self.modulus
+        } else if *coeff == self.modulus - 1 {
+            constant
+        } else {
+            return None;
+        };
+        Some((monomial[0], value))
+    }
+}
+
+/// `poly` with every variable moved back by `offset`.
+fn shift(poly: Poly, offset: usize) -> Poly {
+    poly.into_iter()
+        .map(|(m, c)| (m.into_iter().map(|v| v - offset).collect(), c))
+        .collect()
+}
+
+fn poly_json(poly: &Poly, vars: usize) -> Json {
+    let degree = poly.keys().map(Vec::len).max().unwrap_or(0);
+    let terms = poly
+        .iter()
+        .map(|(monomial, coeff)| {
+            let mut exponents = vec![0; vars];
+            for var in monomial {
+                exponents[*var] += 1;
+            }
+            Json::Array(vec![
+                Json::Int(i128::from(*coeff)),
+                Json::Array(exponents.into_iter().map(Json::Int).collect()),
+            ])
+        })
+        .collect();
+    Json::Object(vec![
+        ("degree".to_string(), Json::Int(degree as i128)),
+        ("terms".to_string(), Json::Array(terms)),
+    ])
+}
+
+impl Builder {
+    /// The modulus shared by every `Add` and `Mul`: 2^32 for wrapping
+    /// arithmetic, or the modulus of modular arithmetic.
+    fn air_modulus(&self) -> Result<u64, AirError> {
+        let mut modulus = None;
+        for node in self.nodes.values() {
+            let mode = match &node.op {
+                Some(Op::Add(_, _, mode) | Op::Mul(_, _, mode)) => mode.or(self.arithmetic),
+                _ => continue,
+            };
+            let m = match mode {
+                Arithmetic::Inherit | Arithmetic::Wrapping => 1u64 << 32,
+                Arithmetic::Modular(m) => u64::from(m.get()),
+                Arithmetic::Checked | Arithmetic::Saturating => {
+                    return Err(AirError::Arithmetic(node.id))
+                }
+            };
+            if *modulus.get_or_insert(m) != m {
+                return Err(AirError::Arithmetic(node.id));
+            }
+        }
+        Ok(modulus.unwrap_or(1 << 32))
+    }
+
+    /// Encodes the execution trace `trace` (as returned by `state_machine`:
+    /// one state per row) as an AIR in JSON. Each step must compute its next
+    /// state from the current one with the same polynomials, without hints
+    /// or inputs outside the trace. Constraints between cells of one row or
+    /// of consecutive rows become transition constraints; constraints fixing
+    /// a single cell, and constant cells of the first row, become boundary
+    /// constraints.
+    pub fn to_air(&self, trace: &[Vec<Node>]) -> Result<String, AirError> {
+        let width = trace.first().ok_or(AirError::EmptyTrace)?.len();
+        for (row, cells) in trace.iter().enumerate() {
+            if cells.len() != width {
+                return Err(AirError::Ragged {
+                    row,
+                    width: cells.len(),
+                    expected: width,
+                });
+            }
+            if let Some(node) = cells.iter().find(|n| !self.is_live(n)) {
+                return Err(AirError::UnknownNode(node.id));
+            }
+        }
+        let expander = Expander {
+            graph: self,
+            modulus: self.air_modulus()?,
+        };
+        let steps = trace.len() - 1;
+
+        // Constraints, in terms of the first cell holding each node, grouped
+        // by the step whose rows they relate.
+        let mut cells: HashMap<NodeId, usize> = HashMap::new();
+        for (row, nodes) in trace.iter().enumerate() {
+            for (col, node) in nodes.iter().enumerate() {
+                cells.entry(node.id).or_insert(row * width + col);
+            }
+        }
+        let mut boundary: Vec<(usize, usize, u64)> = trace[0]
+            .iter()
+            .enumerate()
+            .filter_map(|(col, node)| match self.nodes[&node.id].op {
+                Some(Op::Const(v)) => Some((0, col, u64::from(v) % expander.modulus)),
+                _ => None,
+            })
+            .collect();
+        let mut anchored: Vec<Vec<Poly>> = vec![Vec::new(); steps.max(1)];
+        let mut memo = HashMap::new();
+        for &(a, b) in &self.constraints {
+            let pa = expander.expand(a, &cells, &mut memo)?;
+            let pb = expander.expand(b, &cells, &mut memo)?;
+            let poly = expander.sub(&pa, &pb);
+            if let Some((var, value)) = expander.as_boundary(&poly) {
+                boundary.push((var / width, var % width, value));
+                continue;
+            }
+            let rows = poly.keys().flatten().map(|var| var / width);
+            let first = rows.clone().min().unwrap_or(0);
+            let last = rows.max().unwrap_or(0);
+            let anchor = first.min(steps.max(1) - 1);
+            if last > anchor + 1 {
+                return Err(AirError::Span { a, b });
+            }
+            anchored[anchor].push(shift(poly, anchor * width));
+        }
+        boundary.sort_unstable();
+        boundary.dedup();
+
+        let mut transitions: Vec<Poly> = Vec::new();
+        for step in 0..steps.max(1) {
+            let mut constraints = core::mem::take(&mut anchored[step]);
+            constraints.sort();
+            constraints.dedup();
+            let mut polys = Vec::with_capacity(width + constraints.len());
+            if step < steps {
+                let leaves: HashMap<NodeId, usize> = trace[step]
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(col, node)| (node.id, col))
+                    .collect();
+                let mut memo = HashMap::new();
+                for (col, node) in trace[step + 1].iter().enumerate() {
+                    let next = expander.expand(node.id, &leaves, &mut memo)?;
+                    polys.push(expander.sub(&Expander::variable(width + col), &next));
+                }
+            }
+            polys.extend(constraints);
+            if step == 0 {
+                transitions = polys;
+            } else if polys != transitions {
+                return Err(AirError::NotUniform { step });
+            }
+        }
+
+        Ok(Json::Object(vec![
+            (
+                "modulus".to_string(),
+                Json::Str(expander.modulus.to_string()),
+            ),
+            ("width".to_string(), Json::Int(width as i128)),
+            ("length".to_string(), Json::Int(trace.len() as i128)),
+            (
+                "trace".to_string(),
+                Json::Array(
+                    trace
+                        .iter()
+                        .map(|row| {
+                            Json::Array(row.iter().map(|n| Json::Int(n.id.0 as i128)).collect())
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "transitions".to_string(),
+                Json::Array(
+                    transitions
+                        .iter()
+                        .map(|p| poly_json(p, 2 * width))
+                        .collect(),
+                ),
+            ),
+            (
+                "boundary".to_string(),
+                Json::Array(
+                    boundary
+                        .iter()
+                        .map(|(row, col, value)| {
+                            Json::Object(vec![
+                                ("row".to_string(), Json::Int(*row as i128)),
+                                ("column".to_string(), Json::Int(*col as i128)),
+                                ("value".to_string(), Json::Int(i128::from(*value))),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_pretty_string())
+    }
+}
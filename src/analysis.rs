@@ -0,0 +1,115 @@
+//! Structural analyses over the graph.
+
+use std::collections::HashMap;
+
+use crate::{Builder, Node, NodeId, Op};
+
+/// Depth information for the cone of one output node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CriticalPath {
+    /// Longest dependency chain, from a leaf (input or constant) to the output.
+    pub path: Vec<NodeId>,
+    /// Number of operations on the longest chain; leaves are at depth 0.
+    pub depth: usize,
+    /// `level_counts[d]` is the number of nodes in the cone at depth `d`. Nodes on
+    /// the same level do not depend on each other and could run in parallel.
+    pub level_counts: Vec<usize>,
+}
+
+/// What the graph lowers to as a constraint system, for estimating prover
+/// cost before exporting. Counts follow `to_r1cs`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConstraintStats {
+    /// `mul` nodes; each becomes one `A * B = C` row.
+    pub mul_gates: usize,
+    /// Equality constraints added with `assert_equal`.
+    pub equalities: usize,
+    /// Rows of the constraint system: `mul_gates + equalities`.
+    pub constraints: usize,
+    /// Highest degree of an equality constraint as a polynomial in the inputs
+    /// and hints, i.e. before products are given wires of their own. 0 without
+    /// constraints.
+    pub max_degree: usize,
+    /// Wires: the constant 1, plus one per input, product and hint.
+    pub witness_size: usize,
+}
+
+impl Builder {
+    /// Computes the longest dependency chain leading to `output` and how many
+    /// nodes of its cone sit on each depth level.
+    pub fn critical_path(&self, output: &Node) -> CriticalPath {
+        let mut levels: HashMap<NodeId, usize> = HashMap::new();
+        let mut predecessor: HashMap<NodeId, NodeId> = HashMap::new();
+        for id in self.cone_of(output.id) {
+            let operands = match self.nodes.get(&id).and_then(|n| n.op.as_ref()) {
+                Some(op) => op.operands(),
+                None => Vec::new(),
+            };
+            let deepest = operands
+                .iter()
+                .filter_map(|p| levels.get(p).map(|level| (*level, *p)))
+                .max_by_key(|(level, p)| (*level, std::cmp::Reverse(p.0)));
+            let level = match deepest {
+                Some((level, p)) => {
+                    predecessor.insert(id, p);
+                    level + 1
+                }
+                None => 0,
+            };
+            levels.insert(id, level);
+        }
+
+        let depth = levels.get(&output.id).copied().unwrap_or(0);
+        let mut path = vec![output.id];
+        while let Some(p) = predecessor.get(path.last().unwrap()) {
+            path.push(*p);
+        }
+        path.reverse();
+
+        let mut level_counts = vec![0; depth + 1];
+        for level in levels.values() {
+            level_counts[*level] += 1;
+        }
+        CriticalPath {
+            path,
+            depth,
+            level_counts,
+        }
+    }
+
+    /// Counts the gates, constraints and witness wires the graph lowers to
+    /// and the highest constraint degree.
+    pub fn constraint_stats(&self) -> ConstraintStats {
+        let mut degrees: HashMap<NodeId, usize> = HashMap::new();
+        let mut stats = ConstraintStats {
+            witness_size: 1,
+            equalities: self.constraints.len(),
+            ..ConstraintStats::default()
+        };
+        for node in self.sorted_nodes() {
+            let degree_of = |id: &NodeId| degrees.get(id).copied().unwrap_or(0);
+            let degree = match &node.op {
+                Some(Op::Const(_)) => 0,
+                Some(Op::Add(a, b, _)) => degree_of(a).max(degree_of(b)),
+                Some(Op::Mul(a, b, _)) => {
+                    stats.mul_gates += 1;
+                    stats.witness_size += 1;
+                    degree_of(a).saturating_add(degree_of(b))
+                }
+                None | Some(Op::Hint(..)) => {
+                    stats.witness_size += 1;
+                    1
+                }
+            };
+            degrees.insert(node.id, degree);
+        }
+        stats.constraints = stats.mul_gates + stats.equalities;
+        stats.max_degree = self
+            .constraints
+            .iter()
+            .map(|(a, b)| degrees[a].max(degrees[b]))
+            .max()
+            .unwrap_or(0);
+        stats
+    }
+}
@@ -0,0 +1,348 @@
+//! Automatic differentiation over `add`/`mul` graphs.
+//!
+//! Derivatives follow the graph's own arithmetic (wrapping `u32`), i.e. they are
+//! formal derivatives of the polynomial each node computes.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Builder, Node, NodeId, Op};
+
+/// A value paired with its derivative with respect to one input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dual {
+    pub value: u32,
+    pub derivative: u32,
+}
+
+impl Dual {
+    fn add(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value.wrapping_add(other.value),
+            derivative: self.derivative.wrapping_add(other.derivative),
+        }
+    }
+
+    fn mul(self, other: Dual) -> Dual {
+        Dual {
+            value: self.value.wrapping_mul(other.value),
+            derivative: self
+                .value
+                .wrapping_mul(other.derivative)
+                .wrapping_add(self.derivative.wrapping_mul(other.value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffError {
+    /// The output depends on a node whose derivative is unknown (e.g. a hint).
+    NonDifferentiable(NodeId),
+    /// The node to differentiate with respect to is not an input.
+    NotAnInput(NodeId),
+    /// An input in the output's cone has no value.
+    MissingInput(NodeId),
+    /// An op refers to a node that does not exist.
+    UnknownNode(NodeId),
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::NonDifferentiable(id) => write!(f, "Node {:?} is not differentiable", id),
+            DiffError::NotAnInput(id) => write!(f, "Node {:?} is not an input", id),
+            DiffError::MissingInput(id) => write!(f, "input Node {:?} has no value", id),
+            DiffError::UnknownNode(id) => write!(f, "Node {:?} does not exist", id),
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+impl Builder {
+    /// Evaluates `output` and its derivative with respect to the input `wrt` at
+    /// the point given by `inputs`, using dual numbers. The builder itself is not
+    /// modified.
+    pub fn derive_forward(
+        &self,
+        output: &Node,
+        wrt: &Node,
+        inputs: &HashMap<NodeId, u32>,
+    ) -> Result<Dual, DiffError> {
+        match self.nodes.get(&wrt.id) {
+            Some(node) if node.op.is_none() => {}
+            Some(_) => return Err(DiffError::NotAnInput(wrt.id)),
+            None => return Err(DiffError::UnknownNode(wrt.id)),
+        }
+        let mut duals: HashMap<NodeId, Dual> = HashMap::new();
+        for id in self.cone_of(output.id) {
+            let node = self.nodes.get(&id).ok_or(DiffError::UnknownNode(id))?;
+            let get = |x: &NodeId| duals.get(x).copied().ok_or(DiffError::UnknownNode(*x));
+            let dual = match &node.op {
+                None => {
+                    let value = inputs
+                        .get(&id)
+                        .copied()
+                        .ok_or(DiffError::MissingInput(id))?;
+                    Dual {
+                        value,
+                        derivative: u32::from(id == wrt.id),
+                    }
+                }
+                Some(Op::Const(v)) => Dual {
+                    value: *v,
+                    derivative: 0,
+                },
+                Some(Op::Add(a, b, _)) => get(a)?.add(get(b)?),
+                Some(Op::Mul(a, b, _)) => get(a)?.mul(get(b)?),
+                Some(Op::Hint(_, _)) => return Err(DiffError::NonDifferentiable(id)),
+            };
+            duals.insert(id, dual);
+        }
+        duals
+            .get(&output.id)
+            .copied()
+            .ok_or(DiffError::UnknownNode(output.id))
+    }
+}
+
+/// Supplies derivatives for a hint so reverse-mode differentiation can pass
+/// through it.
+pub trait Differentiable {
+    /// Given the hint's operands and result as nodes of the gradient graph,
+    /// builds and returns one node per operand holding `∂hint/∂operand`.
+    fn partials(&self, builder: &mut Builder, operands: &[Node], result: &Node) -> Vec<Node>;
+}
+
+impl<F> Differentiable for F
+where
+    F: Fn(&mut Builder, &[Node], &Node) -> Vec<Node>,
+{
+    fn partials(&self, builder: &mut Builder, operands: &[Node], result: &Node) -> Vec<Node> {
+        self(builder, operands, result)
+    }
+}
+
+/// Derivatives for named hints, looked up by `Builder::gradient_with`.
+#[derive(Default)]
+pub struct HintDerivatives {
+    derivatives: HashMap<String, Box<dyn Differentiable>>,
+}
+
+impl HintDerivatives {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the derivative of the hint named `name` (see `Builder::hint_named`).
+    pub fn register(&mut self, name: &str, derivative: impl Differentiable + 'static) {
+        self.derivatives
+            .insert(name.to_string(), Box::new(derivative));
+    }
+}
+
+/// A graph computing a value together with its gradient, built by
+/// `Builder::gradient`.
+pub struct Gradient {
+    pub graph: Builder,
+    /// Node in `graph` for every node of the original graph that was copied,
+    /// including all inputs listed in `wrt`.
+    pub node_map: HashMap<NodeId, NodeId>,
+    /// `∂output/∂wrt[i]` as nodes of `graph`, in the order of `wrt`.
+    pub gradients: Vec<NodeId>,
+}
+
+impl Builder {
+    /// Builds a new graph that computes `output` and `∂output/∂input` for every
+    /// input in `wrt`, by reverse-mode accumulation over `add`/`mul`.
+    /// Fails with `NonDifferentiable` if the output depends on a hint; use
+    /// `gradient_with` to provide hint derivatives.
+    pub fn gradient(&self, output: &Node, wrt: &[Node]) -> Result<Gradient, DiffError> {
+        self.gradient_with(output, wrt, &HintDerivatives::new())
+    }
+
+    /// Like `gradient`, with derivatives for named hints.
+    pub fn gradient_with(
+        &self,
+        output: &Node,
+        wrt: &[Node],
+        derivatives: &HintDerivatives,
+    ) -> Result<Gradient, DiffError> {
+        for input in wrt {
+            match self.nodes.get(&input.id) {
+                Some(node) if node.op.is_none() => {}
+                Some(_) => return Err(DiffError::NotAnInput(input.id)),
+                None => return Err(DiffError::UnknownNode(input.id)),
+            }
+        }
+        let cone = self.cone_of(output.id);
+        let mut g = Builder::new();
+        let mut primal: HashMap<NodeId, Node> = HashMap::new();
+        for id in &cone {
+            let node = self.nodes.get(id).ok_or(DiffError::UnknownNode(*id))?;
+            let new_node = match &node.op {
+                None => g.init(),
+                Some(Op::Const(v)) => g.constant(*v),
+                Some(Op::Add(a, b, _)) => g.add(&primal[a], &primal[b]),
+                Some(Op::Mul(a, b, _)) => g.mul(&primal[a], &primal[b]),
+                Some(Op::Hint(parents, f)) => {
+                    let ids = parents.iter().map(|p| primal[p].id).collect();
+                    g.new_node(Some(Op::Hint(ids, f.clone())))
+                }
+            };
+            primal.insert(*id, new_node);
+        }
+        for input in wrt {
+            primal.entry(input.id).or_insert_with(|| g.init());
+        }
+
+        let seed = g.constant(1);
+        let mut adjoints: HashMap<NodeId, Node> = HashMap::new();
+        adjoints.insert(output.id, seed);
+        for id in cone.iter().rev() {
+            let adjoint = match adjoints.get(id) {
+                Some(a) => *a,
+                None => continue,
+            };
+            let op = self.nodes[id].op.clone();
+            let contributions: Vec<(NodeId, Node)> = match &op {
+                None | Some(Op::Const(_)) => Vec::new(),
+                Some(Op::Add(a, b, _)) => vec![(*a, adjoint), (*b, adjoint)],
+                Some(Op::Mul(a, b, _)) => {
+                    let da = scale(&mut g, &adjoint, &primal[b], &seed);
+                    let db = scale(&mut g, &adjoint, &primal[a], &seed);
+                    vec![(*a, da), (*b, db)]
+                }
+                Some(Op::Hint(parents, f)) => {
+                    let derivative = f
+                        .name()
+                        .and_then(|name| derivatives.derivatives.get(name))
+                        .ok_or(DiffError::NonDifferentiable(*id))?;
+                    let operands: Vec<Node> = parents.iter().map(|p| primal[p]).collect();
+                    let partials = derivative.partials(&mut g, &operands, &primal[id]);
+                    if partials.len() != parents.len() {
+                        return Err(DiffError::NonDifferentiable(*id));
+                    }
+                    parents
+                        .iter()
+                        .zip(partials)
+                        .map(|(p, d)| (*p, scale(&mut g, &adjoint, &d, &seed)))
+                        .collect()
+                }
+            };
+            for (target, contribution) in contributions {
+                let sum = match adjoints.remove(&target) {
+                    Some(existing) => g.add(&existing, &contribution),
+                    None => contribution,
+                };
+                adjoints.insert(target, sum);
+            }
+        }
+
+        let mut gradients = Vec::with_capacity(wrt.len());
+        for input in wrt {
+            let node = match adjoints.get(&input.id) {
+                Some(node) => *node,
+                None => g.constant(0),
+            };
+            gradients.push(node.id);
+        }
+        let node_map = primal.into_iter().map(|(k, v)| (k, v.id)).collect();
+        Ok(Gradient {
+            graph: g,
+            node_map,
+            gradients,
+        })
+    }
+}
+
+/// `adjoint * factor`, skipping the multiplication when the adjoint is the seed 1.
+fn scale(g: &mut Builder, adjoint: &Node, factor: &Node, seed: &Node) -> Node {
+    if adjoint.id == seed.id {
+        *factor
+    } else {
+        g.mul(adjoint, factor)
+    }
+}
+
+/// Symbolic derivative of one node, with the trivial cases kept out of the graph.
+#[derive(Clone)]
+enum Sym {
+    Zero,
+    One,
+    Node(Node),
+}
+
+impl Builder {
+    fn sym_add(&mut self, a: Sym, b: Sym) -> Sym {
+        match (a, b) {
+            (Sym::Zero, x) | (x, Sym::Zero) => x,
+            (Sym::One, Sym::One) => Sym::Node(self.constant(2)),
+            (Sym::One, Sym::Node(n)) | (Sym::Node(n), Sym::One) => {
+                let one = self.constant(1);
+                Sym::Node(self.add(&n, &one))
+            }
+            (Sym::Node(x), Sym::Node(y)) => Sym::Node(self.add(&x, &y)),
+        }
+    }
+
+    /// `factor * d`
+    fn sym_scale(&mut self, factor: &Node, d: Sym) -> Sym {
+        match d {
+            Sym::Zero => Sym::Zero,
+            Sym::One => Sym::Node(*factor),
+            Sym::Node(n) => Sym::Node(self.mul(factor, &n)),
+        }
+    }
+
+    /// Adds nodes computing `∂output/∂wrt` to this graph and returns the
+    /// derivative node, so derivative-based constraints (e.g. monotonicity at
+    /// sample points) can be asserted alongside the original computation.
+    /// Only `Const`/`Add`/`Mul` nodes are supported in the output's cone.
+    pub fn differentiate(&mut self, output: &Node, wrt: &Node) -> Result<Node, DiffError> {
+        match self.nodes.get(&wrt.id) {
+            Some(node) if node.op.is_none() => {}
+            Some(_) => return Err(DiffError::NotAnInput(wrt.id)),
+            None => return Err(DiffError::UnknownNode(wrt.id)),
+        }
+        let cone = self.cone_of(output.id);
+        if let Some(id) = cone.iter().find(|id| {
+            matches!(
+                self.nodes.get(id).map(|n| &n.op),
+                Some(Some(Op::Hint(_, _)))
+            )
+        }) {
+            return Err(DiffError::NonDifferentiable(*id));
+        }
+        let mut derivatives: HashMap<NodeId, Sym> = HashMap::new();
+        for id in cone {
+            let node = self
+                .nodes
+                .get(&id)
+                .ok_or(DiffError::UnknownNode(id))?
+                .clone();
+            let d = match &node.op {
+                None if id == wrt.id => Sym::One,
+                None | Some(Op::Const(_)) => Sym::Zero,
+                Some(Op::Add(a, b, _)) => {
+                    let (da, db) = (derivatives[a].clone(), derivatives[b].clone());
+                    self.sym_add(da, db)
+                }
+                Some(Op::Mul(a, b, _)) => {
+                    let (da, db) = (derivatives[a].clone(), derivatives[b].clone());
+                    let (na, nb) = (self.handle_of(*a), self.handle_of(*b));
+                    let left = self.sym_scale(&nb, da);
+                    let right = self.sym_scale(&na, db);
+                    self.sym_add(left, right)
+                }
+                Some(Op::Hint(_, _)) => return Err(DiffError::NonDifferentiable(id)),
+            };
+            derivatives.insert(id, d);
+        }
+        Ok(match derivatives.remove(&output.id) {
+            Some(Sym::Node(n)) => n,
+            Some(Sym::One) => self.constant(1),
+            Some(Sym::Zero) | None => self.constant(0),
+        })
+    }
+}
@@ -0,0 +1,31 @@
+use std::process::ExitCode;
+
+use succint_graph::grpc;
+use succint_graph::service::GraphService;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:50051";
+
+/// `graphengine-grpc [ADDR]`: serves the gRPC API of
+/// `proto/graphengine.proto` on ADDR (127.0.0.1:50051 by default). Exits with
+/// 2 when the address cannot be bound or the server fails.
+fn main() -> ExitCode {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDR.to_string());
+    let result = tokio::runtime::Runtime::new().and_then(|runtime| {
+        runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            eprintln!("serving gRPC on {}", listener.local_addr()?);
+            grpc::serve(listener, GraphService::new())
+                .await
+                .map_err(std::io::Error::other)
+        })
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}: {}", addr, e);
+            ExitCode::from(2)
+        }
+    }
+}
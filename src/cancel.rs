@@ -0,0 +1,41 @@
+//! Aborting long evaluations from another thread.
+
+use core::fmt;
+use core::sync::atomic::AtomicBool;
+
+use crate::collections::HashMap;
+use crate::{Builder, EvalSummary, NodeId};
+
+/// Evaluation stopped because its cancellation flag was set. Nodes computed
+/// before that keep their values, so `witness()` returns the partial result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    /// What was computed up to the cancellation; `unresolved` lists the rest.
+    pub summary: EvalSummary,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "evaluation cancelled after {} nodes with {} unresolved",
+            self.summary.evaluated,
+            self.summary.unresolved.len()
+        )
+    }
+}
+
+impl core::error::Error for Cancelled {}
+
+impl Builder {
+    /// Like `fill_nodes`, checking `cancel` before every node and stopping
+    /// with `Cancelled` once it is set. Share the flag (e.g. through an
+    /// `Arc<AtomicBool>`) with the code that may abort the evaluation.
+    pub fn fill_nodes_cancellable(
+        &mut self,
+        inputs: HashMap<NodeId, u32>,
+        cancel: &AtomicBool,
+    ) -> Result<EvalSummary, Cancelled> {
+        self.fill_nodes_until(inputs, Some(cancel))
+    }
+}
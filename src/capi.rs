@@ -0,0 +1,235 @@
+//! C ABI for embedding the engine in C/C++ hosts (feature `capi`).
+//!
+//! `build.rs` generates the matching declarations in `include/succint_graph.h`
+//! from this file with cbindgen. Builders are opaque heap pointers owned by
+//! the caller and released with `sg_builder_free`; strings returned by the
+//! library are released with `sg_string_free`. Node ids are `uint64_t`, and
+//! functions that create nodes return `SG_INVALID_NODE` when given an unknown
+//! id or a null builder. Status codes are `0` for success and `-1` for invalid
+//! arguments.
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CString};
+use std::ptr;
+
+use crate::{Builder, DotOptions, Node, NodeId};
+
+/// Returned instead of a node id on invalid arguments.
+pub const SG_INVALID_NODE: u64 = u64::MAX;
+
+fn node(builder: &Builder, id: u64) -> Option<Node> {
+    builder.handle(NodeId(usize::try_from(id).ok()?))
+}
+
+fn into_c_string(text: String) -> *mut c_char {
+    match CString::new(text) {
+        Ok(s) => s.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+/// `builder` must be null or a live builder.
+unsafe fn binary(
+    builder: *mut Builder,
+    a: u64,
+    b: u64,
+    op: fn(&mut Builder, &Node, &Node) -> Node,
+) -> u64 {
+    let Some(builder) = builder.as_mut() else {
+        return SG_INVALID_NODE;
+    };
+    match (node(builder, a), node(builder, b)) {
+        (Some(a), Some(b)) => op(builder, &a, &b).id.0 as u64,
+        _ => SG_INVALID_NODE,
+    }
+}
+
+/// Creates an empty builder.
+#[no_mangle]
+pub extern "C" fn sg_builder_new() -> *mut Builder {
+    Box::into_raw(Box::new(Builder::new()))
+}
+
+/// Releases a builder created by `sg_builder_new`.
+///
+/// # Safety
+/// `builder` must be null or a pointer from `sg_builder_new` not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn sg_builder_free(builder: *mut Builder) {
+    if !builder.is_null() {
+        drop(Box::from_raw(builder));
+    }
+}
+
+/// Adds an input node.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_input(builder: *mut Builder) -> u64 {
+    match builder.as_mut() {
+        Some(builder) => builder.init().id.0 as u64,
+        None => SG_INVALID_NODE,
+    }
+}
+
+/// Adds a constant node.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_constant(builder: *mut Builder, value: u32) -> u64 {
+    match builder.as_mut() {
+        Some(builder) => builder.constant(value).id.0 as u64,
+        None => SG_INVALID_NODE,
+    }
+}
+
+/// Adds `a + b`.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_add(builder: *mut Builder, a: u64, b: u64) -> u64 {
+    binary(builder, a, b, |builder, a, b| builder.add(a, b))
+}
+
+/// Adds `a * b`.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_mul(builder: *mut Builder, a: u64, b: u64) -> u64 {
+    binary(builder, a, b, |builder, a, b| builder.mul(a, b))
+}
+
+/// Constrains `a == b`.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_assert_equal(builder: *mut Builder, a: u64, b: u64) -> i32 {
+    let Some(builder) = builder.as_mut() else {
+        return -1;
+    };
+    match (node(builder, a), node(builder, b)) {
+        (Some(a), Some(b)) => {
+            builder.assert_equal(&a, &b);
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Evaluates the graph with `len` input assignments `ids[i] = values[i]`.
+///
+/// # Safety
+/// `builder` must be null or a live builder; `ids` and `values` must point to
+/// `len` readable elements (or may be null when `len` is 0).
+#[no_mangle]
+pub unsafe extern "C" fn sg_fill(
+    builder: *mut Builder,
+    ids: *const u64,
+    values: *const u32,
+    len: usize,
+) -> i32 {
+    let Some(builder) = builder.as_mut() else {
+        return -1;
+    };
+    let mut inputs = HashMap::new();
+    if len > 0 {
+        if ids.is_null() || values.is_null() {
+            return -1;
+        }
+        let (ids, values) = (
+            std::slice::from_raw_parts(ids, len),
+            std::slice::from_raw_parts(values, len),
+        );
+        for (id, value) in ids.iter().zip(values) {
+            let Some(node) = node(builder, *id) else {
+                return -1;
+            };
+            inputs.insert(node.id, *value);
+        }
+    }
+    builder.fill_nodes(inputs);
+    0
+}
+
+/// Writes the value of node `id` to `out`. Returns `1` if the node has a
+/// value, `0` if it was not evaluated and `-1` for invalid arguments.
+///
+/// # Safety
+/// `builder` must be null or a live builder; `out` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn sg_value(builder: *const Builder, id: u64, out: *mut u32) -> i32 {
+    let (Some(builder), false) = (builder.as_ref(), out.is_null()) else {
+        return -1;
+    };
+    let Some(node) = node(builder, id) else {
+        return -1;
+    };
+    match builder.value(node.id) {
+        Some(v) => {
+            *out = v;
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Returns `1` if every constraint holds, `0` if not and `-1` for a null builder.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_check_constraints(builder: *const Builder) -> i32 {
+    match builder.as_ref() {
+        Some(builder) => i32::from(builder.check_constraints()),
+        None => -1,
+    }
+}
+
+/// The graph in Graphviz DOT format with values and constraints. Release with
+/// `sg_string_free`.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_to_dot(builder: *const Builder) -> *mut c_char {
+    let Some(builder) = builder.as_ref() else {
+        return ptr::null_mut();
+    };
+    let mut out = Vec::new();
+    if builder
+        .write_dot(&mut out, &DotOptions::annotated())
+        .is_err()
+    {
+        return ptr::null_mut();
+    }
+    into_c_string(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// The graph in S-expression format. Release with `sg_string_free`.
+///
+/// # Safety
+/// `builder` must be null or a live builder.
+#[no_mangle]
+pub unsafe extern "C" fn sg_to_sexpr(builder: *const Builder) -> *mut c_char {
+    match builder.as_ref() {
+        Some(builder) => into_c_string(builder.to_sexpr()),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+/// `text` must be null or a string from this library not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn sg_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
@@ -0,0 +1,281 @@
+//! CBOR (RFC 8949) encoding of graphs, for consumers that already speak CBOR
+//! and find JSON too heavy on the wire.
+//!
+//! A graph is a map with the same content as version 3 of the binary format,
+//! op tags included (0 input, 1 const, 2 add, 3 mul, 4 hint):
+//!
+//! ```text
+//! {
+//!   "version": 3,
+//!   "nodes": [[0, 0], [1, 1, 7], [2, 2, 0, 1], [3, 4, "gadgets::isqrt", [2]]],
+//!   "constraints": [[2, 3]],
+//!   "scopes": [[2, "sum"]],
+//!   "arithmetic": "wrapping",
+//!   "modes": [[2, "checked"]]
+//! }
+//! ```
+//!
+//! The map is a serde type written and read with ciborium (feature `cbor`).
+//! Only unsigned integers, text strings, arrays and maps with text keys are
+//! used, all with definite lengths, so minimal CBOR decoders can read it.
+
+use std::fmt;
+
+use ciborium::de::Error as CborError;
+use serde::de::{self, DeserializeOwned, IgnoredAny, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::serialize::{
+    DecodeError, RawGraph, RawOp, GRAPH_FORMAT_VERSION, TAG_ADD, TAG_CONST, TAG_HINT, TAG_INPUT,
+    TAG_MUL,
+};
+use crate::{Arithmetic, Builder, HintRegistry, Limits, NodeId, Op};
+
+/// Nesting deeper than any encoding written here, to bound recursion on
+/// hostile input.
+const MAX_DEPTH: usize = 16;
+
+/// Encodes `value`, which cannot fail when writing to memory.
+pub(crate) fn to_vec(value: &impl Serialize) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out).expect("CBOR encoding into a Vec cannot fail");
+    out
+}
+
+/// Decodes exactly one item spanning all of `bytes`.
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut rest = bytes;
+    let value = ciborium::de::from_reader_with_recursion_limit(&mut rest, MAX_DEPTH).map_err(
+        |e| match e {
+            CborError::Io(_) => DecodeError::new("unexpected end of CBOR data"),
+            CborError::Syntax(offset) => {
+                DecodeError::new(format!("invalid CBOR at byte {}", offset))
+            }
+            CborError::Semantic(_, message) => DecodeError::new(message),
+            CborError::RecursionLimitExceeded => DecodeError::new("CBOR nesting is too deep"),
+        },
+    )?;
+    if !rest.is_empty() {
+        return Err(DecodeError::new("trailing bytes after CBOR item"));
+    }
+    Ok(value)
+}
+
+pub(crate) fn node_id(id: u64) -> Result<NodeId, DecodeError> {
+    usize::try_from(id)
+        .map(NodeId)
+        .map_err(|_| DecodeError::new("node id does not fit in usize"))
+}
+
+fn id(id: NodeId) -> u64 {
+    id.0 as u64
+}
+
+fn arithmetic(name: &str) -> Result<Arithmetic, DecodeError> {
+    Arithmetic::from_name(name)
+        .ok_or_else(|| DecodeError::new(format!("unknown arithmetic mode `{}`", name)))
+}
+
+/// The graph map of the module docs.
+#[derive(Serialize, Deserialize)]
+struct GraphMap {
+    version: u64,
+    nodes: Vec<NodeItem>,
+    constraints: Vec<(u64, u64)>,
+    scopes: Vec<(u64, String)>,
+    arithmetic: String,
+    modes: Vec<(u64, String)>,
+}
+
+/// One node as `[id, tag, operands...]`.
+struct NodeItem {
+    id: u64,
+    op: RawOp,
+}
+
+impl Serialize for NodeItem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = match &self.op {
+            RawOp::Input => 2,
+            RawOp::Const(_) => 3,
+            _ => 4,
+        };
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        seq.serialize_element(&self.id)?;
+        match &self.op {
+            RawOp::Input => seq.serialize_element(&TAG_INPUT)?,
+            RawOp::Const(v) => {
+                seq.serialize_element(&TAG_CONST)?;
+                seq.serialize_element(v)?;
+            }
+            RawOp::Add(a, b) => {
+                seq.serialize_element(&TAG_ADD)?;
+                seq.serialize_element(&id(*a))?;
+                seq.serialize_element(&id(*b))?;
+            }
+            RawOp::Mul(a, b) => {
+                seq.serialize_element(&TAG_MUL)?;
+                seq.serialize_element(&id(*a))?;
+                seq.serialize_element(&id(*b))?;
+            }
+            RawOp::Hint(name, parents) => {
+                seq.serialize_element(&TAG_HINT)?;
+                seq.serialize_element(name)?;
+                let parents: Vec<u64> = parents.iter().map(|p| id(*p)).collect();
+                seq.serialize_element(&parents)?;
+            }
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeItem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+/// The next element of a node array, which must be there.
+fn element<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
+    seq: &mut A,
+    what: &str,
+) -> Result<T, A::Error> {
+    seq.next_element()?
+        .ok_or_else(|| de::Error::custom(format!("node without {}", what)))
+}
+
+/// A node id element, checked to fit in `usize`.
+fn id_element<'de, A: SeqAccess<'de>>(seq: &mut A) -> Result<NodeId, A::Error> {
+    node_id(element(seq, "operand")?).map_err(|e| de::Error::custom(e.message))
+}
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = NodeItem;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a node array [id, tag, operands...]")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NodeItem, A::Error> {
+        let id = element(&mut seq, "id")?;
+        let tag: u64 = element(&mut seq, "op tag")?;
+        let op = match u8::try_from(tag) {
+            Ok(TAG_INPUT) => RawOp::Input,
+            Ok(TAG_CONST) => RawOp::Const(element(&mut seq, "value")?),
+            Ok(TAG_ADD) => RawOp::Add(id_element(&mut seq)?, id_element(&mut seq)?),
+            Ok(TAG_MUL) => RawOp::Mul(id_element(&mut seq)?, id_element(&mut seq)?),
+            Ok(TAG_HINT) => {
+                let name = element(&mut seq, "hint name")?;
+                let parents: Vec<u64> = element(&mut seq, "hint parents")?;
+                let parents = parents
+                    .into_iter()
+                    .map(node_id)
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| de::Error::custom(e.message))?;
+                RawOp::Hint(name, parents)
+            }
+            _ => return Err(de::Error::custom(format!("unknown op tag {}", tag))),
+        };
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom(format!("node {} has extra items", id)));
+        }
+        Ok(NodeItem { id, op })
+    }
+}
+
+impl Builder {
+    /// Serializes the graph structure (not its values) as CBOR. Like
+    /// `to_bytes`, hints are stored by name and anonymous ones cannot be
+    /// decoded again.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let nodes = self
+            .sorted_nodes()
+            .into_iter()
+            .map(|node| NodeItem {
+                id: id(node.id),
+                op: match &node.op {
+                    None => RawOp::Input,
+                    Some(Op::Const(v)) => RawOp::Const(*v),
+                    Some(Op::Add(a, b, _)) => RawOp::Add(*a, *b),
+                    Some(Op::Mul(a, b, _)) => RawOp::Mul(*a, *b),
+                    Some(Op::Hint(parents, f)) => {
+                        RawOp::Hint(f.name().unwrap_or("").to_string(), parents.clone())
+                    }
+                },
+            })
+            .collect();
+        let mut scopes: Vec<(&NodeId, &String)> = self.node_scopes.iter().collect();
+        scopes.sort_by_key(|(id, _)| id.0);
+        let modes = self
+            .sorted_nodes()
+            .into_iter()
+            .filter_map(|node| {
+                let mode = self.arithmetic_of(&self.handle_of(node.id))?;
+                Some((id(node.id), mode.to_string()))
+            })
+            .collect();
+        to_vec(&GraphMap {
+            version: u64::from(GRAPH_FORMAT_VERSION),
+            nodes,
+            constraints: self
+                .constraints
+                .iter()
+                .map(|(a, b)| (id(*a), id(*b)))
+                .collect(),
+            scopes: scopes
+                .into_iter()
+                .map(|(node, path)| (id(*node), path.clone()))
+                .collect(),
+            arithmetic: self.arithmetic.to_string(),
+            modes,
+        })
+    }
+
+    /// Decodes a graph written by `to_cbor`, resolving hint names through
+    /// `hints`.
+    pub fn from_cbor(bytes: &[u8], hints: &HintRegistry) -> Result<Builder, DecodeError> {
+        Self::from_cbor_with_limits(bytes, hints, Limits::default())
+    }
+
+    /// Like `from_cbor`, refusing graphs that exceed `limits`.
+    pub fn from_cbor_with_limits(
+        bytes: &[u8],
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<Builder, DecodeError> {
+        let graph: GraphMap = from_slice(bytes)?;
+        if graph.version != u64::from(GRAPH_FORMAT_VERSION) {
+            return Err(DecodeError::new(format!(
+                "CBOR graphs have format version {}, found {}",
+                GRAPH_FORMAT_VERSION, graph.version
+            )));
+        }
+        let raw = RawGraph {
+            nodes: graph
+                .nodes
+                .into_iter()
+                .map(|node| Ok((node_id(node.id)?, node.op)))
+                .collect::<Result<_, DecodeError>>()?,
+            constraints: graph
+                .constraints
+                .into_iter()
+                .map(|(a, b)| Ok((node_id(a)?, node_id(b)?)))
+                .collect::<Result<_, DecodeError>>()?,
+            scopes: graph
+                .scopes
+                .into_iter()
+                .map(|(node, path)| Ok((node_id(node)?, path)))
+                .collect::<Result<_, DecodeError>>()?,
+            arithmetic: arithmetic(&graph.arithmetic)?,
+            modes: graph
+                .modes
+                .into_iter()
+                .map(|(node, mode)| Ok((node_id(node)?, arithmetic(&mode)?)))
+                .collect::<Result<_, DecodeError>>()?,
+        };
+        raw.into_builder(hints, limits)
+    }
+}
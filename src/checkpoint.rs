@@ -0,0 +1,241 @@
+//! Checkpoints for speculative construction: build a branch, then keep it or
+//! roll the builder back, without cloning the graph.
+//!
+//! Additions are undone by position: nodes above the checkpoint's id and
+//! constraints past its count are dropped. Edits that change or remove older
+//! nodes (`replace_node`, `remove_subtree`, `prune`, `substitute`,
+//! `minimize_constraints`) first save what they overwrite in an undo log,
+//! which `rollback` replays backwards.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::ops::RangeInclusive;
+use core::panic::Location;
+
+use crate::collections::HashMap;
+use crate::observe::Observer;
+use crate::prelude::*;
+use crate::{Builder, NodeData, NodeId};
+
+/// A point in a builder's history, taken by `Builder::checkpoint`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    graph: u64,
+    epoch: u64,
+    next_id: usize,
+    constraints: usize,
+    instances: usize,
+    undo: usize,
+    outputs: Vec<(String, NodeId)>,
+    reordered: bool,
+    scopes: Vec<String>,
+}
+
+/// State an edit overwrote after a checkpoint was taken.
+#[derive(Clone)]
+pub(crate) enum Undo {
+    /// A node's op and value before an edit rewired it.
+    Node(NodeData),
+    /// A node and everything attached to it before it was removed.
+    Removed(Box<RemovedNode>),
+    /// The constraints before an edit rewired or removed some of them.
+    Constraints(
+        Vec<(NodeId, NodeId)>,
+        HashMap<(NodeId, NodeId), &'static Location<'static>>,
+    ),
+}
+
+#[derive(Clone)]
+pub(crate) struct RemovedNode {
+    data: NodeData,
+    generation: u32,
+    scope: Option<String>,
+    label: Option<String>,
+    metadata: Option<BTreeMap<String, String>>,
+    location: Option<&'static Location<'static>>,
+    input_names: Vec<String>,
+    input_range: Option<RangeInclusive<u32>>,
+    observers: Option<Vec<Observer>>,
+    /// `(instance, position)` of each gadget instance listing the node.
+    instances: Vec<(usize, usize)>,
+}
+
+impl Builder {
+    /// Records the current state so later additions and edits can be
+    /// discarded with `rollback`. Only counters, the declared outputs and the
+    /// open scope names are stored; from here on, edits to the nodes that
+    /// already exist save what they overwrite until `rollback` or `commit`.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.undo_floor = self.next_id;
+        Checkpoint {
+            graph: self.graph_id,
+            epoch: self.checkpoint_epoch,
+            next_id: self.next_id,
+            constraints: self.constraints.len(),
+            instances: self.instances.len(),
+            undo: self.undo.len(),
+            outputs: self.outputs.clone(),
+            reordered: self.reordered,
+            scopes: self.scope_stack.clone(),
+        }
+    }
+
+    /// Removes every node, constraint, gadget instance and output added since
+    /// `checkpoint`, undoes the edits made to older nodes and constraints
+    /// since then, and restores the declared outputs and the scope stack.
+    /// Labels and metadata set on older nodes are kept, and values are not
+    /// restored, so evaluate again afterwards. Costs time proportional to what
+    /// is discarded.
+    ///
+    /// # Panics
+    /// If the checkpoint belongs to another builder, was taken before a
+    /// `commit`, or the builder was already rolled back past it.
+    pub fn rollback(&mut self, checkpoint: &Checkpoint) {
+        assert_eq!(
+            checkpoint.graph, self.graph_id,
+            "rollback: checkpoint belongs to a different builder"
+        );
+        assert_eq!(
+            checkpoint.epoch, self.checkpoint_epoch,
+            "rollback: checkpoint was committed"
+        );
+        assert!(
+            checkpoint.next_id <= self.next_id && checkpoint.undo <= self.undo.len(),
+            "rollback: builder was already rolled back past this checkpoint"
+        );
+        let undone: Vec<Undo> = self.undo.drain(checkpoint.undo..).collect();
+        for entry in undone.into_iter().rev() {
+            match entry {
+                Undo::Node(data) => {
+                    if let Some(node) = self.nodes.get_mut(&data.id) {
+                        *node = data;
+                    }
+                }
+                Undo::Removed(removed) => self.restore_node(*removed),
+                Undo::Constraints(constraints, locations) => {
+                    self.constraints = constraints;
+                    self.constraint_locations = locations;
+                }
+            }
+        }
+
+        let kept = checkpoint.constraints.min(self.constraints.len());
+        let dropped: Vec<_> = self.constraints.drain(kept..).collect();
+        for pair in dropped {
+            if !self.constraints.contains(&pair) {
+                self.constraint_locations.remove(&pair);
+            }
+        }
+        self.undo_floor = checkpoint.next_id;
+        for id in checkpoint.next_id..self.next_id {
+            self.forget_node(NodeId(id));
+        }
+        self.next_id = checkpoint.next_id;
+        self.instances.truncate(checkpoint.instances);
+        self.outputs = checkpoint.outputs.clone();
+        self.reordered = checkpoint.reordered;
+        self.scope_stack = checkpoint.scopes.clone();
+    }
+
+    /// Keeps everything built since the checkpoints taken so far and drops
+    /// their undo log. Rolling back to one of them afterwards panics.
+    pub fn commit(&mut self) {
+        self.undo.clear();
+        self.undo_floor = 0;
+        self.checkpoint_epoch += 1;
+    }
+
+    /// Whether edits to `id` must be saved for an outstanding checkpoint.
+    fn logs_edits_to(&self, id: NodeId) -> bool {
+        id.0 < self.undo_floor
+    }
+
+    /// Saves the op and value of `id` before an edit changes them.
+    pub(crate) fn log_node(&mut self, id: NodeId) {
+        if !self.logs_edits_to(id) {
+            return;
+        }
+        if let Some(data) = self.nodes.get(&id) {
+            self.undo.push(Undo::Node(data.clone()));
+        }
+    }
+
+    /// Saves the constraints before an edit rewires or removes some of them.
+    pub(crate) fn log_constraints(&mut self) {
+        if self.undo_floor > 0 {
+            self.undo.push(Undo::Constraints(
+                self.constraints.clone(),
+                self.constraint_locations.clone(),
+            ));
+        }
+    }
+
+    /// Saves everything `forget_node` is about to drop for `id`.
+    pub(crate) fn log_removal(&mut self, id: NodeId) {
+        if !self.logs_edits_to(id) {
+            return;
+        }
+        let (Some(data), Some(generation)) = (self.nodes.get(&id), self.nodes.generation(id))
+        else {
+            return;
+        };
+        let removed = RemovedNode {
+            data: data.clone(),
+            generation,
+            scope: self.node_scopes.get(&id).cloned(),
+            label: self.labels.get(&id).cloned(),
+            metadata: self.metadata.get(&id).cloned(),
+            location: self.locations.get(&id).copied(),
+            input_names: self
+                .input_names
+                .iter()
+                .filter(|(_, n)| **n == id)
+                .map(|(name, _)| name.clone())
+                .collect(),
+            input_range: self.input_ranges.get(&id).cloned(),
+            observers: self.observers.get(&id).cloned(),
+            instances: self
+                .instances
+                .iter()
+                .enumerate()
+                .filter_map(|(i, instance)| {
+                    let position = instance.nodes.iter().position(|n| *n == id)?;
+                    Some((i, position))
+                })
+                .collect(),
+        };
+        self.undo.push(Undo::Removed(Box::new(removed)));
+    }
+
+    fn restore_node(&mut self, removed: RemovedNode) {
+        let id = removed.data.id;
+        self.nodes.restore(id, removed.data, removed.generation);
+        if let Some(scope) = removed.scope {
+            self.node_scopes.insert(id, scope);
+        }
+        if let Some(label) = removed.label {
+            self.insert_label(id, label);
+        }
+        if let Some(entries) = removed.metadata {
+            self.insert_metadata(id, entries);
+        }
+        if let Some(location) = removed.location {
+            self.locations.insert(id, location);
+        }
+        for name in removed.input_names {
+            self.input_names.insert(name, id);
+        }
+        if let Some(range) = removed.input_range {
+            self.input_ranges.insert(id, range);
+        }
+        if let Some(observers) = removed.observers {
+            self.observers.insert(id, observers);
+        }
+        for (instance, position) in removed.instances {
+            if let Some(instance) = self.instances.get_mut(instance) {
+                let position = position.min(instance.nodes.len());
+                instance.nodes.insert(position, id);
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! The `graphengine` command line, for operating on serialized graphs
+//! without writing Rust.
+//!
+//! ```text
+//! graphengine eval graph.json --inputs inputs.json [--witness witness.json]
+//! graphengine export graph.json --format dot|mermaid|r1cs
+//! graphengine check graph.json witness.json
+//! graphengine demo
+//! ```
+//!
+//! Graph files use the gadget JSON format (see `Gadget::save`): the gadget's
+//! input ports name the values read from `inputs.json`, a flat object such as
+//! `{"x": 3}`, and its output ports are the values `eval` prints. Only command
+//! output goes to stdout; with `GRAPHENGINE_LOG` set, the library's progress
+//! messages go to stderr.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::json::Json;
+use crate::{
+    gadgets, Builder, DotOptions, Gadget, HintRegistry, ImportError, NodeId, WitnessError,
+    WitnessFormat,
+};
+
+pub const USAGE: &str = "\
+usage:
+  graphengine eval <graph.json> --inputs <inputs.json> [--witness <witness.json>]
+  graphengine export <graph.json> --format dot|mermaid|r1cs
+  graphengine check <graph.json> <witness.json>
+  graphengine demo";
+
+#[derive(Debug)]
+pub enum CliError {
+    /// The arguments do not match any command.
+    Usage(String),
+    Io(io::Error),
+    Graph(ImportError),
+    Witness(WitnessError),
+    /// The inputs file is malformed or does not match the graph's inputs.
+    Inputs(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(msg) => write!(f, "{}\n{}", msg, USAGE),
+            CliError::Io(e) => write!(f, "I/O error: {}", e),
+            CliError::Graph(e) => write!(f, "{}", e),
+            CliError::Witness(e) => write!(f, "{}", e),
+            CliError::Inputs(msg) => write!(f, "invalid inputs: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+impl From<ImportError> for CliError {
+    fn from(e: ImportError) -> Self {
+        CliError::Graph(e)
+    }
+}
+
+impl From<WitnessError> for CliError {
+    fn from(e: WitnessError) -> Self {
+        CliError::Witness(e)
+    }
+}
+
+fn usage(message: impl Into<String>) -> CliError {
+    CliError::Usage(message.into())
+}
+
+/// Positional arguments and `--flag value` options of one command.
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &'a [String], flags: &[&str]) -> Result<Self, CliError> {
+        let mut parsed = Args {
+            positional: vec![],
+            options: HashMap::new(),
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(flag) = arg.strip_prefix("--") {
+                if !flags.contains(&flag) {
+                    return Err(usage(format!("unknown option `{}`", arg)));
+                }
+                let value = iter
+                    .next()
+                    .ok_or_else(|| usage(format!("`{}` needs a value", arg)))?;
+                parsed.options.insert(flag, value);
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn expect_positional(&self, names: &[&str]) -> Result<(), CliError> {
+        if self.positional.len() == names.len() {
+            Ok(())
+        } else {
+            Err(usage(format!("expected {}", names.join(" "))))
+        }
+    }
+}
+
+/// Loads a gadget file as a standalone graph whose outputs are the gadget's
+/// output ports. Hints from the `gadgets` library are available.
+fn load_graph(path: &str) -> Result<(Builder, Vec<(String, NodeId)>), CliError> {
+    let mut hints = HintRegistry::new();
+    gadgets::register_hints(&mut hints);
+    let gadget = Gadget::load(path, &hints)?;
+    let mut graph = gadget.graph;
+    graph.outputs = gadget.outputs;
+    Ok((graph, gadget.inputs))
+}
+
+fn read_inputs(path: &str, ports: &[(String, NodeId)]) -> Result<HashMap<NodeId, u32>, CliError> {
+    let json =
+        Json::parse(&fs::read_to_string(path)?).map_err(|e| CliError::Inputs(e.to_string()))?;
+    let Json::Object(entries) = json else {
+        return Err(CliError::Inputs(
+            "expected an object of input values".into(),
+        ));
+    };
+    let mut values = HashMap::new();
+    for (name, value) in entries {
+        let id = ports
+            .iter()
+            .find(|(port, _)| *port == name)
+            .map(|(_, id)| *id)
+            .ok_or_else(|| CliError::Inputs(format!("the graph has no input `{}`", name)))?;
+        let value = value
+            .as_int()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| CliError::Inputs(format!("`{}` must be a u32", name)))?;
+        values.insert(id, value);
+    }
+    if let Some((name, _)) = ports.iter().find(|(_, id)| !values.contains_key(id)) {
+        return Err(CliError::Inputs(format!("missing value for `{}`", name)));
+    }
+    Ok(values)
+}
+
+/// Writes the constraints that do not hold and returns whether all of them do.
+fn report_constraints<W: Write>(graph: &Builder, out: &mut W) -> io::Result<bool> {
+    let mut failed = 0;
+    for (a, b) in &graph.constraints {
+        let (va, vb) = (graph.value(*a), graph.value(*b));
+        if va != vb {
+            failed += 1;
+            writeln!(
+                out,
+                "constraint failed: n{} = {:?}, n{} = {:?}",
+                a.0, va, b.0, vb
+            )?;
+        }
+    }
+    if failed == 0 {
+        writeln!(out, "ok: {} constraints hold", graph.constraints.len())?;
+    }
+    Ok(failed == 0)
+}
+
+fn eval<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let args = Args::parse(args, &["inputs", "witness"])?;
+    args.expect_positional(&["<graph.json>"])?;
+    let inputs_path = args
+        .options
+        .get("inputs")
+        .ok_or_else(|| usage("`eval` needs --inputs <inputs.json>"))?;
+    let (mut graph, ports) = load_graph(args.positional[0])?;
+    let inputs = read_inputs(inputs_path, &ports)?;
+    graph.fill_nodes(inputs);
+    let outputs = graph
+        .output_values()
+        .into_iter()
+        .map(|(name, value)| {
+            let value = value.map_or(Json::Null, |v| Json::Int(i128::from(v)));
+            (name, value)
+        })
+        .collect();
+    write!(out, "{}", Json::Object(outputs).to_pretty_string())?;
+    if let Some(path) = args.options.get("witness") {
+        graph.witness().save(path, WitnessFormat::Json)?;
+    }
+    Ok(graph
+        .constraints
+        .iter()
+        .all(|(a, b)| graph.value(*a) == graph.value(*b)))
+}
+
+fn export<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let args = Args::parse(args, &["format"])?;
+    args.expect_positional(&["<graph.json>"])?;
+    let (graph, _) = load_graph(args.positional[0])?;
+    match args.options.get("format").copied().unwrap_or("dot") {
+        "dot" => graph.write_dot(out, &DotOptions::default())?,
+        "mermaid" => write!(out, "{}", graph.to_mermaid())?,
+        "r1cs" => write!(out, "{}", graph.to_r1cs())?,
+        other => return Err(usage(format!("unknown export format `{}`", other))),
+    }
+    Ok(true)
+}
+
+fn check<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    let args = Args::parse(args, &[])?;
+    args.expect_positional(&["<graph.json>", "<witness.json>"])?;
+    let (mut graph, _) = load_graph(args.positional[0])?;
+    let witness = crate::Witness::load(args.positional[1])?;
+    graph.apply_witness(&witness)?;
+    Ok(report_constraints(&graph, out)?)
+}
+
+/// Builds and evaluates f(x) = x^2 + x + 5 at x = 3 and writes `graph.dot`.
+fn demo<W: Write>(out: &mut W) -> Result<bool, CliError> {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let x_squared = builder.mul(&x, &x);
+    let five = builder.constant(5);
+    let x_squared_plus_x = builder.add(&x_squared, &x);
+    let y = builder.add(&x_squared_plus_x, &five);
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 3);
+    builder.fill_nodes(inputs);
+    let ok = builder.check_constraints();
+    writeln!(out, "f(3) = {:?}", builder.value(y.id))?;
+    builder.to_dot()?;
+    Ok(ok)
+}
+
+/// Runs one command, given the arguments after the program name. Returns
+/// whether the graph's constraints held (always `true` for `export`).
+pub fn run<W: Write>(args: &[String], out: &mut W) -> Result<bool, CliError> {
+    match args.split_first() {
+        Some((command, rest)) => match command.as_str() {
+            "eval" => eval(rest, out),
+            "export" => export(rest, out),
+            "check" => check(rest, out),
+            "demo" => demo(out),
+            other => Err(usage(format!("unknown command `{}`", other))),
+        },
+        None => Err(usage("no command given")),
+    }
+}
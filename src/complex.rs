@@ -0,0 +1,78 @@
+//! Complex values as pairs of nodes, for signal-processing style graphs.
+//!
+//! Components use the graph's wrapping arithmetic, so a negative component
+//! such as `-3` is held as `2^32 - 3`.
+
+use crate::{Builder, Node};
+
+/// A complex value `re + im·i`, one node per component.
+#[derive(Clone, Debug)]
+pub struct ComplexNode {
+    pub re: Node,
+    pub im: Node,
+}
+
+impl Builder {
+    /// A complex input: two fresh input nodes.
+    #[track_caller]
+    pub fn complex_input(&mut self) -> ComplexNode {
+        ComplexNode {
+            re: self.init(),
+            im: self.init(),
+        }
+    }
+
+    #[track_caller]
+    pub fn complex_constant(&mut self, re: u32, im: u32) -> ComplexNode {
+        ComplexNode {
+            re: self.constant(re),
+            im: self.constant(im),
+        }
+    }
+
+    /// `(a + bi) + (c + di) = (a + c) + (b + d)i`.
+    #[track_caller]
+    pub fn complex_add(&mut self, x: &ComplexNode, y: &ComplexNode) -> ComplexNode {
+        ComplexNode {
+            re: self.add(&x.re, &y.re),
+            im: self.add(&x.im, &y.im),
+        }
+    }
+
+    /// `(a + bi)(c + di) = (ac - bd) + (ad + bc)i`.
+    #[track_caller]
+    pub fn complex_mul(&mut self, x: &ComplexNode, y: &ComplexNode) -> ComplexNode {
+        let ac = self.mul(&x.re, &y.re);
+        let bd = self.mul(&x.im, &y.im);
+        let minus_one = self.constant(u32::MAX);
+        let minus_bd = self.mul(&bd, &minus_one);
+        let ad = self.mul(&x.re, &y.im);
+        let bc = self.mul(&x.im, &y.re);
+        ComplexNode {
+            re: self.add(&ac, &minus_bd),
+            im: self.add(&ad, &bc),
+        }
+    }
+
+    /// `a - bi`.
+    #[track_caller]
+    pub fn complex_conj(&mut self, x: &ComplexNode) -> ComplexNode {
+        let minus_one = self.constant(u32::MAX);
+        ComplexNode {
+            re: x.re,
+            im: self.mul(&x.im, &minus_one),
+        }
+    }
+
+    /// Constrains both components of `x` and `y` to be equal.
+    #[track_caller]
+    pub fn assert_complex_equal(&mut self, x: &ComplexNode, y: &ComplexNode) {
+        self.assert_equal(&x.re, &y.re);
+        self.assert_equal(&x.im, &y.im);
+    }
+
+    /// `(re, im)` once both components are evaluated.
+    pub fn complex_value(&self, x: &ComplexNode) -> Option<(u32, u32)> {
+        Some((self.value(x.re.id)?, self.value(x.im.id)?))
+    }
+}
@@ -0,0 +1,549 @@
+//! Declarative graph definitions in TOML or YAML, so computations can be
+//! described and tweaked in a config file instead of Rust code.
+//!
+//! ```text
+//! # TOML                            # YAML
+//! inputs = ["x", "y"]               inputs: [x, y]
+//! constraints = ["total == 12"]     expressions:
+//! outputs = ["total"]                 sq: x * x
+//!                                     total: sq + y + 3
+//! [expressions]                     constraints:
+//! sq = "x * x"                        - total == 12
+//! total = "sq + y + 3"              outputs: [total]
+//! ```
+//!
+//! Expressions use `+`, `*`, parentheses, `u32` literals, the names of inputs
+//! and earlier expressions, and hint calls such as `isqrt(total)`, resolved
+//! through a `HintRegistry`. Constraints are `a == b` between two expressions.
+//! Inputs and expressions label their nodes with their names; outputs are
+//! declared with `mark_output`.
+//!
+//! Only the subset of each language such files need is read: comments,
+//! top-level keys, one `[expressions]` table (TOML) or mapping (YAML), strings
+//! and lists of strings. Anchors, multi-line strings and escapes are not
+//! supported.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{Builder, HintRegistry, LimitError, Limits, Node, NodeId, ParseError};
+
+#[derive(Debug)]
+pub enum DefinitionError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The file extension is not `.toml`, `.yaml` or `.yml`.
+    UnknownFormat(String),
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DefinitionError::Io(e) => write!(f, "definition I/O error: {}", e),
+            DefinitionError::Parse(e) => write!(f, "invalid definition: {}", e),
+            DefinitionError::UnknownFormat(ext) => write!(
+                f,
+                "unknown definition format `{}` (expected .toml, .yaml or .yml)",
+                ext
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DefinitionError {}
+
+impl From<io::Error> for DefinitionError {
+    fn from(e: io::Error) -> Self {
+        DefinitionError::Io(e)
+    }
+}
+
+impl From<ParseError> for DefinitionError {
+    fn from(e: ParseError) -> Self {
+        DefinitionError::Parse(e)
+    }
+}
+
+/// The sections of a definition, each entry with its line number.
+#[derive(Default)]
+struct Definition {
+    inputs: Vec<(String, usize)>,
+    expressions: Vec<(String, String, usize)>,
+    constraints: Vec<(String, usize)>,
+    outputs: Vec<(String, usize)>,
+}
+
+impl Definition {
+    fn list(&mut self, key: &str, line: usize) -> Result<&mut Vec<(String, usize)>, ParseError> {
+        match key {
+            "inputs" => Ok(&mut self.inputs),
+            "constraints" => Ok(&mut self.constraints),
+            "outputs" => Ok(&mut self.outputs),
+            "expressions" => Err(ParseError::new(
+                line,
+                "`expressions` must map names to expressions",
+            )),
+            other => Err(ParseError::new(line, format!("unknown key `{}`", other))),
+        }
+    }
+}
+
+/// Drops a `#` comment that is not inside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// A possibly quoted scalar.
+fn scalar(s: &str, line: usize) -> Result<String, ParseError> {
+    let s = s.trim();
+    let unquoted = ['"', '\'']
+        .into_iter()
+        .find_map(|q| s.strip_prefix(q)?.strip_suffix(q));
+    match unquoted {
+        Some(inner) => Ok(inner.to_string()),
+        None if s.is_empty() || s.starts_with(['"', '\'']) => {
+            Err(ParseError::new(line, format!("invalid value `{}`", s)))
+        }
+        None => Ok(s.to_string()),
+    }
+}
+
+/// Items of a `[a, "b", f(c, d)]` list; commas inside quotes or parentheses
+/// do not separate items.
+fn flow_list(s: &str, line: usize) -> Result<Vec<(String, usize)>, ParseError> {
+    let inner = s
+        .trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| ParseError::new(line, format!("expected a list, found `{}`", s.trim())))?;
+    let mut items = Vec::new();
+    let (mut quote, mut depth, mut start) = (None, 0usize, 0);
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                items.push((scalar(&inner[start..i], line)?, line));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    // A trailing comma is allowed.
+    if !inner[start..].trim().is_empty() {
+        items.push((scalar(&inner[start..], line)?, line));
+    }
+    Ok(items)
+}
+
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, strip_comment(l).trim_end()))
+        .filter(|(_, l)| !l.trim().is_empty())
+}
+
+fn parse_toml(text: &str) -> Result<Definition, ParseError> {
+    let mut def = Definition::default();
+    let mut in_expressions = false;
+    let mut lines = lines(text);
+    while let Some((line, s)) = lines.next() {
+        let s = s.trim();
+        if let Some(table) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            if table.trim() != "expressions" {
+                return Err(ParseError::new(
+                    line,
+                    format!("unknown table `{}`", table.trim()),
+                ));
+            }
+            in_expressions = true;
+            continue;
+        }
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| ParseError::new(line, "expected `key = value`"))?;
+        let key = scalar(key, line)?;
+        let mut value = value.trim().to_string();
+        // Arrays may span several lines.
+        if value.starts_with('[') {
+            while !value.ends_with(']') {
+                let (_, more) = lines
+                    .next()
+                    .ok_or_else(|| ParseError::new(line, "unterminated array"))?;
+                value.push(' ');
+                value.push_str(more.trim());
+            }
+        }
+        if in_expressions {
+            def.expressions.push((key, scalar(&value, line)?, line));
+        } else {
+            let items = flow_list(&value, line)?;
+            def.list(&key, line)?.extend(items);
+        }
+    }
+    Ok(def)
+}
+
+fn parse_yaml(text: &str) -> Result<Definition, ParseError> {
+    let mut def = Definition::default();
+    let mut open: Option<String> = None;
+    for (line, raw) in lines(text) {
+        let s = raw.trim();
+        if !raw.starts_with([' ', '\t']) {
+            let (key, rest) = s
+                .split_once(':')
+                .ok_or_else(|| ParseError::new(line, "expected `key:`"))?;
+            let key = scalar(key, line)?;
+            let rest = rest.trim();
+            if rest.is_empty() {
+                if key != "expressions" {
+                    def.list(&key, line)?;
+                }
+                open = Some(key);
+                continue;
+            }
+            open = None;
+            let items = if rest.starts_with('[') {
+                flow_list(rest, line)?
+            } else {
+                vec![(scalar(rest, line)?, line)]
+            };
+            def.list(&key, line)?.extend(items);
+            continue;
+        }
+        match open.as_deref() {
+            None => return Err(ParseError::new(line, "unexpected indentation")),
+            Some("expressions") => {
+                let (name, expr) = s
+                    .split_once(':')
+                    .ok_or_else(|| ParseError::new(line, "expected `name: expression`"))?;
+                def.expressions
+                    .push((scalar(name, line)?, scalar(expr, line)?, line));
+            }
+            Some(key) => {
+                let item = s
+                    .strip_prefix('-')
+                    .ok_or_else(|| ParseError::new(line, "expected `- item`"))?;
+                let item = scalar(item, line)?;
+                def.list(key, line)?.push((item, line));
+            }
+        }
+    }
+    Ok(def)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(u32),
+    Name(String),
+    Sym(char),
+}
+
+fn tokenize(expr: &str, line: usize) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = expr.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let mut word = String::new();
+            while let Some(&c) = chars
+                .peek()
+                .filter(|c| c.is_ascii_alphanumeric() || **c == '_')
+            {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(if c.is_ascii_digit() {
+                Token::Num(
+                    word.parse().map_err(|_| {
+                        ParseError::new(line, format!("invalid constant `{}`", word))
+                    })?,
+                )
+            } else {
+                Token::Name(word)
+            });
+        } else if "+*(),".contains(c) {
+            tokens.push(Token::Sym(c));
+            chars.next();
+        } else {
+            return Err(ParseError::new(
+                line,
+                format!("unexpected `{}` in expression", c),
+            ));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive-descent reader of one expression, adding its nodes to the
+/// builder.
+struct Expr<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: usize,
+    builder: &'a mut Builder,
+    names: &'a HashMap<String, Node>,
+    hints: &'a HintRegistry,
+}
+
+impl Expr<'_> {
+    fn eat(&mut self, sym: char) -> bool {
+        let found = self.tokens.get(self.pos) == Some(&Token::Sym(sym));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, sym: char) -> Result<(), ParseError> {
+        if self.eat(sym) {
+            Ok(())
+        } else {
+            Err(ParseError::new(self.line, format!("expected `{}`", sym)))
+        }
+    }
+
+    fn limit_error(&self, e: LimitError) -> ParseError {
+        ParseError::new(self.line, e.to_string())
+    }
+
+    fn sum(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.product()?;
+        while self.eat('+') {
+            let rhs = self.product()?;
+            node = self
+                .builder
+                .try_add(&node, &rhs)
+                .map_err(|e| self.limit_error(e))?;
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.atom()?;
+        while self.eat('*') {
+            let rhs = self.atom()?;
+            node = self
+                .builder
+                .try_mul(&node, &rhs)
+                .map_err(|e| self.limit_error(e))?;
+        }
+        Ok(node)
+    }
+
+    fn atom(&mut self) -> Result<Node, ParseError> {
+        let line = self.line;
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Num(v)) => self
+                .builder
+                .try_constant(v)
+                .map_err(|e| self.limit_error(e)),
+            Some(Token::Sym('(')) => {
+                let node = self.sum()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) if self.eat('(') => {
+                let func = self
+                    .hints
+                    .get(&name)
+                    .ok_or_else(|| ParseError::new(line, format!("unknown hint `{}`", name)))?;
+                let mut parents = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        parents.push(self.sum()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                self.builder
+                    .try_hint_named(&name, &parents, func)
+                    .map_err(|e| self.limit_error(e))
+            }
+            Some(Token::Name(name)) => self
+                .names
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| ParseError::new(line, format!("unknown name `{}`", name))),
+            Some(Token::Sym(c)) => Err(ParseError::new(line, format!("unexpected `{}`", c))),
+            None => Err(ParseError::new(line, "unexpected end of expression")),
+        }
+    }
+}
+
+fn expression(
+    builder: &mut Builder,
+    names: &HashMap<String, Node>,
+    hints: &HintRegistry,
+    text: &str,
+    line: usize,
+) -> Result<Node, ParseError> {
+    let mut expr = Expr {
+        tokens: tokenize(text, line)?,
+        pos: 0,
+        line,
+        builder,
+        names,
+        hints,
+    };
+    let node = expr.sum()?;
+    if expr.pos < expr.tokens.len() {
+        return Err(ParseError::new(
+            line,
+            format!("unexpected trailing input in `{}`", text),
+        ));
+    }
+    Ok(node)
+}
+
+fn bind(
+    builder: &mut Builder,
+    names: &mut HashMap<String, Node>,
+    name: String,
+    node: Node,
+    line: usize,
+) -> Result<(), ParseError> {
+    if builder.label_of(&node).is_none() {
+        builder.set_label(&node, &name);
+    }
+    if names.insert(name.clone(), node).is_some() {
+        return Err(ParseError::new(
+            line,
+            format!("`{}` is defined twice", name),
+        ));
+    }
+    Ok(())
+}
+
+impl Definition {
+    fn build(
+        self,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        let mut builder = Builder::with_limits(limits);
+        let mut names = HashMap::new();
+        for (name, line) in self.inputs {
+            let node = builder
+                .try_init()
+                .map_err(|e| ParseError::new(line, e.to_string()))?;
+            bind(&mut builder, &mut names, name, node, line)?;
+        }
+        // Each expression may use the names bound before it.
+        for (name, text, line) in self.expressions {
+            let node = expression(&mut builder, &names, hints, &text, line)?;
+            bind(&mut builder, &mut names, name, node, line)?;
+        }
+        for (text, line) in self.constraints {
+            let (a, b) = text
+                .split_once("==")
+                .ok_or_else(|| ParseError::new(line, "expected `a == b`"))?;
+            let a = expression(&mut builder, &names, hints, a, line)?;
+            let b = expression(&mut builder, &names, hints, b, line)?;
+            builder
+                .try_assert_equal(&a, &b)
+                .map_err(|e| ParseError::new(line, e.to_string()))?;
+        }
+        for (name, line) in self.outputs {
+            let node = names
+                .get(&name)
+                .ok_or_else(|| ParseError::new(line, format!("unknown name `{}`", name)))?;
+            if builder.output_id(&name).is_some() {
+                return Err(ParseError::new(
+                    line,
+                    format!("output `{}` is declared twice", name),
+                ));
+            }
+            builder.mark_output(node, &name);
+        }
+        let names = names
+            .into_iter()
+            .map(|(name, node)| (name, node.id))
+            .collect();
+        Ok((builder, names))
+    }
+}
+
+impl Builder {
+    /// Builds a graph from a TOML definition. Returns the new builder and the
+    /// node bound to every input and expression name.
+    pub fn from_toml(
+        text: &str,
+        hints: &HintRegistry,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        parse_toml(text)?.build(hints, Limits::default())
+    }
+
+    /// Like `from_toml`, refusing graphs that exceed `limits`.
+    pub fn from_toml_with_limits(
+        text: &str,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        parse_toml(text)?.build(hints, limits)
+    }
+
+    /// Builds a graph from a YAML definition; see `from_toml`.
+    pub fn from_yaml(
+        text: &str,
+        hints: &HintRegistry,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        parse_yaml(text)?.build(hints, Limits::default())
+    }
+
+    /// Like `from_yaml`, refusing graphs that exceed `limits`.
+    pub fn from_yaml_with_limits(
+        text: &str,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        parse_yaml(text)?.build(hints, limits)
+    }
+
+    /// Reads a definition file, choosing TOML or YAML by its extension.
+    pub fn load_definition(
+        path: impl AsRef<Path>,
+        hints: &HintRegistry,
+    ) -> Result<(Builder, HashMap<String, NodeId>), DefinitionError> {
+        Self::load_definition_with_limits(path, hints, Limits::default())
+    }
+
+    /// Like `load_definition`, refusing graphs that exceed `limits`.
+    pub fn load_definition_with_limits(
+        path: impl AsRef<Path>,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<(Builder, HashMap<String, NodeId>), DefinitionError> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_string();
+        let parse = match ext.as_str() {
+            "toml" => Builder::from_toml_with_limits,
+            "yaml" | "yml" => Builder::from_yaml_with_limits,
+            _ => return Err(DefinitionError::UnknownFormat(ext)),
+        };
+        Ok(parse(&fs::read_to_string(path)?, hints, limits)?)
+    }
+}
@@ -0,0 +1,134 @@
+//! Structural diff between two graphs.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::{Arithmetic, Builder, NodeId, Op};
+
+/// A node whose operation differs between the two graphs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeChange {
+    pub id: NodeId,
+    pub before: String,
+    pub after: String,
+}
+
+/// Result of `Builder::diff`. Ops are shown in their canonical text form
+/// (e.g. `add 0 1`, `const 8`, `hint half [2]`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GraphDiff {
+    pub added_nodes: Vec<(NodeId, String)>,
+    pub removed_nodes: Vec<(NodeId, String)>,
+    pub changed_nodes: Vec<NodeChange>,
+    pub added_constraints: Vec<(NodeId, NodeId)>,
+    pub removed_constraints: Vec<(NodeId, NodeId)>,
+}
+
+impl GraphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty()
+            && self.removed_nodes.is_empty()
+            && self.changed_nodes.is_empty()
+            && self.added_constraints.is_empty()
+            && self.removed_constraints.is_empty()
+    }
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, op) in &self.removed_nodes {
+            writeln!(f, "- node {}: {}", id.0, op)?;
+        }
+        for (id, op) in &self.added_nodes {
+            writeln!(f, "+ node {}: {}", id.0, op)?;
+        }
+        for change in &self.changed_nodes {
+            writeln!(
+                f,
+                "~ node {}: {} -> {}",
+                change.id.0, change.before, change.after
+            )?;
+        }
+        for (a, b) in &self.removed_constraints {
+            writeln!(f, "- constraint {} == {}", a.0, b.0)?;
+        }
+        for (a, b) in &self.added_constraints {
+            writeln!(f, "+ constraint {} == {}", a.0, b.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Canonical text for an op: commutative operands are ordered by id.
+pub(crate) fn canonical_op(op: &Option<Op>) -> String {
+    let ordered = |a: &NodeId, b: &NodeId| (a.0.min(b.0), a.0.max(b.0));
+    match op {
+        None => "input".to_string(),
+        Some(Op::Const(v)) => format!("const {}", v),
+        Some(Op::Add(a, b, mode)) => {
+            let (a, b) = ordered(a, b);
+            format!("add {} {}{}", a, b, mode_suffix(*mode))
+        }
+        Some(Op::Mul(a, b, mode)) => {
+            let (a, b) = ordered(a, b);
+            format!("mul {} {}{}", a, b, mode_suffix(*mode))
+        }
+        Some(Op::Hint(parents, f)) => {
+            let ids: Vec<String> = parents.iter().map(|p| p.0.to_string()).collect();
+            format!(
+                "hint {} [{}]",
+                f.name().unwrap_or("anonymous"),
+                ids.join(", ")
+            )
+        }
+    }
+}
+
+fn mode_suffix(mode: Arithmetic) -> String {
+    match mode {
+        Arithmetic::Inherit => String::new(),
+        mode => format!(" {}", mode),
+    }
+}
+
+fn canonical_constraints(builder: &Builder) -> BTreeSet<(NodeId, NodeId)> {
+    builder
+        .constraints
+        .iter()
+        .map(|(a, b)| ((*a).min(*b), (*a).max(*b)))
+        .collect()
+}
+
+impl Builder {
+    /// Lists the nodes and constraints that differ between `self` (before) and
+    /// `other` (after). Nodes are matched by id; `add`/`mul` operand order and
+    /// constraint direction and order are ignored.
+    pub fn diff(&self, other: &Builder) -> GraphDiff {
+        let mut diff = GraphDiff::default();
+        for node in self.sorted_nodes() {
+            match other.nodes.get(&node.id) {
+                None => diff.removed_nodes.push((node.id, canonical_op(&node.op))),
+                Some(after) => {
+                    let (before, after) = (canonical_op(&node.op), canonical_op(&after.op));
+                    if before != after {
+                        diff.changed_nodes.push(NodeChange {
+                            id: node.id,
+                            before,
+                            after,
+                        });
+                    }
+                }
+            }
+        }
+        for node in other.sorted_nodes() {
+            if !self.nodes.contains_key(&node.id) {
+                diff.added_nodes.push((node.id, canonical_op(&node.op)));
+            }
+        }
+        let before = canonical_constraints(self);
+        let after = canonical_constraints(other);
+        diff.removed_constraints = before.difference(&after).copied().collect();
+        diff.added_constraints = after.difference(&before).copied().collect();
+        diff
+    }
+}
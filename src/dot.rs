@@ -0,0 +1,290 @@
+//! Graphviz DOT export for computation graphs.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+
+use crate::{Arithmetic, Builder, NodeData, Op};
+
+/// Direction in which Graphviz lays out the ranks of the graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RankDir {
+    TopBottom,
+    LeftRight,
+    BottomTop,
+    RightLeft,
+}
+
+impl fmt::Display for RankDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            RankDir::TopBottom => "TB",
+            RankDir::LeftRight => "LR",
+            RankDir::BottomTop => "BT",
+            RankDir::RightLeft => "RL",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Shape and colour used to render one kind of node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeStyle {
+    pub shape: String,
+    pub color: String,
+}
+
+impl NodeStyle {
+    pub fn new(shape: &str, color: &str) -> Self {
+        NodeStyle {
+            shape: shape.to_string(),
+            color: color.to_string(),
+        }
+    }
+}
+
+/// Controls how `write_dot` renders a graph.
+///
+/// The default produces a top-to-bottom layout with node ids in the labels and
+/// one colour per operation kind; values and constraints are left out.
+#[derive(Clone, Debug)]
+pub struct DotOptions {
+    pub rankdir: RankDir,
+    /// Include node ids in the labels (`Add Node1 + Node2` instead of `Add`).
+    pub show_ids: bool,
+    /// Append each node's evaluated value to its label.
+    pub show_values: bool,
+    /// Draw constraints as dashed edges, coloured red when they do not hold.
+    pub show_constraints: bool,
+    /// Group nodes created inside builder scopes into nested Graphviz clusters.
+    pub cluster_scopes: bool,
+    /// Append each node's metadata to its label as `key=value` lines.
+    pub show_metadata: bool,
+    pub input_style: NodeStyle,
+    pub const_style: NodeStyle,
+    pub add_style: NodeStyle,
+    pub mul_style: NodeStyle,
+    pub hint_style: NodeStyle,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions {
+            rankdir: RankDir::TopBottom,
+            show_ids: true,
+            show_values: false,
+            show_constraints: false,
+            cluster_scopes: true,
+            show_metadata: false,
+            input_style: NodeStyle::new("box", "lightblue"),
+            const_style: NodeStyle::new("box", "lightgray"),
+            add_style: NodeStyle::new("ellipse", "palegreen"),
+            mul_style: NodeStyle::new("ellipse", "khaki"),
+            hint_style: NodeStyle::new("diamond", "plum"),
+        }
+    }
+}
+
+impl DotOptions {
+    /// Default styling plus evaluated values and constraint edges, for debugging
+    /// a graph after `fill_nodes`.
+    pub fn annotated() -> Self {
+        DotOptions {
+            show_values: true,
+            show_constraints: true,
+            ..DotOptions::default()
+        }
+    }
+
+    fn style_for(&self, op: &Option<Op>) -> &NodeStyle {
+        match op {
+            Some(Op::Const(_)) => &self.const_style,
+            Some(Op::Add(_, _, _)) => &self.add_style,
+            Some(Op::Mul(_, _, _)) => &self.mul_style,
+            Some(Op::Hint(_, _)) => &self.hint_style,
+            None => &self.input_style,
+        }
+    }
+}
+
+impl Builder {
+    /// Outputs the current graph structure in DOT format for visualization using Graphviz.
+    /// Labels nodes with their operations and shows edges based on computation dependencies.
+    pub fn to_dot(&self) -> io::Result<()> {
+        self.to_dot_with(&DotOptions::default())
+    }
+
+    /// Like `to_dot`, but also labels every node with its evaluated value and draws
+    /// constraints as dashed edges, coloured red when the constraint does not hold.
+    /// Intended to be called after `fill_nodes` when debugging a failing graph.
+    pub fn to_dot_annotated(&self) -> io::Result<()> {
+        self.to_dot_with(&DotOptions::annotated())
+    }
+
+    /// Writes `graph.dot` using the given styling options.
+    pub fn to_dot_with(&self, options: &DotOptions) -> io::Result<()> {
+        let mut file = File::create("graph.dot")?;
+        self.write_dot(&mut file, options)
+    }
+
+    /// Writes the DOT representation of the graph to any writer.
+    /// Nodes, clusters and edges are emitted in id order so the output is reproducible.
+    pub fn write_dot<W: Write>(&self, out: &mut W, options: &DotOptions) -> io::Result<()> {
+        writeln!(out, "digraph ComputationalGraph {{")?;
+        writeln!(out, "  rankdir={};", options.rankdir)?;
+        let mut root = ScopeTree::default();
+        for node in self.sorted_nodes() {
+            let scope = match self.node_scopes.get(&node.id) {
+                Some(path) if options.cluster_scopes => path.as_str(),
+                _ => "",
+            };
+            root.insert(scope, node);
+        }
+        let mut next_cluster = 0;
+        root.write(out, self, options, 1, &mut next_cluster)?;
+        for node in self.sorted_nodes() {
+            if let Some(op) = &node.op {
+                match op {
+                    Op::Add(a, b, _) | Op::Mul(a, b, _) => {
+                        writeln!(out, "  Node{} -> Node{};", a.0, node.id.0)?;
+                        writeln!(out, "  Node{} -> Node{};", b.0, node.id.0)?;
+                    }
+                    Op::Hint(parents, _) => {
+                        for p in parents {
+                            writeln!(out, "  Node{} -> Node{};", p.0, node.id.0)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if options.show_constraints {
+            for (a, b) in &self.constraints {
+                let val_a = self.nodes.get(a).and_then(|n| n.value);
+                let val_b = self.nodes.get(b).and_then(|n| n.value);
+                let color = if val_a == val_b { "gray" } else { "red" };
+                writeln!(
+                    out,
+                    "  Node{} -> Node{} [style=dashed, dir=none, color={}];",
+                    a.0, b.0, color
+                )?;
+            }
+        }
+        writeln!(out, "}}")
+    }
+}
+
+/// Nodes grouped by scope path, used to emit nested `subgraph cluster_*` blocks.
+#[derive(Default)]
+struct ScopeTree<'a> {
+    nodes: Vec<&'a NodeData>,
+    children: Vec<(String, ScopeTree<'a>)>,
+}
+
+impl<'a> ScopeTree<'a> {
+    fn insert(&mut self, path: &str, node: &'a NodeData) {
+        let mut tree = self;
+        for segment in path.split('/').filter(|s| !s.is_empty()) {
+            let idx = match tree.children.iter().position(|(name, _)| name == segment) {
+                Some(idx) => idx,
+                None => {
+                    tree.children
+                        .push((segment.to_string(), ScopeTree::default()));
+                    tree.children.len() - 1
+                }
+            };
+            tree = &mut tree.children[idx].1;
+        }
+        tree.nodes.push(node);
+    }
+
+    fn write<W: Write>(
+        &self,
+        out: &mut W,
+        builder: &Builder,
+        options: &DotOptions,
+        depth: usize,
+        next_cluster: &mut usize,
+    ) -> io::Result<()> {
+        let indent = "  ".repeat(depth);
+        for node in &self.nodes {
+            let style = options.style_for(&node.op);
+            writeln!(
+                out,
+                "{}Node{} [label=\"{}\", shape={}, style=filled, fillcolor=\"{}\"{}]",
+                indent,
+                node.id.0,
+                node_label(
+                    node,
+                    builder.labels.get(&node.id),
+                    builder.metadata_map(node.id),
+                    options,
+                ),
+                style.shape,
+                style.color,
+                if builder.is_output_id(node.id) {
+                    ", peripheries=2"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        for (name, child) in &self.children {
+            writeln!(out, "{}subgraph cluster_{} {{", indent, next_cluster)?;
+            *next_cluster += 1;
+            writeln!(out, "{}  label=\"{}\";", indent, escape(name))?;
+            child.write(out, builder, options, depth + 1, next_cluster)?;
+            writeln!(out, "{}}}", indent)?;
+        }
+        Ok(())
+    }
+}
+
+/// `text` as the body of a quoted DOT label: backslashes and double quotes
+/// are escaped, and line breaks become DOT's `\n`.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn node_label(
+    node: &NodeData,
+    name: Option<&String>,
+    metadata: Option<&BTreeMap<String, String>>,
+    options: &DotOptions,
+) -> String {
+    let mut label = match &node.op {
+        Some(Op::Const(v)) => format!("Const({})", v),
+        Some(Op::Add(a, b, _)) if options.show_ids => format!("Add Node{} + Node{}", a.0, b.0),
+        Some(Op::Mul(a, b, _)) if options.show_ids => format!("Mul Node{} * Node{}", a.0, b.0),
+        Some(Op::Add(_, _, _)) => "Add".to_string(),
+        Some(Op::Mul(_, _, _)) => "Mul".to_string(),
+        Some(Op::Hint(_, _)) => "Hint".to_string(),
+        None => "Input".to_string(),
+    };
+    if let Some(Op::Add(_, _, mode) | Op::Mul(_, _, mode)) = &node.op {
+        if *mode != Arithmetic::Inherit {
+            label.push_str(&format!(" [{}]", mode));
+        }
+    }
+    if options.show_ids {
+        label = format!("Node{}: {}", node.id.0, label);
+    }
+    if let Some(name) = name {
+        label = format!("{}\\n{}", escape(name), label);
+    }
+    if options.show_values {
+        match node.value {
+            Some(v) => label.push_str(&format!("\\n= {}", v)),
+            None => label.push_str("\\n= ?"),
+        }
+    }
+    if options.show_metadata {
+        for (key, value) in metadata.into_iter().flatten() {
+            label.push_str(&format!("\\n{}", escape(&format!("{}={}", key, value))));
+        }
+    }
+    label
+}
@@ -0,0 +1,167 @@
+//! The `graph!` macro: a compact way to write graphs for fixtures and examples.
+//!
+//! Expressions are ordinary Rust expressions over `Var` handles, so `+`, `*`,
+//! parentheses and `u32` literals behave as usual and each operator emits the
+//! matching builder call.
+
+use std::cell::RefCell;
+use std::ops::{Add, Mul};
+
+use crate::{Builder, Node, NodeId};
+
+/// A node of a graph under construction by `graph!`.
+#[derive(Clone, Copy)]
+pub struct Var<'a> {
+    graph: &'a RefCell<Builder>,
+    id: NodeId,
+}
+
+impl<'a> Var<'a> {
+    #[track_caller]
+    pub fn input(graph: &'a RefCell<Builder>) -> Self {
+        let id = graph.borrow_mut().init().id;
+        Var { graph, id }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    fn node(&self) -> Node {
+        self.graph.borrow().handle_of(self.id)
+    }
+
+    #[track_caller]
+    fn binary(self, other: Var<'a>, op: fn(&mut Builder, &Node, &Node) -> Node) -> Var<'a> {
+        let (a, b) = (self.node(), other.node());
+        let id = op(&mut self.graph.borrow_mut(), &a, &b).id;
+        Var {
+            graph: self.graph,
+            id,
+        }
+    }
+
+    #[track_caller]
+    fn lift(self, value: u32) -> Var<'a> {
+        value.into_var(self.graph)
+    }
+}
+
+/// Values usable as operands in `graph!` expressions.
+pub trait IntoVar<'a> {
+    fn into_var(self, graph: &'a RefCell<Builder>) -> Var<'a>;
+}
+
+impl<'a> IntoVar<'a> for Var<'a> {
+    #[track_caller]
+    fn into_var(self, _: &'a RefCell<Builder>) -> Var<'a> {
+        self
+    }
+}
+
+impl<'a> IntoVar<'a> for u32 {
+    #[track_caller]
+    fn into_var(self, graph: &'a RefCell<Builder>) -> Var<'a> {
+        let id = graph.borrow_mut().constant(self).id;
+        Var { graph, id }
+    }
+}
+
+impl<'a> Add for Var<'a> {
+    type Output = Var<'a>;
+
+    #[track_caller]
+    fn add(self, other: Var<'a>) -> Var<'a> {
+        self.binary(other, |b, x, y| b.add(x, y))
+    }
+}
+
+impl<'a> Add<u32> for Var<'a> {
+    type Output = Var<'a>;
+
+    #[track_caller]
+    fn add(self, other: u32) -> Var<'a> {
+        self.binary(self.lift(other), |b, x, y| b.add(x, y))
+    }
+}
+
+impl<'a> Add<Var<'a>> for u32 {
+    type Output = Var<'a>;
+
+    #[track_caller]
+    fn add(self, other: Var<'a>) -> Var<'a> {
+        other.lift(self).binary(other, |b, x, y| b.add(x, y))
+    }
+}
+
+impl<'a> Mul for Var<'a> {
+    type Output = Var<'a>;
+
+    #[track_caller]
+    fn mul(self, other: Var<'a>) -> Var<'a> {
+        self.binary(other, |b, x, y| b.mul(x, y))
+    }
+}
+
+impl<'a> Mul<u32> for Var<'a> {
+    type Output = Var<'a>;
+
+    #[track_caller]
+    fn mul(self, other: u32) -> Var<'a> {
+        self.binary(self.lift(other), |b, x, y| b.mul(x, y))
+    }
+}
+
+impl<'a> Mul<Var<'a>> for u32 {
+    type Output = Var<'a>;
+
+    #[track_caller]
+    fn mul(self, other: Var<'a>) -> Var<'a> {
+        other.lift(self).binary(other, |b, x, y| b.mul(x, y))
+    }
+}
+
+/// Adds an equality constraint between two `graph!` operands.
+#[track_caller]
+pub fn assert_equal<'a>(graph: &'a RefCell<Builder>, a: impl IntoVar<'a>, b: impl IntoVar<'a>) {
+    let (a, b) = (a.into_var(graph).node(), b.into_var(graph).node());
+    graph.borrow_mut().assert_equal(&a, &b);
+}
+
+/// Builds a graph from a small DSL and returns it together with the node id of
+/// every named input and `let` binding.
+///
+/// ```ignore
+/// let (mut builder, names) = graph! {
+///     inputs: x, z;
+///     let y = x * x + x + 8;
+///     assert y == z;
+/// };
+/// ```
+///
+/// The left-hand side of `assert` must be a single token tree (a name, a
+/// literal or a parenthesized expression); the right-hand side may be any
+/// expression.
+#[macro_export]
+macro_rules! graph {
+    (inputs: $($input:ident),* ; $($body:tt)*) => {{
+        let graph = ::std::cell::RefCell::new($crate::Builder::new());
+        let mut names = ::std::collections::HashMap::<String, $crate::NodeId>::new();
+        $(
+            let $input = $crate::dsl::Var::input(&graph);
+            names.insert(stringify!($input).to_string(), $input.id());
+        )*
+        $crate::graph!(@stmts graph names; $($body)*);
+        (graph.into_inner(), names)
+    }};
+    (@stmts $graph:ident $names:ident;) => {};
+    (@stmts $graph:ident $names:ident; let $name:ident = $value:expr; $($rest:tt)*) => {
+        let $name = $crate::dsl::IntoVar::into_var($value, &$graph);
+        $names.insert(stringify!($name).to_string(), $name.id());
+        $crate::graph!(@stmts $graph $names; $($rest)*);
+    };
+    (@stmts $graph:ident $names:ident; assert $lhs:tt == $rhs:expr; $($rest:tt)*) => {
+        $crate::dsl::assert_equal(&$graph, $lhs, $rhs);
+        $crate::graph!(@stmts $graph $names; $($rest)*);
+    };
+}
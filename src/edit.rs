@@ -0,0 +1,169 @@
+//! Structural edits: replacing a node everywhere it is used and removing
+//! parts of a graph, keeping dependents and constraints consistent.
+
+use crate::collections::HashSet;
+use core::fmt;
+
+use crate::prelude::*;
+use crate::{Builder, Node, NodeId};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    UnknownNode(NodeId),
+    /// The replacement depends on the node it would replace.
+    Cycle {
+        old: NodeId,
+        new: NodeId,
+    },
+    /// The node is still read by other nodes; replace it first.
+    HasDependents {
+        node: NodeId,
+        dependents: Vec<NodeId>,
+    },
+    /// A pattern or replacement graph passed to `substitute` is unusable.
+    InvalidPattern(String),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::UnknownNode(id) => write!(f, "unknown node {:?}", id),
+            EditError::Cycle { old, new } => {
+                write!(f, "replacing {:?} with {:?} would create a cycle", old, new)
+            }
+            EditError::HasDependents { node, dependents } => {
+                write!(f, "{:?} is still used by {:?}", node, dependents)
+            }
+            EditError::InvalidPattern(msg) => write!(f, "invalid pattern: {}", msg),
+        }
+    }
+}
+
+impl core::error::Error for EditError {}
+
+impl Builder {
+    /// Fails for handles to removed nodes, even if their id was reused.
+    fn require(&self, node: &Node) -> Result<(), EditError> {
+        if self.is_live(node) {
+            Ok(())
+        } else {
+            Err(EditError::UnknownNode(node.id))
+        }
+    }
+
+    /// Clears the values of `roots` and everything downstream of them.
+    pub(crate) fn invalidate_from(&mut self, roots: Vec<NodeId>) {
+        let dependents = self.dependents_map();
+        let mut seen = HashSet::new();
+        let mut stack = roots;
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.value = None;
+            }
+            stack.extend(dependents.get(&id).into_iter().flatten());
+        }
+    }
+
+    /// Makes every node, constraint and output that uses `old` use `new`
+    /// instead, then removes `old`. Values downstream of the change are
+    /// cleared. `new` may have been created after the nodes that now read it.
+    pub fn replace_node(&mut self, old: &Node, new: &Node) -> Result<(), EditError> {
+        self.require(old)?;
+        self.require(new)?;
+        if old.id == new.id {
+            return Ok(());
+        }
+        if self.cone_of(new.id).contains(&old.id) {
+            return Err(EditError::Cycle {
+                old: old.id,
+                new: new.id,
+            });
+        }
+        let readers: Vec<NodeId> = self.dependents_map().remove(&old.id).unwrap_or_default();
+        for id in &readers {
+            self.log_node(*id);
+            if let Some(op) = self.nodes.get_mut(id).and_then(|n| n.op.as_mut()) {
+                op.replace_operand(old.id, new.id);
+            }
+            self.reordered |= *id <= new.id;
+        }
+        let swap = |id: NodeId| if id == old.id { new.id } else { id };
+        if self
+            .constraints
+            .iter()
+            .any(|(a, b)| *a == old.id || *b == old.id)
+        {
+            self.log_constraints();
+        }
+        for pair in self.constraints.iter_mut() {
+            let rewired = (swap(pair.0), swap(pair.1));
+            if rewired != *pair {
+                if let Some(location) = self.constraint_locations.remove(pair) {
+                    self.constraint_locations.entry(rewired).or_insert(location);
+                }
+                *pair = rewired;
+            }
+        }
+        for (_, id) in self.outputs.iter_mut() {
+            *id = swap(*id);
+        }
+        self.forget_node(old.id);
+        self.invalidate_from(readers);
+        Ok(())
+    }
+
+    /// Removes `node`, the constraints that mention it, and every node in its
+    /// operand cone that is no longer used by anything. Inputs, declared
+    /// outputs and constrained nodes are kept. Fails if other nodes still read
+    /// `node`. Returns the removed ids in ascending order.
+    pub fn remove_subtree(&mut self, node: &Node) -> Result<Vec<NodeId>, EditError> {
+        self.require(node)?;
+        if let Some(dependents) = self.dependents_map().remove(&node.id) {
+            let mut dependents: Vec<NodeId> = dependents;
+            dependents.dedup();
+            return Err(EditError::HasDependents {
+                node: node.id,
+                dependents,
+            });
+        }
+        let dropped: Vec<(NodeId, NodeId)> = self
+            .constraints
+            .iter()
+            .filter(|(a, b)| *a == node.id || *b == node.id)
+            .copied()
+            .collect();
+        if !dropped.is_empty() {
+            self.log_constraints();
+        }
+        self.constraints
+            .retain(|(a, b)| *a != node.id && *b != node.id);
+        for pair in dropped {
+            self.constraint_locations.remove(&pair);
+        }
+        self.outputs.retain(|(_, id)| *id != node.id);
+
+        let mut removed = vec![];
+        let mut stack = vec![node.id];
+        while let Some(id) = stack.pop() {
+            let Some(current) = self.nodes.get(&id) else {
+                continue;
+            };
+            let keep = id != node.id
+                && (current.op.is_none()
+                    || self.outputs.iter().any(|(_, o)| *o == id)
+                    || self.constraints.iter().any(|(a, b)| *a == id || *b == id)
+                    || self.nodes.values().any(|n| n.operands().contains(&id)));
+            if keep {
+                continue;
+            }
+            stack.extend(current.operands());
+            self.forget_node(id);
+            removed.push(id);
+        }
+        removed.sort();
+        Ok(removed)
+    }
+}
@@ -0,0 +1,233 @@
+//! Checking whether two graphs compute the same outputs.
+//!
+//! Inputs are matched by position (ascending id) and outputs are the sink nodes
+//! of each graph, also by ascending id.
+
+use std::collections::HashMap;
+
+use crate::rng::SplitMix64;
+use crate::violation::Evaluator;
+use crate::{Arithmetic, Builder, NodeId, Op};
+
+/// Largest number of input assignments `Bounded` enumerates.
+const BOUNDED_LIMIT: u64 = 1 << 24;
+
+/// How `Builder::equivalent_to` should compare two graphs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EquivalenceStrategy {
+    /// Hash-cons both graphs into one canonical DAG (commutativity of add/mul
+    /// and common subexpressions are normalized) and compare output classes.
+    /// Can prove equivalence but never refute it.
+    Structural,
+    /// Evaluate both graphs on `samples` random input vectors.
+    Randomized { samples: usize, seed: u64 },
+    /// Try `Structural` first and fall back to `Randomized`.
+    Auto { samples: usize, seed: u64 },
+    /// Evaluate both graphs on every assignment of inputs below `2^bits`,
+    /// which proves equivalence for that domain. Gives up with `Unknown` when
+    /// there are more than 2^24 assignments.
+    Bounded { bits: u32 },
+    /// Prove with the Z3 SMT solver that the outputs agree for every input
+    /// below `2^bits` (`bits` up to 32), or find a counterexample. Gives
+    /// `Unknown` for graphs with anonymous hints.
+    #[cfg(feature = "z3")]
+    Smt { bits: u32 },
+}
+
+/// Outcome of an equivalence check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Equivalence {
+    /// Outputs are structurally identical after canonicalization.
+    Proven,
+    /// Outputs agree for every input below `2^bits`, by enumeration or SMT.
+    ProvenBounded { bits: u32 },
+    /// No difference was found on this many random samples.
+    Tested { samples: usize },
+    /// These inputs (in ascending input id order) produce different outputs.
+    Counterexample { inputs: Vec<u32> },
+    /// The structural check could not decide, the bounded domain was too
+    /// large to enumerate, or the solver gave up.
+    Unknown,
+    /// The graphs do not have the same number of inputs or outputs.
+    Incompatible(String),
+}
+
+impl Equivalence {
+    /// True for `Proven`, `ProvenBounded` and `Tested`.
+    pub fn holds(&self) -> bool {
+        matches!(
+            self,
+            Equivalence::Proven | Equivalence::ProvenBounded { .. } | Equivalence::Tested { .. }
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CanonKey {
+    Input(usize),
+    Const(u32),
+    Add(usize, usize, Arithmetic),
+    Mul(usize, usize, Arithmetic),
+    NamedHint(String, Vec<usize>),
+    /// Anonymous hints cannot be compared, so each one is its own class.
+    AnonymousHint(usize, NodeId),
+}
+
+/// Assigns every node of `graph` a class in the shared `table`.
+fn canonicalize(
+    graph: &Builder,
+    graph_index: usize,
+    table: &mut HashMap<CanonKey, usize>,
+) -> HashMap<NodeId, usize> {
+    let input_pos: HashMap<NodeId, usize> = graph
+        .input_ids()
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| (id, i))
+        .collect();
+    let mut classes: HashMap<NodeId, usize> = HashMap::new();
+    for node in graph.sorted_nodes() {
+        let class = |id: &NodeId| classes.get(id).copied().unwrap_or(usize::MAX);
+        let key = match &node.op {
+            None => CanonKey::Input(input_pos[&node.id]),
+            Some(Op::Const(v)) => CanonKey::Const(*v),
+            Some(Op::Add(a, b, mode)) => {
+                let (a, b) = (class(a), class(b));
+                CanonKey::Add(a.min(b), a.max(b), mode.or(graph.arithmetic))
+            }
+            Some(Op::Mul(a, b, mode)) => {
+                let (a, b) = (class(a), class(b));
+                CanonKey::Mul(a.min(b), a.max(b), mode.or(graph.arithmetic))
+            }
+            Some(Op::Hint(parents, f)) => match f.name() {
+                Some(name) => {
+                    CanonKey::NamedHint(name.to_string(), parents.iter().map(class).collect())
+                }
+                None => CanonKey::AnonymousHint(graph_index, node.id),
+            },
+        };
+        let next = table.len();
+        let id = *table.entry(key).or_insert(next);
+        classes.insert(node.id, id);
+    }
+    classes
+}
+
+impl Builder {
+    /// Evaluates a copy of the graph with the given input values (ascending
+    /// input id order) and returns the values of `outputs`.
+    pub(crate) fn evaluate_with(&self, inputs: &[u32], outputs: &[NodeId]) -> Vec<Option<u32>> {
+        let mut copy = self.clone();
+        let assignment = self.input_ids().into_iter().zip(inputs.iter().copied());
+        copy.fill_nodes(assignment.collect());
+        outputs
+            .iter()
+            .map(|id| copy.nodes.get(id).and_then(|n| n.value))
+            .collect()
+    }
+
+    /// Checks whether `self` and `other` compute the same outputs for the same
+    /// inputs, e.g. to confirm an optimization pass preserved behavior.
+    pub fn equivalent_to(&self, other: &Builder, strategy: EquivalenceStrategy) -> Equivalence {
+        let (inputs_a, inputs_b) = (self.input_ids(), other.input_ids());
+        let (outputs_a, outputs_b) = (self.output_ids(), other.output_ids());
+        if inputs_a.len() != inputs_b.len() {
+            return Equivalence::Incompatible(format!(
+                "{} inputs vs {} inputs",
+                inputs_a.len(),
+                inputs_b.len()
+            ));
+        }
+        if outputs_a.len() != outputs_b.len() {
+            return Equivalence::Incompatible(format!(
+                "{} outputs vs {} outputs",
+                outputs_a.len(),
+                outputs_b.len()
+            ));
+        }
+
+        let (samples, seed) = match strategy {
+            EquivalenceStrategy::Structural | EquivalenceStrategy::Auto { .. } => {
+                let mut table = HashMap::new();
+                let classes_a = canonicalize(self, 0, &mut table);
+                let classes_b = canonicalize(other, 1, &mut table);
+                let same = outputs_a
+                    .iter()
+                    .zip(&outputs_b)
+                    .all(|(a, b)| classes_a[a] == classes_b[b]);
+                match (same, strategy) {
+                    (true, _) => return Equivalence::Proven,
+                    (false, EquivalenceStrategy::Auto { samples, seed }) => (samples, seed),
+                    (false, _) => return Equivalence::Unknown,
+                }
+            }
+            EquivalenceStrategy::Randomized { samples, seed } => (samples, seed),
+            EquivalenceStrategy::Bounded { bits } => {
+                return self.enumerate_equivalence(other, bits);
+            }
+            #[cfg(feature = "z3")]
+            EquivalenceStrategy::Smt { bits } => return self.smt_equivalence(other, bits),
+        };
+
+        let mut rng = SplitMix64::new(seed);
+        for _ in 0..samples {
+            let inputs: Vec<u32> = (0..inputs_a.len()).map(|_| rng.next_u32()).collect();
+            if self.evaluate_with(&inputs, &outputs_a) != other.evaluate_with(&inputs, &outputs_b) {
+                return Equivalence::Counterexample { inputs };
+            }
+        }
+        Equivalence::Tested { samples }
+    }
+
+    /// Proves with Z3 that both graphs agree on every input below `2^bits`:
+    /// `equivalent_to` with `EquivalenceStrategy::Smt { bits }`.
+    #[cfg(feature = "z3")]
+    pub fn prove_equivalent(&self, other: &Builder, bits: u32) -> Equivalence {
+        self.equivalent_to(other, EquivalenceStrategy::Smt { bits })
+    }
+
+    /// Compares the outputs of both graphs on every input assignment below
+    /// `2^bits`, the first input varying fastest.
+    fn enumerate_equivalence(&self, other: &Builder, bits: u32) -> Equivalence {
+        let (inputs_a, inputs_b) = (self.input_ids(), other.input_ids());
+        let (outputs_a, outputs_b) = (self.output_ids(), other.output_ids());
+        let max = match bits {
+            0 => 0,
+            bits => u32::MAX >> (32 - bits.min(32)),
+        };
+        let space = (u64::from(max) + 1).checked_pow(inputs_a.len() as u32);
+        if space.is_none_or(|space| space > BOUNDED_LIMIT) {
+            return Equivalence::Unknown;
+        }
+        let (eval_a, eval_b) = (Evaluator::new(self), Evaluator::new(other));
+        let mut current = vec![0u32; inputs_a.len()];
+        loop {
+            let values_a = eval_a.values(
+                &inputs_a
+                    .iter()
+                    .copied()
+                    .zip(current.iter().copied())
+                    .collect(),
+            );
+            let values_b = eval_b.values(
+                &inputs_b
+                    .iter()
+                    .copied()
+                    .zip(current.iter().copied())
+                    .collect(),
+            );
+            let differ = outputs_a
+                .iter()
+                .zip(&outputs_b)
+                .any(|(a, b)| values_a.get(a) != values_b.get(b));
+            if differ {
+                return Equivalence::Counterexample { inputs: current };
+            }
+            let Some(position) = current.iter().position(|v| *v < max) else {
+                return Equivalence::ProvenBounded { bits };
+            };
+            current[position] += 1;
+            current[..position].fill(0);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Exhaustive verification of graphs whose inputs are declared over small
+//! ranges: every assignment in the cartesian product is evaluated, split
+//! across threads.
+
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::violation::{search_exhaustively, space_size, Evaluator};
+use crate::{Builder, NodeId, Violation};
+
+/// Outcome of `Builder::verify_exhaustive`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exhaustion {
+    /// Every constraint holds for all of these assignments.
+    Verified { assignments: u64 },
+    /// The first failing assignment, the first input varying fastest.
+    Failed(Violation),
+    /// These inputs have no declared range (see `declare_range`).
+    Unbounded(Vec<NodeId>),
+    /// The input space has more than `u64::MAX` assignments.
+    TooLarge,
+}
+
+impl Exhaustion {
+    /// True for `Verified`.
+    pub fn holds(&self) -> bool {
+        matches!(self, Exhaustion::Verified { .. })
+    }
+}
+
+/// Splits `domain` into at most `parts` contiguous, non-empty ranges.
+fn split(domain: &RangeInclusive<u32>, parts: usize) -> Vec<RangeInclusive<u32>> {
+    let (start, end) = (u64::from(*domain.start()), u64::from(*domain.end()));
+    let len = end - start + 1;
+    let parts = (parts as u64).clamp(1, len);
+    (0..parts)
+        .map(|k| {
+            let lo = start + len * k / parts;
+            let hi = start + len * (k + 1) / parts - 1;
+            lo as u32..=hi as u32
+        })
+        .collect()
+}
+
+impl Builder {
+    /// Checks the constraints under every assignment of the declared input
+    /// ranges, using one thread per available core.
+    pub fn verify_exhaustive(&self) -> Exhaustion {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        self.verify_exhaustive_with(threads)
+    }
+
+    /// Like `verify_exhaustive`, on `threads` threads. The result does not
+    /// depend on the thread count.
+    pub fn verify_exhaustive_with(&self, threads: usize) -> Exhaustion {
+        let inputs = self.input_ids();
+        let unbounded: Vec<NodeId> = inputs
+            .iter()
+            .filter(|id| !self.input_ranges.contains_key(id))
+            .copied()
+            .collect();
+        if !unbounded.is_empty() {
+            return Exhaustion::Unbounded(unbounded);
+        }
+        let inputs: Vec<(NodeId, RangeInclusive<u32>)> = inputs
+            .into_iter()
+            .map(|id| (id, self.input_ranges[&id].clone()))
+            .collect();
+        let Some(assignments) = space_size(&inputs) else {
+            return Exhaustion::TooLarge;
+        };
+
+        // The last input varies slowest, so splitting its range gives chunks
+        // that are contiguous in search order.
+        let evaluator = Evaluator::new(self);
+        let chunks: Vec<Vec<(NodeId, RangeInclusive<u32>)>> = match inputs.split_last() {
+            Some(((id, domain), rest)) => split(domain, threads)
+                .into_iter()
+                .map(|part| {
+                    let mut chunk = rest.to_vec();
+                    chunk.push((*id, part));
+                    chunk
+                })
+                .collect(),
+            None => vec![vec![]],
+        };
+        let first_failure = AtomicUsize::new(usize::MAX);
+        let results: Vec<Option<Violation>> = thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .iter()
+                .enumerate()
+                .map(|(k, chunk)| {
+                    let (evaluator, first_failure) = (&evaluator, &first_failure);
+                    scope.spawn(move || {
+                        let found = search_exhaustively(evaluator, chunk, || {
+                            first_failure.load(Ordering::Relaxed) < k
+                        });
+                        if found.is_some() {
+                            first_failure.fetch_min(k, Ordering::Relaxed);
+                        }
+                        found
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("verification thread panicked"))
+                .collect()
+        });
+        match results.into_iter().flatten().next() {
+            Some(violation) => Exhaustion::Failed(violation),
+            None => Exhaustion::Verified { assignments },
+        }
+    }
+}
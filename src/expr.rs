@@ -0,0 +1,102 @@
+//! Infix pretty-printing of the expression computed by a node.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Builder, Node, NodeId, Op};
+
+impl Builder {
+    /// Reconstructs the infix expression computed by `node`, e.g. `((x0*x0) + x0) + 8`.
+    ///
+    /// Inputs are printed as `x<id>`, constants as their value and hints as
+    /// `hint(...)`. Intermediate nodes that are referenced more than once inside
+    /// the expression are hoisted into `let` bindings so sharing stays visible:
+    /// `let n1 = x0*x0 in (n1 + n1) + 8`.
+    pub fn expr_string(&self, node: &Node) -> String {
+        let mut uses: HashMap<NodeId, usize> = HashMap::new();
+        let mut seen = HashSet::new();
+        self.count_uses(node.id, &mut uses, &mut seen);
+
+        let mut bindings: Vec<NodeId> = uses
+            .iter()
+            .filter(|(id, count)| **count > 1 && self.is_compound(**id))
+            .map(|(id, _)| *id)
+            .collect();
+        self.sort_topologically(&mut bindings);
+        let shared: HashSet<NodeId> = bindings.iter().copied().collect();
+
+        let mut out = String::new();
+        for id in bindings {
+            out.push_str(&format!(
+                "let n{} = {} in ",
+                id.0,
+                self.render(id, &shared, true)
+            ));
+        }
+        out.push_str(&self.render(node.id, &shared, true));
+        out
+    }
+
+    fn is_compound(&self, id: NodeId) -> bool {
+        matches!(
+            self.nodes.get(&id).and_then(|n| n.op.as_ref()),
+            Some(Op::Add(_, _, _)) | Some(Op::Mul(_, _, _)) | Some(Op::Hint(_, _))
+        )
+    }
+
+    fn operands(&self, id: NodeId) -> Vec<NodeId> {
+        match self.nodes.get(&id).and_then(|n| n.op.as_ref()) {
+            Some(op) => op.operands(),
+            None => Vec::new(),
+        }
+    }
+
+    fn count_uses(
+        &self,
+        id: NodeId,
+        uses: &mut HashMap<NodeId, usize>,
+        seen: &mut HashSet<NodeId>,
+    ) {
+        if !seen.insert(id) {
+            return;
+        }
+        for operand in self.operands(id) {
+            *uses.entry(operand).or_insert(0) += 1;
+            self.count_uses(operand, uses, seen);
+        }
+    }
+
+    /// Renders `id`; shared nodes other than the one being defined (`top`) are
+    /// printed by name. Non-top compound expressions are parenthesized.
+    fn render(&self, id: NodeId, shared: &HashSet<NodeId>, top: bool) -> String {
+        if !top && shared.contains(&id) {
+            return format!("n{}", id.0);
+        }
+        let s = match self.nodes.get(&id).map(|n| &n.op) {
+            None => return "?".to_string(),
+            Some(None) => return format!("x{}", id.0),
+            Some(Some(Op::Const(v))) => return v.to_string(),
+            Some(Some(Op::Add(a, b, _))) => format!(
+                "{} + {}",
+                self.render(*a, shared, false),
+                self.render(*b, shared, false)
+            ),
+            Some(Some(Op::Mul(a, b, _))) => format!(
+                "{}*{}",
+                self.render(*a, shared, false),
+                self.render(*b, shared, false)
+            ),
+            Some(Some(Op::Hint(parents, _))) => {
+                let args: Vec<String> = parents
+                    .iter()
+                    .map(|p| self.render(*p, shared, false))
+                    .collect();
+                return format!("hint({})", args.join(", "));
+            }
+        };
+        if top {
+            s
+        } else {
+            format!("({})", s)
+        }
+    }
+}
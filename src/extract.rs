@@ -0,0 +1,138 @@
+//! Cutting a region out of a graph as a standalone graph, so a failing part
+//! of a huge graph can be isolated and shared as a minimal reproducer.
+
+use core::fmt;
+
+use crate::collections::{HashMap, HashSet};
+use crate::prelude::*;
+use crate::{Builder, Node, NodeId, Op};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExtractError {
+    /// A selected node does not exist.
+    UnknownNode(NodeId),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractError::UnknownNode(id) => write!(f, "Node {:?} does not exist", id),
+        }
+    }
+}
+
+impl core::error::Error for ExtractError {}
+
+impl Builder {
+    /// Copies the nodes in `nodes` into a new graph, with the edges between
+    /// them. Operands outside the selection (the boundary) become inputs of
+    /// the new graph, labelled like the nodes they replace. Constraints
+    /// between selected nodes, declared outputs, input names and ranges,
+    /// scopes, labels, metadata and source locations of selected nodes come
+    /// along; gadget instance records do not.
+    ///
+    /// Returns the new graph and the node each selected or boundary id now
+    /// maps to; boundary values from this graph's evaluation feed the new
+    /// inputs.
+    pub fn extract(
+        &self,
+        nodes: &[NodeId],
+    ) -> Result<(Builder, HashMap<NodeId, Node>), ExtractError> {
+        let selected: HashSet<NodeId> = nodes.iter().copied().collect();
+        let mut ids: Vec<NodeId> = selected.iter().copied().collect();
+        if let Some(id) = ids.iter().find(|id| !self.nodes.contains_key(id)) {
+            return Err(ExtractError::UnknownNode(*id));
+        }
+        self.sort_topologically(&mut ids);
+
+        let mut out = Builder::new();
+        out.arithmetic = self.arithmetic;
+        // Equal constants stay separate nodes, so every selected id keeps
+        // its own counterpart.
+        out.set_intern_constants(false);
+        let mut map: HashMap<NodeId, Node> = HashMap::new();
+        for id in ids {
+            let node = &self.nodes[&id];
+            let operands = node.op.as_ref().map(Op::operands).unwrap_or_default();
+            for operand in operands {
+                map.entry(operand).or_insert_with(|| {
+                    let input = out.init();
+                    if let Some(label) = self.labels.get(&operand) {
+                        out.insert_label(input.id, label.clone());
+                    }
+                    input
+                });
+            }
+            let copy = match &node.op {
+                None => out.init(),
+                Some(Op::Const(v)) => out.constant(*v),
+                Some(Op::Add(a, b, mode)) => out.add_with(&map[a], &map[b], *mode),
+                Some(Op::Mul(a, b, mode)) => out.mul_with(&map[a], &map[b], *mode),
+                Some(Op::Hint(parents, f)) => {
+                    let parents = parents.iter().map(|p| map[p].id).collect();
+                    out.new_node(Some(Op::Hint(parents, f.clone())))
+                }
+            };
+            if let Some(label) = self.labels.get(&id) {
+                out.insert_label(copy.id, label.clone());
+            }
+            if let Some(scope) = self.node_scopes.get(&id) {
+                out.node_scopes.insert(copy.id, scope.clone());
+            }
+            if let Some(location) = self.locations.get(&id) {
+                out.locations.insert(copy.id, location);
+            }
+            if let Some(entries) = self.metadata.get(&id) {
+                out.insert_metadata(copy.id, entries.clone());
+            }
+            if let Some(range) = self.input_ranges.get(&id) {
+                out.input_ranges.insert(copy.id, range.clone());
+            }
+            map.insert(id, copy);
+        }
+        out.set_intern_constants(self.intern_constants);
+
+        for (name, id) in &self.input_names {
+            if selected.contains(id) {
+                out.input_names.insert(name.clone(), map[id].id);
+            }
+        }
+        for (a, b) in &self.constraints {
+            if !(selected.contains(a) && selected.contains(b)) {
+                continue;
+            }
+            let (na, nb) = (map[a], map[b]);
+            out.assert_equal(&na, &nb);
+            if let Some(location) = self.constraint_locations.get(&(*a, *b)) {
+                out.constraint_locations.insert((na.id, nb.id), location);
+            }
+        }
+        for (name, id) in &self.outputs {
+            if selected.contains(id) {
+                out.outputs.push((name.clone(), map[id].id));
+            }
+        }
+        log!(
+            "✂️ Extracted {} nodes ({} boundary inputs)",
+            selected.len(),
+            map.len() - selected.len()
+        );
+        Ok((out, map))
+    }
+
+    /// Extracts `roots` together with everything they depend on, which needs
+    /// no boundary inputs. Useful for isolating a single failing output.
+    pub fn extract_cone(
+        &self,
+        roots: &[NodeId],
+    ) -> Result<(Builder, HashMap<NodeId, Node>), ExtractError> {
+        let mut nodes = Vec::new();
+        for root in roots {
+            if !self.nodes.contains_key(root) {
+                return Err(ExtractError::UnknownNode(*root));
+            }
+            nodes.extend(self.cone_of(*root));
+        }
+        self.extract(&nodes)
+    }
+}
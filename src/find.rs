@@ -0,0 +1,101 @@
+//! Locating nodes by predicate, label or metadata key, so tooling can find
+//! "the node labeled `fee_total`" without scanning internals. Labels and
+//! metadata keys are indexed as they are set, so those lookups do not walk
+//! the graph.
+
+use alloc::collections::BTreeMap;
+
+use crate::prelude::*;
+use crate::{Builder, NodeData, NodeId};
+
+impl Builder {
+    /// Nodes for which `predicate` holds, in the order of `nodes()`.
+    pub fn find<'a, F>(&'a self, predicate: F) -> impl Iterator<Item = &'a NodeData> + 'a
+    where
+        F: Fn(&NodeData) -> bool + 'a,
+    {
+        self.nodes().filter(move |node| predicate(node))
+    }
+
+    /// Nodes labelled `label`, in ascending id order. Labels need not be
+    /// unique.
+    pub fn find_by_label<'a>(&'a self, label: &str) -> impl Iterator<Item = &'a NodeData> + 'a {
+        self.label_index
+            .get(label)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.nodes.get(id))
+    }
+
+    /// Nodes that have metadata under `key`, with their value, in ascending
+    /// id order.
+    pub fn find_by_metadata<'a>(
+        &'a self,
+        key: &'a str,
+    ) -> impl Iterator<Item = (&'a NodeData, &'a str)> + 'a {
+        self.metadata_index
+            .get(key)
+            .into_iter()
+            .flatten()
+            .filter_map(move |id| {
+                let value = self.metadata.get(id)?.get(key)?;
+                Some((self.nodes.get(id)?, value.as_str()))
+            })
+    }
+
+    /// Sets the label of `id`, keeping the label index in step.
+    pub(crate) fn insert_label(&mut self, id: NodeId, label: String) {
+        self.remove_label(id);
+        self.label_index
+            .entry(label.clone())
+            .or_default()
+            .insert(id);
+        self.labels.insert(id, label);
+    }
+
+    pub(crate) fn remove_label(&mut self, id: NodeId) {
+        let Some(old) = self.labels.remove(&id) else {
+            return;
+        };
+        if let Some(ids) = self.label_index.get_mut(&old) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.label_index.remove(&old);
+            }
+        }
+    }
+
+    /// Replaces all metadata of `id`, keeping the key index in step.
+    pub(crate) fn insert_metadata(&mut self, id: NodeId, entries: BTreeMap<String, String>) {
+        self.remove_all_metadata(id);
+        for key in entries.keys() {
+            self.index_metadata_key(id, key);
+        }
+        self.metadata.insert(id, entries);
+    }
+
+    pub(crate) fn remove_all_metadata(&mut self, id: NodeId) {
+        let Some(entries) = self.metadata.remove(&id) else {
+            return;
+        };
+        for key in entries.keys() {
+            self.unindex_metadata_key(id, key);
+        }
+    }
+
+    pub(crate) fn index_metadata_key(&mut self, id: NodeId, key: &str) {
+        self.metadata_index
+            .entry(key.to_string())
+            .or_default()
+            .insert(id);
+    }
+
+    pub(crate) fn unindex_metadata_key(&mut self, id: NodeId, key: &str) {
+        if let Some(ids) = self.metadata_index.get_mut(key) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.metadata_index.remove(key);
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Stable structural fingerprint of a graph.
+//!
+//! The hash is FNV-1a over a canonical byte encoding of the graph, so it does not
+//! depend on HashMap iteration order, the Rust version, or the platform.
+
+use crate::{Arithmetic, Builder, Op};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Minimal FNV-1a hasher with a fixed, documented output.
+pub(crate) struct Fnv64(u64);
+
+impl Fnv64 {
+    pub(crate) fn new() -> Self {
+        Fnv64(FNV_OFFSET)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub(crate) fn write_u64(&mut self, v: u64) {
+        self.write(&v.to_le_bytes());
+    }
+
+    /// Writes nothing for `Inherit`, so graphs without overrides keep their
+    /// fingerprints.
+    fn write_mode(&mut self, mode: Arithmetic) {
+        match mode {
+            Arithmetic::Inherit => {}
+            Arithmetic::Wrapping => self.write(&[1]),
+            Arithmetic::Checked => self.write(&[2]),
+            Arithmetic::Saturating => self.write(&[3]),
+            Arithmetic::Modular(m) => {
+                self.write(&[4]);
+                self.write_u64(u64::from(m.get()));
+            }
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Builder {
+    /// Returns a hash over the canonicalized structure of the graph: every node's
+    /// op, operands and constants (in id order) and the set of equality constraints.
+    ///
+    /// Evaluated values are not included, and neither are hint function bodies —
+    /// two hints over the same parents hash identically. Constraints are treated
+    /// as unordered and symmetric, so `assert_equal(a, b)` and `assert_equal(b, a)`
+    /// produce the same fingerprint.
+    pub fn fingerprint(&self) -> u64 {
+        let mut h = Fnv64::new();
+        let nodes = self.sorted_nodes();
+        h.write_u64(nodes.len() as u64);
+        for node in nodes {
+            h.write_u64(node.id.0 as u64);
+            match &node.op {
+                None => h.write(&[0]),
+                Some(Op::Const(v)) => {
+                    h.write(&[1]);
+                    h.write_u64(u64::from(*v));
+                }
+                Some(Op::Add(a, b, mode)) => {
+                    h.write(&[2]);
+                    h.write_u64(a.0 as u64);
+                    h.write_u64(b.0 as u64);
+                    h.write_mode(*mode);
+                }
+                Some(Op::Mul(a, b, mode)) => {
+                    h.write(&[3]);
+                    h.write_u64(a.0 as u64);
+                    h.write_u64(b.0 as u64);
+                    h.write_mode(*mode);
+                }
+                Some(Op::Hint(parents, _)) => {
+                    h.write(&[4]);
+                    h.write_u64(parents.len() as u64);
+                    for p in parents {
+                        h.write_u64(p.0 as u64);
+                    }
+                }
+            }
+        }
+        let mut constraints: Vec<(usize, usize)> = self
+            .constraints
+            .iter()
+            .map(|(a, b)| (a.0.min(b.0), a.0.max(b.0)))
+            .collect();
+        constraints.sort_unstable();
+        h.write_u64(constraints.len() as u64);
+        for (a, b) in constraints {
+            h.write_u64(a as u64);
+            h.write_u64(b as u64);
+        }
+        h.finish()
+    }
+}
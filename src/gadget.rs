@@ -0,0 +1,244 @@
+//! Reusable subgraphs with named input and output ports.
+
+use crate::collections::HashMap;
+
+use crate::prelude::*;
+use crate::{Builder, Node, NodeId};
+
+/// A subgraph that can be instantiated into a builder any number of times.
+///
+/// ```ignore
+/// let adder = Gadget::build("adder", &["a", "b"], &["sum"], |b, ins| {
+///     vec![b.add(&ins[0], &ins[1])]
+/// });
+/// let outs = builder.instantiate(&adder, &[x, y]);
+/// ```
+#[derive(Clone)]
+pub struct Gadget {
+    pub(crate) name: String,
+    pub(crate) graph: Builder,
+    pub(crate) inputs: Vec<(String, NodeId)>,
+    pub(crate) outputs: Vec<(String, NodeId)>,
+}
+
+impl Gadget {
+    /// Builds a gadget by running `body` on a fresh builder. `body` receives one
+    /// input node per name in `inputs` and must return one node per name in
+    /// `outputs`.
+    ///
+    /// # Panics
+    /// If `body` returns a different number of nodes than `outputs` names.
+    pub fn build(
+        name: &str,
+        inputs: &[&str],
+        outputs: &[&str],
+        body: impl FnOnce(&mut Builder, &[Node]) -> Vec<Node>,
+    ) -> Self {
+        let mut graph = Builder::new();
+        let input_nodes: Vec<Node> = inputs.iter().map(|_| graph.init()).collect();
+        let output_nodes = body(&mut graph, &input_nodes);
+        assert_eq!(
+            output_nodes.len(),
+            outputs.len(),
+            "gadget `{}` declares {} outputs but its body returned {}",
+            name,
+            outputs.len(),
+            output_nodes.len()
+        );
+        Gadget {
+            name: name.to_string(),
+            graph,
+            inputs: ports(inputs, &input_nodes),
+            outputs: ports(outputs, &output_nodes),
+        }
+    }
+
+    /// Wraps an already built graph. Input nodes of `graph` that are not listed
+    /// as ports become fresh inputs of the host graph on every instantiation.
+    pub fn from_builder(
+        name: &str,
+        graph: Builder,
+        inputs: &[(&str, Node)],
+        outputs: &[(&str, Node)],
+    ) -> Self {
+        let to_ports = |list: &[(&str, Node)]| {
+            list.iter()
+                .map(|(port, node)| (port.to_string(), node.id))
+                .collect()
+        };
+        Gadget {
+            name: name.to_string(),
+            inputs: to_ports(inputs),
+            outputs: to_ports(outputs),
+            graph,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn input_names(&self) -> Vec<&str> {
+        self.inputs.iter().map(|(n, _)| n.as_str()).collect()
+    }
+
+    pub fn output_names(&self) -> Vec<&str> {
+        self.outputs.iter().map(|(n, _)| n.as_str()).collect()
+    }
+}
+
+fn ports(names: &[&str], nodes: &[Node]) -> Vec<(String, NodeId)> {
+    names
+        .iter()
+        .zip(nodes)
+        .map(|(name, node)| (name.to_string(), node.id))
+        .collect()
+}
+
+impl Builder {
+    /// Copies the gadget's nodes and constraints into this graph, wiring its
+    /// input ports to `inputs`, and returns the nodes bound to its output ports
+    /// in declaration order. The copied nodes are placed in a scope named after
+    /// the gadget, so DOT exports show each instance as a cluster.
+    ///
+    /// # Panics
+    /// If `inputs` does not provide exactly one node per input port.
+    pub fn instantiate(&mut self, gadget: &Gadget, inputs: &[Node]) -> Vec<Node> {
+        assert_eq!(
+            inputs.len(),
+            gadget.inputs.len(),
+            "gadget `{}` expects {} inputs, got {}",
+            gadget.name,
+            gadget.inputs.len(),
+            inputs.len()
+        );
+        let bindings = gadget
+            .inputs
+            .iter()
+            .zip(inputs)
+            .map(|((_, port), node)| (*port, *node))
+            .collect();
+        let first_new = self.next_id;
+        self.enter_scope(&gadget.name);
+        let scope = self.scope_stack.join("/");
+        let map = self.copy_from(&gadget.graph, bindings);
+        self.exit_scope();
+        let outputs: Vec<Node> = gadget.outputs.iter().map(|(_, id)| map[id]).collect();
+        let mut nodes: Vec<NodeId> = map
+            .values()
+            .map(|n| n.id)
+            .filter(|id| id.0 >= first_new)
+            .collect();
+        nodes.sort();
+        self.instances.push(Instance {
+            gadget: gadget.name.clone(),
+            scope,
+            inputs: inputs.iter().map(|n| n.id).collect(),
+            outputs: outputs.iter().map(|n| n.id).collect(),
+            nodes,
+        });
+        outputs
+    }
+
+    /// Gadget instances created by `instantiate`, in creation order.
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// Returns a copy of the graph with instance boundaries resolved for
+    /// backends and viewers; see `InlineMode`. Nodes, ids and constraints are
+    /// unchanged, only the instance bookkeeping and scopes differ.
+    pub fn inline(&self, mode: InlineMode) -> Builder {
+        let mut out = self.clone();
+        match mode {
+            InlineMode::Flatten => {
+                for instance in &self.instances {
+                    let parent = instance.scope.rfind('/').map(|i| &instance.scope[..i]);
+                    for id in &instance.nodes {
+                        let Some(path) = out.node_scopes.get(id) else {
+                            continue;
+                        };
+                        let Some(rest) = strip_scope(path, &instance.scope) else {
+                            continue;
+                        };
+                        let flattened = match (parent, rest.is_empty()) {
+                            (Some(p), true) => p.to_string(),
+                            (Some(p), false) => format!("{}/{}", p, rest),
+                            (None, false) => rest.to_string(),
+                            (None, true) => {
+                                out.node_scopes.remove(id);
+                                continue;
+                            }
+                        };
+                        out.node_scopes.insert(*id, flattened);
+                    }
+                }
+                out.instances.clear();
+            }
+            InlineMode::PreserveBoundaries => {
+                let mut counts: HashMap<&str, usize> = HashMap::new();
+                for (k, instance) in self.instances.iter().enumerate() {
+                    let n = counts.entry(instance.scope.as_str()).or_insert(0);
+                    let unique = format!("{}[{}]", instance.scope, n);
+                    *n += 1;
+                    for id in &instance.nodes {
+                        if let Some(path) = out.node_scopes.get(id) {
+                            if let Some(rest) = strip_scope(path, &instance.scope) {
+                                out.node_scopes.insert(*id, join_scope(&unique, rest));
+                            }
+                        }
+                    }
+                    // Nested instances were recorded before their parent.
+                    for nested in &mut out.instances[..k] {
+                        if let Some(rest) = strip_scope(&nested.scope, &instance.scope) {
+                            nested.scope = join_scope(&unique, rest);
+                        }
+                    }
+                    out.instances[k].scope = unique;
+                }
+            }
+        }
+        out
+    }
+}
+
+/// The remainder of `path` below `scope`, if `path` lies inside `scope`.
+fn strip_scope<'a>(path: &'a str, scope: &str) -> Option<&'a str> {
+    let rest = path.strip_prefix(scope)?;
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix('/')
+    }
+}
+
+fn join_scope(scope: &str, rest: &str) -> String {
+    if rest.is_empty() {
+        scope.to_string()
+    } else {
+        format!("{}/{}", scope, rest)
+    }
+}
+
+/// One instantiation of a gadget.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instance {
+    pub gadget: String,
+    /// Scope path the instance's nodes were created in.
+    pub scope: String,
+    pub inputs: Vec<NodeId>,
+    pub outputs: Vec<NodeId>,
+    /// Nodes created by the instance (excluding the bound inputs).
+    pub nodes: Vec<NodeId>,
+}
+
+/// How `Builder::inline` treats gadget instance boundaries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InlineMode {
+    /// Drop instance records and the scopes introduced by instantiation, leaving
+    /// plain nodes for export backends that do not understand hierarchy.
+    Flatten,
+    /// Keep instance records and give every instance its own scope
+    /// (`adder[0]`, `adder[1]`, ...), so DOT renders each as a separate cluster.
+    PreserveBoundaries,
+}
@@ -0,0 +1,493 @@
+//! Standard gadgets: vetted building blocks that add their own soundness
+//! constraints instead of trusting hints.
+//!
+//! All arithmetic in the graph wraps modulo 2^32, so range facts are
+//! established by bit decomposition. Gadgets that compare or divide take a
+//! `bits` bound on their operands; the constraints are only sound when the
+//! operands really fit in that many bits, which the gadgets enforce by range
+//! checking them.
+//!
+//! Hints created here are named (`gadgets::bit`, `gadgets::divmod`, ...), so graphs
+//! using them can be serialized; `register_hints` adds them to a registry for
+//! reading such graphs back.
+
+pub mod ec;
+pub mod sha256;
+
+use crate::prelude::*;
+use crate::{Builder, HintRegistry, Node};
+
+fn bit_hint(v: &[u32]) -> u32 {
+    v[0].checked_shr(v[1]).unwrap_or(0) & 1
+}
+
+fn div_hint(v: &[u32]) -> u32 {
+    v[0].checked_div(v[1]).unwrap_or(0)
+}
+
+fn rem_hint(v: &[u32]) -> u32 {
+    v[0].checked_rem(v[1]).unwrap_or(0)
+}
+
+/// Output 0 is the quotient, output 1 the remainder.
+fn divmod_hint(v: &[u32]) -> u32 {
+    match v[2] {
+        0 => div_hint(v),
+        _ => rem_hint(v),
+    }
+}
+
+fn isqrt_hint(v: &[u32]) -> u32 {
+    v[0].isqrt()
+}
+
+fn abs_diff_hint(v: &[u32]) -> u32 {
+    v[0].abs_diff(v[1])
+}
+
+fn one_hot_hint(v: &[u32]) -> u32 {
+    u32::from(v[0] == v[1])
+}
+
+fn lo16_hint(v: &[u32]) -> u32 {
+    v[0] & 0xffff
+}
+
+fn hi16_hint(v: &[u32]) -> u32 {
+    v[0] >> 16
+}
+
+/// Registers every hint used by this module, with its operand count, for
+/// `Builder::from_sexpr` and `Builder::from_bytes_any_version`.
+pub fn register_hints(registry: &mut HintRegistry) {
+    registry.register_with_arity("gadgets::bit", 2, bit_hint);
+    // `div` and `rem` are what `divmod` wrote before it used a two-output hint.
+    registry.register_with_arity("gadgets::div", 2, div_hint);
+    registry.register_with_arity("gadgets::rem", 2, rem_hint);
+    // The dividend, the divisor and the output index added by `hint_multi`.
+    registry.register_with_arity("gadgets::divmod", 3, divmod_hint);
+    registry.register_with_arity("gadgets::isqrt", 1, isqrt_hint);
+    registry.register_with_arity("gadgets::abs_diff", 2, abs_diff_hint);
+    registry.register_with_arity("gadgets::one_hot", 2, one_hot_hint);
+    registry.register_with_arity("gadgets::lo16", 1, lo16_hint);
+    registry.register_with_arity("gadgets::hi16", 1, hi16_hint);
+    registry.register_with_arity("gadgets::field_inv", 2, ec::field_inv_hint);
+}
+
+/// `a - b` with wrapping semantics, expressed as `a + b * (2^32 - 1)`.
+pub fn sub(b: &mut Builder, x: &Node, y: &Node) -> Node {
+    let minus_one = b.constant(u32::MAX);
+    let neg = b.mul(y, &minus_one);
+    b.add(x, &neg)
+}
+
+/// Constrains `x` to be 0 or 1 via `x * x == x`.
+pub fn assert_bool(b: &mut Builder, x: &Node) {
+    let sq = b.mul(x, x);
+    b.assert_equal(&sq, x);
+}
+
+/// Decomposes `x` into `n` boolean nodes, least significant first, and
+/// constrains their weighted sum to equal `x`. This also proves `x < 2^n`.
+///
+/// # Panics
+/// If `n` is 0 or larger than 32.
+pub fn to_bits(b: &mut Builder, x: &Node, n: u32) -> Vec<Node> {
+    assert!((1..=32).contains(&n), "bit width must be in 1..=32");
+    let mut bits = Vec::with_capacity(n as usize);
+    let mut sum: Option<Node> = None;
+    for i in 0..n {
+        let index = b.constant(i);
+        let bit = b.hint_named("gadgets::bit", &[*x, index], bit_hint);
+        assert_bool(b, &bit);
+        let weight = b.constant(1u32 << i);
+        let term = b.mul(&bit, &weight);
+        sum = Some(match sum {
+            Some(acc) => b.add(&acc, &term),
+            None => term,
+        });
+        bits.push(bit);
+    }
+    if let Some(sum) = sum {
+        b.assert_equal(&sum, x);
+    }
+    bits
+}
+
+/// Recomposes boolean nodes, least significant first, into `sum bits[i] * 2^i`.
+///
+/// # Panics
+/// If `bits` is empty or longer than 32.
+pub fn from_bits(b: &mut Builder, bits: &[Node]) -> Node {
+    assert!(
+        (1..=32).contains(&bits.len()),
+        "bit width must be in 1..=32"
+    );
+    let mut sum = bits[0];
+    for (i, bit) in bits.iter().enumerate().skip(1) {
+        let weight = b.constant(1u32 << i);
+        let term = b.mul(bit, &weight);
+        sum = b.add(&sum, &term);
+    }
+    sum
+}
+
+/// `x AND y` for boolean nodes.
+pub fn bit_and(b: &mut Builder, x: &Node, y: &Node) -> Node {
+    b.mul(x, y)
+}
+
+/// `x XOR y` for boolean nodes, as `x + y - 2xy`.
+pub fn bit_xor(b: &mut Builder, x: &Node, y: &Node) -> Node {
+    let xy = b.mul(x, y);
+    let minus_two = b.constant(u32::MAX - 1);
+    let twice = b.mul(&xy, &minus_two);
+    let sum = b.add(x, y);
+    b.add(&sum, &twice)
+}
+
+/// `NOT x` for a boolean node, as `1 - x`.
+pub fn bit_not(b: &mut Builder, x: &Node) -> Node {
+    let one = b.constant(1);
+    sub(b, &one, x)
+}
+
+/// Constrains `x < 2^bits`.
+pub fn range_check(b: &mut Builder, x: &Node, bits: u32) {
+    to_bits(b, x, bits);
+}
+
+/// Splits `x` into its low and high 16-bit halves, constrained by
+/// `lo + hi * 2^16 == x` with both halves range checked.
+pub fn split16(b: &mut Builder, x: &Node) -> (Node, Node) {
+    let lo = b.hint_named("gadgets::lo16", &[*x], lo16_hint);
+    let hi = b.hint_named("gadgets::hi16", &[*x], hi16_hint);
+    range_check(b, &lo, 16);
+    range_check(b, &hi, 16);
+    let shift = b.constant(1 << 16);
+    let high = b.mul(&hi, &shift);
+    let recomposed = b.add(&lo, &high);
+    b.assert_equal(&recomposed, x);
+    (lo, hi)
+}
+
+/// Encodes `index` as `n` boolean nodes of which exactly the one at position
+/// `index` is 1, constrained by booleanity, `sum e_i == 1` and
+/// `sum i * e_i == index`. The constraints also prove `index < n`.
+///
+/// # Panics
+/// If `n` is 0.
+pub fn one_hot(b: &mut Builder, index: &Node, n: u32) -> Vec<Node> {
+    assert!(n > 0, "one_hot needs at least one position");
+    let mut flags = Vec::with_capacity(n as usize);
+    let mut count: Option<Node> = None;
+    let mut weighted: Option<Node> = None;
+    for i in 0..n {
+        let position = b.constant(i);
+        let flag = b.hint_named("gadgets::one_hot", &[*index, position], one_hot_hint);
+        assert_bool(b, &flag);
+        let term = b.mul(&flag, &position);
+        count = Some(match count {
+            Some(acc) => b.add(&acc, &flag),
+            None => flag,
+        });
+        weighted = Some(match weighted {
+            Some(acc) => b.add(&acc, &term),
+            None => term,
+        });
+        flags.push(flag);
+    }
+    let one = b.constant(1);
+    b.assert_equal(&count.unwrap(), &one);
+    b.assert_equal(&weighted.unwrap(), index);
+    flags
+}
+
+/// A read-only table of constants. Reads are constrained: each one costs
+/// a `one_hot` encoding of the index and an inner product with the table, so
+/// linear in the table size, and an out-of-range index fails the constraints.
+#[derive(Clone, Debug)]
+pub struct Rom {
+    entries: Vec<Node>,
+}
+
+impl Rom {
+    /// Creates one constant node per entry.
+    ///
+    /// # Panics
+    /// If `table` is empty.
+    pub fn new(b: &mut Builder, table: &[u32]) -> Self {
+        assert!(!table.is_empty(), "a ROM needs at least one entry");
+        Rom {
+            entries: table.iter().map(|&v| b.constant(v)).collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entry at `index`.
+    pub fn read(&self, b: &mut Builder, index: &Node) -> Node {
+        let flags = one_hot(b, index, self.entries.len() as u32);
+        let mut value: Option<Node> = None;
+        for (flag, entry) in flags.iter().zip(&self.entries) {
+            let term = b.mul(flag, entry);
+            value = Some(match value {
+                Some(acc) => b.add(&acc, &term),
+                None => term,
+            });
+        }
+        value.unwrap()
+    }
+}
+
+/// Returns a boolean node that is 1 iff `x < y`. Both operands are range
+/// checked to `bits` bits.
+///
+/// # Panics
+/// If `bits` is 0 or larger than 31.
+pub fn less_than(b: &mut Builder, x: &Node, y: &Node, bits: u32) -> Node {
+    assert!(
+        (1..=31).contains(&bits),
+        "comparator width must be in 1..=31"
+    );
+    range_check(b, x, bits);
+    range_check(b, y, bits);
+    // x - y + 2^bits lies in [1, 2^(bits+1)); its top bit is set iff x >= y.
+    let offset = b.constant(1u32 << bits);
+    let shifted = b.add(x, &offset);
+    let diff = sub(b, &shifted, y);
+    let diff_bits = to_bits(b, &diff, bits + 1);
+    let one = b.constant(1);
+    sub(b, &one, &diff_bits[bits as usize])
+}
+
+/// Returns `if cond { x } else { y }`, constraining `cond` to be boolean.
+pub fn select(b: &mut Builder, cond: &Node, x: &Node, y: &Node) -> Node {
+    assert_bool(b, cond);
+    let delta = sub(b, x, y);
+    let picked = b.mul(cond, &delta);
+    b.add(y, &picked)
+}
+
+/// Returns `x` clamped to `lo..=hi`, for a `bits`-bit `x`.
+///
+/// # Panics
+/// If `lo > hi`, `hi` does not fit in `bits` bits or `bits` is not in `1..=31`.
+pub fn clamp(b: &mut Builder, x: &Node, lo: u32, hi: u32, bits: u32) -> Node {
+    assert!(lo <= hi, "clamp needs lo <= hi");
+    assert!(
+        (1..=31).contains(&bits) && hi < 1 << bits,
+        "clamp bounds must fit in `bits` bits"
+    );
+    let (lo, hi) = (b.constant(lo), b.constant(hi));
+    let below = less_than(b, x, &lo, bits);
+    let above = less_than(b, &hi, x, bits);
+    let upper = select(b, &above, &hi, x);
+    select(b, &below, &lo, &upper)
+}
+
+/// `min(x + y, 2^bits - 1)` for `bits`-bit operands.
+///
+/// # Panics
+/// If `bits` is 0 or larger than 30.
+pub fn saturating_add(b: &mut Builder, x: &Node, y: &Node, bits: u32) -> Node {
+    assert!(
+        (1..=30).contains(&bits),
+        "saturating_add width must be in 1..=30"
+    );
+    range_check(b, x, bits);
+    range_check(b, y, bits);
+    let sum = b.add(x, y);
+    saturate(b, &sum, bits, bits + 1)
+}
+
+/// `min(x * y, 2^bits - 1)` for `bits`-bit operands.
+///
+/// # Panics
+/// If `bits` is 0 or larger than 15 (the exact product must not wrap).
+pub fn saturating_mul(b: &mut Builder, x: &Node, y: &Node, bits: u32) -> Node {
+    assert!(
+        (1..=15).contains(&bits),
+        "saturating_mul width must be in 1..=15"
+    );
+    range_check(b, x, bits);
+    range_check(b, y, bits);
+    let product = b.mul(x, y);
+    saturate(b, &product, bits, 2 * bits)
+}
+
+/// `min(value, 2^bits - 1)` for a `value_bits`-bit `value`.
+fn saturate(b: &mut Builder, value: &Node, bits: u32, value_bits: u32) -> Node {
+    let max = b.constant((1 << bits) - 1);
+    let over = less_than(b, &max, value, value_bits);
+    select(b, &over, &max, value)
+}
+
+/// Comparator pairs `(i, j)` with `i < j` of Batcher's merge-exchange
+/// network (Knuth's Algorithm 5.2.2M), which sorts any `n` inputs.
+fn merge_exchange(n: usize) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    if n < 2 {
+        return pairs;
+    }
+    let t = usize::BITS - (n - 1).leading_zeros();
+    let mut p = 1 << (t - 1);
+    while p > 0 {
+        let (mut q, mut r, mut d) = (1 << (t - 1), 0, p);
+        loop {
+            pairs.extend((0..n - d).filter(|i| i & p == r).map(|i| (i, i + d)));
+            if q == p {
+                break;
+            }
+            d = q - p;
+            q >>= 1;
+            r = p;
+        }
+        p >>= 1;
+    }
+    pairs
+}
+
+/// Sorts `bits`-bit values ascending with a sorting network. Every
+/// comparator is a `less_than` followed by two `select`s, so the outputs are
+/// a permutation of the inputs and in order by construction, with no hint
+/// to trust.
+pub fn sort(b: &mut Builder, nodes: &[Node], bits: u32) -> Vec<Node> {
+    let mut wires = nodes.to_vec();
+    for (i, j) in merge_exchange(wires.len()) {
+        let (x, y) = (wires[i], wires[j]);
+        let swap = less_than(b, &y, &x, bits);
+        wires[i] = select(b, &swap, &y, &x);
+        wires[j] = select(b, &swap, &x, &y);
+    }
+    wires
+}
+
+/// How `assert_permutation` compares two lists.
+#[derive(Clone, Debug)]
+pub enum PermutationCheck {
+    /// Sort both lists of `bits`-bit values with `sort` and compare them
+    /// position by position. Sound without assumptions, at the cost of two
+    /// sorting networks.
+    Sorted { bits: u32 },
+    /// Check `prod (r - x_i) == prod (r - y_i)` for the challenge `r`, with
+    /// one multiplication per element. Only meaningful over a prime modulus
+    /// (`Builder::with_modulus`) and with a challenge the prover cannot pick,
+    /// such as a hash of the lists; then two different multisets pass with
+    /// probability at most `len / p`.
+    GrandProduct { challenge: Node },
+}
+
+/// Constrains `xs` and `ys` to hold the same multiset of values.
+///
+/// # Panics
+/// If the lists have different lengths.
+pub fn assert_permutation(b: &mut Builder, xs: &[Node], ys: &[Node], check: &PermutationCheck) {
+    assert_eq!(xs.len(), ys.len(), "permutations must have equal lengths");
+    match check {
+        PermutationCheck::Sorted { bits } => {
+            let xs = sort(b, xs, *bits);
+            let ys = sort(b, ys, *bits);
+            for (x, y) in xs.iter().zip(&ys) {
+                b.assert_equal(x, y);
+            }
+        }
+        PermutationCheck::GrandProduct { challenge } => {
+            let minus_one = b.constant(b.modulus().map_or(u32::MAX, |p| p - 1));
+            let product = |b: &mut Builder, list: &[Node]| {
+                let mut acc = b.constant(1);
+                for x in list {
+                    let neg = b.mul(x, &minus_one);
+                    let factor = b.add(challenge, &neg);
+                    acc = b.mul(&acc, &factor);
+                }
+                acc
+            };
+            let (px, py) = (product(b, xs), product(b, ys));
+            b.assert_equal(&px, &py);
+        }
+    }
+}
+
+/// Returns `|x - y|`, checked against a comparator on `bits`-bit operands.
+pub fn abs_diff(b: &mut Builder, x: &Node, y: &Node, bits: u32) -> Node {
+    let d = b.hint_named("gadgets::abs_diff", &[*x, *y], abs_diff_hint);
+    let lt = less_than(b, x, y, bits);
+    let y_minus_x = sub(b, y, x);
+    let x_minus_y = sub(b, x, y);
+    let expected = select(b, &lt, &y_minus_x, &x_minus_y);
+    b.assert_equal(&d, &expected);
+    d
+}
+
+/// Recomputes a Merkle root from `leaf` and its authentication `path`, and
+/// constrains it to equal `root`. Each step is `(sibling, direction)`, where
+/// `direction` is a boolean node: 0 when the current node is the left child
+/// (`hasher(current, sibling)`), 1 when it is the right one. Digests are
+/// single nodes and `hasher` builds one two-to-one hash gadget per level.
+/// Returns the recomputed root.
+pub fn merkle_verify(
+    b: &mut Builder,
+    leaf: &Node,
+    path: &[(Node, Node)],
+    root: &Node,
+    mut hasher: impl FnMut(&mut Builder, &Node, &Node) -> Node,
+) -> Node {
+    let mut current = *leaf;
+    for (sibling, direction) in path {
+        let left = select(b, direction, sibling, &current);
+        let right = select(b, direction, &current, sibling);
+        current = hasher(b, &left, &right);
+    }
+    b.assert_equal(&current, root);
+    current
+}
+
+/// Integer division with remainder for `bits`-bit operands. Returns `(q, r)`,
+/// computed by one two-output hint and constrained by `q * d + r == a`,
+/// `r < d` and `q < 2^bits`.
+///
+/// # Panics
+/// If `bits` is 0 or larger than 16 (larger operands could make `q * d + r`
+/// wrap around and admit false quotients).
+pub fn divmod(b: &mut Builder, a: &Node, d: &Node, bits: u32) -> (Node, Node) {
+    assert!((1..=16).contains(&bits), "divmod width must be in 1..=16");
+    let outputs = b.hint_multi("gadgets::divmod", &[*a, *d], 2, divmod_hint);
+    let (q, r) = (outputs[0], outputs[1]);
+    range_check(b, &q, bits);
+    let qd = b.mul(&q, d);
+    let recomposed = b.add(&qd, &r);
+    b.assert_equal(&recomposed, a);
+    let lt = less_than(b, &r, d, bits);
+    let one = b.constant(1);
+    b.assert_equal(&lt, &one);
+    (q, r)
+}
+
+/// Integer square root of a `bits`-bit value: returns `s` constrained by
+/// `s * s <= x < (s + 1) * (s + 1)`.
+///
+/// # Panics
+/// If `bits` is 0 or larger than 30.
+pub fn sqrt(b: &mut Builder, x: &Node, bits: u32) -> Node {
+    assert!((1..=30).contains(&bits), "sqrt width must be in 1..=30");
+    range_check(b, x, bits);
+    let s = b.hint_named("gadgets::isqrt", &[*x], isqrt_hint);
+    // s < 2^ceil(bits/2) keeps s^2 + 2s below 2^32, so nothing below can wrap.
+    range_check(b, &s, bits.div_ceil(2));
+    let sq = b.mul(&s, &s);
+    let below = sub(b, x, &sq); // x - s^2 >= 0
+    range_check(b, &below, bits);
+    let two = b.constant(2);
+    let two_s = b.mul(&two, &s);
+    let next = b.add(&sq, &two_s); // (s + 1)^2 - 1
+    let above = sub(b, &next, x); // (s + 1)^2 - 1 - x >= 0
+    range_check(b, &above, bits);
+    s
+}
@@ -0,0 +1,234 @@
+//! Elliptic-curve gadgets for short Weierstrass curves `y^2 = x^3 + ax + b`
+//! over the prime field of a `Builder::with_modulus` builder.
+//!
+//! Points are projective `(X : Y : Z)` with the identity at `(0 : 1 : 0)`.
+//! Addition uses the complete formulas of Renes, Costello and Batina
+//! ("Complete addition formulas for prime order elliptic curves",
+//! Algorithm 1): they hold for every pair of points, doublings and the
+//! identity included, provided the curve has odd order. Only `to_affine`
+//! needs a hint.
+
+use super::to_bits;
+use crate::prelude::*;
+use crate::{Builder, Node};
+
+/// The curve `y^2 = x^3 + ax + b` over the builder's field. Its order must
+/// be odd for the addition formulas to be complete.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Curve {
+    pub a: u32,
+    pub b: u32,
+}
+
+/// A point in projective coordinates.
+#[derive(Clone, Debug)]
+pub struct Point {
+    pub x: Node,
+    pub y: Node,
+    pub z: Node,
+}
+
+pub(super) fn field_inv_hint(v: &[u32]) -> u32 {
+    // z^(p - 2) by square and multiply, which is 1/z for prime p. A zero
+    // modulus can only come from a malformed graph; answer 0 rather than
+    // dividing by it.
+    if v[1] == 0 {
+        return 0;
+    }
+    let (p, mut base, mut exp) = (u64::from(v[1]), u64::from(v[0]), v[1].wrapping_sub(2));
+    let mut result = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % p;
+        }
+        base = base * base % p;
+        exp >>= 1;
+    }
+    (result % p) as u32
+}
+
+fn prime(b: &Builder) -> u32 {
+    b.modulus()
+        .expect("elliptic-curve gadgets need a Builder::with_modulus field")
+}
+
+fn sub(b: &mut Builder, x: &Node, y: &Node) -> Node {
+    let minus_one = b.constant(prime(b) - 1);
+    let neg = b.mul(y, &minus_one);
+    b.add(x, &neg)
+}
+
+/// `if cond { x } else { y }` for field elements, with `cond` boolean.
+fn select(b: &mut Builder, cond: &Node, x: &Node, y: &Node) -> Node {
+    let delta = sub(b, x, y);
+    let picked = b.mul(cond, &delta);
+    b.add(y, &picked)
+}
+
+fn select_point(b: &mut Builder, cond: &Node, p: &Point, q: &Point) -> Point {
+    Point {
+        x: select(b, cond, &p.x, &q.x),
+        y: select(b, cond, &p.y, &q.y),
+        z: select(b, cond, &p.z, &q.z),
+    }
+}
+
+/// The point with affine coordinates `(x, y)`. Combine with
+/// `assert_on_curve` when the coordinates come from inputs.
+pub fn point(b: &mut Builder, x: &Node, y: &Node) -> Point {
+    Point {
+        x: *x,
+        y: *y,
+        z: b.constant(1),
+    }
+}
+
+/// The point at infinity.
+pub fn identity(b: &mut Builder) -> Point {
+    Point {
+        x: b.constant(0),
+        y: b.constant(1),
+        z: b.constant(0),
+    }
+}
+
+/// Constrains the affine point `(x, y)` to lie on `curve`.
+pub fn assert_on_curve(b: &mut Builder, curve: &Curve, x: &Node, y: &Node) {
+    let (a, c) = (b.constant(curve.a), b.constant(curve.b));
+    let y2 = b.mul(y, y);
+    let x2 = b.mul(x, x);
+    let x2_plus_a = b.add(&x2, &a);
+    let rhs = b.mul(&x2_plus_a, x);
+    let rhs = b.add(&rhs, &c);
+    b.assert_equal(&y2, &rhs);
+}
+
+/// `p + q`, for any two points of the curve.
+///
+/// # Panics
+/// If the builder has no modulus.
+pub fn add(b: &mut Builder, curve: &Curve, p: &Point, q: &Point) -> Point {
+    let a = b.constant(curve.a);
+    let b3 = b.constant(((3 * u64::from(curve.b)) % u64::from(prime(b))) as u32);
+    let (x1, y1, z1) = (&p.x, &p.y, &p.z);
+    let (x2, y2, z2) = (&q.x, &q.y, &q.z);
+
+    let t0 = b.mul(x1, x2);
+    let t1 = b.mul(y1, y2);
+    let t2 = b.mul(z1, z2);
+    let t3 = b.add(x1, y1);
+    let t4 = b.add(x2, y2);
+    let t3 = b.mul(&t3, &t4);
+    let t4 = b.add(&t0, &t1);
+    let t3 = sub(b, &t3, &t4);
+    let t4 = b.add(x1, z1);
+    let t5 = b.add(x2, z2);
+    let t4 = b.mul(&t4, &t5);
+    let t5 = b.add(&t0, &t2);
+    let t4 = sub(b, &t4, &t5);
+    let t5 = b.add(y1, z1);
+    let x3 = b.add(y2, z2);
+    let t5 = b.mul(&t5, &x3);
+    let x3 = b.add(&t1, &t2);
+    let t5 = sub(b, &t5, &x3);
+    let z3 = b.mul(&a, &t4);
+    let x3 = b.mul(&b3, &t2);
+    let z3 = b.add(&x3, &z3);
+    let x3 = sub(b, &t1, &z3);
+    let z3 = b.add(&t1, &z3);
+    let y3 = b.mul(&x3, &z3);
+    let t1 = b.add(&t0, &t0);
+    let t1 = b.add(&t1, &t0);
+    let t2 = b.mul(&a, &t2);
+    let t4 = b.mul(&b3, &t4);
+    let t1 = b.add(&t1, &t2);
+    let t2 = sub(b, &t0, &t2);
+    let t2 = b.mul(&a, &t2);
+    let t4 = b.add(&t4, &t2);
+    let t0 = b.mul(&t1, &t4);
+    let y3 = b.add(&y3, &t0);
+    let t0 = b.mul(&t5, &t4);
+    let x3 = b.mul(&t3, &x3);
+    let x3 = sub(b, &x3, &t0);
+    let t0 = b.mul(&t3, &t1);
+    let z3 = b.mul(&t5, &z3);
+    let z3 = b.add(&z3, &t0);
+    Point {
+        x: x3,
+        y: y3,
+        z: z3,
+    }
+}
+
+/// `2p`.
+pub fn double(b: &mut Builder, curve: &Curve, p: &Point) -> Point {
+    add(b, curve, p, p)
+}
+
+/// `scalar * p` for a `bits`-bit scalar, with a fixed window of `window`
+/// bits: the multiples `0p ..= (2^window - 1)p` are tabulated once, then
+/// each window costs `window` doublings, one table lookup and one addition.
+///
+/// # Panics
+/// If the builder has no modulus, `2^bits` exceeds it (the scalar's bit
+/// decomposition would no longer be unique) or `window` is not in `1..=8`.
+pub fn scalar_mul(
+    b: &mut Builder,
+    curve: &Curve,
+    scalar: &Node,
+    bits: u32,
+    window: u32,
+    p: &Point,
+) -> Point {
+    assert!(
+        bits >= 1 && 1u64 << bits <= u64::from(prime(b)),
+        "scalar must have between 1 bit and log2(modulus) bits"
+    );
+    assert!((1..=8).contains(&window), "window must be in 1..=8");
+    let scalar_bits = to_bits(b, scalar, bits);
+    let mut table = vec![identity(b), p.clone()];
+    while table.len() < 1 << window {
+        let next = add(b, curve, table.last().unwrap(), p);
+        table.push(next);
+    }
+
+    let mut acc = identity(b);
+    let chunks: Vec<&[Node]> = scalar_bits.chunks(window as usize).collect();
+    for chunk in chunks.into_iter().rev() {
+        for _ in 0..chunk.len() {
+            acc = double(b, curve, &acc);
+        }
+        // Select the table entry bit by bit, least significant first.
+        let mut entries = table[..1 << chunk.len()].to_vec();
+        for bit in chunk {
+            entries = entries
+                .chunks(2)
+                .map(|pair| select_point(b, bit, &pair[1], &pair[0]))
+                .collect();
+        }
+        acc = add(b, curve, &acc, &entries[0]);
+    }
+    acc
+}
+
+/// The affine coordinates of `p`, constrained by `z * z_inv == 1`, so the
+/// identity cannot be converted.
+pub fn to_affine(b: &mut Builder, p: &Point) -> (Node, Node) {
+    let modulus = b.constant(prime(b));
+    let z_inv = b.hint_named("gadgets::field_inv", &[p.z, modulus], field_inv_hint);
+    let one = b.constant(1);
+    let product = b.mul(&p.z, &z_inv);
+    b.assert_equal(&product, &one);
+    (b.mul(&p.x, &z_inv), b.mul(&p.y, &z_inv))
+}
+
+/// Constrains `p` and `q` to be the same point: `X1 Z2 == X2 Z1` and
+/// `Y1 Z2 == Y2 Z1`.
+pub fn assert_point_equal(b: &mut Builder, p: &Point, q: &Point) {
+    let x1z2 = b.mul(&p.x, &q.z);
+    let x2z1 = b.mul(&q.x, &p.z);
+    b.assert_equal(&x1z2, &x2z1);
+    let y1z2 = b.mul(&p.y, &q.z);
+    let y2z1 = b.mul(&q.y, &p.z);
+    b.assert_equal(&y1z2, &y2z1);
+}
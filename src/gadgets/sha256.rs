@@ -0,0 +1,182 @@
+//! The SHA-256 compression function (FIPS 180-4) over bit nodes, so
+//! commitments to byte strings can be computed and constrained in a graph.
+//!
+//! Words are ordinary nodes. Each word that feeds a bitwise function is
+//! decomposed with `to_bits`, the function is evaluated bit by bit with
+//! `bit_and`/`bit_xor`/`bit_not`, and modular additions use the graph's own
+//! 2^32 wrapping. One compression adds roughly 200,000 nodes; evaluate such
+//! graphs with `EvalOrder::Topological`.
+
+use super::{bit_and, bit_not, bit_xor, from_bits, range_check, to_bits};
+use crate::prelude::*;
+use crate::{Builder, Node};
+
+/// The initial hash value.
+pub const IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// A word node together with its bits, least significant first.
+#[derive(Clone)]
+struct Word {
+    value: Node,
+    bits: Vec<Node>,
+}
+
+impl Word {
+    fn new(b: &mut Builder, value: Node) -> Self {
+        let bits = to_bits(b, &value, 32);
+        Word { value, bits }
+    }
+
+    fn rotr(&self, n: usize) -> Vec<Node> {
+        (0..32).map(|i| self.bits[(i + n) % 32]).collect()
+    }
+
+    fn shr(&self, b: &mut Builder, n: usize) -> Vec<Node> {
+        let zero = b.constant(0);
+        (0..32)
+            .map(|i| *self.bits.get(i + n).unwrap_or(&zero))
+            .collect()
+    }
+}
+
+fn xor3(b: &mut Builder, x: &[Node], y: &[Node], z: &[Node]) -> Node {
+    let bits: Vec<Node> = (0..32)
+        .map(|i| {
+            let xy = bit_xor(b, &x[i], &y[i]);
+            bit_xor(b, &xy, &z[i])
+        })
+        .collect();
+    from_bits(b, &bits)
+}
+
+fn big_sigma(b: &mut Builder, w: &Word, r: [usize; 3]) -> Node {
+    xor3(b, &w.rotr(r[0]), &w.rotr(r[1]), &w.rotr(r[2]))
+}
+
+fn small_sigma(b: &mut Builder, w: &Word, r: [usize; 2], s: usize) -> Node {
+    let shifted = w.shr(b, s);
+    xor3(b, &w.rotr(r[0]), &w.rotr(r[1]), &shifted)
+}
+
+/// `(e AND f) XOR (NOT e AND g)`
+fn ch(b: &mut Builder, e: &Word, f: &Word, g: &Word) -> Node {
+    let bits: Vec<Node> = (0..32)
+        .map(|i| {
+            let ef = bit_and(b, &e.bits[i], &f.bits[i]);
+            let not_e = bit_not(b, &e.bits[i]);
+            let eg = bit_and(b, &not_e, &g.bits[i]);
+            bit_xor(b, &ef, &eg)
+        })
+        .collect();
+    from_bits(b, &bits)
+}
+
+/// `(a AND b) XOR (a AND c) XOR (b AND c)`
+fn maj(b: &mut Builder, x: &Word, y: &Word, z: &Word) -> Node {
+    let bits: Vec<Node> = (0..32)
+        .map(|i| {
+            let xy = bit_and(b, &x.bits[i], &y.bits[i]);
+            let xz = bit_and(b, &x.bits[i], &z.bits[i]);
+            let yz = bit_and(b, &y.bits[i], &z.bits[i]);
+            let acc = bit_xor(b, &xy, &xz);
+            bit_xor(b, &acc, &yz)
+        })
+        .collect();
+    from_bits(b, &bits)
+}
+
+fn sum(b: &mut Builder, terms: &[&Node]) -> Node {
+    let mut acc = *terms[0];
+    for term in &terms[1..] {
+        acc = b.add(&acc, *term);
+    }
+    acc
+}
+
+/// Applies the compression function to an 8-word chaining `state` and a
+/// 16-word message `block` (big-endian words), returning the next state.
+///
+/// # Panics
+/// If `state` does not have 8 words or `block` does not have 16.
+pub fn compress(b: &mut Builder, state: &[Node], block: &[Node]) -> Vec<Node> {
+    assert_eq!(state.len(), 8, "SHA-256 state has 8 words");
+    assert_eq!(block.len(), 16, "SHA-256 block has 16 words");
+    let mut w: Vec<Word> = block.iter().map(|x| Word::new(b, *x)).collect();
+    for t in 16..64 {
+        let s1 = small_sigma(b, &w[t - 2], [17, 19], 10);
+        let s0 = small_sigma(b, &w[t - 15], [7, 18], 3);
+        let next = sum(b, &[&s1, &w[t - 7].value, &s0, &w[t - 16].value]);
+        w.push(Word::new(b, next));
+    }
+
+    let mut v: Vec<Word> = state.iter().map(|x| Word::new(b, *x)).collect();
+    for (t, k) in K.iter().enumerate() {
+        let [a, bb, c, d, e, f, g, h] = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| &v[i]);
+        let s1 = big_sigma(b, e, [6, 11, 25]);
+        let choice = ch(b, e, f, g);
+        let k = b.constant(*k);
+        let t1 = sum(b, &[&h.value, &s1, &choice, &k, &w[t].value]);
+        let s0 = big_sigma(b, a, [2, 13, 22]);
+        let majority = maj(b, a, bb, c);
+        let t2 = b.add(&s0, &majority);
+        let new_e = b.add(&d.value, &t1);
+        let new_a = b.add(&t1, &t2);
+        let new_e = Word::new(b, new_e);
+        let new_a = Word::new(b, new_a);
+        v.pop();
+        v.insert(4, new_e);
+        v.remove(3);
+        v.insert(0, new_a);
+    }
+    state
+        .iter()
+        .zip(&v)
+        .map(|(x, y)| b.add(x, &y.value))
+        .collect()
+}
+
+/// Hashes a message of byte nodes, each range checked to 8 bits, and returns
+/// the digest as 8 big-endian words. Padding depends only on the length, so
+/// it is added as constants.
+pub fn hash(b: &mut Builder, message: &[Node]) -> Vec<Node> {
+    for byte in message {
+        range_check(b, byte, 8);
+    }
+    let bit_len = (message.len() as u64) * 8;
+    let mut bytes = message.to_vec();
+    let mut padding = vec![0x80u8];
+    padding.resize(1 + (119 - message.len() % 64) % 64, 0);
+    padding.extend(bit_len.to_be_bytes());
+    bytes.extend(padding.into_iter().map(|p| b.constant(p.into())));
+
+    let mut state: Vec<Node> = IV.iter().map(|&x| b.constant(x)).collect();
+    for block in bytes.chunks(64) {
+        let words: Vec<Node> = block
+            .chunks(4)
+            .map(|word| {
+                let mut acc = word[0];
+                for byte in &word[1..] {
+                    let shift = b.constant(256);
+                    let shifted = b.mul(&acc, &shift);
+                    acc = b.add(&shifted, byte);
+                }
+                acc
+            })
+            .collect();
+        state = compress(b, &state, &words);
+    }
+    state
+}
@@ -0,0 +1,147 @@
+//! A gRPC front-end for `GraphService`, generated from
+//! `proto/graphengine.proto` with tonic and prost (feature `grpc`).
+//!
+//! Each RPC makes the matching `GraphService` call and translates the result.
+//! Unknown graphs are answered with `NOT_FOUND`, unparsable graphs and unknown
+//! inputs with `INVALID_ARGUMENT`, and evaluating before every input has a
+//! value with `FAILED_PRECONDITION`. Messages larger
+//! than `MAX_MESSAGE_BYTES` are refused and requests slower than
+//! `REQUEST_TIMEOUT` are cancelled. The `graphengine-grpc` binary serves this
+//! on a TCP port.
+
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::service::{GraphService, ServiceError};
+
+/// Messages and service stubs generated from `proto/graphengine.proto`.
+pub mod proto {
+    tonic::include_proto!("graphengine");
+}
+
+use proto::graph_engine_server::{GraphEngine, GraphEngineServer};
+
+/// Largest request message accepted, in bytes.
+pub const MAX_MESSAGE_BYTES: usize = 16 << 20;
+
+/// Longest a request may take to be answered.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl From<ServiceError> for Status {
+    fn from(e: ServiceError) -> Self {
+        let message = e.to_string();
+        match e {
+            ServiceError::UnknownGraph(_) => Status::not_found(message),
+            ServiceError::Graph(_) | ServiceError::UnknownInput(_) => {
+                Status::invalid_argument(message)
+            }
+            ServiceError::MissingInput(_) => Status::failed_precondition(message),
+            ServiceError::Cancelled => Status::deadline_exceeded(message),
+        }
+    }
+}
+
+/// Implements the generated `GraphEngine` service on a shared `GraphService`.
+pub struct GrpcService {
+    service: GraphService,
+}
+
+impl GrpcService {
+    pub fn new(service: GraphService) -> Self {
+        GrpcService { service }
+    }
+
+    /// The service wrapped for `tonic::transport::Server`, with the message
+    /// size limit applied.
+    pub fn into_server(self) -> GraphEngineServer<GrpcService> {
+        GraphEngineServer::new(self).max_decoding_message_size(MAX_MESSAGE_BYTES)
+    }
+}
+
+#[tonic::async_trait]
+impl GraphEngine for GrpcService {
+    async fn upload_graph(
+        &self,
+        request: Request<proto::UploadGraphRequest>,
+    ) -> Result<Response<proto::UploadGraphResponse>, Status> {
+        let handle = self.service.upload(&request.into_inner().graph_json)?;
+        Ok(Response::new(proto::UploadGraphResponse {
+            handle,
+            input_names: self.service.input_names(handle)?,
+        }))
+    }
+
+    async fn submit_inputs(
+        &self,
+        request: Request<proto::SubmitInputsRequest>,
+    ) -> Result<Response<proto::SubmitInputsResponse>, Status> {
+        let request = request.into_inner();
+        let values: Vec<(&str, u32)> = request
+            .values
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        self.service.submit_inputs(request.handle, &values)?;
+        Ok(Response::new(proto::SubmitInputsResponse {}))
+    }
+
+    async fn evaluate(
+        &self,
+        request: Request<proto::EvaluateRequest>,
+    ) -> Result<Response<proto::EvaluateResponse>, Status> {
+        let outputs = self.service.evaluate(request.into_inner().handle)?;
+        Ok(Response::new(proto::EvaluateResponse {
+            outputs: outputs
+                .into_iter()
+                .map(|(name, value)| proto::OutputValue { name, value })
+                .collect(),
+        }))
+    }
+
+    async fn constraint_report(
+        &self,
+        request: Request<proto::ConstraintReportRequest>,
+    ) -> Result<Response<proto::ConstraintReportResponse>, Status> {
+        let report = self
+            .service
+            .constraint_report(request.into_inner().handle)?;
+        Ok(Response::new(proto::ConstraintReportResponse {
+            total: report.total as u64,
+            failed: report
+                .failed
+                .into_iter()
+                .map(|c| proto::FailedConstraint {
+                    left: c.left.0 as u64,
+                    right: c.right.0 as u64,
+                    left_value: c.left_value,
+                    right_value: c.right_value,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn remove_graph(
+        &self,
+        request: Request<proto::RemoveGraphRequest>,
+    ) -> Result<Response<proto::RemoveGraphResponse>, Status> {
+        self.service.remove(request.into_inner().handle)?;
+        Ok(Response::new(proto::RemoveGraphResponse {}))
+    }
+}
+
+/// Serves the gRPC API on `listener`. Connections that fail to be accepted
+/// are skipped, so the server keeps running.
+pub async fn serve(
+    listener: TcpListener,
+    service: GraphService,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .add_service(GrpcService::new(service).into_server())
+        .serve_with_incoming(TcpIncoming::from(listener))
+        .await
+}
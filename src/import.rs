@@ -0,0 +1,373 @@
+//! Gadgets stored as JSON files, so common components can be shipped as
+//! artifacts and spliced into a graph at build time.
+//!
+//! ```text
+//! {
+//!   "gadget": "adder",
+//!   "inputs": [["a", 0], ["b", 1]],
+//!   "outputs": [["sum", 2]],
+//!   "nodes": [
+//!     {"id": 0, "op": "input"},
+//!     {"id": 1, "op": "input"},
+//!     {"id": 2, "op": "add", "args": [0, 1]}
+//!   ],
+//!   "constraints": [[2, 2]]
+//! }
+//! ```
+//!
+//! Node ops are `input`, `const` (with `value`), `add`, `mul` and `hint` (with
+//! `name`, resolved through a `HintRegistry`). `add` and `mul` nodes may carry an
+//! arithmetic `mode` (`"checked"`, `"mod:7"`, ...) and any node a `scope` path.
+//! Anonymous hints are written with an empty name and cannot be loaded again.
+//!
+//! Constraint systems from other tools are read by the functions of this
+//! module, such as `r1cs` for circom's binary `.r1cs` files.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::json::Json;
+use crate::{
+    gadgets, Arithmetic, Builder, Gadget, HintFn, HintRegistry, LimitError, Limits, Node, NodeId,
+    Op,
+};
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    /// The file is not a valid gadget encoding.
+    Format(String),
+    /// The number of nodes passed to `import` does not match the gadget's inputs.
+    Arity {
+        expected: usize,
+        found: usize,
+    },
+    /// The gadget is larger than the `Limits` it was decoded under.
+    Limit(LimitError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!(f, "gadget I/O error: {}", e),
+            ImportError::Format(msg) => write!(f, "invalid gadget file: {}", msg),
+            ImportError::Arity { expected, found } => write!(
+                f,
+                "gadget takes {} inputs but {} were given",
+                expected, found
+            ),
+            ImportError::Limit(e) => write!(f, "gadget exceeds limits: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(e: io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<LimitError> for ImportError {
+    fn from(e: LimitError) -> Self {
+        ImportError::Limit(e)
+    }
+}
+
+fn format_error(message: impl Into<String>) -> ImportError {
+    ImportError::Format(message.into())
+}
+
+fn id_json(id: NodeId) -> Json {
+    Json::Int(id.0 as i128)
+}
+
+fn ports_json(ports: &[(String, NodeId)]) -> Json {
+    Json::Array(
+        ports
+            .iter()
+            .map(|(name, id)| Json::Array(vec![Json::Str(name.clone()), id_json(*id)]))
+            .collect(),
+    )
+}
+
+fn node_id(json: &Json) -> Result<NodeId, ImportError> {
+    json.as_int()
+        .and_then(|v| usize::try_from(v).ok())
+        .map(NodeId)
+        .ok_or_else(|| format_error("node ids must be non-negative integers"))
+}
+
+fn mode(entry: &Json) -> Result<Arithmetic, ImportError> {
+    match entry.get("mode") {
+        None => Ok(Arithmetic::Inherit),
+        Some(mode) => mode
+            .as_str()
+            .and_then(Arithmetic::from_name)
+            .ok_or_else(|| format_error("invalid arithmetic `mode`")),
+    }
+}
+
+fn ports(json: &Json, key: &str) -> Result<Vec<(String, NodeId)>, ImportError> {
+    let entries = json
+        .get(key)
+        .and_then(Json::as_array)
+        .ok_or_else(|| format_error(format!("missing `{}` array", key)))?;
+    entries
+        .iter()
+        .map(|entry| match entry.as_array() {
+            Some([name, id]) => {
+                let name = name
+                    .as_str()
+                    .ok_or_else(|| format_error("port names must be strings"))?;
+                Ok((name.to_string(), node_id(id)?))
+            }
+            _ => Err(format_error(format!(
+                "each `{}` entry must be a [name, node id] pair",
+                key
+            ))),
+        })
+        .collect()
+}
+
+impl Gadget {
+    /// Serializes the gadget's structure and ports as JSON.
+    pub fn to_json(&self) -> String {
+        let nodes = self
+            .graph
+            .sorted_nodes()
+            .into_iter()
+            .map(|node| {
+                let mut fields = vec![("id".to_string(), id_json(node.id))];
+                let (op, args) = match &node.op {
+                    None => ("input", vec![]),
+                    Some(Op::Const(v)) => {
+                        fields.push(("value".to_string(), Json::Int(i128::from(*v))));
+                        ("const", vec![])
+                    }
+                    Some(Op::Add(a, b, mode)) | Some(Op::Mul(a, b, mode)) => {
+                        if *mode != Arithmetic::Inherit {
+                            fields.push(("mode".to_string(), Json::Str(mode.to_string())));
+                        }
+                        let op = match node.op {
+                            Some(Op::Add(..)) => "add",
+                            _ => "mul",
+                        };
+                        (op, vec![*a, *b])
+                    }
+                    Some(Op::Hint(parents, f)) => {
+                        let name = f.name().unwrap_or("").to_string();
+                        fields.push(("name".to_string(), Json::Str(name)));
+                        ("hint", parents.clone())
+                    }
+                };
+                fields.insert(1, ("op".to_string(), Json::Str(op.to_string())));
+                if !args.is_empty() {
+                    let args = args.into_iter().map(id_json).collect();
+                    fields.push(("args".to_string(), Json::Array(args)));
+                }
+                if let Some(scope) = self.graph.node_scopes.get(&node.id) {
+                    fields.push(("scope".to_string(), Json::Str(scope.clone())));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+        let constraints = self
+            .graph
+            .constraints
+            .iter()
+            .map(|(a, b)| Json::Array(vec![id_json(*a), id_json(*b)]))
+            .collect();
+        Json::Object(vec![
+            ("gadget".to_string(), Json::Str(self.name.clone())),
+            ("inputs".to_string(), ports_json(&self.inputs)),
+            ("outputs".to_string(), ports_json(&self.outputs)),
+            ("nodes".to_string(), Json::Array(nodes)),
+            ("constraints".to_string(), Json::Array(constraints)),
+        ])
+        .to_pretty_string()
+    }
+
+    /// Parses a gadget written by `to_json`. Hint names are resolved through `hints`.
+    pub fn from_json(text: &str, hints: &HintRegistry) -> Result<Gadget, ImportError> {
+        Self::from_json_with_limits(text, hints, Limits::default())
+    }
+
+    /// Like `from_json`, refusing gadgets whose graph exceeds `limits`.
+    pub fn from_json_with_limits(
+        text: &str,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<Gadget, ImportError> {
+        let json = Json::parse(text).map_err(|e| format_error(e.to_string()))?;
+        let name = json
+            .get("gadget")
+            .and_then(Json::as_str)
+            .ok_or_else(|| format_error("missing `gadget` name"))?
+            .to_string();
+        let mut graph = Builder::with_limits(limits);
+        let entries = json
+            .get("nodes")
+            .and_then(Json::as_array)
+            .ok_or_else(|| format_error("missing `nodes` array"))?;
+        for entry in entries {
+            let id = node_id(
+                entry
+                    .get("id")
+                    .ok_or_else(|| format_error("node without `id`"))?,
+            )?;
+            if !Builder::decodable_id(id, entries.len()) {
+                return Err(format_error(format!(
+                    "node id {} is out of range for {} nodes",
+                    id.0,
+                    entries.len()
+                )));
+            }
+            if graph.nodes.contains_key(&id) {
+                return Err(format_error(format!("node {} appears twice", id.0)));
+            }
+            let args = match entry.get("args") {
+                Some(args) => args
+                    .as_array()
+                    .ok_or_else(|| format_error("`args` must be an array"))?
+                    .iter()
+                    .map(|arg| {
+                        let arg = node_id(arg)?;
+                        if graph.nodes.contains_key(&arg) {
+                            Ok(arg)
+                        } else {
+                            Err(format_error(format!(
+                                "node {} references unknown node {}",
+                                id.0, arg.0
+                            )))
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => vec![],
+            };
+            let op = match (entry.get("op").and_then(Json::as_str), args.as_slice()) {
+                (Some("input"), []) => None,
+                (Some("const"), []) => {
+                    let value = entry
+                        .get("value")
+                        .and_then(Json::as_int)
+                        .and_then(|v| u32::try_from(v).ok())
+                        .ok_or_else(|| format_error("`const` needs a u32 `value`"))?;
+                    Some(Op::Const(value))
+                }
+                (Some("add"), [a, b]) => Some(Op::Add(*a, *b, mode(entry)?)),
+                (Some("mul"), [a, b]) => Some(Op::Mul(*a, *b, mode(entry)?)),
+                (Some("hint"), _) => {
+                    let hint = entry.get("name").and_then(Json::as_str).unwrap_or("");
+                    let func = hints
+                        .get(hint)
+                        .ok_or_else(|| format_error(format!("unknown hint `{}`", hint)))?;
+                    Some(Op::Hint(args.clone(), HintFn::named(hint, func)))
+                }
+                (op, _) => {
+                    return Err(format_error(format!(
+                        "node {} has invalid op {:?} for {} args",
+                        id.0,
+                        op.unwrap_or(""),
+                        args.len()
+                    )))
+                }
+            };
+            if let Some(scope) = entry.get("scope").and_then(Json::as_str) {
+                graph.node_scopes.insert(id, scope.to_string());
+            }
+            graph.insert_node(id, op)?;
+        }
+        let constraints = json
+            .get("constraints")
+            .and_then(Json::as_array)
+            .ok_or_else(|| format_error("missing `constraints` array"))?;
+        for pair in constraints {
+            match pair.as_array() {
+                Some([a, b]) => {
+                    let (a, b) = (node_id(a)?, node_id(b)?);
+                    if !graph.nodes.contains_key(&a) || !graph.nodes.contains_key(&b) {
+                        return Err(format_error("constraint on unknown node"));
+                    }
+                    graph.insert_constraint(a, b)?;
+                }
+                _ => return Err(format_error("each constraint must be a [a, b] pair")),
+            }
+        }
+        let inputs = ports(&json, "inputs")?;
+        let outputs = ports(&json, "outputs")?;
+        for (port, id) in inputs.iter().chain(&outputs) {
+            if !graph.nodes.contains_key(id) {
+                return Err(format_error(format!(
+                    "port `{}` refers to unknown node {}",
+                    port, id.0
+                )));
+            }
+        }
+        if let Some((port, _)) = inputs.iter().find(|(_, id)| graph.nodes[id].op.is_some()) {
+            return Err(format_error(format!(
+                "input port `{}` is not an input node",
+                port
+            )));
+        }
+        Ok(Gadget {
+            name,
+            graph,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Writes the gadget to `path` as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ImportError> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    /// Reads a gadget file written by `save`.
+    pub fn load(path: impl AsRef<Path>, hints: &HintRegistry) -> Result<Gadget, ImportError> {
+        let text = fs::read_to_string(path)?;
+        Self::from_json(&text, hints)
+    }
+}
+
+impl Builder {
+    /// Loads the gadget stored at `path` and instantiates it on `inputs`,
+    /// returning its outputs in port order. Hints from the `gadgets` library
+    /// are available; use `import_with` to supply other hints.
+    pub fn import(
+        &mut self,
+        path: impl AsRef<Path>,
+        inputs: &[Node],
+    ) -> Result<Vec<Node>, ImportError> {
+        let mut hints = HintRegistry::new();
+        gadgets::register_hints(&mut hints);
+        self.import_with(path, inputs, &hints)
+    }
+
+    /// Like `import`, resolving hint names through `hints`.
+    pub fn import_with(
+        &mut self,
+        path: impl AsRef<Path>,
+        inputs: &[Node],
+        hints: &HintRegistry,
+    ) -> Result<Vec<Node>, ImportError> {
+        let gadget = Gadget::load(path, hints)?;
+        if gadget.inputs.len() != inputs.len() {
+            return Err(ImportError::Arity {
+                expected: gadget.inputs.len(),
+                found: inputs.len(),
+            });
+        }
+        Ok(self.instantiate(&gadget, inputs))
+    }
+}
+
+/// Reads the circom `.r1cs` file at `path` as a graph; see
+/// `Builder::from_circom_r1cs`.
+pub fn r1cs(path: impl AsRef<Path>) -> Result<Builder, ImportError> {
+    Builder::from_circom_r1cs(&fs::read(path)?)
+}
@@ -0,0 +1,153 @@
+//! Named inputs, so callers can feed values by name instead of tracking NodeIds,
+//! and the value ranges inputs are declared over.
+
+use crate::collections::HashMap;
+use core::fmt;
+use core::ops::RangeInclusive;
+
+use crate::prelude::*;
+use crate::{Builder, EvalSummary, Node, NodeId};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InputError {
+    /// No input was declared with this name.
+    UnknownName(String),
+    /// The graph has no node with this id.
+    UnknownNode(NodeId),
+    /// A value was given for a node computed by an operation.
+    NotAnInput(NodeId),
+    /// These inputs have no value and none was given, in ascending id order.
+    MissingInputs(Vec<NodeId>),
+    /// An input already holds a different value from an earlier call.
+    ConflictingInput {
+        node: NodeId,
+        previous: u32,
+        new: u32,
+    },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::UnknownName(name) => write!(f, "no input named `{}`", name),
+            InputError::UnknownNode(id) => write!(f, "the graph has no node {:?}", id),
+            InputError::NotAnInput(id) => write!(f, "{:?} is computed, not an input", id),
+            InputError::MissingInputs(ids) => write!(f, "no value for inputs {:?}", ids),
+            InputError::ConflictingInput {
+                node,
+                previous,
+                new,
+            } => write!(f, "{:?} is already set to {}, not {}", node, previous, new),
+        }
+    }
+}
+
+impl core::error::Error for InputError {}
+
+impl Builder {
+    /// Creates an input node addressable by `name`. The name also becomes the
+    /// node's label.
+    ///
+    /// # Panics
+    /// If an input with the same name already exists.
+    #[track_caller]
+    pub fn input(&mut self, name: &str) -> Node {
+        assert!(
+            !self.input_names.contains_key(name),
+            "input `{}` is already declared",
+            name
+        );
+        let node = self.init();
+        self.input_names.insert(name.to_string(), node.id);
+        self.set_label(&node, name);
+        node
+    }
+
+    /// Id of the input declared as `name`.
+    pub fn input_id(&self, name: &str) -> Option<NodeId> {
+        self.input_names.get(name).copied()
+    }
+
+    /// Declares that `input` only takes values in `range`. Searches such as
+    /// `find_violation` and `verify_exhaustive` use it as the input's domain.
+    ///
+    /// # Panics
+    /// If `input` is not an input node or `range` is empty.
+    pub fn declare_range(&mut self, input: &Node, range: RangeInclusive<u32>) {
+        assert!(
+            self.nodes.get(&input.id).is_some_and(|n| n.op.is_none()),
+            "{:?} is not an input",
+            input.id
+        );
+        assert!(!range.is_empty(), "empty range for {:?}", input.id);
+        self.input_ranges.insert(input.id, range);
+    }
+
+    /// The range declared for `input`, if any.
+    pub fn input_range(&self, input: &Node) -> Option<RangeInclusive<u32>> {
+        self.input_ranges.get(&input.id).cloned()
+    }
+
+    /// Checks that every id in `inputs` is an input node without a different
+    /// value from an earlier call and that, together with values set earlier,
+    /// every input gets a value.
+    pub fn validate_inputs(&self, inputs: &HashMap<NodeId, u32>) -> Result<(), InputError> {
+        let mut ids: Vec<NodeId> = inputs.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            match self.nodes.get(&id) {
+                None => return Err(InputError::UnknownNode(id)),
+                Some(node) if node.op.is_some() => return Err(InputError::NotAnInput(id)),
+                Some(node) => {
+                    if let Some(previous) = node.value.filter(|v| *v != inputs[&id]) {
+                        return Err(InputError::ConflictingInput {
+                            node: id,
+                            previous,
+                            new: inputs[&id],
+                        });
+                    }
+                }
+            }
+        }
+        let mut missing: Vec<NodeId> = self
+            .nodes
+            .values()
+            .filter(|n| n.op.is_none() && n.value.is_none() && !inputs.contains_key(&n.id))
+            .map(|n| n.id)
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            missing.sort();
+            Err(InputError::MissingInputs(missing))
+        }
+    }
+
+    /// `fill_nodes` after `validate_inputs`; nothing is evaluated if the
+    /// inputs are invalid. Unlike `fill_nodes`, changing an input is an
+    /// error; clear the values or start from a fresh copy to re-evaluate.
+    pub fn try_fill_nodes(
+        &mut self,
+        inputs: HashMap<NodeId, u32>,
+    ) -> Result<EvalSummary, InputError> {
+        self.validate_inputs(&inputs)?;
+        Ok(self.fill_nodes(inputs))
+    }
+
+    /// Like `fill_nodes`, keyed by input name. Nothing is evaluated if any name
+    /// is unknown.
+    pub fn fill_nodes_named<T: Into<u32>>(
+        &mut self,
+        inputs: HashMap<&str, T>,
+    ) -> Result<(), InputError> {
+        let mut by_id = HashMap::new();
+        for (name, value) in inputs {
+            let id = self
+                .input_id(name)
+                .ok_or_else(|| InputError::UnknownName(name.to_string()))?;
+            by_id.insert(id, value.into());
+        }
+        self.fill_nodes(by_id);
+        Ok(())
+    }
+}
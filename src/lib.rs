@@ -0,0 +1,1199 @@
+/*!
+ A minimal, production-ready computational graph library in Rust.
+
+This library allows users to construct and evaluate arithmetic computation graphs with support for:
+- Addition and multiplication nodes
+- Custom hint-based nodes for extended functionality (e.g. division, square root)
+- Value propagation from input nodes
+- Equality constraint enforcement
+- Graph export in DOT format for visualization (added for understanding)
+
+The code is optimized for clarity, extensibility, and performance, with test cases validating correctness and edge-case handling.
+*/
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use core::cmp::Reverse;
+use core::fmt::Debug;
+use core::ops::RangeInclusive;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use collections::{HashMap, HashSet};
+use prelude::*;
+use store::NodeStore;
+
+/// Maps and sets used in the public API. Without the `std` feature there is no
+/// hasher, so the ordered collections from `alloc` stand in for them.
+pub mod collections {
+    #[cfg(not(feature = "std"))]
+    pub use alloc::collections::{BTreeMap as HashMap, BTreeSet as HashSet};
+    #[cfg(feature = "std")]
+    pub use std::collections::{HashMap, HashSet};
+}
+
+/// The `alloc` items `std` puts in its prelude, for modules built without it.
+mod prelude {
+    pub(crate) use alloc::string::{String, ToString};
+    pub(crate) use alloc::vec::Vec;
+    pub(crate) use alloc::{format, vec};
+}
+
+/// Progress messages, written to stderr once `set_logging(true)` is called
+/// and only when `std` is available.
+macro_rules! log {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "std")]
+        if $crate::logging() {
+            eprintln!($($arg)*);
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = format_args!($($arg)*);
+    };
+}
+
+#[cfg(feature = "std")]
+static LOGGING: AtomicBool = AtomicBool::new(false);
+
+/// Turns the library's progress messages (node creation, each evaluated
+/// value, ...) on or off for the whole process. They are off by default and
+/// go to stderr, so programs printing their results to stdout stay parseable.
+#[cfg(feature = "std")]
+pub fn set_logging(enabled: bool) {
+    LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether progress messages are printed; see `set_logging`.
+#[cfg(feature = "std")]
+pub fn logging() -> bool {
+    LOGGING.load(Ordering::Relaxed)
+}
+
+// Graph construction, evaluation and editing work with `alloc` alone.
+mod cancel;
+mod checkpoint;
+mod complex;
+mod edit;
+mod extract;
+mod find;
+mod gadget;
+pub mod gadgets;
+mod inputs;
+mod limits;
+mod lint;
+mod location;
+mod merge;
+mod metadata;
+mod minimize;
+mod modular;
+mod observe;
+mod operand;
+mod outputs;
+mod query;
+mod store;
+mod substitute;
+mod summary;
+mod template;
+mod typed;
+mod unroll;
+mod validate;
+mod vector;
+mod visit;
+mod watch;
+mod wide;
+
+// Analyses, file formats and bindings need `std`.
+#[cfg(feature = "std")]
+mod air;
+#[cfg(feature = "std")]
+mod analysis;
+#[cfg(feature = "std")]
+mod autodiff;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "cbor")]
+mod cbor;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "std")]
+mod definition;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "std")]
+mod dot;
+#[cfg(feature = "std")]
+pub mod dsl;
+#[cfg(feature = "std")]
+mod equivalence;
+#[cfg(feature = "std")]
+mod exhaustive;
+#[cfg(feature = "std")]
+mod expr;
+#[cfg(feature = "std")]
+mod fingerprint;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "std")]
+pub mod import;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod mermaid;
+#[cfg(feature = "std")]
+mod onnx;
+#[cfg(feature = "std")]
+mod plan;
+#[cfg(feature = "std")]
+mod profile;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+#[cfg(feature = "std")]
+mod r1cs;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+mod sensitivity;
+#[cfg(feature = "std")]
+mod serialize;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod service;
+#[cfg(feature = "std")]
+mod sexpr;
+#[cfg(feature = "z3")]
+mod smt;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "uniffi")]
+pub mod uniffi;
+#[cfg(feature = "uniffi")]
+#[doc(hidden)]
+pub use crate::uniffi::UniFfiTag;
+#[cfg(feature = "std")]
+mod violation;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "std")]
+mod witness;
+
+pub use cancel::Cancelled;
+pub use checkpoint::Checkpoint;
+pub use complex::ComplexNode;
+pub use edit::EditError;
+pub use extract::ExtractError;
+pub use gadget::{Gadget, InlineMode, Instance};
+pub use inputs::InputError;
+pub use limits::{LimitError, Limits};
+pub use lint::{Lint, LintKind};
+pub use merge::MergeError;
+pub use modular::{Arithmetic, Modulus};
+pub use operand::Operand;
+pub use query::OpKind;
+pub use template::{PolyEval, Sum, Template};
+pub use typed::{BoolNode, FieldNode, RangeCheckedNode};
+pub use validate::{Diagnostic, DiagnosticKind, Severity};
+pub use vector::VecNode;
+pub use visit::{Traversal, Visitor};
+pub use watch::{OutputChange, Watcher};
+pub use wide::{WideNode, Width};
+
+#[cfg(feature = "std")]
+pub use air::AirError;
+#[cfg(feature = "std")]
+pub use analysis::{ConstraintStats, CriticalPath};
+#[cfg(feature = "std")]
+pub use autodiff::{DiffError, Differentiable, Dual, Gradient, HintDerivatives};
+#[cfg(feature = "std")]
+pub use definition::DefinitionError;
+#[cfg(feature = "std")]
+pub use diff::{GraphDiff, NodeChange};
+#[cfg(feature = "std")]
+pub use dot::{DotOptions, NodeStyle, RankDir};
+#[cfg(feature = "std")]
+pub use equivalence::{Equivalence, EquivalenceStrategy};
+#[cfg(feature = "std")]
+pub use exhaustive::Exhaustion;
+#[cfg(feature = "std")]
+pub use import::ImportError;
+#[cfg(feature = "std")]
+pub use onnx::OnnxError;
+#[cfg(feature = "std")]
+pub use plan::{EvalPlan, PlanCache, PlanError};
+#[cfg(feature = "std")]
+pub use profile::{NodeTiming, Profile};
+#[cfg(feature = "std")]
+pub use sensitivity::{ConstraintChange, Perturbation, SensitivityReport, ValueChange};
+#[cfg(feature = "std")]
+pub use serialize::{DecodeError, GRAPH_FORMAT_VERSION};
+#[cfg(feature = "std")]
+pub use sexpr::ParseError;
+#[cfg(feature = "std")]
+pub use violation::{HintCheck, Violation, ViolationSearch};
+#[cfg(feature = "std")]
+pub use witness::{Witness, WitnessDiff, WitnessError, WitnessFormat, WitnessMismatch};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub usize);
+
+/// Handle to a node, returned by the builder methods that create nodes and
+/// passed to the ones that read them. Handles are `Copy`; the node itself
+/// (its op and value) lives in the builder, see `Builder::node`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub id: NodeId,
+    /// Identity of the builder that created this node; see `Builder::owns`.
+    graph: u64,
+    /// Generation of the id's slot when the node was created; see
+    /// `Builder::is_live`.
+    generation: u32,
+}
+
+/// A node as stored in a graph: its op and, after evaluation, its value.
+#[derive(Debug, Clone)]
+pub struct NodeData {
+    pub id: NodeId,
+    value: Option<u32>,
+    op: Option<Op>,
+}
+
+/// How far past the number of nodes in an encoding a decoded id may lie; see
+/// `Builder::decodable_id`.
+#[cfg(feature = "std")]
+const MAX_DECODED_ID_GAP: usize = 1 << 16;
+
+/// Source of unique builder identities.
+static NEXT_GRAPH_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+pub struct HintFn {
+    func: fn(&[u32]) -> u32,
+    name: Option<String>,
+}
+
+impl HintFn {
+    pub fn new(func: fn(&[u32]) -> u32) -> Self {
+        HintFn { func, name: None }
+    }
+
+    /// Creates a hint that carries a name, so textual formats can refer to it
+    /// and readers can resolve it again through a `HintRegistry`.
+    pub fn named(name: &str, func: fn(&[u32]) -> u32) -> Self {
+        HintFn {
+            func,
+            name: Some(name.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl Debug for HintFn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "HintFn({})", name),
+            None => write!(f, "HintFn"),
+        }
+    }
+}
+
+/// Maps hint names to functions so graphs read back from text can be rebuilt
+/// with their hints attached.
+#[derive(Clone, Debug, Default)]
+pub struct HintRegistry {
+    hints: HashMap<String, fn(&[u32]) -> u32>,
+    arities: HashMap<String, usize>,
+}
+
+impl HintRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `func` under `name`, replacing any earlier registration.
+    pub fn register(&mut self, name: &str, func: fn(&[u32]) -> u32) {
+        self.hints.insert(name.to_string(), func);
+        self.arities.remove(name);
+    }
+
+    /// Like `register`, for a function that reads exactly `arity` operands,
+    /// so graphs calling it with any other number can be rejected before
+    /// they are evaluated.
+    pub fn register_with_arity(&mut self, name: &str, arity: usize, func: fn(&[u32]) -> u32) {
+        self.register(name, func);
+        self.arities.insert(name.to_string(), arity);
+    }
+
+    pub fn get(&self, name: &str) -> Option<fn(&[u32]) -> u32> {
+        self.hints.get(name).copied()
+    }
+
+    /// The operand count `name` was registered with, if any.
+    pub fn arity(&self, name: &str) -> Option<usize> {
+        self.arities.get(name).copied()
+    }
+}
+
+#[derive(Clone)]
+pub enum Op {
+    Const(u32),
+    /// The mode overrides the builder's overflow policy unless it is `Inherit`.
+    Add(NodeId, NodeId, Arithmetic),
+    Mul(NodeId, NodeId, Arithmetic),
+    Hint(Vec<NodeId>, HintFn),
+}
+
+impl Op {
+    /// Nodes this op reads from, in operand order.
+    pub(crate) fn operands(&self) -> Vec<NodeId> {
+        match self {
+            Op::Const(_) => Vec::new(),
+            Op::Add(a, b, _) | Op::Mul(a, b, _) => vec![*a, *b],
+            Op::Hint(parents, _) => parents.clone(),
+        }
+    }
+
+    /// Makes every read of `from` read `to` instead.
+    pub(crate) fn replace_operand(&mut self, from: NodeId, to: NodeId) {
+        let swap = |id: &mut NodeId| {
+            if *id == from {
+                *id = to;
+            }
+        };
+        match self {
+            Op::Const(_) => {}
+            Op::Add(a, b, _) | Op::Mul(a, b, _) => {
+                swap(a);
+                swap(b);
+            }
+            Op::Hint(parents, _) => parents.iter_mut().for_each(swap),
+        }
+    }
+
+    /// Computes this op's value from its operands' values, or `None` if any
+    /// operand has no value yet or a checked operation overflows. Nodes
+    /// without an override follow `policy`.
+    pub(crate) fn apply(
+        &self,
+        policy: Arithmetic,
+        value_of: impl Fn(NodeId) -> Option<u32>,
+    ) -> Option<u32> {
+        match self {
+            Op::Const(val) => Some(*val),
+            Op::Add(a, b, mode) => mode.or(policy).add(value_of(*a)?, value_of(*b)?),
+            Op::Mul(a, b, mode) => mode.or(policy).mul(value_of(*a)?, value_of(*b)?),
+            Op::Hint(parents, f) => {
+                let vals = parents
+                    .iter()
+                    .map(|pid| value_of(*pid))
+                    .collect::<Option<Vec<u32>>>()?;
+                Some((f.func)(&vals))
+            }
+        }
+    }
+}
+
+impl Debug for Op {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Op::Const(v) => write!(f, "Const({})", v),
+            Op::Add(a, b, Arithmetic::Inherit) => write!(f, "Add({:?}, {:?})", a, b),
+            Op::Mul(a, b, Arithmetic::Inherit) => write!(f, "Mul({:?}, {:?})", a, b),
+            Op::Add(a, b, mode) => write!(f, "Add({:?}, {:?}, {})", a, b, mode),
+            Op::Mul(a, b, mode) => write!(f, "Mul({:?}, {:?}, {})", a, b, mode),
+            Op::Hint(_, _) => write!(f, "Hint(...)"),
+        }
+    }
+}
+
+/// The order in which `fill_nodes` visits nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvalOrder {
+    /// Propagate through a LIFO worklist seeded with every node in ascending
+    /// id order, so the highest ids are tried first. A computed node pushes
+    /// its dependents in ascending id order. The order is deterministic, but
+    /// nodes are tried before their parents are ready and revisited later.
+    #[default]
+    Worklist,
+    /// One pass in topological order (ascending ids unless the graph was
+    /// edited), visiting each node once after its parents.
+    Topological,
+}
+
+/// What one `fill_nodes` call did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalSummary {
+    /// Operation nodes computed by this call.
+    pub evaluated: usize,
+    /// Operation nodes that already had a value and were left untouched.
+    pub skipped_already_set: usize,
+    /// Nodes still without a value afterwards, in evaluation order.
+    pub unresolved: Vec<NodeId>,
+    /// Hint functions invoked.
+    pub hint_calls: usize,
+}
+
+impl EvalSummary {
+    /// True when every node has a value.
+    pub fn is_complete(&self) -> bool {
+        self.unresolved.is_empty()
+    }
+
+    fn record(&mut self, op: &Op) {
+        self.evaluated += 1;
+        if matches!(op, Op::Hint(..)) {
+            self.hint_calls += 1;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Builder {
+    graph_id: u64,
+    next_id: usize,
+    nodes: NodeStore,
+    constraints: Vec<(NodeId, NodeId)>,
+    scope_stack: Vec<String>,
+    node_scopes: HashMap<NodeId, String>,
+    labels: HashMap<NodeId, String>,
+    /// Nodes carrying each label, kept in step with `labels`.
+    label_index: HashMap<String, BTreeSet<NodeId>>,
+    instances: Vec<gadget::Instance>,
+    input_names: HashMap<String, NodeId>,
+    input_ranges: HashMap<NodeId, RangeInclusive<u32>>,
+    metadata: HashMap<NodeId, BTreeMap<String, String>>,
+    /// Nodes carrying each metadata key, kept in step with `metadata`.
+    metadata_index: HashMap<String, BTreeSet<NodeId>>,
+    locations: HashMap<NodeId, &'static Location<'static>>,
+    constraint_locations: HashMap<(NodeId, NodeId), &'static Location<'static>>,
+    outputs: Vec<(String, NodeId)>,
+    /// Set once an edit makes some node read an operand with a larger id, so
+    /// ascending ids are no longer a topological order.
+    reordered: bool,
+    eval_order: EvalOrder,
+    arithmetic: Arithmetic,
+    observers: HashMap<NodeId, Vec<observe::Observer>>,
+    limits: Limits,
+    /// The node `constant` returns for each value while interning is on.
+    constant_pool: HashMap<u32, NodeId>,
+    intern_constants: bool,
+    /// What edits overwrote since the outstanding checkpoints were taken.
+    undo: Vec<checkpoint::Undo>,
+    /// Edits to nodes below this id are saved in `undo`.
+    undo_floor: usize,
+    /// Bumped by `commit`, which invalidates earlier checkpoints.
+    checkpoint_epoch: u64,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    /// Initializes a new Builder instance with an empty graph structure.
+    /// Sets up internal state including node counter, node map, and constraints list.
+    pub fn new() -> Self {
+        Self {
+            graph_id: NEXT_GRAPH_ID.fetch_add(1, Ordering::Relaxed),
+            next_id: 0,
+            nodes: NodeStore::new(),
+            constraints: Vec::new(),
+            scope_stack: Vec::new(),
+            node_scopes: HashMap::new(),
+            labels: HashMap::new(),
+            label_index: HashMap::new(),
+            instances: Vec::new(),
+            input_names: HashMap::new(),
+            input_ranges: HashMap::new(),
+            metadata: HashMap::new(),
+            metadata_index: HashMap::new(),
+            locations: HashMap::new(),
+            constraint_locations: HashMap::new(),
+            outputs: Vec::new(),
+            reordered: false,
+            eval_order: EvalOrder::default(),
+            arithmetic: Arithmetic::Inherit,
+            observers: HashMap::new(),
+            limits: Limits::default(),
+            constant_pool: HashMap::new(),
+            intern_constants: true,
+            undo: Vec::new(),
+            undo_floor: 0,
+            checkpoint_epoch: 0,
+        }
+    }
+
+    /// Creates and registers a new node with a specified operation in the graph.
+    /// Used internally to generate nodes for all operations.
+    ///
+    /// # Panics
+    /// If the graph already has `Limits::max_nodes` nodes.
+    #[track_caller]
+    fn new_node(&mut self, op: Option<Op>) -> Node {
+        if let Err(e) = self.check_node_limit() {
+            panic!("{}", e);
+        }
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        log!("➕ Created Node {:?} with op {:?}", id, op);
+        let generation = self.nodes.insert(
+            id,
+            NodeData {
+                id,
+                value: None,
+                op,
+            },
+        );
+        self.locations.insert(id, Location::caller());
+        if !self.scope_stack.is_empty() {
+            self.node_scopes.insert(id, self.scope_stack.join("/"));
+        }
+        Node {
+            id,
+            graph: self.graph_id,
+            generation,
+        }
+    }
+
+    /// A handle to the node currently stored under `id`, which must exist.
+    pub(crate) fn handle_of(&self, id: NodeId) -> Node {
+        Node {
+            id,
+            graph: self.graph_id,
+            generation: self.nodes.generation(id).unwrap_or_default(),
+        }
+    }
+
+    /// Whether a decoder may give a node `id` when the encoding holds
+    /// `entries` nodes. Ids keep the gaps left by removed nodes, but slot
+    /// storage grows to the largest id, so an unchecked id from untrusted
+    /// input could demand any amount of memory.
+    #[cfg(feature = "std")]
+    pub(crate) fn decodable_id(id: NodeId, entries: usize) -> bool {
+        id.0 < entries.saturating_add(MAX_DECODED_ID_GAP)
+    }
+
+    /// Inserts a node with a fixed id, for decoders that rebuild a graph with
+    /// its original numbering. Operands must already exist, and `id` must be
+    /// `decodable_id`. Fails if the node would exceed the builder's limits.
+    #[cfg(feature = "std")]
+    pub(crate) fn insert_node(&mut self, id: NodeId, op: Option<Op>) -> Result<(), LimitError> {
+        match &op {
+            Some(Op::Hint(parents, _)) => self.check_hint_limit(parents.len())?,
+            _ => self.check_node_limit()?,
+        }
+        if let Some(op) = &op {
+            self.reordered |= op.operands().iter().any(|operand| *operand >= id);
+        }
+        self.nodes.insert(
+            id,
+            NodeData {
+                id,
+                value: None,
+                op,
+            },
+        );
+        self.next_id = self.next_id.max(id.0 + 1);
+        Ok(())
+    }
+
+    /// Adds the constraint `a == b` for a decoder, within the builder's
+    /// limits. Both nodes must already exist.
+    #[cfg(feature = "std")]
+    pub(crate) fn insert_constraint(&mut self, a: NodeId, b: NodeId) -> Result<(), LimitError> {
+        self.check_constraint_limit()?;
+        self.constraints.push((a, b));
+        Ok(())
+    }
+
+    /// Whether `node` was created by this builder (or the builder it was
+    /// cloned from). Ids of foreign nodes refer to unrelated nodes here.
+    pub fn owns(&self, node: &Node) -> bool {
+        node.graph == self.graph_id
+    }
+
+    /// Whether `node` still refers to a node of this graph: it was created by
+    /// this builder and has not been removed (by an edit, `prune` or
+    /// `rollback`) since. A removed node's id may be given to a new node; the
+    /// old handle stays dead.
+    pub fn is_live(&self, node: &Node) -> bool {
+        self.owns(node) && self.nodes.generation(node.id) == Some(node.generation)
+    }
+
+    /// Debug-build check that `node` is not a handle from another builder,
+    /// and a check in all builds that it was not removed.
+    #[track_caller]
+    fn check_owner(&self, node: &Node) {
+        debug_assert!(
+            self.owns(node),
+            "{:?} belongs to a different builder; pass nodes only to the builder that created them",
+            node.id
+        );
+        assert!(
+            self.nodes.generation(node.id) == Some(node.generation),
+            "{:?} is a stale handle: its node was removed from the graph",
+            node.id
+        );
+    }
+
+    /// Opens a named scope; every node created until the matching `exit_scope`
+    /// is tagged with the current scope path (e.g. `layer1/adder`).
+    pub fn enter_scope(&mut self, name: &str) {
+        self.scope_stack.push(name.to_string());
+    }
+
+    /// Closes the innermost scope opened with `enter_scope`.
+    pub fn exit_scope(&mut self) {
+        self.scope_stack.pop();
+    }
+
+    /// Runs `f` inside a named namespace and returns its result. Namespaces nest,
+    /// so nodes created in `b.namespace("layer1", |b| b.namespace("adder", ...))`
+    /// get the path `layer1/adder`.
+    pub fn namespace<R>(&mut self, name: &str, f: impl FnOnce(&mut Builder) -> R) -> R {
+        self.enter_scope(name);
+        let result = f(self);
+        self.exit_scope();
+        result
+    }
+
+    /// Gives a node a human-readable name, shown in DOT exports and constraint
+    /// failure messages as the last segment of its path.
+    pub fn set_label(&mut self, node: &Node, label: &str) {
+        self.insert_label(node.id, label.to_string());
+    }
+
+    pub fn label_of(&self, node: &Node) -> Option<&str> {
+        self.labels.get(&node.id).map(|s| s.as_str())
+    }
+
+    /// Full hierarchical path of a node, e.g. `layer1/adder/out`. Unlabeled
+    /// nodes use `n<id>` as the last segment.
+    pub fn path_of(&self, node: &Node) -> String {
+        self.path_of_id(node.id)
+    }
+
+    pub(crate) fn path_of_id(&self, id: NodeId) -> String {
+        let name = match self.labels.get(&id) {
+            Some(label) => label.clone(),
+            None => format!("n{}", id.0),
+        };
+        match self.node_scopes.get(&id) {
+            Some(scope) => format!("{}/{}", scope, name),
+            None => name,
+        }
+    }
+
+    /// How a node is referred to in messages: its path when it has a scope or
+    /// label, otherwise `Node NodeId(n)`.
+    pub(crate) fn describe(&self, id: NodeId) -> String {
+        if self.labels.contains_key(&id) || self.node_scopes.contains_key(&id) {
+            format!("{} ({:?})", self.path_of_id(id), id)
+        } else {
+            format!("Node {:?}", id)
+        }
+    }
+
+    /// Drops a node and everything recorded about it. Callers must make sure
+    /// no remaining node or constraint refers to it.
+    pub(crate) fn forget_node(&mut self, id: NodeId) {
+        self.log_removal(id);
+        self.nodes.remove(&id);
+        self.node_scopes.remove(&id);
+        self.remove_label(id);
+        self.remove_all_metadata(id);
+        self.locations.remove(&id);
+        self.input_names.retain(|_, n| *n != id);
+        self.input_ranges.remove(&id);
+        self.observers.remove(&id);
+        self.outputs.retain(|(_, n)| *n != id);
+        for instance in &mut self.instances {
+            instance.nodes.retain(|n| *n != id);
+        }
+    }
+
+    /// Returns all nodes in a valid creation order: ascending ids, unless graph
+    /// edits reordered the graph, in which case a topological order that
+    /// prefers smaller ids. Used wherever output must not depend on HashMap
+    /// iteration order.
+    pub(crate) fn sorted_nodes(&self) -> Vec<&NodeData> {
+        let mut ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        self.sort_topologically(&mut ids);
+        ids.iter().map(|id| &self.nodes[id]).collect()
+    }
+
+    /// Sorts `ids` so every node comes after its operands.
+    pub(crate) fn sort_topologically(&self, ids: &mut [NodeId]) {
+        if self.reordered {
+            let rank = self.topo_rank();
+            ids.sort_by_key(|id| rank.get(id).copied().unwrap_or(usize::MAX));
+        } else {
+            ids.sort();
+        }
+    }
+
+    /// Position of each node in a topological order (Kahn's algorithm, smallest
+    /// ready id first).
+    fn topo_rank(&self) -> HashMap<NodeId, usize> {
+        let mut pending: HashMap<NodeId, usize> = HashMap::new();
+        let mut dependents: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        let mut ready = BinaryHeap::new();
+        for node in self.nodes.values() {
+            let operands = node.op.as_ref().map(Op::operands).unwrap_or_default();
+            if operands.is_empty() {
+                ready.push(Reverse(node.id));
+            }
+            pending.insert(node.id, operands.len());
+            for operand in operands {
+                dependents.entry(operand).or_default().push(node.id);
+            }
+        }
+        let mut rank = HashMap::new();
+        while let Some(Reverse(id)) = ready.pop() {
+            rank.insert(id, rank.len());
+            for dep in dependents.get(&id).into_iter().flatten() {
+                let count = pending.get_mut(dep).expect("dependent exists");
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(Reverse(*dep));
+                }
+            }
+        }
+        rank
+    }
+
+    /// Input nodes ordered by id.
+    pub(crate) fn input_ids(&self) -> Vec<NodeId> {
+        self.sorted_nodes()
+            .into_iter()
+            .filter(|n| n.op.is_none())
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Nodes that no other node reads from, ordered by id. These are the values
+    /// a graph ultimately computes.
+    pub(crate) fn sink_ids(&self) -> Vec<NodeId> {
+        let used: HashSet<NodeId> = self
+            .nodes
+            .values()
+            .filter_map(|n| n.op.as_ref())
+            .flat_map(|op| op.operands())
+            .collect();
+        self.sorted_nodes()
+            .into_iter()
+            .filter(|n| !used.contains(&n.id))
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// Maps every node to the nodes that read it directly.
+    pub(crate) fn dependents_map(&self) -> HashMap<NodeId, Vec<NodeId>> {
+        let mut dependents: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        for node in self.sorted_nodes() {
+            if let Some(op) = &node.op {
+                for operand in op.operands() {
+                    dependents.entry(operand).or_default().push(node.id);
+                }
+            }
+        }
+        dependents
+    }
+
+    /// Nodes `id` depends on (including itself), in evaluation order.
+    pub(crate) fn cone_of(&self, id: NodeId) -> Vec<NodeId> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            if let Some(op) = self.nodes.get(&id).and_then(|n| n.op.as_ref()) {
+                stack.extend(op.operands());
+            }
+        }
+        let mut cone: Vec<NodeId> = seen.into_iter().collect();
+        self.sort_topologically(&mut cone);
+        cone
+    }
+
+    /// Returns the scope path a node was created in, if any.
+    pub fn scope_of(&self, node: &Node) -> Option<&str> {
+        self.node_scopes.get(&node.id).map(|s| s.as_str())
+    }
+
+    /// Defines a new input node in the graph that requires external value assignment.
+    /// Input nodes have no computation logic and must be initialized via `fill_nodes`.
+    #[track_caller]
+    pub fn init(&mut self) -> Node {
+        self.new_node(None)
+    }
+
+    /// Creates a new node with a constant, predefined value.
+    /// This node always evaluates to the same value during computation.
+    ///
+    /// Constants are interned: while `intern_constants` is on (the default),
+    /// asking for a value again returns the node created the first time, so
+    /// its scope, label and creation location are those of the first call.
+    #[track_caller]
+    pub fn constant(&mut self, value: u32) -> Node {
+        if let Some(node) = self.pooled_constant(value) {
+            return node;
+        }
+        let node = self.new_node(Some(Op::Const(value)));
+        if self.intern_constants {
+            self.constant_pool.insert(value, node.id);
+        }
+        node
+    }
+
+    /// The interned node for `value`, if there is one and it still holds that
+    /// constant (edits and removals may have changed it since).
+    pub(crate) fn pooled_constant(&self, value: u32) -> Option<Node> {
+        if !self.intern_constants {
+            return None;
+        }
+        let id = *self.constant_pool.get(&value)?;
+        match self.nodes.get(&id).map(|n| &n.op) {
+            Some(Some(Op::Const(v))) if *v == value => Some(self.handle_of(id)),
+            _ => None,
+        }
+    }
+
+    /// Turns constant interning on or off. With it off, every `constant`
+    /// call creates a distinct node, e.g. to give each its own label or
+    /// scope. Nodes created so far are kept either way.
+    pub fn set_intern_constants(&mut self, enabled: bool) {
+        self.intern_constants = enabled;
+    }
+
+    pub fn intern_constants(&self) -> bool {
+        self.intern_constants
+    }
+
+    /// Constructs a new node representing the sum of two operands, each a
+    /// node or a `u32` literal (promoted to an interned constant), e.g.
+    /// `builder.add(&x, 5)`.
+    /// The node will evaluate to `a.value + b.value` during graph execution.
+    #[track_caller]
+    pub fn add(&mut self, a: impl Into<Operand>, b: impl Into<Operand>) -> Node {
+        let a = self.operand(a);
+        let b = self.operand(b);
+        self.new_node(Some(Op::Add(a.id, b.id, Arithmetic::Inherit)))
+    }
+
+    /// Constructs a new node representing the product of two operands; see
+    /// `add`.
+    /// The node will evaluate to `a.value * b.value` during graph execution.
+    #[track_caller]
+    pub fn mul(&mut self, a: impl Into<Operand>, b: impl Into<Operand>) -> Node {
+        let a = self.operand(a);
+        let b = self.operand(b);
+        self.new_node(Some(Op::Mul(a.id, b.id, Arithmetic::Inherit)))
+    }
+
+    /// Registers an equality constraint between two nodes.
+    /// This will be validated after graph evaluation via `check_constraints`.
+    #[track_caller]
+    pub fn assert_equal(&mut self, a: &Node, b: &Node) {
+        self.check_owner(a);
+        self.check_owner(b);
+        if let Err(e) = self.check_constraint_limit() {
+            panic!("{}", e);
+        }
+        self.constraints.push((a.id, b.id));
+        self.constraint_locations
+            .entry((a.id, b.id))
+            .or_insert(Location::caller());
+    }
+
+    /// Creates a new node whose value is derived from a user-defined function over parent nodes.
+    /// Useful for custom logic like division, square root, or other non-native operations.
+    #[track_caller]
+    pub fn hint(&mut self, parents: &[Node], func: fn(&[u32]) -> u32) -> Node {
+        parents.iter().for_each(|p| self.check_owner(p));
+        if let Err(e) = self.check_hint_limit(parents.len()) {
+            panic!("{}", e);
+        }
+        let parent_ids = parents.iter().map(|n| n.id).collect();
+        self.new_node(Some(Op::Hint(parent_ids, HintFn::new(func))))
+    }
+
+    /// Same as `hint`, but records a name for the function so the graph can be
+    /// written to and read back from textual formats.
+    #[track_caller]
+    pub fn hint_named(&mut self, name: &str, parents: &[Node], func: fn(&[u32]) -> u32) -> Node {
+        parents.iter().for_each(|p| self.check_owner(p));
+        if let Err(e) = self.check_hint_limit(parents.len()) {
+            panic!("{}", e);
+        }
+        let parent_ids = parents.iter().map(|n| n.id).collect();
+        self.new_node(Some(Op::Hint(parent_ids, HintFn::named(name, func))))
+    }
+
+    /// A hint producing `outputs` results from one function. Output `i` is a
+    /// named hint node that calls `func` with the parents' values followed by
+    /// `i`, so the outputs serialize like any other named hint.
+    #[track_caller]
+    pub fn hint_multi(
+        &mut self,
+        name: &str,
+        parents: &[Node],
+        outputs: usize,
+        func: fn(&[u32]) -> u32,
+    ) -> Vec<Node> {
+        let mut operands = parents.to_vec();
+        (0..outputs)
+            .map(|i| {
+                operands.truncate(parents.len());
+                operands.push(self.constant(i as u32));
+                self.hint_named(name, &operands, func)
+            })
+            .collect()
+    }
+
+    /// Computed value of `node`, or `None` if it has not been evaluated (or
+    /// does not exist).
+    pub fn value(&self, node: NodeId) -> Option<u32> {
+        self.nodes.get(&node).and_then(|n| n.value)
+    }
+
+    /// Computed values of `nodes`, in the same order.
+    pub fn values_of(&self, nodes: &[Node]) -> Vec<Option<u32>> {
+        nodes.iter().map(|n| self.value(n.id)).collect()
+    }
+
+    /// Executes the graph by propagating values from input and constant nodes
+    /// through arithmetic and hint nodes. Continues until no further updates occur.
+    /// Nodes are visited in the builder's `EvalOrder`. Returns what was
+    /// computed and which nodes could not be.
+    ///
+    /// Values for unknown ids are ignored and values for computed nodes
+    /// override them, both with a warning; `try_fill_nodes` rejects them.
+    /// Giving an input a different value than an earlier call re-evaluates
+    /// everything downstream of it.
+    pub fn fill_nodes(&mut self, inputs: HashMap<NodeId, u32>) -> EvalSummary {
+        match self.fill_nodes_until(inputs, None) {
+            Ok(summary) | Err(Cancelled { summary }) => summary,
+        }
+    }
+
+    /// `fill_nodes`, stopping early once `cancel` is set. The nodes computed
+    /// before that keep their values.
+    fn fill_nodes_until(
+        &mut self,
+        inputs: HashMap<NodeId, u32>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<EvalSummary, Cancelled> {
+        let mut summary = EvalSummary::default();
+        let mut inputs: Vec<(NodeId, u32)> = inputs.into_iter().collect();
+        if self.eval_order == EvalOrder::Topological {
+            inputs.sort();
+        }
+        for (id, val) in &inputs {
+            match self.nodes.get(id) {
+                None => {
+                    log!("⚠️ Ignoring value {} for unknown Node {:?}", val, id);
+                }
+                Some(node) if node.op.is_some() => {
+                    log!("⚠️ Overriding computed Node {:?} with {}", id, val);
+                }
+                Some(_) => {
+                    log!("🔧 Setting input Node {:?} = {}", id, val);
+                }
+            }
+        }
+        // An input that changes value invalidates everything computed from it.
+        let changed: Vec<NodeId> = inputs
+            .iter()
+            .filter(|(id, val)| {
+                let old = self.nodes.get(id).and_then(|n| n.value);
+                old.is_some_and(|old| old != *val)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        if !changed.is_empty() {
+            let dependents = self.dependents_map();
+            let stale: Vec<NodeId> = changed
+                .iter()
+                .flat_map(|id| dependents.get(id).into_iter().flatten().copied())
+                .collect();
+            log!(
+                "♻️ Re-evaluating downstream of changed inputs {:?}",
+                changed
+            );
+            self.invalidate_from(stale);
+        }
+        summary.skipped_already_set = self
+            .nodes
+            .values()
+            .filter(|n| n.op.is_some() && n.value.is_some())
+            .count();
+        for (id, val) in inputs {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.value = Some(val);
+            }
+        }
+        let finished = if self.eval_order == EvalOrder::Topological {
+            self.evaluate_in_order(&mut summary, cancel)
+        } else {
+            self.evaluate_worklist(&mut summary, cancel)
+        };
+        summary.unresolved = self
+            .sorted_nodes()
+            .into_iter()
+            .filter(|n| n.value.is_none())
+            .map(|n| n.id)
+            .collect();
+        if finished {
+            Ok(summary)
+        } else {
+            log!("🛑 Evaluation cancelled after {} nodes", summary.evaluated);
+            Err(Cancelled { summary })
+        }
+    }
+
+    /// Propagates values from ready nodes to their dependents until nothing
+    /// changes. Returns false if `cancel` was set before that.
+    fn evaluate_worklist(
+        &mut self,
+        summary: &mut EvalSummary,
+        cancel: Option<&AtomicBool>,
+    ) -> bool {
+        let node_ids: Vec<NodeId> = self.nodes.keys().cloned().collect();
+        let mut worklist: Vec<NodeId> = node_ids.clone();
+        let mut visited = HashSet::new();
+        while let Some(id) = worklist.pop() {
+            if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+                return false;
+            }
+            if visited.contains(&id) {
+                continue;
+            }
+            // Use a local reference to the node
+            let maybe_node = self.nodes.get(&id);
+            if maybe_node.is_none() {
+                continue;
+            }
+            let current_val = maybe_node.and_then(|n| n.value);
+            if current_val.is_some() {
+                visited.insert(id);
+                continue;
+            }
+            let op = maybe_node.and_then(|n| n.op.as_ref());
+            log!("Evaluating Node {:?} with op {:?}", id, op);
+            let computed = op.and_then(|op| {
+                let val = op.apply(self.arithmetic, |pid| {
+                    self.nodes.get(&pid).and_then(|n| n.value)
+                })?;
+                Some((op, val))
+            });
+            if let Some((op, val)) = computed {
+                summary.record(op);
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    node.value = Some(val);
+                    log!("→ Computed Node {:?} = {}", id, val);
+                    self.notify(id, val);
+                    // Add downstream nodes (dependents) to the worklist
+                    // We look for nodes that have this id as a parent
+                    for (other_id, other_node) in self.nodes.iter() {
+                        if let Some(op) = &other_node.op {
+                            let is_dependent = match op {
+                                Op::Add(a, b, _) | Op::Mul(a, b, _) => *a == id || *b == id,
+                                Op::Hint(parents, _) => parents.contains(&id),
+                                _ => false,
+                            };
+                            if is_dependent {
+                                worklist.push(*other_id);
+                            }
+                        }
+                    }
+                }
+                visited.insert(id);
+            }
+            // Nodes whose parents are not ready yet stay unvisited; they are pushed
+            // back onto the worklist once one of their parents gets computed.
+        }
+        true
+    }
+
+    /// Chooses how `fill_nodes` walks the graph. Both orders compute the same
+    /// values and are deterministic; `EvalOrder::Topological` calls each
+    /// node once, after its parents, so hints see their inputs in graph order.
+    pub fn set_eval_order(&mut self, order: EvalOrder) {
+        self.eval_order = order;
+    }
+
+    pub fn eval_order(&self) -> EvalOrder {
+        self.eval_order
+    }
+
+    /// Computes every node without a value in one topological pass. Returns
+    /// false if `cancel` was set before the pass finished.
+    fn evaluate_in_order(
+        &mut self,
+        summary: &mut EvalSummary,
+        cancel: Option<&AtomicBool>,
+    ) -> bool {
+        let mut ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        self.sort_topologically(&mut ids);
+        for id in ids {
+            if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+                return false;
+            }
+            let node = &self.nodes[&id];
+            let Some(op) = node.op.as_ref().filter(|_| node.value.is_none()) else {
+                continue;
+            };
+            log!("Evaluating Node {:?} with op {:?}", id, op);
+            let new_val = op.apply(self.arithmetic, |pid| {
+                self.nodes.get(&pid).and_then(|n| n.value)
+            });
+            if let Some(val) = new_val {
+                summary.record(op);
+                log!("→ Computed Node {:?} = {}", id, val);
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    node.value = Some(val);
+                }
+                self.notify(id, val);
+            }
+        }
+        true
+    }
+
+    /// Validates all equality constraints defined in the graph by comparing evaluated node values.
+    /// Returns true if all constraints hold, otherwise logs mismatches and returns false.
+    pub fn check_constraints(&self) -> bool {
+        let mut all_ok = true;
+        for (a, b) in &self.constraints {
+            let val_a = self.nodes.get(a).and_then(|n| n.value);
+            let val_b = self.nodes.get(b).and_then(|n| n.value);
+            if val_a != val_b {
+                let site = match self.constraint_locations.get(&(*a, *b)) {
+                    Some(location) => format!(" at {}", location),
+                    None => String::new(),
+                };
+                log!(
+                    "⚠️ Constraint failed{}: {} = {:?} != {} = {:?}",
+                    site,
+                    self.describe(*a),
+                    val_a,
+                    self.describe(*b),
+                    val_b
+                );
+                all_ok = false;
+            }
+        }
+        all_ok
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests;
@@ -0,0 +1,162 @@
+//! Caps on graph size, for services that build graphs from untrusted input
+//! and need to bound the memory and evaluation time one request can use.
+
+use core::fmt;
+
+use crate::{Builder, Node};
+
+/// Limits enforced by a `Builder` while the graph is constructed. `None`
+/// means unlimited, which is the default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub max_nodes: Option<usize>,
+    pub max_constraints: Option<usize>,
+    /// Parents of a single hint node.
+    pub max_hint_parents: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitError {
+    TooManyNodes { limit: usize },
+    TooManyConstraints { limit: usize },
+    TooManyHintParents { limit: usize, parents: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooManyNodes { limit } => {
+                write!(f, "graph already has the maximum of {} nodes", limit)
+            }
+            LimitError::TooManyConstraints { limit } => {
+                write!(f, "graph already has the maximum of {} constraints", limit)
+            }
+            LimitError::TooManyHintParents { limit, parents } => write!(
+                f,
+                "hint has {} parents but at most {} are allowed",
+                parents, limit
+            ),
+        }
+    }
+}
+
+impl core::error::Error for LimitError {}
+
+impl Builder {
+    /// An empty graph that refuses to grow past `limits`.
+    pub fn with_limits(limits: Limits) -> Self {
+        let mut builder = Self::new();
+        builder.limits = limits;
+        builder
+    }
+
+    /// Changes the limits. Nodes and constraints already in the graph are
+    /// kept even if they exceed the new limits; only further growth is
+    /// refused.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Whether one more node fits.
+    pub(crate) fn check_node_limit(&self) -> Result<(), LimitError> {
+        match self.limits.max_nodes {
+            Some(limit) if self.nodes.len() >= limit => Err(LimitError::TooManyNodes { limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether one more constraint fits.
+    pub(crate) fn check_constraint_limit(&self) -> Result<(), LimitError> {
+        match self.limits.max_constraints {
+            Some(limit) if self.constraints.len() >= limit => {
+                Err(LimitError::TooManyConstraints { limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a hint node with `parents` parents fits.
+    pub(crate) fn check_hint_limit(&self, parents: usize) -> Result<(), LimitError> {
+        self.check_node_limit()?;
+        match self.limits.max_hint_parents {
+            Some(limit) if parents > limit => {
+                Err(LimitError::TooManyHintParents { limit, parents })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `init`, returning an error instead of panicking when the graph
+    /// is full.
+    #[track_caller]
+    pub fn try_init(&mut self) -> Result<Node, LimitError> {
+        self.check_node_limit()?;
+        Ok(self.init())
+    }
+
+    /// Like `constant`, returning an error instead of panicking when the
+    /// graph is full. An interned constant needs no new node and always
+    /// succeeds.
+    #[track_caller]
+    pub fn try_constant(&mut self, value: u32) -> Result<Node, LimitError> {
+        if let Some(node) = self.pooled_constant(value) {
+            return Ok(node);
+        }
+        self.check_node_limit()?;
+        Ok(self.constant(value))
+    }
+
+    /// Like `add`, returning an error instead of panicking when the graph is
+    /// full.
+    #[track_caller]
+    pub fn try_add(&mut self, a: &Node, b: &Node) -> Result<Node, LimitError> {
+        self.check_node_limit()?;
+        Ok(self.add(a, b))
+    }
+
+    /// Like `mul`, returning an error instead of panicking when the graph is
+    /// full.
+    #[track_caller]
+    pub fn try_mul(&mut self, a: &Node, b: &Node) -> Result<Node, LimitError> {
+        self.check_node_limit()?;
+        Ok(self.mul(a, b))
+    }
+
+    /// Like `hint`, returning an error instead of panicking when the graph is
+    /// full or the hint has too many parents.
+    #[track_caller]
+    pub fn try_hint(
+        &mut self,
+        parents: &[Node],
+        func: fn(&[u32]) -> u32,
+    ) -> Result<Node, LimitError> {
+        self.check_hint_limit(parents.len())?;
+        Ok(self.hint(parents, func))
+    }
+
+    /// Like `hint_named`, returning an error instead of panicking when the
+    /// graph is full or the hint has too many parents.
+    #[track_caller]
+    pub fn try_hint_named(
+        &mut self,
+        name: &str,
+        parents: &[Node],
+        func: fn(&[u32]) -> u32,
+    ) -> Result<Node, LimitError> {
+        self.check_hint_limit(parents.len())?;
+        Ok(self.hint_named(name, parents, func))
+    }
+
+    /// Like `assert_equal`, returning an error instead of panicking when the
+    /// graph has its maximum of constraints.
+    #[track_caller]
+    pub fn try_assert_equal(&mut self, a: &Node, b: &Node) -> Result<(), LimitError> {
+        self.check_constraint_limit()?;
+        self.assert_equal(a, b);
+        Ok(())
+    }
+}
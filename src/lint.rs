@@ -0,0 +1,75 @@
+//! Soundness lints: hints whose values no constraint checks and inputs no
+//! constraint depends on. Either usually means the graph accepts values it
+//! should reject.
+
+use core::fmt;
+use core::panic::Location;
+
+use crate::collections::HashSet;
+use crate::prelude::*;
+use crate::{Builder, NodeId, Op};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LintKind {
+    /// A hint outside the operand cone of every constraint: whatever value it
+    /// produces is accepted.
+    UnconstrainedHint,
+    /// An input no constraint depends on.
+    UnconstrainedInput,
+}
+
+/// One finding of `Builder::lint`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub kind: LintKind,
+    pub node: NodeId,
+    /// The node's scope path and label, or its id.
+    pub name: String,
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            LintKind::UnconstrainedHint => "hint is not checked by any constraint",
+            LintKind::UnconstrainedInput => "input does not affect any constraint",
+        };
+        write!(f, "{}: {}", self.name, what)?;
+        if let Some(location) = self.location {
+            write!(f, " (created at {})", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl Builder {
+    /// Flags hints and inputs that no constraint depends on, in evaluation
+    /// order.
+    pub fn lint(&self) -> Vec<Lint> {
+        let mut constrained = HashSet::new();
+        for (a, b) in &self.constraints {
+            for end in [a, b] {
+                if !constrained.contains(end) {
+                    constrained.extend(self.cone_of(*end));
+                }
+            }
+        }
+        self.sorted_nodes()
+            .into_iter()
+            .filter(|node| !constrained.contains(&node.id))
+            .filter_map(|node| {
+                let kind = match node.op {
+                    None => LintKind::UnconstrainedInput,
+                    Some(Op::Hint(..)) => LintKind::UnconstrainedHint,
+                    _ => return None,
+                };
+                Some(Lint {
+                    kind,
+                    node: node.id,
+                    name: self.describe(node.id),
+                    location: self.locations.get(&node.id).copied(),
+                })
+            })
+            .collect()
+    }
+}
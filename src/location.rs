@@ -0,0 +1,18 @@
+//! Source locations of nodes and constraints, captured with `#[track_caller]`
+//! when they are created so failures can point at the code that built them.
+
+use core::panic::Location;
+
+use crate::{Builder, Node, NodeId};
+
+impl Builder {
+    /// Where `node` was created, e.g. `src/pricing.rs:142:17`.
+    pub fn location_of(&self, node: &Node) -> Option<&'static Location<'static>> {
+        self.locations.get(&node.id).copied()
+    }
+
+    /// Where the constraint `a == b` was added.
+    pub fn constraint_location(&self, a: NodeId, b: NodeId) -> Option<&'static Location<'static>> {
+        self.constraint_locations.get(&(a, b)).copied()
+    }
+}
@@ -0,0 +1,125 @@
+//! Combining independently built graphs.
+
+use crate::collections::HashMap;
+use core::fmt;
+
+use crate::prelude::*;
+use crate::{Builder, Instance, Node, NodeId, Op};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MergeError {
+    /// The connection target in the other graph is not an input node.
+    NotAnInput(NodeId),
+    /// A connection refers to a node that does not exist.
+    UnknownNode(NodeId),
+    /// The same input of the other graph is connected twice.
+    DuplicateConnection(NodeId),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::NotAnInput(id) => write!(f, "Node {:?} is not an input", id),
+            MergeError::UnknownNode(id) => write!(f, "Node {:?} does not exist", id),
+            MergeError::DuplicateConnection(id) => {
+                write!(f, "input Node {:?} is connected more than once", id)
+            }
+        }
+    }
+}
+
+impl core::error::Error for MergeError {}
+
+impl Builder {
+    /// Copies every node, constraint, scope, metadata entry, input range, source
+    /// location and gadget instance record of `other` into this graph.
+    /// Nodes listed in `bindings` are not copied; references to them resolve to
+    /// the bound node instead. Returns the node each `other` id now maps to.
+    pub(crate) fn copy_from(
+        &mut self,
+        other: &Builder,
+        mut bindings: HashMap<NodeId, Node>,
+    ) -> HashMap<NodeId, Node> {
+        for node in other.sorted_nodes() {
+            if bindings.contains_key(&node.id) {
+                continue;
+            }
+            let copy = match &node.op {
+                None => self.init(),
+                Some(Op::Const(v)) => self.constant(*v),
+                Some(Op::Add(a, b, mode)) => self.add_with(&bindings[a], &bindings[b], *mode),
+                Some(Op::Mul(a, b, mode)) => self.mul_with(&bindings[a], &bindings[b], *mode),
+                Some(Op::Hint(parents, f)) => {
+                    let ids = parents.iter().map(|p| bindings[p].id).collect();
+                    self.new_node(Some(Op::Hint(ids, f.clone())))
+                }
+            };
+            if let Some(scope) = other.node_scopes.get(&node.id) {
+                let path = match self.node_scopes.get(&copy.id) {
+                    Some(prefix) => format!("{}/{}", prefix, scope),
+                    None => scope.clone(),
+                };
+                self.node_scopes.insert(copy.id, path);
+            }
+            if let Some(location) = other.locations.get(&node.id) {
+                self.locations.insert(copy.id, location);
+            }
+            if let Some(entries) = other.metadata.get(&node.id) {
+                self.insert_metadata(copy.id, entries.clone());
+            }
+            if let Some(range) = other.input_ranges.get(&node.id) {
+                self.input_ranges.insert(copy.id, range.clone());
+            }
+            bindings.insert(node.id, copy);
+        }
+        for (a, b) in &other.constraints {
+            let location = other.constraint_locations.get(&(*a, *b));
+            let (a, b) = (bindings[a], bindings[b]);
+            self.assert_equal(&a, &b);
+            if let Some(location) = location {
+                self.constraint_locations.insert((a.id, b.id), location);
+            }
+        }
+        let prefix = self.scope_stack.join("/");
+        for instance in &other.instances {
+            let remap = |ids: &[NodeId]| ids.iter().map(|id| bindings[id].id).collect();
+            let scope = if prefix.is_empty() {
+                instance.scope.clone()
+            } else {
+                format!("{}/{}", prefix, instance.scope)
+            };
+            self.instances.push(Instance {
+                gadget: instance.gadget.clone(),
+                scope,
+                inputs: remap(&instance.inputs),
+                outputs: remap(&instance.outputs),
+                nodes: remap(&instance.nodes),
+            });
+        }
+        bindings
+    }
+
+    /// Imports all nodes and constraints of `other`, remapping their ids.
+    /// Each `(ours, theirs)` connection feeds node `ours` of this graph into
+    /// input `theirs` of `other`, so the two graphs become one.
+    /// Returns the node in this graph for every node id of `other`.
+    pub fn merge(
+        &mut self,
+        other: &Builder,
+        connections: &[(NodeId, NodeId)],
+    ) -> Result<HashMap<NodeId, Node>, MergeError> {
+        let mut bindings = HashMap::new();
+        for (ours, theirs) in connections {
+            let source = self.handle(*ours).ok_or(MergeError::UnknownNode(*ours))?;
+            match other.nodes.get(theirs) {
+                Some(node) if node.op.is_none() => {}
+                Some(_) => return Err(MergeError::NotAnInput(*theirs)),
+                None => return Err(MergeError::UnknownNode(*theirs)),
+            }
+            if bindings.insert(*theirs, source).is_some() {
+                return Err(MergeError::DuplicateConnection(*theirs));
+            }
+        }
+        Ok(self.copy_from(other, bindings))
+    }
+}
@@ -0,0 +1,100 @@
+//! Mermaid flowchart export, for graphs embedded in Markdown documentation.
+
+use std::fmt::Write;
+
+use crate::{Arithmetic, Builder, NodeData, Op};
+
+fn mermaid_label(node: &NodeData, name: Option<&String>) -> String {
+    let op = match &node.op {
+        None => "input".to_string(),
+        Some(Op::Const(v)) => format!("const {}", v),
+        Some(Op::Add(_, _, Arithmetic::Inherit)) => "+".to_string(),
+        Some(Op::Mul(_, _, Arithmetic::Inherit)) => "×".to_string(),
+        Some(Op::Add(_, _, mode)) => format!("+ {}", mode),
+        Some(Op::Mul(_, _, mode)) => format!("× {}", mode),
+        Some(Op::Hint(_, f)) => match f.name() {
+            Some(name) => format!("hint {}", name),
+            None => "hint".to_string(),
+        },
+    };
+    let mut label = match name {
+        Some(name) => format!("{}: {}", name, op),
+        None => format!("n{}: {}", node.id.0, op),
+    };
+    if let Some(v) = node.value {
+        let _ = write!(label, " = {}", v);
+    }
+    label.replace('"', "#quot;")
+}
+
+impl Builder {
+    /// The graph as a Mermaid `flowchart`. Inputs are drawn as stadiums,
+    /// outputs as double circles, scopes as subgraphs and constraints as
+    /// dotted links.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("flowchart TD\n");
+        // Stable sort by scope path keeps each scope contiguous and in
+        // evaluation order, so every subgraph is opened exactly once.
+        let scope_of = |node: &NodeData| -> Vec<&str> {
+            self.node_scopes
+                .get(&node.id)
+                .map(|path| path.split('/').filter(|s| !s.is_empty()).collect())
+                .unwrap_or_default()
+        };
+        let mut nodes = self.sorted_nodes();
+        nodes.sort_by_key(|n| scope_of(n));
+        let mut open: Vec<&str> = Vec::new();
+        let mut next_subgraph = 0;
+        for node in nodes {
+            let segments = scope_of(node);
+            let common = open
+                .iter()
+                .zip(&segments)
+                .take_while(|(a, b)| a == b)
+                .count();
+            while open.len() > common {
+                open.pop();
+                let _ = writeln!(out, "{}end", "  ".repeat(open.len() + 1));
+            }
+            for segment in &segments[common..] {
+                let _ = writeln!(
+                    out,
+                    "{}subgraph s{}[\"{}\"]",
+                    "  ".repeat(open.len() + 1),
+                    next_subgraph,
+                    segment.replace('"', "#quot;")
+                );
+                next_subgraph += 1;
+                open.push(segment);
+            }
+            let label = mermaid_label(node, self.labels.get(&node.id));
+            let shape = if self.is_output_id(node.id) {
+                format!("((\"{}\"))", label)
+            } else if node.op.is_none() {
+                format!("([\"{}\"])", label)
+            } else {
+                format!("[\"{}\"]", label)
+            };
+            let _ = writeln!(
+                out,
+                "{}n{}{}",
+                "  ".repeat(open.len() + 1),
+                node.id.0,
+                shape
+            );
+        }
+        while !open.is_empty() {
+            open.pop();
+            let _ = writeln!(out, "{}end", "  ".repeat(open.len() + 1));
+        }
+        for node in self.sorted_nodes() {
+            for operand in node.operands() {
+                let _ = writeln!(out, "  n{} --> n{}", operand.0, node.id.0);
+            }
+        }
+        for (a, b) in &self.constraints {
+            let _ = writeln!(out, "  n{} -. \"==\" .- n{}", a.0, b.0);
+        }
+        out
+    }
+}
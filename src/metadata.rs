@@ -0,0 +1,47 @@
+//! Free-form key/value metadata on nodes, for units, provenance or UI hints
+//! that the graph itself does not interpret.
+
+#[cfg(feature = "std")]
+use alloc::collections::BTreeMap;
+
+use crate::prelude::*;
+use crate::{Builder, Node};
+
+impl Builder {
+    /// Attaches `value` under `key` to `node`, replacing any earlier value.
+    pub fn set_metadata(&mut self, node: &Node, key: &str, value: &str) {
+        self.index_metadata_key(node.id, key);
+        self.metadata
+            .entry(node.id)
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+    }
+
+    pub fn metadata(&self, node: &Node, key: &str) -> Option<&str> {
+        self.metadata.get(&node.id)?.get(key).map(|s| s.as_str())
+    }
+
+    /// All metadata of `node`, ordered by key.
+    pub fn metadata_of(&self, node: &Node) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.metadata
+            .get(&node.id)
+            .into_iter()
+            .flat_map(|m| m.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+
+    /// Removes `key` from `node`, returning its previous value.
+    pub fn remove_metadata(&mut self, node: &Node, key: &str) -> Option<String> {
+        let entries = self.metadata.get_mut(&node.id)?;
+        let old = entries.remove(key)?;
+        if entries.is_empty() {
+            self.metadata.remove(&node.id);
+        }
+        self.unindex_metadata_key(node.id, key);
+        Some(old)
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn metadata_map(&self, id: crate::NodeId) -> Option<&BTreeMap<String, String>> {
+        self.metadata.get(&id)
+    }
+}
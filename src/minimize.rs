@@ -0,0 +1,118 @@
+//! Removing constraints that other constraints or the graph's structure
+//! already imply, to shrink exported circuits.
+
+use crate::collections::{HashMap, HashSet};
+use crate::prelude::*;
+use crate::{Arithmetic, Builder, NodeId, Op};
+
+/// What a node computes, in terms of the structural classes of its operands.
+/// Ordered as well as hashable, so it can key the `BTreeMap`-based maps of
+/// `no_std` builds.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Shape {
+    Const(u32),
+    Add(usize, usize, Mode),
+    Mul(usize, usize, Mode),
+    Hint(String, Vec<usize>),
+}
+
+/// An effective `Arithmetic` as an orderable key.
+type Mode = (u8, u32);
+
+fn mode_key(mode: Arithmetic) -> Mode {
+    match mode {
+        Arithmetic::Inherit | Arithmetic::Wrapping => (0, 0),
+        Arithmetic::Checked => (1, 0),
+        Arithmetic::Saturating => (2, 0),
+        Arithmetic::Modular(m) => (3, m.get()),
+    }
+}
+
+/// Union-find over structural classes.
+struct Classes {
+    parent: Vec<usize>,
+}
+
+impl Classes {
+    fn find(&mut self, mut class: usize) -> usize {
+        while self.parent[class] != class {
+            self.parent[class] = self.parent[self.parent[class]];
+            class = self.parent[class];
+        }
+        class
+    }
+}
+
+impl Builder {
+    /// Numbers nodes so that nodes computing the same op over the same
+    /// operands share a number: equal constants, commuted sums and products,
+    /// and named hints with the same name and parents. Inputs and unnamed
+    /// hints each get their own.
+    fn structural_classes(&self) -> HashMap<NodeId, usize> {
+        let mut shapes: HashMap<Shape, usize> = HashMap::new();
+        let mut classes: HashMap<NodeId, usize> = HashMap::new();
+        for node in self.sorted_nodes() {
+            let class_of = |id: &NodeId| classes[id];
+            let ordered = |a: &NodeId, b: &NodeId| {
+                let (a, b) = (class_of(a), class_of(b));
+                (a.min(b), a.max(b))
+            };
+            let shape = match &node.op {
+                None => None,
+                Some(Op::Const(v)) => Some(Shape::Const(*v)),
+                Some(Op::Add(a, b, mode)) => {
+                    let (a, b) = ordered(a, b);
+                    Some(Shape::Add(a, b, mode_key(mode.or(self.arithmetic))))
+                }
+                Some(Op::Mul(a, b, mode)) => {
+                    let (a, b) = ordered(a, b);
+                    Some(Shape::Mul(a, b, mode_key(mode.or(self.arithmetic))))
+                }
+                Some(Op::Hint(parents, f)) => f.name().map(|name| {
+                    Shape::Hint(name.to_string(), parents.iter().map(class_of).collect())
+                }),
+            };
+            let fresh = classes.len();
+            let class = match shape {
+                Some(shape) => *shapes.entry(shape).or_insert(fresh),
+                None => fresh,
+            };
+            classes.insert(node.id, class);
+        }
+        classes
+    }
+
+    /// Removes constraints implied by the graph's structure or by the
+    /// constraints kept before them: duplicates (in either order), `x == x`,
+    /// constraints between nodes that compute the same op over the same
+    /// operands, and constraints that follow from others by transitivity.
+    /// Constraints are considered in the order they were added and the first
+    /// of each redundant group is kept. Returns how many were removed.
+    pub fn minimize_constraints(&mut self) -> usize {
+        let classes = self.structural_classes();
+        let mut union = Classes {
+            parent: (0..self.nodes.len()).collect(),
+        };
+        let before = self.constraints.len();
+        self.log_constraints();
+        let constraints = core::mem::take(&mut self.constraints);
+        for (a, b) in constraints {
+            let (Some(&ca), Some(&cb)) = (classes.get(&a), classes.get(&b)) else {
+                // Dangling constraints are `validate`'s business; keep them.
+                self.constraints.push((a, b));
+                continue;
+            };
+            let (ra, rb) = (union.find(ca), union.find(cb));
+            if ra == rb {
+                log!("✂️ Removed redundant constraint {:?} == {:?}", a, b);
+                continue;
+            }
+            union.parent[ra] = rb;
+            self.constraints.push((a, b));
+        }
+        let kept: HashSet<(NodeId, NodeId)> = self.constraints.iter().copied().collect();
+        self.constraint_locations
+            .retain(|pair, _| kept.contains(pair));
+        before - self.constraints.len()
+    }
+}
@@ -0,0 +1,191 @@
+//! Overflow policies for `Add` and `Mul`: wrapping, checked, saturating or
+//! modulo a modulus chosen at run time with `Builder::with_modulus`, so modular
+//! experiments need no new value type per modulus. The builder holds the
+//! default policy and individual nodes may override it.
+//!
+//! Products are reduced with Barrett reduction: a reciprocal computed once per
+//! modulus turns the 64-bit division of every `Mul` into a multiplication and
+//! at most two subtractions. Montgomery form would save a little more per
+//! product, but node values are stored in normal form, so every node would pay
+//! a conversion in and out again.
+
+use core::fmt;
+
+use crate::{Builder, Node, Op};
+
+/// A nonzero modulus that `Add` and `Mul` results are reduced by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Modulus {
+    m: u32,
+    /// `floor((2^64 - 1) / m)`, the Barrett reciprocal.
+    reciprocal: u64,
+}
+
+impl Modulus {
+    /// Returns `None` for 0.
+    pub fn new(m: u32) -> Option<Self> {
+        (m != 0).then(|| Modulus {
+            m,
+            reciprocal: u64::MAX / u64::from(m),
+        })
+    }
+
+    pub fn get(self) -> u32 {
+        self.m
+    }
+
+    /// `x mod m` for any 64-bit `x`, without a division.
+    pub fn reduce(self, x: u64) -> u32 {
+        let m = u64::from(self.m);
+        // The estimate undershoots the true quotient by at most 2.
+        let q = ((u128::from(x) * u128::from(self.reciprocal)) >> 64) as u64;
+        let mut r = x - q * m;
+        while r >= m {
+            r -= m;
+        }
+        r as u32
+    }
+
+    /// `(a + b) mod m`, computed without overflow.
+    pub fn add(self, a: u32, b: u32) -> u32 {
+        self.reduce(u64::from(a) + u64::from(b))
+    }
+
+    /// `(a * b) mod m`, computed without overflow.
+    pub fn mul(self, a: u32, b: u32) -> u32 {
+        self.reduce(u64::from(a) * u64::from(b))
+    }
+}
+
+/// How an `Add` or `Mul` node treats results that do not fit in a `u32`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Arithmetic {
+    /// Follow the builder's policy, which is `Wrapping` unless set otherwise.
+    #[default]
+    Inherit,
+    /// Results wrap modulo 2^32.
+    Wrapping,
+    /// A result that overflows leaves the node without a value, so it shows
+    /// up in `EvalSummary::unresolved`.
+    Checked,
+    /// Results stop at `u32::MAX`.
+    Saturating,
+    /// Results are reduced modulo the given modulus.
+    Modular(Modulus),
+}
+
+impl Arithmetic {
+    /// `self`, or `policy` if `self` is `Inherit`.
+    pub fn or(self, policy: Arithmetic) -> Arithmetic {
+        match self {
+            Arithmetic::Inherit => policy,
+            mode => mode,
+        }
+    }
+
+    pub(crate) fn add(self, a: u32, b: u32) -> Option<u32> {
+        match self {
+            Arithmetic::Inherit | Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+            Arithmetic::Checked => a.checked_add(b),
+            Arithmetic::Saturating => Some(a.saturating_add(b)),
+            Arithmetic::Modular(m) => Some(m.add(a, b)),
+        }
+    }
+
+    pub(crate) fn mul(self, a: u32, b: u32) -> Option<u32> {
+        match self {
+            Arithmetic::Inherit | Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+            Arithmetic::Checked => a.checked_mul(b),
+            Arithmetic::Saturating => Some(a.saturating_mul(b)),
+            Arithmetic::Modular(m) => Some(m.mul(a, b)),
+        }
+    }
+
+    /// Parses the names written by `Display`: `inherit`, `wrapping`,
+    /// `checked`, `saturating` or `mod:<m>`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "inherit" => Some(Arithmetic::Inherit),
+            "wrapping" => Some(Arithmetic::Wrapping),
+            "checked" => Some(Arithmetic::Checked),
+            "saturating" => Some(Arithmetic::Saturating),
+            _ => {
+                let m = name.strip_prefix("mod:")?.parse().ok()?;
+                Modulus::new(m).map(Arithmetic::Modular)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Arithmetic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arithmetic::Inherit => write!(f, "inherit"),
+            Arithmetic::Wrapping => write!(f, "wrapping"),
+            Arithmetic::Checked => write!(f, "checked"),
+            Arithmetic::Saturating => write!(f, "saturating"),
+            Arithmetic::Modular(m) => write!(f, "mod:{}", m.get()),
+        }
+    }
+}
+
+impl Builder {
+    /// A builder whose `Add` and `Mul` nodes evaluate modulo `modulus` instead
+    /// of wrapping modulo 2^32. Inputs, constants and hint results are stored
+    /// as given, and constraints compare values exactly, so keep them below
+    /// `modulus`. The `gadgets` module assumes wrapping arithmetic and is not
+    /// sound under a modulus.
+    ///
+    /// # Panics
+    /// If `modulus` is 0.
+    pub fn with_modulus(modulus: u32) -> Self {
+        let mut builder = Builder::new();
+        let modulus = Modulus::new(modulus).expect("modulus must be nonzero");
+        builder.arithmetic = Arithmetic::Modular(modulus);
+        builder
+    }
+
+    /// The modulus of the builder's policy, if it is `Arithmetic::Modular`.
+    pub fn modulus(&self) -> Option<u32> {
+        match self.arithmetic {
+            Arithmetic::Modular(m) => Some(m.get()),
+            _ => None,
+        }
+    }
+
+    /// Sets the policy for `Add` and `Mul` nodes that do not override it.
+    /// Values computed earlier are kept; clear them to re-evaluate.
+    pub fn set_arithmetic(&mut self, policy: Arithmetic) {
+        self.arithmetic = policy;
+    }
+
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// Like `add`, with `mode` overriding the builder's policy for this node.
+    #[track_caller]
+    pub fn add_with(&mut self, a: &Node, b: &Node, mode: Arithmetic) -> Node {
+        self.check_owner(a);
+        self.check_owner(b);
+        self.new_node(Some(Op::Add(a.id, b.id, mode)))
+    }
+
+    /// Like `mul`, with `mode` overriding the builder's policy for this node.
+    #[track_caller]
+    pub fn mul_with(&mut self, a: &Node, b: &Node, mode: Arithmetic) -> Node {
+        self.check_owner(a);
+        self.check_owner(b);
+        self.new_node(Some(Op::Mul(a.id, b.id, mode)))
+    }
+
+    /// The override recorded on an `Add` or `Mul` node, if it has one.
+    pub fn arithmetic_of(&self, node: &Node) -> Option<Arithmetic> {
+        match self.nodes.get(&node.id)?.op {
+            Some(Op::Add(_, _, mode)) | Some(Op::Mul(_, _, mode)) => {
+                Some(mode).filter(|mode| *mode != Arithmetic::Inherit)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Callbacks fired as node values are computed, so consumers can stream
+//! results while the rest of the graph is still being evaluated.
+
+use alloc::sync::Arc;
+
+use crate::{Builder, Node, NodeId};
+
+/// A callback registered with `Builder::on_value`.
+pub(crate) type Observer = Arc<dyn Fn(NodeId, u32) + Send + Sync>;
+
+impl Builder {
+    /// Calls `callback` with the node's id and value each time an evaluation
+    /// (`fill_nodes` and its variants, `fill_with_plan`, `evaluate_outputs`)
+    /// computes `node`. Values supplied as inputs do not fire it. Callbacks
+    /// run in evaluation order, on the evaluating thread, and are kept by
+    /// clones of the builder.
+    pub fn on_value(
+        &mut self,
+        node: &Node,
+        callback: impl Fn(NodeId, u32) + Send + Sync + 'static,
+    ) {
+        self.check_owner(node);
+        self.observers
+            .entry(node.id)
+            .or_default()
+            .push(Arc::new(callback));
+    }
+
+    /// Removes every callback registered on `node`.
+    pub fn clear_observers(&mut self, node: &Node) {
+        self.observers.remove(&node.id);
+    }
+
+    pub(crate) fn notify(&self, id: NodeId, value: u32) {
+        for observer in self.observers.get(&id).into_iter().flatten() {
+            observer(id, value);
+        }
+    }
+}
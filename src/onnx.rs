@@ -0,0 +1,418 @@
+//! Importer for a restricted subset of ONNX models.
+//!
+//! Supported: graph inputs, integer initializers, and `Add`, `Mul` and `Constant`
+//! nodes whose tensors hold exactly one non-negative integer that fits in `u32`.
+//! Anything else is rejected with an `OnnxError` naming the offending construct.
+//!
+//! The protobuf wire format is decoded by hand for the handful of fields used
+//! here, so no protobuf dependency is needed.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Builder, LimitError, Limits, Node, NodeId};
+
+/// Error produced when an ONNX model cannot be imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnnxError {
+    pub message: String,
+}
+
+impl OnnxError {
+    fn new(message: impl Into<String>) -> Self {
+        OnnxError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for OnnxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ONNX import failed: {}", self.message)
+    }
+}
+
+impl std::error::Error for OnnxError {}
+
+impl From<LimitError> for OnnxError {
+    fn from(e: LimitError) -> Self {
+        OnnxError::new(e.to_string())
+    }
+}
+
+// Field numbers from onnx.proto.
+const MODEL_GRAPH: u64 = 7;
+const GRAPH_NODE: u64 = 1;
+const GRAPH_INITIALIZER: u64 = 5;
+const GRAPH_INPUT: u64 = 11;
+const GRAPH_OUTPUT: u64 = 12;
+const NODE_INPUT: u64 = 1;
+const NODE_OUTPUT: u64 = 2;
+const NODE_OP_TYPE: u64 = 4;
+const NODE_ATTRIBUTE: u64 = 5;
+const ATTR_NAME: u64 = 1;
+const ATTR_I: u64 = 3;
+const ATTR_T: u64 = 5;
+const TENSOR_DIMS: u64 = 1;
+const TENSOR_DATA_TYPE: u64 = 2;
+const TENSOR_INT32_DATA: u64 = 5;
+const TENSOR_INT64_DATA: u64 = 7;
+const TENSOR_NAME: u64 = 8;
+const TENSOR_RAW_DATA: u64 = 9;
+const TENSOR_UINT64_DATA: u64 = 11;
+const VALUE_INFO_NAME: u64 = 1;
+
+// TensorProto.DataType values.
+const UINT8: u64 = 2;
+const INT8: u64 = 3;
+const UINT16: u64 = 4;
+const INT16: u64 = 5;
+const INT32: u64 = 6;
+const INT64: u64 = 7;
+const UINT32: u64 = 12;
+const UINT64: u64 = 13;
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct Fields<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Fields { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, OnnxError> {
+        let mut result = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .buf
+                .get(self.pos)
+                .ok_or_else(|| OnnxError::new("truncated varint"))?;
+            self.pos += 1;
+            result |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        Err(OnnxError::new("varint too long"))
+    }
+
+    fn skip(&mut self, n: usize) -> Result<&'a [u8], OnnxError> {
+        let end = self.pos.checked_add(n).filter(|end| *end <= self.buf.len());
+        let end = end.ok_or_else(|| OnnxError::new("truncated field"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn next_field(&mut self) -> Result<Option<(u64, Value<'a>)>, OnnxError> {
+        if self.pos >= self.buf.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.skip(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.skip(len)?)
+            }
+            5 => {
+                self.skip(4)?;
+                Value::Fixed
+            }
+            other => return Err(OnnxError::new(format!("unsupported wire type {}", other))),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+fn string(bytes: &[u8]) -> Result<String, OnnxError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| OnnxError::new("invalid UTF-8 string"))
+}
+
+/// Reads a repeated integer field that may be packed or unpacked.
+fn push_ints(value: Value<'_>, out: &mut Vec<u64>) -> Result<(), OnnxError> {
+    match value {
+        Value::Varint(v) => out.push(v),
+        Value::Bytes(bytes) => {
+            let mut packed = Fields::new(bytes);
+            while packed.pos < bytes.len() {
+                out.push(packed.varint()?);
+            }
+        }
+        Value::Fixed => return Err(OnnxError::new("unexpected fixed-width integer")),
+    }
+    Ok(())
+}
+
+struct Tensor {
+    name: String,
+    value: u32,
+}
+
+fn parse_tensor(bytes: &[u8]) -> Result<Tensor, OnnxError> {
+    let mut name = String::new();
+    let mut dims = Vec::new();
+    let mut data_type = 0;
+    let mut ints = Vec::new();
+    let mut raw: Option<&[u8]> = None;
+    let mut fields = Fields::new(bytes);
+    while let Some((field, value)) = fields.next_field()? {
+        match (field, value) {
+            (TENSOR_NAME, Value::Bytes(b)) => name = string(b)?,
+            (TENSOR_DIMS, v) => push_ints(v, &mut dims)?,
+            (TENSOR_DATA_TYPE, Value::Varint(v)) => data_type = v,
+            (TENSOR_INT32_DATA | TENSOR_INT64_DATA | TENSOR_UINT64_DATA, v) => {
+                push_ints(v, &mut ints)?
+            }
+            (TENSOR_RAW_DATA, Value::Bytes(b)) => raw = Some(b),
+            _ => {}
+        }
+    }
+    let described = if name.is_empty() {
+        "tensor".to_string()
+    } else {
+        format!("tensor `{}`", name)
+    };
+    if dims.iter().product::<u64>() != 1 {
+        return Err(OnnxError::new(format!(
+            "{} has shape {:?}; only single-element tensors are supported",
+            described, dims
+        )));
+    }
+    let (width, signed) = match data_type {
+        UINT8 => (1, false),
+        INT8 => (1, true),
+        UINT16 => (2, false),
+        INT16 => (2, true),
+        INT32 => (4, true),
+        UINT32 => (4, false),
+        INT64 => (8, true),
+        UINT64 => (8, false),
+        other => {
+            return Err(OnnxError::new(format!(
+                "{} has unsupported data type {}",
+                described, other
+            )))
+        }
+    };
+    let raw_value: i128 = match raw {
+        Some(bytes) => {
+            if bytes.len() != width {
+                return Err(OnnxError::new(format!(
+                    "{} has malformed raw_data",
+                    described
+                )));
+            }
+            let mut buf = [0u8; 8];
+            buf[..width].copy_from_slice(bytes);
+            let unsigned = u64::from_le_bytes(buf);
+            let bits = width * 8;
+            if signed && bits < 64 && unsigned >> (bits - 1) & 1 == 1 {
+                unsigned as i128 - (1i128 << bits)
+            } else if signed {
+                unsigned as i64 as i128
+            } else {
+                unsigned as i128
+            }
+        }
+        None => match ints.as_slice() {
+            [v] if signed => match data_type {
+                INT64 => *v as i64 as i128,
+                _ => *v as i64 as i32 as i128,
+            },
+            [v] => *v as i128,
+            _ => {
+                return Err(OnnxError::new(format!(
+                    "{} does not hold exactly one value",
+                    described
+                )))
+            }
+        },
+    };
+    let value = u32::try_from(raw_value).map_err(|_| {
+        OnnxError::new(format!(
+            "{} holds {}, which does not fit in u32",
+            described, raw_value
+        ))
+    })?;
+    Ok(Tensor { name, value })
+}
+
+fn value_info_name(bytes: &[u8]) -> Result<String, OnnxError> {
+    let mut fields = Fields::new(bytes);
+    while let Some((field, value)) = fields.next_field()? {
+        if let (VALUE_INFO_NAME, Value::Bytes(b)) = (field, value) {
+            return string(b);
+        }
+    }
+    Err(OnnxError::new("graph input or output without a name"))
+}
+
+struct OnnxNode {
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    /// Value of the `value` / `value_int` attribute, used by `Constant`.
+    constant: Option<u32>,
+}
+
+fn parse_node(bytes: &[u8]) -> Result<OnnxNode, OnnxError> {
+    let mut node = OnnxNode {
+        op_type: String::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        constant: None,
+    };
+    let mut fields = Fields::new(bytes);
+    while let Some((field, value)) = fields.next_field()? {
+        match (field, value) {
+            (NODE_INPUT, Value::Bytes(b)) => node.inputs.push(string(b)?),
+            (NODE_OUTPUT, Value::Bytes(b)) => node.outputs.push(string(b)?),
+            (NODE_OP_TYPE, Value::Bytes(b)) => node.op_type = string(b)?,
+            (NODE_ATTRIBUTE, Value::Bytes(b)) => {
+                let mut attr_name = String::new();
+                let mut attr_value = None;
+                let mut attr = Fields::new(b);
+                while let Some((field, value)) = attr.next_field()? {
+                    match (field, value) {
+                        (ATTR_NAME, Value::Bytes(b)) => attr_name = string(b)?,
+                        (ATTR_T, Value::Bytes(b)) => attr_value = Some(parse_tensor(b)?.value),
+                        (ATTR_I, Value::Varint(v)) => {
+                            let v = v as i64;
+                            attr_value = Some(u32::try_from(v).map_err(|_| {
+                                OnnxError::new(format!("constant {} does not fit in u32", v))
+                            })?);
+                        }
+                        _ => {}
+                    }
+                }
+                if attr_name == "value" || attr_name == "value_int" {
+                    node.constant = attr_value;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(node)
+}
+
+impl Builder {
+    /// Imports an ONNX model (the serialized `ModelProto` bytes).
+    ///
+    /// Graph inputs become input nodes, initializers and `Constant` nodes become
+    /// constants, and `Add`/`Mul` map onto the corresponding ops. Returns the
+    /// builder and a map from every ONNX value name to its node.
+    pub fn from_onnx(bytes: &[u8]) -> Result<(Builder, HashMap<String, NodeId>), OnnxError> {
+        Self::from_onnx_with_limits(bytes, Limits::default())
+    }
+
+    /// Like `from_onnx`, refusing models whose graph exceeds `limits`.
+    pub fn from_onnx_with_limits(
+        bytes: &[u8],
+        limits: Limits,
+    ) -> Result<(Builder, HashMap<String, NodeId>), OnnxError> {
+        let mut graph = None;
+        let mut model = Fields::new(bytes);
+        while let Some((field, value)) = model.next_field()? {
+            if let (MODEL_GRAPH, Value::Bytes(b)) = (field, value) {
+                graph = Some(b);
+            }
+        }
+        let graph = graph.ok_or_else(|| OnnxError::new("model has no graph"))?;
+
+        let mut initializers = Vec::new();
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut nodes = Vec::new();
+        let mut fields = Fields::new(graph);
+        while let Some((field, value)) = fields.next_field()? {
+            match (field, value) {
+                (GRAPH_NODE, Value::Bytes(b)) => nodes.push(parse_node(b)?),
+                (GRAPH_INITIALIZER, Value::Bytes(b)) => initializers.push(parse_tensor(b)?),
+                (GRAPH_INPUT, Value::Bytes(b)) => inputs.push(value_info_name(b)?),
+                (GRAPH_OUTPUT, Value::Bytes(b)) => outputs.push(value_info_name(b)?),
+                _ => {}
+            }
+        }
+
+        let mut builder = Builder::with_limits(limits);
+        let mut names: HashMap<String, Node> = HashMap::new();
+        for tensor in initializers {
+            let node = builder.try_constant(tensor.value)?;
+            names.insert(tensor.name, node);
+        }
+        // Older exporters also list initializers as graph inputs; those stay constants.
+        for input in inputs {
+            if let Entry::Vacant(entry) = names.entry(input) {
+                entry.insert(builder.try_init()?);
+            }
+        }
+        for node in nodes {
+            let lookup = |name: &String| {
+                names
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| OnnxError::new(format!("unknown value `{}`", name)))
+            };
+            let created = match (node.op_type.as_str(), node.inputs.as_slice()) {
+                ("Add", [a, b]) => {
+                    let (a, b) = (lookup(a)?, lookup(b)?);
+                    builder.try_add(&a, &b)?
+                }
+                ("Mul", [a, b]) => {
+                    let (a, b) = (lookup(a)?, lookup(b)?);
+                    builder.try_mul(&a, &b)?
+                }
+                ("Constant", []) => {
+                    let value = node.constant.ok_or_else(|| {
+                        OnnxError::new("Constant node without an integer `value` attribute")
+                    })?;
+                    builder.try_constant(value)?
+                }
+                ("Add" | "Mul" | "Constant", args) => {
+                    return Err(OnnxError::new(format!(
+                        "{} node with {} inputs",
+                        node.op_type,
+                        args.len()
+                    )))
+                }
+                (other, _) => {
+                    return Err(OnnxError::new(format!("unsupported operator `{}`", other)))
+                }
+            };
+            let output = match node.outputs.as_slice() {
+                [output] => output.clone(),
+                _ => {
+                    return Err(OnnxError::new(format!(
+                        "{} node must have exactly one output",
+                        node.op_type
+                    )))
+                }
+            };
+            names.insert(output, created);
+        }
+        for output in &outputs {
+            if !names.contains_key(output) {
+                return Err(OnnxError::new(format!(
+                    "graph output `{}` is never produced",
+                    output
+                )));
+            }
+        }
+        let names = names.into_iter().map(|(k, v)| (k, v.id)).collect();
+        Ok((builder, names))
+    }
+}
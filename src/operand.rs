@@ -0,0 +1,38 @@
+//! Operands of `add` and `mul`: an existing node or a literal, so scalar
+//! arithmetic does not need a separate `constant` call.
+
+use crate::{Builder, Node};
+
+/// A node, or a `u32` promoted to an interned constant node when used.
+/// Nodes convert by reference, like every other builder argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operand {
+    Node(Node),
+    Const(u32),
+}
+
+impl From<&Node> for Operand {
+    fn from(node: &Node) -> Self {
+        Operand::Node(*node)
+    }
+}
+
+impl From<u32> for Operand {
+    fn from(value: u32) -> Self {
+        Operand::Const(value)
+    }
+}
+
+impl Builder {
+    /// The node for `operand`, creating (or reusing) its constant.
+    #[track_caller]
+    pub(crate) fn operand(&mut self, operand: impl Into<Operand>) -> Node {
+        match operand.into() {
+            Operand::Node(node) => {
+                self.check_owner(&node);
+                node
+            }
+            Operand::Const(value) => self.constant(value),
+        }
+    }
+}
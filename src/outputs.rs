@@ -0,0 +1,118 @@
+//! Declared outputs. Once a graph names the nodes that matter, pruning,
+//! demand-driven evaluation, analyses and exports work from those instead of
+//! guessing from the graph's sinks.
+
+use crate::collections::{HashMap, HashSet};
+
+use crate::prelude::*;
+use crate::{Builder, Node, NodeData, NodeId};
+
+impl Builder {
+    /// Declares `node` as an output named `name`. Outputs keep their
+    /// declaration order.
+    ///
+    /// # Panics
+    /// If an output with the same name already exists.
+    pub fn mark_output(&mut self, node: &Node, name: &str) {
+        assert!(
+            self.output_id(name).is_none(),
+            "output `{}` is already declared",
+            name
+        );
+        self.outputs.push((name.to_string(), node.id));
+    }
+
+    pub fn output_id(&self, name: &str) -> Option<NodeId> {
+        self.outputs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, id)| *id)
+    }
+
+    /// Declared outputs as `(name, node)` pairs, in declaration order.
+    pub fn outputs(&self) -> impl Iterator<Item = (&str, &NodeData)> + '_ {
+        self.outputs
+            .iter()
+            .filter_map(|(name, id)| Some((name.as_str(), self.nodes.get(id)?)))
+    }
+
+    pub fn is_output(&self, node: &Node) -> bool {
+        self.is_output_id(node.id)
+    }
+
+    pub(crate) fn is_output_id(&self, node: NodeId) -> bool {
+        self.outputs.iter().any(|(_, id)| *id == node)
+    }
+
+    /// The nodes a graph computes: its declared outputs, or every sink when
+    /// none are declared.
+    pub(crate) fn output_ids(&self) -> Vec<NodeId> {
+        if self.outputs.is_empty() {
+            self.sink_ids()
+        } else {
+            self.outputs.iter().map(|(_, id)| *id).collect()
+        }
+    }
+
+    /// Nodes needed to compute the outputs and check every constraint.
+    fn demanded(&self) -> HashSet<NodeId> {
+        let roots = self
+            .output_ids()
+            .into_iter()
+            .chain(self.constraints.iter().flat_map(|(a, b)| [*a, *b]));
+        roots.flat_map(|id| self.cone_of(id)).collect()
+    }
+
+    /// Removes every node that neither an output nor a constraint depends on.
+    /// Inputs are always kept so existing input maps stay valid. Returns the
+    /// number of nodes removed.
+    pub fn prune(&mut self) -> usize {
+        let keep = self.demanded();
+        let dead: Vec<NodeId> = self
+            .nodes
+            .values()
+            .filter(|n| n.op.is_some() && !keep.contains(&n.id))
+            .map(|n| n.id)
+            .collect();
+        for id in &dead {
+            self.forget_node(*id);
+        }
+        dead.len()
+    }
+
+    /// Evaluates only the nodes the declared outputs depend on and returns the
+    /// output values in declaration order. Other nodes keep their old values.
+    pub fn evaluate_outputs(&mut self, inputs: HashMap<NodeId, u32>) -> Vec<(String, Option<u32>)> {
+        let mut needed: Vec<NodeId> = self
+            .outputs
+            .iter()
+            .flat_map(|(_, id)| self.cone_of(*id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        self.sort_topologically(&mut needed);
+        for id in needed {
+            let value = match &self.nodes[&id].op {
+                None => inputs.get(&id).copied(),
+                Some(op) => op.apply(self.arithmetic, |p| {
+                    self.nodes.get(&p).and_then(|n| n.value)
+                }),
+            };
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.value = value;
+            }
+            if let Some(value) = value.filter(|_| !inputs.contains_key(&id)) {
+                self.notify(id, value);
+            }
+        }
+        self.output_values()
+    }
+
+    /// Current values of the declared outputs, in declaration order.
+    pub fn output_values(&self) -> Vec<(String, Option<u32>)> {
+        self.outputs
+            .iter()
+            .map(|(name, id)| (name.clone(), self.value(*id)))
+            .collect()
+    }
+}
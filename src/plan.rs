@@ -0,0 +1,368 @@
+//! Precompiled evaluation plans, for evaluating the same graph on many inputs
+//! without re-deriving its order on every call.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Arithmetic, Builder, EvalSummary, NodeId, Op};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlanError {
+    /// The plan was compiled for a graph with different nodes.
+    Mismatch { plan: usize, graph: usize },
+    /// The plan was compiled for a graph with a different structure.
+    Fingerprint { plan: u64, graph: u64 },
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanError::Mismatch { plan, graph } => write!(
+                f,
+                "plan covers {} nodes but the graph has {}; it was compiled for another graph",
+                plan, graph
+            ),
+            PlanError::Fingerprint { plan, graph } => write!(
+                f,
+                "plan was compiled for graph {:016x} but this graph is {:016x}",
+                plan, graph
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+/// The function behind a hint node.
+type Hint = fn(&[u32]) -> u32;
+
+/// What a plan step computes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tag {
+    Input,
+    Const,
+    Add,
+    Mul,
+    Hint,
+}
+
+/// One node of a plan, with operands as positions in the plan's order.
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    tag: Tag,
+    /// Operand positions of `Add`/`Mul`, the value of `Const`, and for `Hint`
+    /// the range of its operand positions in `hint_args`.
+    args: [u32; 2],
+    mode: Arithmetic,
+}
+
+/// A graph's topological order, a dense index for each node and the nodes
+/// that read each one. Depends only on the graph's structure, so one plan
+/// serves every graph with the same fingerprint.
+///
+/// Steps are small structs in one vector indexed by position in the order,
+/// so evaluation walks dense memory instead of the graph's hash map.
+#[derive(Clone, Debug)]
+pub struct EvalPlan {
+    fingerprint: u64,
+    order: Vec<NodeId>,
+    index: HashMap<NodeId, usize>,
+    steps: Vec<Step>,
+    hint_args: Vec<u32>,
+    dependents: Vec<Vec<usize>>,
+}
+
+impl EvalPlan {
+    /// Fingerprint of the graph the plan was compiled from.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Number of nodes covered.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Node ids in evaluation order.
+    pub fn order(&self) -> &[NodeId] {
+        &self.order
+    }
+
+    /// Position of `id` in `order()`.
+    pub fn index_of(&self, id: NodeId) -> Option<usize> {
+        self.index.get(&id).copied()
+    }
+
+    /// Nodes that read `id` directly, in evaluation order.
+    pub fn dependents(&self, id: NodeId) -> Vec<NodeId> {
+        self.index_of(id)
+            .map(|i| self.dependents[i].iter().map(|&d| self.order[d]).collect())
+            .unwrap_or_default()
+    }
+
+    /// Computes every step into `values` (one slot per step, in order).
+    /// Values already in `values` are kept; `hints` holds the hint function
+    /// of each `Hint` step, in order.
+    fn run(
+        &self,
+        policy: Arithmetic,
+        hints: &[Hint],
+        values: &mut [Option<u32>],
+        summary: &mut EvalSummary,
+    ) {
+        let mut hints = hints.iter();
+        let mut operands = Vec::new();
+        for (i, step) in self.steps.iter().enumerate() {
+            let Step { tag, args, mode } = *step;
+            let hint = if tag == Tag::Hint { hints.next() } else { None };
+            if values[i].is_some() {
+                continue;
+            }
+            let [a, b] = args;
+            let (a, b) = (a as usize, b as usize);
+            values[i] = match tag {
+                Tag::Input => continue,
+                Tag::Const => Some(a as u32),
+                Tag::Add => values[a]
+                    .zip(values[b])
+                    .and_then(|(x, y)| mode.or(policy).add(x, y)),
+                Tag::Mul => values[a]
+                    .zip(values[b])
+                    .and_then(|(x, y)| mode.or(policy).mul(x, y)),
+                Tag::Hint => {
+                    operands.clear();
+                    let ready = self.hint_args[a..b]
+                        .iter()
+                        .all(|&p| match values[p as usize] {
+                            Some(v) => {
+                                operands.push(v);
+                                true
+                            }
+                            None => false,
+                        });
+                    match hint {
+                        Some(f) if ready => {
+                            summary.hint_calls += 1;
+                            Some(f(&operands))
+                        }
+                        _ => None,
+                    }
+                }
+            };
+            if values[i].is_some() {
+                summary.evaluated += 1;
+            }
+        }
+    }
+}
+
+impl Builder {
+    /// Compiles the graph's evaluation order for `fill_with_plan`.
+    pub fn eval_plan(&self) -> EvalPlan {
+        let mut order: Vec<NodeId> = self.nodes.keys().copied().collect();
+        self.sort_topologically(&mut order);
+        let index: HashMap<NodeId, usize> =
+            order.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let position = |id: &NodeId| index[id] as u32;
+        let mut plan = EvalPlan {
+            fingerprint: self.fingerprint(),
+            steps: Vec::with_capacity(order.len()),
+            hint_args: Vec::new(),
+            dependents: vec![Vec::new(); order.len()],
+            order: Vec::new(),
+            index: HashMap::new(),
+        };
+        for (i, id) in order.iter().enumerate() {
+            let op = self.nodes[id].op.as_ref();
+            for operand in op.map(Op::operands).unwrap_or_default() {
+                plan.dependents[index[&operand]].push(i);
+            }
+            let (tag, args, mode) = match op {
+                None => (Tag::Input, [0, 0], Arithmetic::Inherit),
+                Some(Op::Const(v)) => (Tag::Const, [*v, 0], Arithmetic::Inherit),
+                Some(Op::Add(a, b, mode)) => (Tag::Add, [position(a), position(b)], *mode),
+                Some(Op::Mul(a, b, mode)) => (Tag::Mul, [position(a), position(b)], *mode),
+                Some(Op::Hint(parents, _)) => {
+                    let start = plan.hint_args.len() as u32;
+                    plan.hint_args.extend(parents.iter().map(position));
+                    (
+                        Tag::Hint,
+                        [start, plan.hint_args.len() as u32],
+                        Arithmetic::Inherit,
+                    )
+                }
+            };
+            plan.steps.push(Step { tag, args, mode });
+        }
+        plan.order = order;
+        plan.index = index;
+        plan
+    }
+
+    /// The hint functions of `plan`'s hint steps, in order, taken from this
+    /// graph since fingerprints do not cover hint bodies.
+    fn plan_hints(&self, plan: &EvalPlan) -> Result<Vec<Hint>, PlanError> {
+        let mismatch = || PlanError::Mismatch {
+            plan: plan.len(),
+            graph: self.nodes.len(),
+        };
+        if plan.len() != self.nodes.len() {
+            return Err(mismatch());
+        }
+        let fingerprint = self.fingerprint();
+        if plan.fingerprint != fingerprint {
+            return Err(PlanError::Fingerprint {
+                plan: plan.fingerprint,
+                graph: fingerprint,
+            });
+        }
+        plan.order
+            .iter()
+            .zip(&plan.steps)
+            .filter(|(_, step)| step.tag == Tag::Hint)
+            .map(
+                |(id, _)| match self.nodes.get(id).and_then(|n| n.op.as_ref()) {
+                    Some(Op::Hint(_, f)) => Ok(f.func),
+                    _ => Err(mismatch()),
+                },
+            )
+            .collect()
+    }
+
+    /// Evaluates the graph from scratch along `plan`: every value is
+    /// recomputed from `inputs`, as `fill_nodes` would on a fresh graph.
+    /// The plan must come from this graph or one with the same fingerprint.
+    pub fn fill_with_plan(
+        &mut self,
+        plan: &EvalPlan,
+        inputs: &HashMap<NodeId, u32>,
+    ) -> Result<EvalSummary, PlanError> {
+        let hints = self.plan_hints(plan)?;
+        let mut values: Vec<Option<u32>> = plan
+            .order
+            .iter()
+            .map(|id| inputs.get(id).copied())
+            .collect();
+        let mut summary = EvalSummary::default();
+        plan.run(self.arithmetic, &hints, &mut values, &mut summary);
+        for (id, value) in plan.order.iter().zip(values) {
+            if let Some(node) = self.nodes.get_mut(id) {
+                node.value = value;
+            }
+            match value {
+                Some(value) if !inputs.contains_key(id) => self.notify(*id, value),
+                Some(_) => {}
+                None => summary.unresolved.push(*id),
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Evaluates `plan` once per input set without touching the graph's own
+    /// values. Each result holds one value per node in `plan.order()`.
+    pub fn evaluate_batch(
+        &self,
+        plan: &EvalPlan,
+        batch: &[HashMap<NodeId, u32>],
+    ) -> Result<Vec<Vec<Option<u32>>>, PlanError> {
+        let hints = self.plan_hints(plan)?;
+        let mut summary = EvalSummary::default();
+        Ok(batch
+            .iter()
+            .map(|inputs| {
+                let mut values: Vec<Option<u32>> = vec![None; plan.len()];
+                for (id, value) in inputs {
+                    if let Some(i) = plan.index_of(*id) {
+                        values[i] = Some(*value);
+                    }
+                }
+                plan.run(self.arithmetic, &hints, &mut values, &mut summary);
+                values
+            })
+            .collect())
+    }
+}
+
+/// Plans for recently evaluated graphs, keyed by fingerprint, evicting the
+/// least recently used once `capacity` plans are held.
+#[derive(Clone, Debug)]
+pub struct PlanCache {
+    capacity: usize,
+    /// Most recently used last.
+    plans: VecDeque<Arc<EvalPlan>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl PlanCache {
+    /// # Panics
+    /// If `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "a plan cache needs room for at least one plan"
+        );
+        PlanCache {
+            capacity,
+            plans: VecDeque::with_capacity(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The plan for `graph`, compiling and caching it on a miss. The plan is
+    /// shared, so it stays usable after the cache lock is released or the
+    /// plan is evicted.
+    pub fn plan_for(&mut self, graph: &Builder) -> Arc<EvalPlan> {
+        let fingerprint = graph.fingerprint();
+        match self.plans.iter().position(|p| p.fingerprint == fingerprint) {
+            Some(i) => {
+                self.hits += 1;
+                let plan = self.plans.remove(i).expect("position is in bounds");
+                self.plans.push_back(plan);
+            }
+            None => {
+                self.misses += 1;
+                if self.plans.len() == self.capacity {
+                    self.plans.pop_front();
+                }
+                self.plans.push_back(Arc::new(graph.eval_plan()));
+            }
+        }
+        Arc::clone(self.plans.back().expect("a plan was just inserted"))
+    }
+
+    /// Whether a plan for `fingerprint` is cached. Does not count as a use.
+    pub fn contains(&self, fingerprint: u64) -> bool {
+        self.plans.iter().any(|p| p.fingerprint == fingerprint)
+    }
+
+    pub fn len(&self) -> usize {
+        self.plans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plans.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn clear(&mut self) {
+        self.plans.clear();
+    }
+}
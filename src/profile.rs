@@ -0,0 +1,153 @@
+//! Evaluation profiling: time spent per node and per op kind, with a
+//! collapsed-stack export for flamegraph tools.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::{Builder, EvalSummary, NodeId, OpKind};
+
+/// Time spent computing one node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeTiming {
+    pub node: NodeId,
+    pub kind: OpKind,
+    /// Scope path and label (or `n<id>`), as in `path_of`.
+    pub path: String,
+    pub time: Duration,
+}
+
+/// Timings recorded by `fill_nodes_profiled`.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    /// In evaluation order.
+    timings: Vec<NodeTiming>,
+}
+
+impl Profile {
+    /// Every computed node, in evaluation order.
+    pub fn timings(&self) -> &[NodeTiming] {
+        &self.timings
+    }
+
+    pub fn total(&self) -> Duration {
+        self.timings.iter().map(|t| t.time).sum()
+    }
+
+    /// Node count and total time per op kind, slowest kind first.
+    pub fn by_kind(&self) -> Vec<(OpKind, usize, Duration)> {
+        let mut kinds: HashMap<OpKind, (usize, Duration)> = HashMap::new();
+        for timing in &self.timings {
+            let entry = kinds.entry(timing.kind).or_default();
+            entry.0 += 1;
+            entry.1 += timing.time;
+        }
+        let mut kinds: Vec<_> = kinds.into_iter().map(|(k, (n, t))| (k, n, t)).collect();
+        kinds.sort_by(|a, b| b.2.cmp(&a.2).then(a.1.cmp(&b.1)));
+        kinds
+    }
+
+    /// The `limit` slowest nodes, slowest first (ties by id).
+    pub fn slowest(&self, limit: usize) -> Vec<&NodeTiming> {
+        let mut timings: Vec<&NodeTiming> = self.timings.iter().collect();
+        timings.sort_by(|a, b| b.time.cmp(&a.time).then(a.node.cmp(&b.node)));
+        timings.truncate(limit);
+        timings
+    }
+
+    /// A plain-text table of time per op kind followed by the 20 slowest nodes.
+    pub fn report(&self) -> String {
+        let mut out = format!("{:<8} {:>8} {:>12}\n", "kind", "nodes", "time");
+        for (kind, count, time) in self.by_kind() {
+            out.push_str(&format!("{:<8} {:>8} {:>12?}\n", kind, count, time));
+        }
+        out.push_str(&format!(
+            "{:<8} {:>8} {:>12?}\n\n",
+            "total",
+            self.timings.len(),
+            self.total()
+        ));
+        out.push_str(&format!(
+            "{:<8} {:<6} {:>12}  path\n",
+            "node", "kind", "time"
+        ));
+        for timing in self.slowest(20) {
+            out.push_str(&format!(
+                "{:<8} {:<6} {:>12?}  {}\n",
+                timing.node.0, timing.kind, timing.time, timing.path
+            ));
+        }
+        out
+    }
+
+    /// One `frame;frame;... nanoseconds` line per node, with scopes as
+    /// frames, in the collapsed-stack format read by `flamegraph.pl` and
+    /// `inferno`.
+    pub fn collapsed_stacks(&self) -> String {
+        let mut out = String::new();
+        for timing in &self.timings {
+            let frames = timing.path.replace(';', ":").replace('/', ";");
+            out.push_str(&format!("{} {}\n", frames, timing.time.as_nanos()));
+        }
+        out
+    }
+
+    /// Writes `collapsed_stacks` to `path`.
+    pub fn write_collapsed(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.collapsed_stacks())
+    }
+}
+
+impl Builder {
+    /// Evaluates the graph from scratch in topological order, timing every
+    /// op node. Computed values from earlier calls are discarded so each node
+    /// is measured; otherwise this behaves like `fill_nodes`. Timing adds
+    /// overhead, so use it for diagnosis rather than production runs.
+    pub fn fill_nodes_profiled(&mut self, inputs: HashMap<NodeId, u32>) -> (EvalSummary, Profile) {
+        let mut summary = EvalSummary::default();
+        for node in self.nodes.values_mut() {
+            node.value = None;
+        }
+        for (id, value) in inputs {
+            if let Some(node) = self.nodes.get_mut(&id) {
+                node.value = Some(value);
+            }
+        }
+        let mut ids: Vec<NodeId> = self.nodes.keys().copied().collect();
+        self.sort_topologically(&mut ids);
+        let mut profile = Profile::default();
+        for id in ids {
+            let node = &self.nodes[&id];
+            let Some(op) = node.op.as_ref().filter(|_| node.value.is_none()) else {
+                continue;
+            };
+            let start = Instant::now();
+            let value = op.apply(self.arithmetic, |pid| {
+                self.nodes.get(&pid).and_then(|n| n.value)
+            });
+            let time = start.elapsed();
+            if let Some(value) = value {
+                summary.record(op);
+                profile.timings.push(NodeTiming {
+                    node: id,
+                    kind: node.kind(),
+                    path: self.path_of_id(id),
+                    time,
+                });
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    node.value = Some(value);
+                }
+                self.notify(id, value);
+            }
+        }
+        summary.unresolved = self
+            .sorted_nodes()
+            .into_iter()
+            .filter(|n| n.value.is_none())
+            .map(|n| n.id)
+            .collect();
+        (summary, profile)
+    }
+}
@@ -0,0 +1,170 @@
+//! Protocol Buffers encoding of graphs, following the `Graph` message of
+//! `proto/graph.proto`, so services in other languages can exchange graphs
+//! through code generated from that schema. The crate's own message types are
+//! generated from the same file by build.rs with prost (feature `protobuf`).
+
+use prost::Message;
+
+use crate::serialize::{DecodeError, RawGraph, RawOp, GRAPH_FORMAT_VERSION};
+use crate::{Arithmetic, Builder, HintRegistry, Limits, NodeId, Op};
+
+/// Messages generated from `proto/graph.proto`.
+pub(crate) mod proto {
+    include!(concat!(env!("OUT_DIR"), "/graphengine.graph.rs"));
+}
+
+use proto::node::Op as NodeOp;
+
+impl From<prost::DecodeError> for DecodeError {
+    fn from(e: prost::DecodeError) -> Self {
+        DecodeError::new(e.to_string())
+    }
+}
+
+fn id(id: NodeId) -> u64 {
+    id.0 as u64
+}
+
+pub(crate) fn node_id(id: u64) -> Result<NodeId, DecodeError> {
+    usize::try_from(id)
+        .map(NodeId)
+        .map_err(|_| DecodeError::new("node id does not fit in usize"))
+}
+
+fn arithmetic(name: &str) -> Result<Arithmetic, DecodeError> {
+    // The proto3 default for an unset string field.
+    if name.is_empty() {
+        return Ok(Arithmetic::Inherit);
+    }
+    Arithmetic::from_name(name)
+        .ok_or_else(|| DecodeError::new(format!("unknown arithmetic mode `{}`", name)))
+}
+
+fn binary(a: NodeId, b: NodeId) -> proto::Binary {
+    proto::Binary { a: id(a), b: id(b) }
+}
+
+fn decode_node(node: proto::Node) -> Result<(NodeId, RawOp), DecodeError> {
+    let id = node_id(node.id)?;
+    let op = match node.op {
+        None => return Err(DecodeError::new(format!("node {} has no op", id.0))),
+        Some(NodeOp::Input(_)) => RawOp::Input,
+        Some(NodeOp::Constant(v)) => RawOp::Const(v),
+        Some(NodeOp::Add(pair)) => RawOp::Add(node_id(pair.a)?, node_id(pair.b)?),
+        Some(NodeOp::Mul(pair)) => RawOp::Mul(node_id(pair.a)?, node_id(pair.b)?),
+        Some(NodeOp::Hint(hint)) => RawOp::Hint(
+            hint.name,
+            hint.parents
+                .into_iter()
+                .map(node_id)
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+    Ok((id, op))
+}
+
+impl Builder {
+    /// Serializes the graph structure (not its values) as a `Graph` message
+    /// of `proto/graph.proto`. Like `to_bytes`, hints are stored by name and
+    /// anonymous ones cannot be decoded again.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        let nodes = self
+            .sorted_nodes()
+            .into_iter()
+            .map(|node| proto::Node {
+                id: id(node.id),
+                op: Some(match &node.op {
+                    None => NodeOp::Input(proto::Input {}),
+                    Some(Op::Const(v)) => NodeOp::Constant(*v),
+                    Some(Op::Add(a, b, _)) => NodeOp::Add(binary(*a, *b)),
+                    Some(Op::Mul(a, b, _)) => NodeOp::Mul(binary(*a, *b)),
+                    Some(Op::Hint(parents, f)) => NodeOp::Hint(proto::Hint {
+                        name: f.name().unwrap_or("").to_string(),
+                        parents: parents.iter().map(|p| id(*p)).collect(),
+                    }),
+                }),
+            })
+            .collect();
+        let mut scopes: Vec<(&NodeId, &String)> = self.node_scopes.iter().collect();
+        scopes.sort_by_key(|(id, _)| id.0);
+        let modes = self
+            .sorted_nodes()
+            .into_iter()
+            .filter_map(|node| {
+                let mode = self.arithmetic_of(&self.handle_of(node.id))?;
+                Some(proto::Mode {
+                    node: id(node.id),
+                    arithmetic: mode.to_string(),
+                })
+            })
+            .collect();
+        proto::Graph {
+            version: u32::from(GRAPH_FORMAT_VERSION),
+            nodes,
+            constraints: self
+                .constraints
+                .iter()
+                .map(|(a, b)| proto::Constraint {
+                    a: id(*a),
+                    b: id(*b),
+                })
+                .collect(),
+            scopes: scopes
+                .into_iter()
+                .map(|(node, path)| proto::Scope {
+                    node: id(*node),
+                    path: path.clone(),
+                })
+                .collect(),
+            arithmetic: self.arithmetic.to_string(),
+            modes,
+        }
+        .encode_to_vec()
+    }
+
+    /// Decodes a `Graph` message written by `to_protobuf` or by any
+    /// implementation of `proto/graph.proto`, resolving hint names through
+    /// `hints`.
+    pub fn from_protobuf(bytes: &[u8], hints: &HintRegistry) -> Result<Builder, DecodeError> {
+        Self::from_protobuf_with_limits(bytes, hints, Limits::default())
+    }
+
+    /// Like `from_protobuf`, refusing graphs that exceed `limits`.
+    pub fn from_protobuf_with_limits(
+        bytes: &[u8],
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<Builder, DecodeError> {
+        let graph = proto::Graph::decode(bytes)?;
+        if graph.version != u32::from(GRAPH_FORMAT_VERSION) {
+            return Err(DecodeError::new(format!(
+                "protobuf graphs have format version {}, found {}",
+                GRAPH_FORMAT_VERSION, graph.version
+            )));
+        }
+        let raw = RawGraph {
+            nodes: graph
+                .nodes
+                .into_iter()
+                .map(decode_node)
+                .collect::<Result<_, _>>()?,
+            constraints: graph
+                .constraints
+                .into_iter()
+                .map(|pair| Ok((node_id(pair.a)?, node_id(pair.b)?)))
+                .collect::<Result<_, DecodeError>>()?,
+            scopes: graph
+                .scopes
+                .into_iter()
+                .map(|scope| Ok((node_id(scope.node)?, scope.path)))
+                .collect::<Result<_, DecodeError>>()?,
+            arithmetic: arithmetic(&graph.arithmetic)?,
+            modes: graph
+                .modes
+                .into_iter()
+                .map(|mode| Ok((node_id(mode.node)?, arithmetic(&mode.arithmetic)?)))
+                .collect::<Result<_, DecodeError>>()?,
+        };
+        raw.into_builder(hints, limits)
+    }
+}
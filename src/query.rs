@@ -0,0 +1,190 @@
+//! Read-only iteration over a graph, so analyses and tooling can be written
+//! outside the crate.
+
+use core::fmt;
+
+use crate::prelude::*;
+use crate::{Builder, Node, NodeData, NodeId, Op};
+
+/// A set of node ids as one bit per id, cheaper than a hash set for the
+/// dense ids builders hand out.
+#[derive(Default)]
+struct Marks(Vec<u64>);
+
+impl Marks {
+    /// Marks `id`, returning whether it was unmarked.
+    fn insert(&mut self, id: NodeId) -> bool {
+        let (word, bit) = (id.0 / 64, 1u64 << (id.0 % 64));
+        if word >= self.0.len() {
+            self.0.resize(word + 1, 0);
+        }
+        let fresh = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        fresh
+    }
+}
+
+/// The kind of operation a node performs, without its operands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpKind {
+    Input,
+    Const,
+    Add,
+    Mul,
+    Hint,
+}
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OpKind::Input => "input",
+            OpKind::Const => "const",
+            OpKind::Add => "add",
+            OpKind::Mul => "mul",
+            OpKind::Hint => "hint",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl NodeData {
+    pub fn kind(&self) -> OpKind {
+        match &self.op {
+            None => OpKind::Input,
+            Some(Op::Const(_)) => OpKind::Const,
+            Some(Op::Add(..)) => OpKind::Add,
+            Some(Op::Mul(..)) => OpKind::Mul,
+            Some(Op::Hint(..)) => OpKind::Hint,
+        }
+    }
+
+    /// Nodes this node reads from, in operand order.
+    pub fn operands(&self) -> Vec<NodeId> {
+        self.op.as_ref().map(Op::operands).unwrap_or_default()
+    }
+
+    /// The value of a constant node.
+    pub fn constant_value(&self) -> Option<u32> {
+        match &self.op {
+            Some(Op::Const(v)) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// The node's value after evaluation.
+    pub fn value(&self) -> Option<u32> {
+        self.value
+    }
+}
+
+impl Builder {
+    pub fn node(&self, id: NodeId) -> Option<&NodeData> {
+        self.nodes.get(&id)
+    }
+
+    /// A handle to the node `id`, for passing to builder methods.
+    pub fn handle(&self, id: NodeId) -> Option<Node> {
+        self.nodes.contains_key(&id).then(|| self.handle_of(id))
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// All nodes, in ascending id order unless edits reordered the graph; see
+    /// `topo_iter`.
+    pub fn nodes(&self) -> impl Iterator<Item = &NodeData> + '_ {
+        self.topo_iter()
+    }
+
+    /// All nodes in dependency order: every node comes after its operands,
+    /// so evaluators and code generators can process them in one pass. Ties
+    /// go to the smallest id, making the order deterministic; until an edit
+    /// rewires a node onto a newer one it is simply ascending id order.
+    pub fn topo_iter(&self) -> impl Iterator<Item = &NodeData> + '_ {
+        self.sorted_nodes().into_iter()
+    }
+
+    /// Input nodes in ascending id order.
+    pub fn inputs(&self) -> impl Iterator<Item = &NodeData> + '_ {
+        self.nodes_with_op(OpKind::Input)
+    }
+
+    /// Constant nodes in ascending id order.
+    pub fn constants(&self) -> impl Iterator<Item = &NodeData> + '_ {
+        self.nodes_with_op(OpKind::Const)
+    }
+
+    /// Nodes of the given kind in ascending id order.
+    pub fn nodes_with_op(&self, kind: OpKind) -> impl Iterator<Item = &NodeData> + '_ {
+        self.nodes().filter(move |n| n.kind() == kind)
+    }
+
+    /// Equality constraints in the order they were added.
+    pub fn constraints(&self) -> impl Iterator<Item = (NodeId, NodeId)> + '_ {
+        self.constraints.iter().copied()
+    }
+
+    /// Every node `node` depends on, directly or through other nodes, in
+    /// evaluation order. The node itself is not included.
+    pub fn ancestors(&self, node: &Node) -> Vec<NodeId> {
+        let mut marks = Marks::default();
+        marks.insert(node.id);
+        let mut found = Vec::new();
+        let mut stack = self.operand_ids(node.id);
+        while let Some(id) = stack.pop() {
+            if marks.insert(id) && self.nodes.contains_key(&id) {
+                found.push(id);
+                stack.extend(self.operand_ids(id));
+            }
+        }
+        self.sort_topologically(&mut found);
+        found
+    }
+
+    /// Every node that depends on `node`, directly or through other nodes,
+    /// in evaluation order. The node itself is not included.
+    pub fn descendants(&self, node: &Node) -> Vec<NodeId> {
+        let dependents = self.dependents_map();
+        let mut marks = Marks::default();
+        marks.insert(node.id);
+        let mut found = Vec::new();
+        let mut stack = vec![node.id];
+        while let Some(id) = stack.pop() {
+            for &dep in dependents.get(&id).into_iter().flatten() {
+                if marks.insert(dep) {
+                    found.push(dep);
+                    stack.push(dep);
+                }
+            }
+        }
+        self.sort_topologically(&mut found);
+        found
+    }
+
+    /// Whether `a` reads `b`, directly or through other nodes. A node does
+    /// not depend on itself. Unless edits reordered the graph, operands have
+    /// smaller ids than their readers, so the search skips every node older
+    /// than `b`.
+    pub fn depends_on(&self, a: &Node, b: &Node) -> bool {
+        let mut marks = Marks::default();
+        let mut stack = self.operand_ids(a.id);
+        while let Some(id) = stack.pop() {
+            if id == b.id {
+                return true;
+            }
+            if (self.reordered || id > b.id) && marks.insert(id) {
+                stack.extend(self.operand_ids(id));
+            }
+        }
+        false
+    }
+
+    fn operand_ids(&self, id: NodeId) -> Vec<NodeId> {
+        self.nodes
+            .get(&id)
+            .and_then(|n| n.op.as_ref())
+            .map(Op::operands)
+            .unwrap_or_default()
+    }
+}
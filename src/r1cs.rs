@@ -0,0 +1,364 @@
+//! Rank-1 constraint system export, for handing a graph to R1CS-based
+//! proving tools.
+//!
+//! ```text
+//! {
+//!   "modulus": "4294967296",
+//!   "n_wires": 3,
+//!   "wires": [[1, 0], [2, 1]],
+//!   "constraints": [[{"1": 1}, {"1": 1}, {"2": 1}]]
+//! }
+//! ```
+//!
+//! Wire 0 is the constant 1. Inputs, products and hints get a wire each
+//! (`wires` maps them back to node ids); constants and sums are folded into
+//! linear combinations. Every `mul` becomes `A * B = C` and every constraint
+//! `a == b` becomes `(a - b) * 1 = 0`. Coefficients are reduced modulo 2^32,
+//! matching the graph's wrapping arithmetic.
+//!
+//! The binary `.r1cs` files written by circom can be read back as a graph. Every
+//! wire except the constant becomes an input node labelled `w<i>`, each linear
+//! combination becomes a sum of products of coefficient constants and wires, and
+//! each row `A * B = C` becomes a `mul` and an `assert_equal`. Public outputs are
+//! declared as outputs named after their wires.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::json::Json;
+use crate::{Arithmetic, Builder, ImportError, Limits, Modulus, Node, NodeId, Op};
+
+type Combination = BTreeMap<usize, u32>;
+
+fn combine(a: &Combination, b: &Combination, scale: u32) -> Combination {
+    let mut sum = a.clone();
+    for (wire, coeff) in b {
+        let entry = sum.entry(*wire).or_insert(0);
+        *entry = entry.wrapping_add(coeff.wrapping_mul(scale));
+    }
+    sum.retain(|_, coeff| *coeff != 0);
+    sum
+}
+
+fn combination_json(lc: &Combination) -> Json {
+    Json::Object(
+        lc.iter()
+            .map(|(wire, coeff)| (wire.to_string(), Json::Int(i128::from(*coeff))))
+            .collect(),
+    )
+}
+
+impl Builder {
+    /// Encodes the graph as an R1CS in JSON.
+    pub fn to_r1cs(&self) -> String {
+        let one = Combination::from([(0, 1)]);
+        let mut wires: Vec<NodeId> = vec![];
+        let mut lcs: BTreeMap<NodeId, Combination> = BTreeMap::new();
+        let mut constraints = vec![];
+        for node in self.sorted_nodes() {
+            let lc = match &node.op {
+                Some(Op::Const(v)) => combine(&Combination::new(), &one, *v),
+                Some(Op::Add(a, b, _)) => combine(&lcs[a], &lcs[b], 1),
+                _ => {
+                    wires.push(node.id);
+                    let wire = Combination::from([(wires.len(), 1)]);
+                    if let Some(Op::Mul(a, b, _)) = &node.op {
+                        constraints.push([lcs[a].clone(), lcs[b].clone(), wire.clone()]);
+                    }
+                    wire
+                }
+            };
+            lcs.insert(node.id, lc);
+        }
+        for (a, b) in &self.constraints {
+            let difference = combine(&lcs[a], &lcs[b], u32::MAX);
+            constraints.push([difference, one.clone(), Combination::new()]);
+        }
+        Json::Object(vec![
+            ("modulus".to_string(), Json::Str((1u64 << 32).to_string())),
+            ("n_wires".to_string(), Json::Int(wires.len() as i128 + 1)),
+            (
+                "wires".to_string(),
+                Json::Array(
+                    wires
+                        .iter()
+                        .enumerate()
+                        .map(|(i, id)| {
+                            Json::Array(vec![Json::Int(i as i128 + 1), Json::Int(id.0 as i128)])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "constraints".to_string(),
+                Json::Array(
+                    constraints
+                        .iter()
+                        .map(|abc| Json::Array(abc.iter().map(combination_json).collect()))
+                        .collect(),
+                ),
+            ),
+        ])
+        .to_pretty_string()
+    }
+}
+
+const CIRCOM_MAGIC: &[u8; 4] = b"r1cs";
+const CIRCOM_VERSION: u32 = 1;
+const SECTION_HEADER: u32 = 1;
+const SECTION_CONSTRAINTS: u32 = 2;
+
+fn format_error(message: impl Into<String>) -> ImportError {
+    ImportError::Format(message.into())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ImportError> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format_error("unexpected end of r1cs data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, ImportError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ImportError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
+
+/// Fits a little-endian field element below 2^64 into a `u64`.
+fn small(bytes: &[u8]) -> Option<u64> {
+    let (low, high) = bytes.split_at(bytes.len().min(8));
+    if high.iter().any(|b| *b != 0) {
+        return None;
+    }
+    let mut word = [0u8; 8];
+    word[..low.len()].copy_from_slice(low);
+    Some(u64::from_le_bytes(word))
+}
+
+/// `a - b` for little-endian numbers of equal length with `a >= b`.
+fn difference(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut borrow = 0i16;
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| {
+            let mut d = i16::from(*x) - i16::from(*y) - borrow;
+            borrow = i16::from(d < 0);
+            if d < 0 {
+                d += 256;
+            }
+            d as u8
+        })
+        .collect()
+}
+
+/// The circom field and how its elements map onto the graph's arithmetic.
+struct Field {
+    prime: Vec<u8>,
+    /// Set when the prime fits in a `u32`, so the field is exact.
+    modulus: Option<Modulus>,
+}
+
+impl Field {
+    /// A coefficient as a graph value. Exact for primes below 2^32; in larger
+    /// fields, `c` and `c - p` must be below 2^32 in magnitude and negative
+    /// coefficients wrap modulo 2^32.
+    fn coefficient(&self, bytes: &[u8]) -> Result<u32, ImportError> {
+        if self.modulus.is_some() {
+            return small(bytes)
+                .and_then(|c| u32::try_from(c).ok())
+                .ok_or_else(|| format_error("coefficient is not reduced modulo the prime"));
+        }
+        if let Some(c) = small(bytes).and_then(|c| u32::try_from(c).ok()) {
+            return Ok(c);
+        }
+        if bytes.iter().rev().cmp(self.prime.iter().rev()).is_ge() {
+            return Err(format_error("coefficient is not reduced modulo the prime"));
+        }
+        small(&difference(&self.prime, bytes))
+            .and_then(|d| u32::try_from(d).ok())
+            .map(u32::wrapping_neg)
+            .ok_or_else(|| format_error("coefficient does not fit in 32 bits as a signed value"))
+    }
+}
+
+/// Graph nodes for circom wires, created as they are first referenced.
+struct Wires {
+    count: u32,
+    nodes: HashMap<u32, Node>,
+}
+
+impl Wires {
+    fn node(&mut self, builder: &mut Builder, wire: u32) -> Result<Node, ImportError> {
+        if wire >= self.count {
+            return Err(format_error(format!(
+                "wire {} is out of range ({} wires)",
+                wire, self.count
+            )));
+        }
+        if let Some(node) = self.nodes.get(&wire) {
+            return Ok(*node);
+        }
+        let node = if wire == 0 {
+            builder.try_constant(1)?
+        } else {
+            let node = builder.try_init()?;
+            builder.set_label(&node, &format!("w{}", wire));
+            node
+        };
+        self.nodes.insert(wire, node);
+        Ok(node)
+    }
+}
+
+impl Builder {
+    /// Reads a constraint system in circom's binary `.r1cs` format. Fields
+    /// whose prime fits in a `u32` use `Arithmetic::Modular` and evaluate
+    /// exactly; larger fields such as BN254 keep wrapping arithmetic, which
+    /// preserves the structure for inspection but only agrees with the field
+    /// while values stay small.
+    pub fn from_circom_r1cs(bytes: &[u8]) -> Result<Builder, ImportError> {
+        Self::from_circom_r1cs_with_limits(bytes, Limits::default())
+    }
+
+    /// Like `from_circom_r1cs`, refusing constraint systems whose graph
+    /// exceeds `limits`.
+    pub fn from_circom_r1cs_with_limits(
+        bytes: &[u8],
+        limits: Limits,
+    ) -> Result<Builder, ImportError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != CIRCOM_MAGIC {
+            return Err(format_error("missing `r1cs` magic"));
+        }
+        let version = reader.u32()?;
+        if version != CIRCOM_VERSION {
+            return Err(format_error(format!(
+                "unsupported r1cs version {}",
+                version
+            )));
+        }
+        // Sections may come in any order; the header is needed first.
+        let mut sections = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let kind = reader.u32()?;
+            let size = usize::try_from(reader.u64()?)
+                .map_err(|_| format_error("section size does not fit in memory"))?;
+            let body = reader.take(size)?;
+            sections.entry(kind).or_insert(body);
+        }
+        let section = |kind: u32, name: &str| {
+            sections
+                .get(&kind)
+                .map(|bytes| Reader { bytes, pos: 0 })
+                .ok_or_else(|| format_error(format!("missing {} section", name)))
+        };
+
+        let mut header = section(SECTION_HEADER, "header")?;
+        let n8 = header.u32()? as usize;
+        if n8 == 0 {
+            return Err(format_error("field element size is zero"));
+        }
+        let prime = header.take(n8)?.to_vec();
+        let modulus = small(&prime)
+            .and_then(|p| u32::try_from(p).ok())
+            .and_then(Modulus::new);
+        let field = Field { prime, modulus };
+        let n_wires = header.u32()?;
+        let n_pub_out = header.u32()?;
+        let n_pub_in = header.u32()?;
+        let n_prv_in = header.u32()?;
+        let _n_labels = header.u64()?;
+        let n_constraints = header.u32()?;
+        let signals = u64::from(n_pub_out) + u64::from(n_pub_in) + u64::from(n_prv_in);
+        if signals >= u64::from(n_wires) {
+            return Err(format_error("more signals than wires"));
+        }
+
+        let mut builder = Builder::with_limits(limits);
+        if let Some(m) = field.modulus {
+            builder.set_arithmetic(Arithmetic::Modular(m));
+        }
+        let mut wires = Wires {
+            count: n_wires,
+            nodes: HashMap::new(),
+        };
+        // Outputs and inputs first, so their node ids follow circom's order.
+        for wire in 1..=signals as u32 {
+            let node = wires.node(&mut builder, wire)?;
+            if wire <= n_pub_out {
+                builder.mark_output(&node, &format!("w{}", wire));
+            }
+        }
+
+        let mut body = section(SECTION_CONSTRAINTS, "constraints")?;
+        for _ in 0..n_constraints {
+            let mut sides = Vec::with_capacity(3);
+            for _ in 0..3 {
+                let mut terms = Vec::new();
+                for _ in 0..body.u32()? {
+                    let wire = body.u32()?;
+                    let coeff = field.coefficient(body.take(n8)?)?;
+                    terms.push((wire, coeff));
+                }
+                sides.push(terms);
+            }
+            let [a, b, c] = [0, 1, 2].map(|i| std::mem::take(&mut sides[i]));
+            let lhs = if a.is_empty() || b.is_empty() {
+                builder.try_constant(0)?
+            } else {
+                let a = builder.linear_combination(&mut wires, &a)?;
+                let b = builder.linear_combination(&mut wires, &b)?;
+                builder.try_mul(&a, &b)?
+            };
+            let rhs = builder.linear_combination(&mut wires, &c)?;
+            builder.try_assert_equal(&lhs, &rhs)?;
+        }
+        if body.pos != body.bytes.len() {
+            return Err(format_error("trailing bytes in constraints section"));
+        }
+        Ok(builder)
+    }
+
+    /// `sum(coeff * wire)`, with unit coefficients and the constant wire
+    /// folded away.
+    fn linear_combination(
+        &mut self,
+        wires: &mut Wires,
+        terms: &[(u32, u32)],
+    ) -> Result<Node, ImportError> {
+        let mut sum: Option<Node> = None;
+        for &(wire, coeff) in terms {
+            let term = match (wire, coeff) {
+                (_, 0) => continue,
+                (0, c) => self.try_constant(c)?,
+                (w, 1) => wires.node(self, w)?,
+                (w, c) => {
+                    let k = self.try_constant(c)?;
+                    let w = wires.node(self, w)?;
+                    self.try_mul(&k, &w)?
+                }
+            };
+            sum = Some(match sum {
+                Some(s) => self.try_add(&s, &term)?,
+                None => term,
+            });
+        }
+        match sum {
+            Some(sum) => Ok(sum),
+            None => Ok(self.try_constant(0)?),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Sensitivity analysis: how outputs and constraints react to small input changes.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::{Builder, NodeId};
+
+/// Value of a node before and after a perturbation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueChange {
+    pub node: NodeId,
+    pub before: Option<u32>,
+    pub after: Option<u32>,
+}
+
+impl ValueChange {
+    /// `after - before` as a signed number, when both values are known.
+    pub fn difference(&self) -> Option<i64> {
+        Some(i64::from(self.after?) - i64::from(self.before?))
+    }
+}
+
+/// A constraint whose operands changed under a perturbation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintChange {
+    pub constraint: (NodeId, NodeId),
+    pub held_before: bool,
+    pub holds_after: bool,
+}
+
+/// Effect of moving one input by `delta`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Perturbation {
+    pub input: NodeId,
+    pub delta: i64,
+    /// Output (sink) nodes whose value changed.
+    pub outputs: Vec<ValueChange>,
+    /// Constraints that read at least one changed node.
+    pub constraints: Vec<ConstraintChange>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SensitivityReport {
+    pub perturbations: Vec<Perturbation>,
+}
+
+impl fmt::Display for SensitivityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for p in &self.perturbations {
+            writeln!(f, "Node {:?} {:+}:", p.input, p.delta)?;
+            for change in &p.outputs {
+                match change.difference() {
+                    Some(d) => writeln!(
+                        f,
+                        "  output Node {:?}: {:?} -> {:?} ({:+})",
+                        change.node, change.before, change.after, d
+                    )?,
+                    None => writeln!(
+                        f,
+                        "  output Node {:?}: {:?} -> {:?}",
+                        change.node, change.before, change.after
+                    )?,
+                }
+            }
+            for c in &p.constraints {
+                let status = match (c.held_before, c.holds_after) {
+                    (true, false) => "breaks",
+                    (false, true) => "becomes satisfied",
+                    (true, true) => "still holds",
+                    (false, false) => "still fails",
+                };
+                writeln!(
+                    f,
+                    "  constraint Node {:?} == Node {:?}: {}",
+                    c.constraint.0, c.constraint.1, status
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Builder {
+    /// Perturbs every evaluated input by +1 and -1 (wrapping), re-evaluates only
+    /// the nodes downstream of it, and reports which outputs and constraints are
+    /// affected. Uses the values from the last `fill_nodes`; the builder itself
+    /// is left unchanged.
+    pub fn sensitivity(&self) -> SensitivityReport {
+        let dependents = self.dependents_map();
+        let outputs: BTreeSet<NodeId> = self.output_ids().into_iter().collect();
+        let value = |id: &NodeId| self.nodes.get(id).and_then(|n| n.value);
+        let mut report = SensitivityReport::default();
+        for input in self.input_ids() {
+            let base = match value(&input) {
+                Some(v) => v,
+                None => continue,
+            };
+            let mut downstream = BTreeSet::new();
+            let mut stack = vec![input];
+            while let Some(id) = stack.pop() {
+                for dep in dependents.get(&id).into_iter().flatten() {
+                    if downstream.insert(*dep) {
+                        stack.push(*dep);
+                    }
+                }
+            }
+            let mut downstream: Vec<NodeId> = downstream.into_iter().collect();
+            self.sort_topologically(&mut downstream);
+            for delta in [1i64, -1] {
+                let moved = if delta > 0 {
+                    base.wrapping_add(1)
+                } else {
+                    base.wrapping_sub(1)
+                };
+                let mut overlay: HashMap<NodeId, Option<u32>> = HashMap::new();
+                overlay.insert(input, Some(moved));
+                for id in &downstream {
+                    let op = self.nodes[id].op.as_ref();
+                    let new = op.and_then(|op| {
+                        op.apply(self.arithmetic, |pid| {
+                            overlay.get(&pid).copied().unwrap_or_else(|| value(&pid))
+                        })
+                    });
+                    overlay.insert(*id, new);
+                }
+                let now = |id: &NodeId| overlay.get(id).copied().unwrap_or_else(|| value(id));
+                let changed = |id: &NodeId| overlay.contains_key(id) && now(id) != value(id);
+                let outputs = outputs
+                    .iter()
+                    .filter(|id| changed(id))
+                    .map(|id| ValueChange {
+                        node: *id,
+                        before: value(id),
+                        after: now(id),
+                    })
+                    .collect();
+                let constraints = self
+                    .constraints
+                    .iter()
+                    .filter(|(a, b)| changed(a) || changed(b))
+                    .map(|(a, b)| ConstraintChange {
+                        constraint: (*a, *b),
+                        held_before: value(a) == value(b),
+                        holds_after: now(a) == now(b),
+                    })
+                    .collect();
+                report.perturbations.push(Perturbation {
+                    input,
+                    delta,
+                    outputs,
+                    constraints,
+                });
+            }
+        }
+        report
+    }
+}
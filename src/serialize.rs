@@ -0,0 +1,393 @@
+//! Versioned binary encoding of graphs.
+//!
+//! Layout: `GRPH`, a `u16` format version, then the version-specific payload. All
+//! integers are little endian and node ids are stored as `u64`.
+//!
+//! * Version 1: nodes (id, op tag, operands; hints carry their registered name)
+//!   followed by constraints.
+//! * Version 2: version 1 plus a trailing table of node scope paths.
+//! * Version 3: version 2 plus the builder's arithmetic policy and a table of
+//!   `Add`/`Mul` nodes that override it, with modes written by name.
+//!
+//! `to_bytes` always writes the latest version. `from_bytes_any_version` decodes
+//! any known version and upgrades it step by step to the latest layout, so
+//! stored graphs keep loading as the format grows.
+
+use std::fmt;
+
+use crate::{Arithmetic, Builder, HintFn, HintRegistry, LimitError, Limits, NodeId, Op};
+
+const MAGIC: &[u8; 4] = b"GRPH";
+
+/// Version written by `Builder::to_bytes`.
+pub const GRAPH_FORMAT_VERSION: u16 = 3;
+
+pub(crate) const TAG_INPUT: u8 = 0;
+pub(crate) const TAG_CONST: u8 = 1;
+pub(crate) const TAG_ADD: u8 = 2;
+pub(crate) const TAG_MUL: u8 = 3;
+pub(crate) const TAG_HINT: u8 = 4;
+
+/// Error produced when decoding a serialized graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    pub message: String,
+}
+
+impl DecodeError {
+    pub(crate) fn new(message: impl Into<String>) -> Self {
+        DecodeError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot decode graph: {}", self.message)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<LimitError> for DecodeError {
+    fn from(e: LimitError) -> Self {
+        DecodeError::new(e.to_string())
+    }
+}
+
+pub(crate) enum RawOp {
+    Input,
+    Const(u32),
+    Add(NodeId, NodeId),
+    Mul(NodeId, NodeId),
+    Hint(String, Vec<NodeId>),
+}
+
+/// Version 1 payload.
+struct RawGraphV1 {
+    nodes: Vec<(NodeId, RawOp)>,
+    constraints: Vec<(NodeId, NodeId)>,
+}
+
+/// Version 2 payload.
+struct RawGraphV2 {
+    nodes: Vec<(NodeId, RawOp)>,
+    constraints: Vec<(NodeId, NodeId)>,
+    scopes: Vec<(NodeId, String)>,
+}
+
+impl From<RawGraphV1> for RawGraphV2 {
+    fn from(v1: RawGraphV1) -> Self {
+        RawGraphV2 {
+            nodes: v1.nodes,
+            constraints: v1.constraints,
+            scopes: Vec::new(),
+        }
+    }
+}
+
+/// Latest payload; older versions are upgraded into this.
+pub(crate) struct RawGraph {
+    pub(crate) nodes: Vec<(NodeId, RawOp)>,
+    pub(crate) constraints: Vec<(NodeId, NodeId)>,
+    pub(crate) scopes: Vec<(NodeId, String)>,
+    pub(crate) arithmetic: Arithmetic,
+    pub(crate) modes: Vec<(NodeId, Arithmetic)>,
+}
+
+impl From<RawGraphV2> for RawGraph {
+    fn from(v2: RawGraphV2) -> Self {
+        RawGraph {
+            nodes: v2.nodes,
+            constraints: v2.constraints,
+            scopes: v2.scopes,
+            arithmetic: Arithmetic::Inherit,
+            modes: Vec::new(),
+        }
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| DecodeError::new("unexpected end of data"))?;
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn id(&mut self) -> Result<NodeId, DecodeError> {
+        Ok(NodeId(self.u64()? as usize))
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| DecodeError::new("invalid UTF-8 string"))
+    }
+
+    fn arithmetic(&mut self) -> Result<Arithmetic, DecodeError> {
+        let name = self.string()?;
+        Arithmetic::from_name(&name)
+            .ok_or_else(|| DecodeError::new(format!("unknown arithmetic mode `{}`", name)))
+    }
+}
+
+fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn decode_v1(cur: &mut Cursor<'_>) -> Result<RawGraphV1, DecodeError> {
+    let count = cur.u64()?;
+    let mut nodes = Vec::new();
+    for _ in 0..count {
+        let id = cur.id()?;
+        let op = match cur.u8()? {
+            TAG_INPUT => RawOp::Input,
+            TAG_CONST => RawOp::Const(cur.u32()?),
+            TAG_ADD => RawOp::Add(cur.id()?, cur.id()?),
+            TAG_MUL => RawOp::Mul(cur.id()?, cur.id()?),
+            TAG_HINT => {
+                let name = cur.string()?;
+                let n = cur.u32()?;
+                let mut parents = Vec::new();
+                for _ in 0..n {
+                    parents.push(cur.id()?);
+                }
+                RawOp::Hint(name, parents)
+            }
+            tag => return Err(DecodeError::new(format!("unknown op tag {}", tag))),
+        };
+        nodes.push((id, op));
+    }
+    let count = cur.u64()?;
+    let mut constraints = Vec::new();
+    for _ in 0..count {
+        constraints.push((cur.id()?, cur.id()?));
+    }
+    Ok(RawGraphV1 { nodes, constraints })
+}
+
+fn decode_v2(cur: &mut Cursor<'_>) -> Result<RawGraphV2, DecodeError> {
+    let mut raw = RawGraphV2::from(decode_v1(cur)?);
+    let count = cur.u64()?;
+    for _ in 0..count {
+        raw.scopes.push((cur.id()?, cur.string()?));
+    }
+    Ok(raw)
+}
+
+fn decode_v3(cur: &mut Cursor<'_>) -> Result<RawGraph, DecodeError> {
+    let mut raw = RawGraph::from(decode_v2(cur)?);
+    raw.arithmetic = cur.arithmetic()?;
+    let count = cur.u64()?;
+    for _ in 0..count {
+        raw.modes.push((cur.id()?, cur.arithmetic()?));
+    }
+    Ok(raw)
+}
+
+impl Builder {
+    /// Serializes the graph structure (not its values) in the latest binary format.
+    /// Hints are stored by name; anonymous hints are written with an empty name
+    /// and cannot be decoded again.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&GRAPH_FORMAT_VERSION.to_le_bytes());
+        let nodes = self.sorted_nodes();
+        put_u64(&mut out, nodes.len() as u64);
+        for node in nodes {
+            put_u64(&mut out, node.id.0 as u64);
+            match &node.op {
+                None => out.push(TAG_INPUT),
+                Some(Op::Const(v)) => {
+                    out.push(TAG_CONST);
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+                Some(Op::Add(a, b, _)) => {
+                    out.push(TAG_ADD);
+                    put_u64(&mut out, a.0 as u64);
+                    put_u64(&mut out, b.0 as u64);
+                }
+                Some(Op::Mul(a, b, _)) => {
+                    out.push(TAG_MUL);
+                    put_u64(&mut out, a.0 as u64);
+                    put_u64(&mut out, b.0 as u64);
+                }
+                Some(Op::Hint(parents, f)) => {
+                    out.push(TAG_HINT);
+                    put_string(&mut out, f.name().unwrap_or(""));
+                    out.extend_from_slice(&(parents.len() as u32).to_le_bytes());
+                    for p in parents {
+                        put_u64(&mut out, p.0 as u64);
+                    }
+                }
+            }
+        }
+        put_u64(&mut out, self.constraints.len() as u64);
+        for (a, b) in &self.constraints {
+            put_u64(&mut out, a.0 as u64);
+            put_u64(&mut out, b.0 as u64);
+        }
+        let mut scopes: Vec<(&NodeId, &String)> = self.node_scopes.iter().collect();
+        scopes.sort_by_key(|(id, _)| id.0);
+        put_u64(&mut out, scopes.len() as u64);
+        for (id, path) in scopes {
+            put_u64(&mut out, id.0 as u64);
+            put_string(&mut out, path);
+        }
+        put_string(&mut out, &self.arithmetic.to_string());
+        let modes: Vec<(NodeId, Arithmetic)> = self
+            .sorted_nodes()
+            .into_iter()
+            .filter_map(|node| Some((node.id, self.arithmetic_of(&self.handle_of(node.id))?)))
+            .collect();
+        put_u64(&mut out, modes.len() as u64);
+        for (id, mode) in modes {
+            put_u64(&mut out, id.0 as u64);
+            put_string(&mut out, &mode.to_string());
+        }
+        out
+    }
+
+    /// Decodes a graph written by any version of `to_bytes`, upgrading older
+    /// layouts to the current one. Hint names are resolved through `hints`.
+    pub fn from_bytes_any_version(
+        bytes: &[u8],
+        hints: &HintRegistry,
+    ) -> Result<Builder, DecodeError> {
+        Self::from_bytes_any_version_with_limits(bytes, hints, Limits::default())
+    }
+
+    /// Like `from_bytes_any_version`, refusing graphs that exceed `limits`.
+    /// The decoded builder keeps `limits`.
+    pub fn from_bytes_any_version_with_limits(
+        bytes: &[u8],
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<Builder, DecodeError> {
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err(DecodeError::new("missing GRPH header"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let mut cur = Cursor { bytes, pos: 6 };
+        let raw = match version {
+            1 => RawGraph::from(RawGraphV2::from(decode_v1(&mut cur)?)),
+            2 => RawGraph::from(decode_v2(&mut cur)?),
+            3 => decode_v3(&mut cur)?,
+            v => {
+                return Err(DecodeError::new(format!(
+                    "format version {} is newer than supported version {}",
+                    v, GRAPH_FORMAT_VERSION
+                )))
+            }
+        };
+        if cur.pos != bytes.len() {
+            return Err(DecodeError::new("trailing bytes after graph"));
+        }
+        raw.into_builder(hints, limits)
+    }
+}
+
+impl RawGraph {
+    pub(crate) fn into_builder(
+        self,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<Builder, DecodeError> {
+        let mut builder = Builder::with_limits(limits);
+        let known = |builder: &Builder, id: &NodeId| {
+            if builder.nodes.contains_key(id) {
+                Ok(*id)
+            } else {
+                Err(DecodeError::new(format!(
+                    "reference to unknown node {}",
+                    id.0
+                )))
+            }
+        };
+        let entries = self.nodes.len();
+        for (id, raw) in self.nodes {
+            if !Builder::decodable_id(id, entries) {
+                return Err(DecodeError::new(format!(
+                    "node id {} is out of range for {} nodes",
+                    id.0, entries
+                )));
+            }
+            if builder.nodes.contains_key(&id) {
+                return Err(DecodeError::new(format!("node {} appears twice", id.0)));
+            }
+            let op = match raw {
+                RawOp::Input => None,
+                RawOp::Const(v) => Some(Op::Const(v)),
+                RawOp::Add(a, b) => Some(Op::Add(
+                    known(&builder, &a)?,
+                    known(&builder, &b)?,
+                    Arithmetic::Inherit,
+                )),
+                RawOp::Mul(a, b) => Some(Op::Mul(
+                    known(&builder, &a)?,
+                    known(&builder, &b)?,
+                    Arithmetic::Inherit,
+                )),
+                RawOp::Hint(name, parents) => {
+                    let func = hints
+                        .get(&name)
+                        .ok_or_else(|| DecodeError::new(format!("unknown hint `{}`", name)))?;
+                    let parents = parents
+                        .iter()
+                        .map(|p| known(&builder, p))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Some(Op::Hint(parents, HintFn::named(&name, func)))
+                }
+            };
+            builder.insert_node(id, op)?;
+        }
+        for (a, b) in self.constraints {
+            let (a, b) = (known(&builder, &a)?, known(&builder, &b)?);
+            builder.insert_constraint(a, b)?;
+        }
+        for (id, path) in self.scopes {
+            known(&builder, &id)?;
+            builder.node_scopes.insert(id, path);
+        }
+        builder.arithmetic = self.arithmetic;
+        for (id, new_mode) in self.modes {
+            match builder.nodes.get_mut(&id).and_then(|n| n.op.as_mut()) {
+                Some(Op::Add(_, _, mode)) | Some(Op::Mul(_, _, mode)) => *mode = new_mode,
+                _ => {
+                    return Err(DecodeError::new(format!(
+                        "arithmetic mode for node {}, which is not an add or mul",
+                        id.0
+                    )))
+                }
+            }
+        }
+        Ok(builder)
+    }
+}
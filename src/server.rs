@@ -0,0 +1,267 @@
+//! An HTTP JSON API over `GraphService`, built on axum, for web backends that
+//! want to evaluate graphs without linking Rust code.
+//!
+//! ```text
+//! POST /graphs                 body: gadget JSON
+//!   201 {"id": 0, "inputs": ["x"]}
+//! POST /graphs/{id}/evaluate   body: {"x": 3}
+//!   200 {"outputs": {"y": 13}, "witness": {...}, "constraints": {"total": 1, "failed": []}}
+//! DELETE /graphs/{id}
+//!   204
+//! ```
+//!
+//! Errors are answered with `{"error": "..."}` and status 400 (bad request
+//! body) or 404 (unknown route or graph). Bodies larger than `MAX_BODY_BYTES`
+//! get 413 and requests slower than `REQUEST_TIMEOUT` get 408, so a single
+//! client cannot exhaust memory or hold a connection open indefinitely.
+//!
+//! Requests are parsed and evaluated on tokio's blocking thread pool, so a
+//! large graph neither stalls the runtime's workers nor the requests for
+//! other graphs. When a request times out its evaluation is cancelled, and
+//! the thread is freed within one node.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::IntoResponse;
+use axum::Router;
+use tokio::net::TcpListener;
+use tower_http::timeout::TimeoutLayer;
+
+use crate::json::Json;
+use crate::service::{ConstraintReport, GraphService, ServiceError};
+
+/// Largest request body accepted, in bytes.
+pub const MAX_BODY_BYTES: usize = 16 << 20;
+
+/// Longest a request may take to arrive and be answered.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+type Shared = Arc<GraphService>;
+
+/// Status code and JSON body of an API response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: Json) -> Self {
+        Response {
+            status,
+            body: body.to_pretty_string(),
+        }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(
+            status,
+            Json::Object(vec![("error".to_string(), Json::Str(message.into()))]),
+        )
+    }
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> axum::response::Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        if self.body.is_empty() {
+            return status.into_response();
+        }
+        (
+            status,
+            [(header::CONTENT_TYPE, "application/json")],
+            self.body,
+        )
+            .into_response()
+    }
+}
+
+impl From<ServiceError> for Response {
+    fn from(e: ServiceError) -> Self {
+        let status = match e {
+            ServiceError::UnknownGraph(_) => 404,
+            ServiceError::Cancelled => 408,
+            _ => 400,
+        };
+        Response::error(status, e.to_string())
+    }
+}
+
+fn optional_value(value: Option<u32>) -> Json {
+    value.map_or(Json::Null, |v| Json::Int(i128::from(v)))
+}
+
+fn report_json(report: &ConstraintReport) -> Json {
+    let failed = report
+        .failed
+        .iter()
+        .map(|c| {
+            Json::Object(vec![
+                ("left".to_string(), Json::Int(c.left.0 as i128)),
+                ("right".to_string(), Json::Int(c.right.0 as i128)),
+                ("left_value".to_string(), optional_value(c.left_value)),
+                ("right_value".to_string(), optional_value(c.right_value)),
+            ])
+        })
+        .collect();
+    Json::Object(vec![
+        ("total".to_string(), Json::Int(report.total as i128)),
+        ("failed".to_string(), Json::Array(failed)),
+    ])
+}
+
+fn upload(service: &GraphService, body: &str) -> Result<Response, ServiceError> {
+    let id = service.upload(body)?;
+    let inputs = service
+        .input_names(id)?
+        .into_iter()
+        .map(Json::Str)
+        .collect();
+    Ok(Response::json(
+        201,
+        Json::Object(vec![
+            ("id".to_string(), Json::Int(i128::from(id))),
+            ("inputs".to_string(), Json::Array(inputs)),
+        ]),
+    ))
+}
+
+fn evaluate(
+    service: &GraphService,
+    id: u64,
+    body: &str,
+    cancel: &AtomicBool,
+) -> Result<Response, ServiceError> {
+    let values = match Json::parse(body) {
+        Ok(Json::Object(entries)) => entries,
+        _ => return Ok(Response::error(400, "expected an object of input values")),
+    };
+    let mut inputs = Vec::with_capacity(values.len());
+    for (name, value) in &values {
+        match value.as_int().and_then(|v| u32::try_from(v).ok()) {
+            Some(value) => inputs.push((name.as_str(), value)),
+            None => return Ok(Response::error(400, format!("`{}` must be a u32", name))),
+        }
+    }
+    service.submit_inputs(id, &inputs)?;
+    let outputs = service
+        .evaluate_cancellable(id, cancel)?
+        .into_iter()
+        .map(|(name, value)| (name, optional_value(value)))
+        .collect();
+    Ok(Response::json(
+        200,
+        Json::Object(vec![
+            ("outputs".to_string(), Json::Object(outputs)),
+            ("witness".to_string(), service.witness(id)?.to_json_value()),
+            (
+                "constraints".to_string(),
+                report_json(&service.constraint_report(id)?),
+            ),
+        ]),
+    ))
+}
+
+/// Answers one request without any HTTP machinery, so the API can also be
+/// mounted inside servers built on other frameworks.
+pub fn route(service: &GraphService, method: &str, path: &str, body: &str) -> Response {
+    route_cancellable(service, method, path, body, &AtomicBool::new(false))
+}
+
+/// `route`, abandoning an evaluation once `cancel` is set.
+fn route_cancellable(
+    service: &GraphService,
+    method: &str,
+    path: &str,
+    body: &str,
+    cancel: &AtomicBool,
+) -> Response {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let id = segments.get(1).and_then(|s| s.parse::<u64>().ok());
+    let result = match (method, segments.as_slice(), id) {
+        ("POST", ["graphs"], _) => upload(service, body),
+        ("POST", ["graphs", _, "evaluate"], Some(id)) => evaluate(service, id, body, cancel),
+        ("DELETE", ["graphs", _], Some(id)) => service.remove(id).map(|()| Response {
+            status: 204,
+            body: String::new(),
+        }),
+        _ => Ok(Response::error(
+            404,
+            format!("no route for {} {}", method, path),
+        )),
+    };
+    result.unwrap_or_else(Response::from)
+}
+
+/// Sets its flag when dropped, which is how a request learns that
+/// `TimeoutLayer` gave up on it.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+async fn handle(
+    State(service): State<Shared>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let declared = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+    // Refuse on the declared length before reading anything; the read itself
+    // is capped too, for bodies that lie about or omit their length.
+    if declared.is_some_and(|len| len > MAX_BODY_BYTES as u64) {
+        return too_large();
+    }
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_BODY_BYTES).await else {
+        return too_large();
+    };
+    let Ok(body) = String::from_utf8(bytes.to_vec()) else {
+        return Response::error(400, "request body is not UTF-8");
+    };
+    let cancel = CancelOnDrop(Arc::new(AtomicBool::new(false)));
+    let flag = Arc::clone(&cancel.0);
+    let work = tokio::task::spawn_blocking(move || {
+        route_cancellable(&service, method.as_str(), uri.path(), &body, &flag)
+    });
+    work.await
+        .unwrap_or_else(|_| Response::error(500, "the request failed while being handled"))
+}
+
+fn too_large() -> Response {
+    Response::error(
+        413,
+        format!("request body exceeds {} bytes", MAX_BODY_BYTES),
+    )
+}
+
+/// The API as an axum `Router`, for serving with `axum::serve` or nesting in
+/// a larger application. Bodies above `MAX_BODY_BYTES` are answered with 413,
+/// and requests that take longer than `REQUEST_TIMEOUT` with 408.
+pub fn router(service: GraphService) -> Router {
+    Router::new()
+        .fallback(handle)
+        .with_state(Arc::new(service))
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            REQUEST_TIMEOUT,
+        ))
+}
+
+/// Serves the API on `listener` until the process ends. Connections are
+/// handled concurrently, and failed `accept` calls are retried rather than
+/// ending the server.
+pub async fn serve(listener: TcpListener, service: GraphService) -> io::Result<()> {
+    axum::serve(listener, router(service)).await
+}
@@ -0,0 +1,316 @@
+//! Transport-independent graph evaluation service: graphs are uploaded as
+//! gadget JSON, receive inputs by port name, and are evaluated and checked on
+//! request. Network front-ends (see `proto/graphengine.proto`) translate their
+//! requests into calls on `GraphService`.
+//!
+//! The service is shared between request handlers as is: its methods take
+//! `&self`, and the table of graphs is only locked to look a graph up or add
+//! or remove one. Each graph has its own lock, so requests on different graphs
+//! are parsed and evaluated in parallel.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    gadgets, Builder, Gadget, HintRegistry, ImportError, Limits, NodeId, Op, PlanCache, Witness,
+};
+
+/// Evaluation plans kept by a `GraphService`.
+const PLAN_CACHE_CAPACITY: usize = 64;
+
+/// Limits a new `GraphService` decodes uploads under, so one request cannot
+/// make the service build an arbitrarily large graph.
+pub const DEFAULT_SERVICE_LIMITS: Limits = Limits {
+    max_nodes: Some(1 << 20),
+    max_constraints: Some(1 << 20),
+    max_hint_parents: Some(1024),
+};
+
+/// Identifies a graph uploaded to a `GraphService`.
+pub type GraphHandle = u64;
+
+#[derive(Debug)]
+pub enum ServiceError {
+    UnknownGraph(GraphHandle),
+    /// The uploaded graph could not be parsed.
+    Graph(ImportError),
+    UnknownInput(String),
+    /// `evaluate` was called before every input received a value.
+    MissingInput(String),
+    /// `evaluate_cancellable` was stopped through its flag.
+    Cancelled,
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::UnknownGraph(handle) => write!(f, "unknown graph {}", handle),
+            ServiceError::Graph(e) => write!(f, "{}", e),
+            ServiceError::UnknownInput(name) => write!(f, "the graph has no input `{}`", name),
+            ServiceError::MissingInput(name) => write!(f, "missing value for input `{}`", name),
+            ServiceError::Cancelled => write!(f, "evaluation was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+impl From<ImportError> for ServiceError {
+    fn from(e: ImportError) -> Self {
+        ServiceError::Graph(e)
+    }
+}
+
+/// A constraint that did not hold, with the values of both sides.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedConstraint {
+    pub left: NodeId,
+    pub right: NodeId,
+    pub left_value: Option<u32>,
+    pub right_value: Option<u32>,
+}
+
+/// The outcome of checking every constraint of an evaluated graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintReport {
+    pub total: usize,
+    pub failed: Vec<FailedConstraint>,
+}
+
+impl ConstraintReport {
+    pub fn holds(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+struct Session {
+    graph: Builder,
+    inputs: Vec<(String, NodeId)>,
+    values: HashMap<NodeId, u32>,
+}
+
+impl Session {
+    /// Fails with the first input that has no value yet.
+    fn check_inputs(&self) -> Result<(), ServiceError> {
+        match self
+            .inputs
+            .iter()
+            .find(|(_, id)| !self.values.contains_key(id))
+        {
+            Some((name, _)) => Err(ServiceError::MissingInput(name.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Fails on the first hint node whose operand count differs from the arity
+/// its name is registered with in `hints`.
+fn check_hint_arities(graph: &Builder, hints: &HintRegistry) -> Result<(), ImportError> {
+    for node in graph.nodes() {
+        let Some(Op::Hint(parents, f)) = &node.op else {
+            continue;
+        };
+        let Some(name) = f.name() else {
+            continue;
+        };
+        if let Some(arity) = hints.arity(name).filter(|arity| *arity != parents.len()) {
+            return Err(ImportError::Format(format!(
+                "node {} calls hint `{}` with {} operands, but it takes {}",
+                node.id.0,
+                name,
+                parents.len(),
+                arity
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Uploaded graphs and their pending inputs.
+pub struct GraphService {
+    sessions: Mutex<HashMap<GraphHandle, Arc<Mutex<Session>>>>,
+    next_handle: AtomicU64,
+    hints: HintRegistry,
+    limits: Limits,
+    /// Shared by sessions, so graphs uploaded more than once compile once.
+    plans: Mutex<PlanCache>,
+}
+
+impl Default for GraphService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Locks `mutex`. A panic while holding one of the service's locks leaves
+/// the sessions themselves consistent, so poisoning is ignored.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl GraphService {
+    /// A service resolving hint names from the `gadgets` library.
+    pub fn new() -> Self {
+        let mut hints = HintRegistry::new();
+        gadgets::register_hints(&mut hints);
+        Self::with_hints(hints)
+    }
+
+    /// A service resolving hint names through `hints` only.
+    pub fn with_hints(hints: HintRegistry) -> Self {
+        GraphService {
+            sessions: Mutex::new(HashMap::new()),
+            next_handle: AtomicU64::new(0),
+            hints,
+            limits: DEFAULT_SERVICE_LIMITS,
+            plans: Mutex::new(PlanCache::new(PLAN_CACHE_CAPACITY)),
+        }
+    }
+
+    /// Changes the limits uploads are decoded under. Graphs already uploaded
+    /// are kept.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// The session behind `handle`, holding the table lock only for the
+    /// lookup.
+    fn session(&self, handle: GraphHandle) -> Result<Arc<Mutex<Session>>, ServiceError> {
+        lock(&self.sessions)
+            .get(&handle)
+            .cloned()
+            .ok_or(ServiceError::UnknownGraph(handle))
+    }
+
+    /// Parses a graph in the gadget JSON format and stores it. The gadget's
+    /// output ports become the graph's outputs. Graphs calling a hint with a
+    /// different number of operands than it was registered with are refused,
+    /// since evaluating them would index past the operands, and so are graphs
+    /// exceeding the service's limits.
+    pub fn upload(&self, json: &str) -> Result<GraphHandle, ServiceError> {
+        let gadget = Gadget::from_json_with_limits(json, &self.hints, self.limits)?;
+        check_hint_arities(&gadget.graph, &self.hints)?;
+        let mut graph = gadget.graph;
+        graph.outputs = gadget.outputs;
+        let session = Session {
+            graph,
+            inputs: gadget.inputs,
+            values: HashMap::new(),
+        };
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed);
+        lock(&self.sessions).insert(handle, Arc::new(Mutex::new(session)));
+        Ok(handle)
+    }
+
+    /// Names of the graph's inputs, in port order.
+    pub fn input_names(&self, handle: GraphHandle) -> Result<Vec<String>, ServiceError> {
+        let session = self.session(handle)?;
+        let session = lock(&session);
+        Ok(session
+            .inputs
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+
+    /// Sets input values by name. Values given earlier are kept unless
+    /// overwritten here.
+    pub fn submit_inputs(
+        &self,
+        handle: GraphHandle,
+        values: &[(&str, u32)],
+    ) -> Result<(), ServiceError> {
+        let session = self.session(handle)?;
+        let mut session = lock(&session);
+        let mut resolved = Vec::with_capacity(values.len());
+        for (name, value) in values {
+            let id = session
+                .inputs
+                .iter()
+                .find(|(port, _)| port == name)
+                .map(|(_, id)| *id)
+                .ok_or_else(|| ServiceError::UnknownInput(name.to_string()))?;
+            resolved.push((id, *value));
+        }
+        session.values.extend(resolved);
+        Ok(())
+    }
+
+    /// Evaluates the graph on the submitted inputs and returns its outputs.
+    /// Evaluation follows the graph's cached `EvalPlan`.
+    pub fn evaluate(
+        &self,
+        handle: GraphHandle,
+    ) -> Result<Vec<(String, Option<u32>)>, ServiceError> {
+        let session = self.session(handle)?;
+        let mut session = lock(&session);
+        session.check_inputs()?;
+        let plan = lock(&self.plans).plan_for(&session.graph);
+        let Session { graph, values, .. } = &mut *session;
+        graph
+            .fill_with_plan(&plan, values)
+            .expect("the plan was compiled from this graph");
+        Ok(graph.output_values())
+    }
+
+    /// Like `evaluate`, but evaluates with `Builder::fill_nodes_cancellable`
+    /// and gives up with `ServiceError::Cancelled` once `cancel` is set, e.g.
+    /// by a request timeout on another thread.
+    pub fn evaluate_cancellable(
+        &self,
+        handle: GraphHandle,
+        cancel: &AtomicBool,
+    ) -> Result<Vec<(String, Option<u32>)>, ServiceError> {
+        let session = self.session(handle)?;
+        let mut session = lock(&session);
+        session.check_inputs()?;
+        let values = session.values.clone();
+        session
+            .graph
+            .fill_nodes_cancellable(values, cancel)
+            .map_err(|_| ServiceError::Cancelled)?;
+        Ok(session.graph.output_values())
+    }
+
+    /// Checks the constraints against the values of the last evaluation.
+    pub fn constraint_report(&self, handle: GraphHandle) -> Result<ConstraintReport, ServiceError> {
+        let session = self.session(handle)?;
+        let graph = &lock(&session).graph;
+        let failed = graph
+            .constraints
+            .iter()
+            .map(|&(left, right)| FailedConstraint {
+                left,
+                right,
+                left_value: graph.value(left),
+                right_value: graph.value(right),
+            })
+            .filter(|c| c.left_value != c.right_value)
+            .collect();
+        Ok(ConstraintReport {
+            total: graph.constraints.len(),
+            failed,
+        })
+    }
+
+    /// The values of the last evaluation.
+    pub fn witness(&self, handle: GraphHandle) -> Result<Witness, ServiceError> {
+        let session = self.session(handle)?;
+        let witness = lock(&session).graph.witness();
+        Ok(witness)
+    }
+
+    /// Drops an uploaded graph. Requests already working on it finish first.
+    pub fn remove(&self, handle: GraphHandle) -> Result<(), ServiceError> {
+        lock(&self.sessions)
+            .remove(&handle)
+            .map(|_| ())
+            .ok_or(ServiceError::UnknownGraph(handle))
+    }
+}
@@ -0,0 +1,371 @@
+//! Lisp-style textual format for graphs.
+//!
+//! ```text
+//! (graph
+//!   (input x0)
+//!   (define n1 (mul x0 x0))
+//!   (define n2 (const 8))
+//!   (define n3 (add n1 n2))
+//!   (assert-equal n3 n2))
+//! ```
+//!
+//! The writer emits one node per line in id order, which keeps diffs small. The
+//! reader additionally accepts nested expressions and bare numbers as constants,
+//! e.g. `(define y (add (mul x x) 8))`. Hints are written as `(hint name args...)`
+//! and resolved through a `HintRegistry` when reading. An `add` or `mul` that
+//! overrides the builder's arithmetic carries its mode as a third argument,
+//! e.g. `(add n1 n2 checked)`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{Arithmetic, Builder, HintRegistry, LimitError, Limits, Node, NodeId, Op};
+
+/// Error produced when reading a textual graph description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Deeper nesting than any graph needs, to bound recursion on hostile input.
+const MAX_DEPTH: usize = 256;
+
+fn too_deep(line: usize) -> ParseError {
+    ParseError::new(line, format!("nesting deeper than {} levels", MAX_DEPTH))
+}
+
+/// A `LimitError` hit while reading the form at `line`.
+fn limit_error(line: usize) -> impl Fn(LimitError) -> ParseError {
+    move |e| ParseError::new(line, e.to_string())
+}
+
+#[derive(Debug)]
+enum Sexp {
+    Atom(String, usize),
+    List(Vec<Sexp>, usize),
+}
+
+impl Sexp {
+    fn line(&self) -> usize {
+        match self {
+            Sexp::Atom(_, line) | Sexp::List(_, line) => *line,
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Vec<(String, usize)> {
+    let mut tokens = Vec::new();
+    for (i, raw_line) in text.lines().enumerate() {
+        let line = raw_line.split(';').next().unwrap_or("");
+        let mut atom = String::new();
+        for c in line.chars() {
+            if c == '(' || c == ')' || c.is_whitespace() {
+                if !atom.is_empty() {
+                    tokens.push((std::mem::take(&mut atom), i + 1));
+                }
+                if !c.is_whitespace() {
+                    tokens.push((c.to_string(), i + 1));
+                }
+            } else {
+                atom.push(c);
+            }
+        }
+        if !atom.is_empty() {
+            tokens.push((atom, i + 1));
+        }
+    }
+    tokens
+}
+
+fn parse_sexp(
+    tokens: &[(String, usize)],
+    pos: &mut usize,
+    depth: usize,
+) -> Result<Sexp, ParseError> {
+    let (token, line) = match tokens.get(*pos) {
+        Some((t, l)) => (t.as_str(), *l),
+        None => {
+            let last = tokens.last().map(|(_, l)| *l).unwrap_or(1);
+            return Err(ParseError::new(last, "unexpected end of input"));
+        }
+    };
+    *pos += 1;
+    match token {
+        "(" => {
+            if depth >= MAX_DEPTH {
+                return Err(too_deep(line));
+            }
+            let mut items = Vec::new();
+            loop {
+                match tokens.get(*pos) {
+                    Some((t, _)) if t == ")" => {
+                        *pos += 1;
+                        return Ok(Sexp::List(items, line));
+                    }
+                    Some(_) => items.push(parse_sexp(tokens, pos, depth + 1)?),
+                    None => return Err(ParseError::new(line, "unclosed `(`")),
+                }
+            }
+        }
+        ")" => Err(ParseError::new(line, "unexpected `)`")),
+        atom => Ok(Sexp::Atom(atom.to_string(), line)),
+    }
+}
+
+struct Reader<'a> {
+    builder: Builder,
+    names: HashMap<String, Node>,
+    hints: &'a HintRegistry,
+}
+
+impl Reader<'_> {
+    fn form(&mut self, form: &Sexp) -> Result<(), ParseError> {
+        let (items, line) = match form {
+            Sexp::List(items, line) => (items, *line),
+            Sexp::Atom(a, line) => {
+                return Err(ParseError::new(
+                    *line,
+                    format!("expected a form, found `{}`", a),
+                ))
+            }
+        };
+        match head(items, line)? {
+            "input" => {
+                let name = atom(items.get(1), line, "input name")?;
+                expect_len(items, 2, line)?;
+                let node = self.builder.try_init().map_err(limit_error(line))?;
+                self.bind(name, node, line)
+            }
+            "define" => {
+                let name = atom(items.get(1), line, "definition name")?;
+                expect_len(items, 3, line)?;
+                let node = self.expr(&items[2], 0)?;
+                self.bind(name, node, line)
+            }
+            "assert-equal" => {
+                expect_len(items, 3, line)?;
+                let a = self.expr(&items[1], 0)?;
+                let b = self.expr(&items[2], 0)?;
+                self.builder
+                    .try_assert_equal(&a, &b)
+                    .map_err(limit_error(line))
+            }
+            other => Err(ParseError::new(line, format!("unknown form `{}`", other))),
+        }
+    }
+
+    fn bind(&mut self, name: &str, node: Node, line: usize) -> Result<(), ParseError> {
+        if self.names.insert(name.to_string(), node).is_some() {
+            return Err(ParseError::new(
+                line,
+                format!("`{}` is defined twice", name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Builds the node for `expr`, nested `depth` levels inside a form.
+    fn expr(&mut self, expr: &Sexp, depth: usize) -> Result<Node, ParseError> {
+        if depth >= MAX_DEPTH {
+            return Err(too_deep(expr.line()));
+        }
+        match expr {
+            Sexp::Atom(a, line) => {
+                if let Ok(v) = a.parse::<u32>() {
+                    return self.builder.try_constant(v).map_err(limit_error(*line));
+                }
+                self.names
+                    .get(a)
+                    .cloned()
+                    .ok_or_else(|| ParseError::new(*line, format!("unknown name `{}`", a)))
+            }
+            Sexp::List(items, line) => {
+                let line = *line;
+                let op = head(items, line)?;
+                match op {
+                    "const" => {
+                        expect_len(items, 2, line)?;
+                        let v = atom(items.get(1), line, "constant value")?;
+                        let v = v.parse::<u32>().map_err(|_| {
+                            ParseError::new(line, format!("invalid constant `{}`", v))
+                        })?;
+                        self.builder.try_constant(v).map_err(limit_error(line))
+                    }
+                    "add" | "mul" => {
+                        let mode = match items.len() {
+                            4 => {
+                                let name = atom(items.get(3), line, "arithmetic mode")?;
+                                Arithmetic::from_name(name).ok_or_else(|| {
+                                    ParseError::new(line, format!("unknown mode `{}`", name))
+                                })?
+                            }
+                            _ => {
+                                expect_len(items, 3, line)?;
+                                Arithmetic::Inherit
+                            }
+                        };
+                        let a = self.expr(&items[1], depth + 1)?;
+                        let b = self.expr(&items[2], depth + 1)?;
+                        self.builder.check_node_limit().map_err(limit_error(line))?;
+                        if op == "add" {
+                            Ok(self.builder.add_with(&a, &b, mode))
+                        } else {
+                            Ok(self.builder.mul_with(&a, &b, mode))
+                        }
+                    }
+                    "hint" => {
+                        let name = atom(items.get(1), line, "hint name")?;
+                        let func = self.hints.get(name).ok_or_else(|| {
+                            ParseError::new(line, format!("unknown hint `{}`", name))
+                        })?;
+                        let mut parents = Vec::new();
+                        for item in &items[2..] {
+                            parents.push(self.expr(item, depth + 1)?);
+                        }
+                        self.builder
+                            .try_hint_named(name, &parents, func)
+                            .map_err(limit_error(line))
+                    }
+                    other => Err(ParseError::new(
+                        line,
+                        format!("unknown operation `{}`", other),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+fn head(items: &[Sexp], line: usize) -> Result<&str, ParseError> {
+    atom(items.first(), line, "form name")
+}
+
+fn atom<'s>(item: Option<&'s Sexp>, line: usize, what: &str) -> Result<&'s str, ParseError> {
+    match item {
+        Some(Sexp::Atom(a, _)) => Ok(a),
+        Some(other) => Err(ParseError::new(other.line(), format!("expected {}", what))),
+        None => Err(ParseError::new(line, format!("missing {}", what))),
+    }
+}
+
+fn expect_len(items: &[Sexp], len: usize, line: usize) -> Result<(), ParseError> {
+    if items.len() != len {
+        return Err(ParseError::new(
+            line,
+            format!("expected {} arguments, found {}", len - 1, items.len() - 1),
+        ));
+    }
+    Ok(())
+}
+
+impl Builder {
+    /// Writes the graph in the S-expression format described in the module docs.
+    /// Inputs are named `x<id>` and all other nodes `n<id>`.
+    pub fn to_sexpr(&self) -> String {
+        let name = |id: &NodeId| match self.nodes.get(id).map(|n| &n.op) {
+            Some(None) => format!("x{}", id.0),
+            _ => format!("n{}", id.0),
+        };
+        let mut out = String::from("(graph");
+        for node in self.sorted_nodes() {
+            let line = match &node.op {
+                None => format!("(input {})", name(&node.id)),
+                Some(op) => {
+                    let expr = match op {
+                        Op::Const(v) => format!("(const {})", v),
+                        Op::Add(a, b, Arithmetic::Inherit) => {
+                            format!("(add {} {})", name(a), name(b))
+                        }
+                        Op::Mul(a, b, Arithmetic::Inherit) => {
+                            format!("(mul {} {})", name(a), name(b))
+                        }
+                        Op::Add(a, b, mode) => format!("(add {} {} {})", name(a), name(b), mode),
+                        Op::Mul(a, b, mode) => format!("(mul {} {} {})", name(a), name(b), mode),
+                        Op::Hint(parents, f) => {
+                            let mut s = format!("(hint {}", f.name().unwrap_or("anonymous"));
+                            for p in parents {
+                                s.push(' ');
+                                s.push_str(&name(p));
+                            }
+                            s.push(')');
+                            s
+                        }
+                    };
+                    format!("(define {} {})", name(&node.id), expr)
+                }
+            };
+            out.push_str("\n  ");
+            out.push_str(&line);
+        }
+        for (a, b) in &self.constraints {
+            out.push_str(&format!("\n  (assert-equal {} {})", name(a), name(b)));
+        }
+        out.push_str(")\n");
+        out
+    }
+
+    /// Reads a graph from the S-expression format. Returns the new builder and the
+    /// node bound to every name in the text.
+    pub fn from_sexpr(
+        text: &str,
+        hints: &HintRegistry,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        Self::from_sexpr_with_limits(text, hints, Limits::default())
+    }
+
+    /// Like `from_sexpr`, refusing graphs that exceed `limits`.
+    pub fn from_sexpr_with_limits(
+        text: &str,
+        hints: &HintRegistry,
+        limits: Limits,
+    ) -> Result<(Builder, HashMap<String, NodeId>), ParseError> {
+        let tokens = tokenize(text);
+        let mut pos = 0;
+        let root = parse_sexp(&tokens, &mut pos, 0)?;
+        if let Some((t, line)) = tokens.get(pos) {
+            return Err(ParseError::new(
+                *line,
+                format!("unexpected `{}` after graph", t),
+            ));
+        }
+        let (items, line) = match &root {
+            Sexp::List(items, line) => (items, *line),
+            Sexp::Atom(_, line) => return Err(ParseError::new(*line, "expected `(graph ...)`")),
+        };
+        if head(items, line)? != "graph" {
+            return Err(ParseError::new(line, "expected `(graph ...)`"));
+        }
+        let mut reader = Reader {
+            builder: Builder::with_limits(limits),
+            names: HashMap::new(),
+            hints,
+        };
+        for form in &items[1..] {
+            reader.form(form)?;
+        }
+        let names = reader
+            .names
+            .into_iter()
+            .map(|(name, node)| (name, node.id))
+            .collect();
+        Ok((reader.builder, names))
+    }
+}
@@ -0,0 +1,162 @@
+//! Equivalence proofs with the Z3 SMT solver (feature `z3`).
+//!
+//! Both graphs become 32-bit vector terms over one shared set of input
+//! variables. Every node is paired with a flag that says whether it has a
+//! value, so checked overflow and undefined operands are modelled the same way
+//! `fill_nodes` treats them. Z3 then looks for inputs below `2^bits` on which
+//! some output pair differs; when there are none the graphs are equivalent on
+//! the whole domain, however wide. Named hints are uninterpreted functions, so
+//! two graphs calling the same hint on equal arguments agree; anonymous hints
+//! cannot be encoded.
+
+use std::collections::HashMap;
+
+use z3::ast::{Bool, BV};
+use z3::{FuncDecl, SatResult, Solver, Sort};
+
+use crate::{Arithmetic, Builder, Equivalence, NodeId, Op};
+
+/// A node's value and whether it has one.
+#[derive(Clone)]
+struct Term {
+    defined: Bool,
+    value: BV,
+}
+
+/// Named hints by name and arity, shared between the two graphs.
+type Hints = HashMap<(String, usize), FuncDecl>;
+
+/// `a op b` under `mode`, where `wide` is the exact result over 64 bits.
+fn arithmetic(mode: Arithmetic, a: &Term, b: &Term, wide: BV, low: BV) -> Term {
+    let both = Bool::and(&[a.defined.clone(), b.defined.clone()]);
+    let overflow = wide.bvuge(1u64 << 32);
+    match mode {
+        Arithmetic::Inherit | Arithmetic::Wrapping => Term {
+            defined: both,
+            value: low,
+        },
+        Arithmetic::Checked => Term {
+            defined: Bool::and(&[both, overflow.not()]),
+            value: low,
+        },
+        Arithmetic::Saturating => Term {
+            defined: both,
+            value: overflow.ite(&BV::from_u64(u64::from(u32::MAX), 32), &low),
+        },
+        Arithmetic::Modular(m) => Term {
+            defined: both,
+            value: wide.bvurem(u64::from(m.get())).extract(31, 0),
+        },
+    }
+}
+
+/// Encodes every node of `graph`, or `None` if it has an anonymous hint.
+fn encode(graph: &Builder, inputs: &[BV], hints: &mut Hints) -> Option<HashMap<NodeId, Term>> {
+    let positions: HashMap<NodeId, usize> = graph
+        .input_ids()
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| (id, i))
+        .collect();
+    let mut terms: HashMap<NodeId, Term> = HashMap::new();
+    for node in graph.sorted_nodes() {
+        let term = match &node.op {
+            None => Term {
+                defined: Bool::from_bool(true),
+                value: inputs[positions[&node.id]].clone(),
+            },
+            Some(Op::Const(v)) => Term {
+                defined: Bool::from_bool(true),
+                value: BV::from_u64(u64::from(*v), 32),
+            },
+            Some(Op::Add(a, b, mode)) => {
+                let (a, b) = (&terms[a], &terms[b]);
+                let wide = a.value.zero_ext(32).bvadd(b.value.zero_ext(32));
+                let low = a.value.bvadd(&b.value);
+                arithmetic(mode.or(graph.arithmetic), a, b, wide, low)
+            }
+            Some(Op::Mul(a, b, mode)) => {
+                let (a, b) = (&terms[a], &terms[b]);
+                let wide = a.value.zero_ext(32).bvmul(b.value.zero_ext(32));
+                let low = a.value.bvmul(&b.value);
+                arithmetic(mode.or(graph.arithmetic), a, b, wide, low)
+            }
+            Some(Op::Hint(parents, f)) => {
+                let name = f.name()?;
+                let decl = hints
+                    .entry((name.to_string(), parents.len()))
+                    .or_insert_with(|| {
+                        let word = Sort::bitvector(32);
+                        let domain = vec![&word; parents.len()];
+                        FuncDecl::new(format!("{}/{}", name, parents.len()), &domain, &word)
+                    });
+                let parents: Vec<&Term> = parents.iter().map(|p| &terms[p]).collect();
+                let args: Vec<&dyn z3::ast::Ast> = parents
+                    .iter()
+                    .map(|t| &t.value as &dyn z3::ast::Ast)
+                    .collect();
+                let defined: Vec<Bool> = parents.iter().map(|t| t.defined.clone()).collect();
+                Term {
+                    defined: Bool::and(&defined),
+                    value: decl.apply(&args).as_bv()?,
+                }
+            }
+        };
+        terms.insert(node.id, term);
+    }
+    Some(terms)
+}
+
+impl Builder {
+    /// Asks Z3 for inputs below `2^bits` on which the outputs of `self` and
+    /// `other` differ. Input and output counts must already match.
+    pub(crate) fn smt_equivalence(&self, other: &Builder, bits: u32) -> Equivalence {
+        let inputs: Vec<BV> = (0..self.input_ids().len())
+            .map(|i| BV::new_const(format!("x{}", i), 32))
+            .collect();
+        let mut hints = Hints::new();
+        let (Some(terms_a), Some(terms_b)) = (
+            encode(self, &inputs, &mut hints),
+            encode(other, &inputs, &mut hints),
+        ) else {
+            return Equivalence::Unknown;
+        };
+
+        let solver = Solver::new();
+        if bits < 32 {
+            for input in &inputs {
+                solver.assert(input.bvult(1u64 << bits));
+            }
+        }
+        let differences: Vec<Bool> = self
+            .output_ids()
+            .iter()
+            .zip(&other.output_ids())
+            .map(|(a, b)| {
+                let (a, b) = (&terms_a[a], &terms_b[b]);
+                let values_differ = Bool::and(&[
+                    a.defined.clone(),
+                    b.defined.clone(),
+                    a.value.eq(&b.value).not(),
+                ]);
+                Bool::or(&[a.defined.xor(&b.defined), values_differ])
+            })
+            .collect();
+        solver.assert(Bool::or(&differences));
+
+        match solver.check() {
+            SatResult::Unsat => Equivalence::ProvenBounded { bits: bits.min(32) },
+            SatResult::Sat => {
+                let Some(model) = solver.get_model() else {
+                    return Equivalence::Unknown;
+                };
+                let inputs = inputs
+                    .iter()
+                    .map(|x| model.eval(x, true).and_then(|v| v.as_u64()).unwrap_or(0) as u32)
+                    .collect();
+                Equivalence::Counterexample { inputs }
+            }
+            SatResult::Unknown => Equivalence::Unknown,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Slot storage for a graph's nodes, indexed by id, with a generation per slot
+//! so handles to removed nodes can be told apart from their successors.
+
+use core::ops::Index;
+
+use crate::prelude::*;
+use crate::{NodeData, NodeId};
+
+#[derive(Clone, Debug, Default)]
+struct Slot {
+    /// Bumped every time the slot's node is removed.
+    generation: u32,
+    node: Option<NodeData>,
+}
+
+/// The nodes of a graph, in slot `id.0`. Iteration is in ascending id order.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NodeStore {
+    slots: Vec<Slot>,
+    len: usize,
+}
+
+impl NodeStore {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn get(&self, id: &NodeId) -> Option<&NodeData> {
+        self.slots.get(id.0)?.node.as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, id: &NodeId) -> Option<&mut NodeData> {
+        self.slots.get_mut(id.0)?.node.as_mut()
+    }
+
+    pub(crate) fn contains_key(&self, id: &NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Generation of the node currently in slot `id`, if any.
+    pub(crate) fn generation(&self, id: NodeId) -> Option<u32> {
+        let slot = self.slots.get(id.0)?;
+        slot.node.as_ref().map(|_| slot.generation)
+    }
+
+    /// Stores `node` in slot `id`, returning the slot's generation.
+    pub(crate) fn insert(&mut self, id: NodeId, node: NodeData) -> u32 {
+        if id.0 >= self.slots.len() {
+            self.slots.resize_with(id.0 + 1, Slot::default);
+        }
+        let slot = &mut self.slots[id.0];
+        if slot.node.replace(node).is_none() {
+            self.len += 1;
+        }
+        slot.generation
+    }
+
+    /// Puts a removed node back into slot `id` under the generation it had,
+    /// so handles taken before its removal are live again.
+    pub(crate) fn restore(&mut self, id: NodeId, node: NodeData, generation: u32) {
+        self.insert(id, node);
+        self.slots[id.0].generation = generation;
+    }
+
+    /// Empties slot `id` and retires its generation.
+    pub(crate) fn remove(&mut self, id: &NodeId) -> Option<NodeData> {
+        let slot = self.slots.get_mut(id.0)?;
+        let node = slot.node.take()?;
+        slot.generation += 1;
+        self.len -= 1;
+        Some(node)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&NodeId, &NodeData)> + '_ {
+        self.values().map(|node| (&node.id, node))
+    }
+
+    pub(crate) fn keys(&self) -> impl Iterator<Item = &NodeId> + '_ {
+        self.values().map(|node| &node.id)
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &NodeData> + '_ {
+        self.slots.iter().filter_map(|slot| slot.node.as_ref())
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut NodeData> + '_ {
+        self.slots.iter_mut().filter_map(|slot| slot.node.as_mut())
+    }
+}
+
+impl Index<&NodeId> for NodeStore {
+    type Output = NodeData;
+
+    fn index(&self, id: &NodeId) -> &NodeData {
+        self.get(id)
+            .unwrap_or_else(|| panic!("no node {:?} in the graph", id))
+    }
+}
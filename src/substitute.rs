@@ -0,0 +1,165 @@
+//! Pattern-based rewriting: find every occurrence of a small subgraph and
+//! replace it with an equivalent one, e.g. swapping a naive gadget for an
+//! optimized version.
+
+use crate::collections::HashMap;
+
+use crate::prelude::*;
+use crate::{Builder, EditError, NodeId, Op};
+
+/// A successful match: pattern inputs bound to graph nodes, plus the graph
+/// nodes matched by the pattern's computed nodes.
+#[derive(Clone, Default)]
+struct Match {
+    bindings: HashMap<NodeId, NodeId>,
+    interior: Vec<NodeId>,
+}
+
+impl Builder {
+    /// The single node a pattern or replacement graph computes.
+    fn single_output(&self, role: &str) -> Result<NodeId, EditError> {
+        match self.output_ids().as_slice() {
+            [id] => Ok(*id),
+            ids => Err(EditError::InvalidPattern(format!(
+                "{} must have exactly one output, found {}",
+                role,
+                ids.len()
+            ))),
+        }
+    }
+
+    /// Tries to match pattern node `p` against graph node `t`, extending `m`.
+    /// Add and Mul operands may match in either order.
+    fn match_at(&self, pattern: &Builder, p: NodeId, t: NodeId, m: &mut Match) -> bool {
+        let (Some(pn), Some(tn)) = (pattern.nodes.get(&p), self.nodes.get(&t)) else {
+            return false;
+        };
+        let operands = match (&pn.op, &tn.op) {
+            (None, _) => {
+                return match m.bindings.get(&p) {
+                    Some(bound) => *bound == t,
+                    None => {
+                        m.bindings.insert(p, t);
+                        true
+                    }
+                };
+            }
+            (Some(Op::Const(a)), Some(Op::Const(b))) => {
+                if a == b {
+                    m.interior.push(t);
+                }
+                return a == b;
+            }
+            (Some(Op::Add(pa, pb, pm)), Some(Op::Add(ta, tb, tm)))
+            | (Some(Op::Mul(pa, pb, pm)), Some(Op::Mul(ta, tb, tm)))
+                if pm.or(pattern.arithmetic) == tm.or(self.arithmetic) =>
+            {
+                let mut swapped = m.clone();
+                if self.match_at(pattern, *pa, *ta, m) && self.match_at(pattern, *pb, *tb, m) {
+                    m.interior.push(t);
+                    return true;
+                }
+                if self.match_at(pattern, *pa, *tb, &mut swapped)
+                    && self.match_at(pattern, *pb, *ta, &mut swapped)
+                {
+                    *m = swapped;
+                    m.interior.push(t);
+                    return true;
+                }
+                return false;
+            }
+            (Some(Op::Hint(pp, pf)), Some(Op::Hint(tp, tf)))
+                if pf.name().is_some() && pf.name() == tf.name() && pp.len() == tp.len() =>
+            {
+                pp.iter().zip(tp)
+            }
+            _ => return false,
+        };
+        for (p, t) in operands {
+            if !self.match_at(pattern, *p, *t, m) {
+                return false;
+            }
+        }
+        m.interior.push(t);
+        true
+    }
+
+    /// Replaces every occurrence of `pattern` with `replacement` and returns
+    /// the number of replacements.
+    ///
+    /// Both graphs must compute exactly one output (declared with
+    /// `mark_output`, or their single sink) and have the same number of inputs,
+    /// which are matched by position: the pattern's inputs match any node,
+    /// consistently, and the replacement's inputs are wired to those nodes.
+    /// Constants must be equal and hints must share a registered name.
+    /// Pattern constraints are not matched; the replacement's constraints are
+    /// added for every occurrence. Matched nodes that are still used elsewhere
+    /// are kept, the rest are removed. Nodes created by a replacement are not
+    /// searched again.
+    pub fn substitute(
+        &mut self,
+        pattern: &Builder,
+        replacement: &Builder,
+    ) -> Result<usize, EditError> {
+        let root = pattern.single_output("pattern")?;
+        let out = replacement.single_output("replacement")?;
+        let (p_inputs, r_inputs) = (pattern.input_ids(), replacement.input_ids());
+        if p_inputs.len() != r_inputs.len() {
+            return Err(EditError::InvalidPattern(format!(
+                "pattern has {} inputs but replacement has {}",
+                p_inputs.len(),
+                r_inputs.len()
+            )));
+        }
+        if pattern.nodes[&root].op.is_none() {
+            return Err(EditError::InvalidPattern(
+                "pattern output must not be an input".into(),
+            ));
+        }
+        let cone = pattern.cone_of(root);
+        if let Some(unused) = p_inputs.iter().find(|id| !cone.contains(id)) {
+            return Err(EditError::InvalidPattern(format!(
+                "pattern input {:?} does not reach the output",
+                unused
+            )));
+        }
+
+        let candidates: Vec<NodeId> = self.sorted_nodes().iter().map(|n| n.id).collect();
+        let mut count = 0;
+        for candidate in candidates {
+            let mut m = Match::default();
+            if !self.match_at(pattern, root, candidate, &mut m) {
+                continue;
+            }
+            let bindings = p_inputs
+                .iter()
+                .zip(&r_inputs)
+                .map(|(p, r)| (*r, self.handle_of(m.bindings[p])))
+                .collect();
+            let map = self.copy_from(replacement, bindings);
+            let old = self.handle_of(candidate);
+            self.replace_node(&old, &map[&out])?;
+            self.remove_unused(m.interior);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Removes the given computed nodes (and then their operands) once nothing
+    /// reads, constrains or outputs them.
+    fn remove_unused(&mut self, mut candidates: Vec<NodeId>) {
+        while let Some(id) = candidates.pop() {
+            let Some(node) = self.nodes.get(&id) else {
+                continue;
+            };
+            let used = node.op.is_none()
+                || self.outputs.iter().any(|(_, o)| *o == id)
+                || self.constraints.iter().any(|(a, b)| *a == id || *b == id)
+                || self.nodes.values().any(|n| n.operands().contains(&id));
+            if !used {
+                candidates.extend(node.operands());
+                self.forget_node(id);
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Human-readable overview of a graph for `println!("{}", builder)`.
+
+use core::fmt;
+
+use crate::prelude::*;
+use crate::{Builder, NodeId, OpKind};
+
+impl Builder {
+    fn summary_name(&self, id: NodeId) -> String {
+        let name = self.path_of_id(id);
+        match self.value(id) {
+            Some(v) => format!("{} = {}", name, v),
+            None => name,
+        }
+    }
+}
+
+/// Prints node counts per op kind, the inputs, the outputs (declared, or the
+/// sinks when none are declared) and the constraints:
+///
+/// ```text
+/// graph: 6 nodes, 1 constraint
+///   input  1 | const  2 | add  1 | mul  1 | hint  1
+/// inputs:      n0 = 7
+/// outputs:     n5 = 8
+/// constraints:
+///   n2 == n5
+/// ```
+impl fmt::Display for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let constraints = self.constraints.len();
+        writeln!(
+            f,
+            "graph: {} node{}, {} constraint{}",
+            self.nodes.len(),
+            if self.nodes.len() == 1 { "" } else { "s" },
+            constraints,
+            if constraints == 1 { "" } else { "s" }
+        )?;
+        let kinds = [
+            OpKind::Input,
+            OpKind::Const,
+            OpKind::Add,
+            OpKind::Mul,
+            OpKind::Hint,
+        ];
+        let counts: Vec<String> = kinds
+            .iter()
+            .map(|kind| format!("{} {:>2}", kind, self.nodes_with_op(*kind).count()))
+            .collect();
+        writeln!(f, "  {}", counts.join(" | "))?;
+
+        let inputs: Vec<String> = self
+            .input_ids()
+            .into_iter()
+            .map(|id| self.summary_name(id))
+            .collect();
+        writeln!(f, "{:<13}{}", "inputs:", inputs.join(", "))?;
+        let outputs: Vec<String> = if self.outputs.is_empty() {
+            self.sink_ids()
+                .into_iter()
+                .map(|id| self.summary_name(id))
+                .collect()
+        } else {
+            self.outputs
+                .iter()
+                .map(|(name, id)| format!("{}: {}", name, self.summary_name(*id)))
+                .collect()
+        };
+        writeln!(f, "{:<13}{}", "outputs:", outputs.join(", "))?;
+        if constraints > 0 {
+            writeln!(f, "constraints:")?;
+            for (a, b) in &self.constraints {
+                writeln!(f, "  {} == {}", self.path_of_id(*a), self.path_of_id(*b))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Parameterized graph templates: families of gadgets that share one definition
+//! and expand into concrete nodes for given parameters.
+
+use crate::prelude::*;
+use crate::{Builder, Gadget, Node};
+
+/// A graph family parameterized by values known at construction time (width,
+/// degree, ...). Implementors describe their ports for the chosen parameters and
+/// emit the corresponding nodes in `expand`.
+pub trait Template {
+    /// Name used for the scope of each expansion, e.g. `poly_eval_3`.
+    fn name(&self) -> String;
+
+    fn input_names(&self) -> Vec<String>;
+
+    fn output_names(&self) -> Vec<String>;
+
+    /// Emits the nodes for one instance. `inputs` holds one node per input name;
+    /// the returned vector must hold one node per output name.
+    fn expand(&self, builder: &mut Builder, inputs: &[Node]) -> Vec<Node>;
+
+    /// Freezes the template into a `Gadget` for these parameters.
+    fn to_gadget(&self) -> Gadget {
+        let inputs = self.input_names();
+        let outputs = self.output_names();
+        let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+        let outputs: Vec<&str> = outputs.iter().map(String::as_str).collect();
+        Gadget::build(&self.name(), &inputs, &outputs, |b, ins| {
+            self.expand(b, ins)
+        })
+    }
+}
+
+/// Evaluates `c0 + c1*x + ... + cn*x^n` with Horner's rule.
+///
+/// Inputs: `x`, `c0`..`cn`. Output: `y`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PolyEval {
+    pub degree: usize,
+}
+
+impl Template for PolyEval {
+    fn name(&self) -> String {
+        format!("poly_eval_{}", self.degree)
+    }
+
+    fn input_names(&self) -> Vec<String> {
+        let mut names = vec!["x".to_string()];
+        names.extend((0..=self.degree).map(|i| format!("c{}", i)));
+        names
+    }
+
+    fn output_names(&self) -> Vec<String> {
+        vec!["y".to_string()]
+    }
+
+    fn expand(&self, builder: &mut Builder, inputs: &[Node]) -> Vec<Node> {
+        let x = &inputs[0];
+        let coefficients = &inputs[1..];
+        let mut acc = coefficients[self.degree];
+        for c in coefficients[..self.degree].iter().rev() {
+            let scaled = builder.mul(&acc, x);
+            acc = builder.add(&scaled, c);
+        }
+        vec![acc]
+    }
+}
+
+/// Sums `width` inputs named `x0`..`x{width-1}` into the output `sum`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sum {
+    pub width: usize,
+}
+
+impl Template for Sum {
+    fn name(&self) -> String {
+        format!("sum_{}", self.width)
+    }
+
+    fn input_names(&self) -> Vec<String> {
+        (0..self.width).map(|i| format!("x{}", i)).collect()
+    }
+
+    fn output_names(&self) -> Vec<String> {
+        vec!["sum".to_string()]
+    }
+
+    fn expand(&self, builder: &mut Builder, inputs: &[Node]) -> Vec<Node> {
+        let mut acc = match inputs.first() {
+            Some(first) => *first,
+            None => return vec![builder.constant(0)],
+        };
+        for x in &inputs[1..] {
+            acc = builder.add(&acc, x);
+        }
+        vec![acc]
+    }
+}
+
+impl Builder {
+    /// Expands `template` directly into this graph inside a scope named after it
+    /// and returns its output nodes.
+    ///
+    /// # Panics
+    /// If `inputs` does not provide one node per template input.
+    pub fn expand(&mut self, template: &dyn Template, inputs: &[Node]) -> Vec<Node> {
+        let expected = template.input_names().len();
+        assert_eq!(
+            inputs.len(),
+            expected,
+            "template `{}` expects {} inputs, got {}",
+            template.name(),
+            expected,
+            inputs.len()
+        );
+        self.enter_scope(&template.name());
+        let outputs = template.expand(self, inputs);
+        self.exit_scope();
+        outputs
+    }
+}
@@ -0,0 +1,264 @@
+//! Random graphs for property-based testing: `random_graph` builds a valid
+//! graph of configurable size and op mix together with inputs under which
+//! every constraint holds, reproducibly from a seed.
+//!
+//! ```ignore
+//! for seed in 0..100 {
+//!     let mut case = random_graph(&RandomGraphConfig { seed, ..Default::default() });
+//!     case.builder.fill_nodes(case.inputs.clone());
+//!     assert!(case.builder.check_constraints());
+//! }
+//! ```
+//!
+//! With the `proptest` feature, `RandomGraph` implements
+//! `proptest::arbitrary::Arbitrary`, so generated graphs can feed `proptest!`
+//! tests directly and failing cases shrink to fewer nodes and inputs.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::rng::SplitMix64;
+use crate::{Builder, HintRegistry, Node, NodeId};
+
+fn xor_hint(v: &[u32]) -> u32 {
+    v.iter().fold(0, |acc, x| acc ^ x)
+}
+
+fn div_hint(v: &[u32]) -> u32 {
+    v[0].checked_div(v[1]).unwrap_or(0)
+}
+
+/// Registers the hints random graphs use (`testing::xor`, `testing::div`),
+/// for reading them back from text or binary encodings.
+pub fn register_hints(registry: &mut HintRegistry) {
+    registry.register("testing::xor", xor_hint);
+    registry.register("testing::div", div_hint);
+}
+
+/// Relative weights of the operations drawn for each generated node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpMix {
+    pub constant: u32,
+    pub add: u32,
+    pub mul: u32,
+    pub hint: u32,
+}
+
+impl Default for OpMix {
+    fn default() -> Self {
+        OpMix {
+            constant: 1,
+            add: 3,
+            mul: 3,
+            hint: 1,
+        }
+    }
+}
+
+/// Controls the shape of the graphs built by `random_graph`.
+#[derive(Clone, Debug)]
+pub struct RandomGraphConfig {
+    pub inputs: usize,
+    /// Number of non-input nodes, not counting the constants that back
+    /// constraints.
+    pub nodes: usize,
+    pub op_mix: OpMix,
+    /// Probability in `[0, 1]` that a generated node is constrained.
+    pub constraint_density: f64,
+    pub seed: u64,
+}
+
+impl Default for RandomGraphConfig {
+    fn default() -> Self {
+        RandomGraphConfig {
+            inputs: 3,
+            nodes: 20,
+            op_mix: OpMix::default(),
+            constraint_density: 0.1,
+            seed: 0,
+        }
+    }
+}
+
+/// A generated graph and inputs that satisfy all of its constraints.
+#[derive(Clone)]
+pub struct RandomGraph {
+    pub builder: Builder,
+    pub inputs: HashMap<NodeId, u32>,
+}
+
+/// Shows the graph in the S-expression format, so failing cases can be read
+/// back with `Builder::from_sexpr` and `register_hints`.
+impl fmt::Debug for RandomGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut inputs: Vec<(usize, u32)> = self.inputs.iter().map(|(id, v)| (id.0, *v)).collect();
+        inputs.sort_unstable();
+        f.debug_struct("RandomGraph")
+            .field("graph", &self.builder.to_sexpr())
+            .field("inputs", &inputs)
+            .finish()
+    }
+}
+
+/// Draws graphs with `random_graph`. `inputs` and `nodes` of the parameters
+/// are upper bounds (with at least one input); the op mix and constraint
+/// density are used as given and the seed is drawn. Shrinking lowers the
+/// node and input counts.
+///
+/// ```ignore
+/// proptest! {
+///     #[test]
+///     fn pruning_keeps_values(case: RandomGraph) {
+///         let mut pruned = case.builder.clone();
+///         pruned.prune();
+///         prop_assert_eq!(
+///             pruned.evaluate_outputs(case.inputs.clone()),
+///             case.builder.clone().evaluate_outputs(case.inputs.clone())
+///         );
+///     }
+/// }
+/// ```
+#[cfg(feature = "proptest")]
+impl proptest::arbitrary::Arbitrary for RandomGraph {
+    type Parameters = RandomGraphConfig;
+    type Strategy = proptest::strategy::BoxedStrategy<RandomGraph>;
+
+    fn arbitrary_with(config: RandomGraphConfig) -> Self::Strategy {
+        use proptest::prelude::*;
+
+        (1..=config.inputs.max(1), 0..=config.nodes, any::<u64>())
+            .prop_map(move |(inputs, nodes, seed)| {
+                random_graph(&RandomGraphConfig {
+                    inputs,
+                    nodes,
+                    seed,
+                    ..config.clone()
+                })
+            })
+            .boxed()
+    }
+}
+
+/// Builds a random graph. Operands are drawn uniformly from the nodes created
+/// so far, so the result is always acyclic. Constraints pin a node to the
+/// value it takes under the generated inputs, so they hold for those inputs
+/// but generally fail for others.
+///
+/// # Panics
+/// If `config.inputs` is 0 while `nodes` is not, or every op weight is 0.
+pub fn random_graph(config: &RandomGraphConfig) -> RandomGraph {
+    let mix = config.op_mix;
+    let total = mix.constant + mix.add + mix.mul + mix.hint;
+    assert!(
+        config.nodes == 0 || (config.inputs > 0 && total > 0),
+        "random graphs need inputs and a non-zero op mix"
+    );
+    let mut rng = SplitMix64::new(config.seed);
+    let mut builder = Builder::new();
+    let mut nodes: Vec<Node> = Vec::with_capacity(config.inputs + config.nodes);
+    let mut inputs = HashMap::new();
+    for _ in 0..config.inputs {
+        let input = builder.init();
+        inputs.insert(input.id, rng.next_u32());
+        nodes.push(input);
+    }
+    let mut generated = Vec::with_capacity(config.nodes);
+    for _ in 0..config.nodes {
+        let pick = |rng: &mut SplitMix64| nodes[rng.next_u64() as usize % nodes.len()];
+        let roll = rng.next_u32() % total;
+        let node = if roll < mix.constant {
+            builder.constant(rng.next_u32() % 1000)
+        } else if roll < mix.constant + mix.add {
+            let (a, b) = (pick(&mut rng), pick(&mut rng));
+            builder.add(&a, &b)
+        } else if roll < mix.constant + mix.add + mix.mul {
+            let (a, b) = (pick(&mut rng), pick(&mut rng));
+            builder.mul(&a, &b)
+        } else if rng.next_u32() & 1 == 0 {
+            let parents = [pick(&mut rng), pick(&mut rng), pick(&mut rng)];
+            builder.hint_named("testing::xor", &parents, xor_hint)
+        } else {
+            let parents = [pick(&mut rng), pick(&mut rng)];
+            builder.hint_named("testing::div", &parents, div_hint)
+        };
+        nodes.push(node);
+        generated.push(node);
+    }
+
+    let mut evaluated = builder.clone();
+    evaluated.fill_nodes(inputs.clone());
+    let threshold = (config.constraint_density.clamp(0.0, 1.0) * u32::MAX as f64) as u32;
+    for node in generated {
+        if rng.next_u32() < threshold || threshold == u32::MAX {
+            if let Some(value) = evaluated.value(node.id) {
+                let expected = builder.constant(value);
+                builder.assert_equal(&node, &expected);
+            }
+        }
+    }
+    RandomGraph { builder, inputs }
+}
+
+/// A random graph on which a property did not hold, after shrinking.
+#[derive(Clone)]
+pub struct PropertyFailure {
+    /// Regenerates `case` through `random_graph`.
+    pub config: RandomGraphConfig,
+    pub case: RandomGraph,
+}
+
+/// Checks `property` on `cases` random graphs shaped by `config` (seeds
+/// `config.seed`, `config.seed + 1`, ...). On failure the node count is shrunk
+/// while the property keeps failing, and the smallest failing case is returned.
+///
+/// ```ignore
+/// check_property(&RandomGraphConfig::default(), 100, |case| {
+///     let mut optimized = case.builder.clone();
+///     optimized.prune();
+///     optimized.evaluate_outputs(case.inputs.clone()) == case.builder.clone().evaluate_outputs(case.inputs.clone())
+/// })
+/// .unwrap_or_else(|failure| panic!("fails for seed {}", failure.config.seed));
+/// ```
+pub fn check_property(
+    config: &RandomGraphConfig,
+    cases: u64,
+    mut property: impl FnMut(&RandomGraph) -> bool,
+) -> Result<(), Box<PropertyFailure>> {
+    for seed in config.seed..config.seed.saturating_add(cases) {
+        let mut failing = RandomGraphConfig {
+            seed,
+            ..config.clone()
+        };
+        let case = random_graph(&failing);
+        if property(&case) {
+            continue;
+        }
+        let mut smallest = case;
+        loop {
+            let n = failing.nodes;
+            let shrunk = [n / 2, n.saturating_sub(1)]
+                .into_iter()
+                .filter(|&nodes| nodes < n)
+                .map(|nodes| RandomGraphConfig {
+                    nodes,
+                    ..failing.clone()
+                })
+                .find_map(|candidate| {
+                    let case = random_graph(&candidate);
+                    (!property(&case)).then_some((candidate, case))
+                });
+            match shrunk {
+                Some((candidate, case)) => {
+                    failing = candidate;
+                    smallest = case;
+                }
+                None => break,
+            }
+        }
+        return Err(Box::new(PropertyFailure {
+            config: failing,
+            case: smallest,
+        }));
+    }
+    Ok(())
+}
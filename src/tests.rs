@@ -22,15 +22,14 @@ fn test_hint_division() {
     let a = builder.init();
     let one = builder.constant(1);
     let b = builder.add(&a, &one);
-    let c = builder.hint(vec![b.clone()], |vals| vals[0] / 8);
     let eight = builder.constant(8);
-    let c_times_8 = builder.mul(&c, &eight);
-    builder.assert_equal(&b, &c_times_8);
+    let (c, rem) = gadgets::divmod(&mut builder, &b, &eight, 8);
 
     let mut inputs = HashMap::new();
     inputs.insert(a.id, 7);
     builder.fill_nodes(inputs);
     assert!(builder.check_constraints());
+    assert_eq!(builder.values_of(&[c, rem]), [Some(1), Some(0)]);
 }
 
 #[test]
@@ -39,9 +38,7 @@ fn test_hint_sqrt() {
     let x = builder.init();
     let seven = builder.constant(7);
     let x_plus_seven = builder.add(&x, &seven);
-    let sqrt_node = builder.hint(vec![x_plus_seven.clone()], |vals| {
-        (vals[0] as f64).sqrt() as u32
-    });
+    let sqrt_node = builder.hint(&[x_plus_seven], |vals| (vals[0] as f64).sqrt() as u32);
     let computed_sq = builder.mul(&sqrt_node, &sqrt_node);
     builder.assert_equal(&computed_sq, &x_plus_seven);
 
@@ -50,3 +47,3959 @@ fn test_hint_sqrt() {
     builder.fill_nodes(inputs);
     assert!(builder.check_constraints());
 }
+
+#[test]
+fn test_dot_annotated_highlights_failed_constraint() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let two = builder.constant(2);
+    let doubled = builder.mul(&x, &two);
+    builder.assert_equal(&doubled, &x);
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 4);
+    builder.fill_nodes(inputs);
+    assert!(!builder.check_constraints());
+
+    let mut out = Vec::new();
+    builder
+        .write_dot(&mut out, &DotOptions::annotated())
+        .unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    assert!(dot.contains("= 8"));
+    assert!(dot.contains(&format!(
+        "Node{} -> Node{} [style=dashed, dir=none, color=red];",
+        doubled.id.0, x.id.0
+    )));
+}
+
+#[test]
+fn test_dot_options_styling() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let _sum = builder.add(&x, &y);
+
+    let options = DotOptions {
+        rankdir: RankDir::LeftRight,
+        show_ids: false,
+        add_style: NodeStyle::new("circle", "orange"),
+        ..DotOptions::default()
+    };
+    let mut out = Vec::new();
+    builder.write_dot(&mut out, &options).unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    assert!(dot.contains("rankdir=LR;"));
+    assert!(dot.contains("[label=\"Add\", shape=circle, style=filled, fillcolor=\"orange\"]"));
+    assert!(!dot.contains("Node0: Input"));
+}
+
+#[test]
+fn test_dot_clusters_scopes() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    builder.enter_scope("layer1");
+    let sq = builder.mul(&x, &x);
+    builder.enter_scope("adder");
+    let sum = builder.add(&sq, &x);
+    builder.exit_scope();
+    builder.exit_scope();
+    builder.enter_scope("say \"hi\"");
+    builder.add(&x, &x);
+    builder.exit_scope();
+    builder.enter_scope("C:\\tmp\nnext");
+    builder.add(&x, &x);
+    builder.exit_scope();
+
+    assert_eq!(builder.scope_of(&x), None);
+    assert_eq!(builder.scope_of(&sq), Some("layer1"));
+    assert_eq!(builder.scope_of(&sum), Some("layer1/adder"));
+
+    let mut out = Vec::new();
+    builder.write_dot(&mut out, &DotOptions::default()).unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    assert!(dot.contains("  subgraph cluster_0 {\n    label=\"layer1\";"));
+    assert!(dot.contains("    subgraph cluster_1 {\n      label=\"adder\";"));
+    assert!(dot.contains("  subgraph cluster_2 {\n    label=\"say \\\"hi\\\"\";"));
+    assert!(dot.contains("  subgraph cluster_3 {\n    label=\"C:\\\\tmp\\nnext\";"));
+}
+
+#[test]
+fn test_node_metadata() {
+    let mut builder = Builder::new();
+    let price = builder.init();
+    let qty = builder.init();
+    let total = builder.mul(&price, &qty);
+    builder.set_metadata(&price, "unit", "USD");
+    builder.set_metadata(&price, "source", "quotes.csv");
+    builder.set_metadata(&total, "unit", "USD");
+    assert_eq!(builder.metadata(&price, "unit"), Some("USD"));
+    assert_eq!(builder.metadata(&qty, "unit"), None);
+    let keys: Vec<&str> = builder.metadata_of(&price).map(|(k, _)| k).collect();
+    assert_eq!(keys, vec!["source", "unit"]);
+    assert_eq!(
+        builder.remove_metadata(&total, "unit"),
+        Some("USD".to_string())
+    );
+    assert_eq!(builder.metadata_of(&total).count(), 0);
+
+    let mut out = Vec::new();
+    builder.write_dot(&mut out, &DotOptions::default()).unwrap();
+    assert!(!String::from_utf8(out).unwrap().contains("unit=USD"));
+    let options = DotOptions {
+        show_metadata: true,
+        ..DotOptions::default()
+    };
+    let mut out = Vec::new();
+    builder.write_dot(&mut out, &options).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .contains("label=\"Node0: Input\\nsource=quotes.csv\\nunit=USD\""));
+}
+
+#[test]
+fn test_dot_output_is_deterministic() {
+    let build = || {
+        let mut builder = Builder::new();
+        let x = builder.init();
+        let y = builder.init();
+        let sq = builder.mul(&x, &x);
+        let sum = builder.add(&sq, &y);
+        let c = builder.constant(9);
+        builder.assert_equal(&sum, &c);
+        let mut out = Vec::new();
+        builder
+            .write_dot(&mut out, &DotOptions::annotated())
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let first = build();
+    for _ in 0..5 {
+        assert_eq!(build(), first);
+    }
+    let positions: Vec<usize> = (0..5)
+        .map(|i| first.find(&format!("  Node{} [", i)).unwrap())
+        .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]));
+}
+
+#[test]
+fn test_fingerprint_is_structural() {
+    let build = |constant: u32, swap: bool| {
+        let mut builder = Builder::new();
+        let x = builder.init();
+        let sq = builder.mul(&x, &x);
+        let c = builder.constant(constant);
+        let y = builder.add(&sq, &c);
+        if swap {
+            builder.assert_equal(&c, &y);
+        } else {
+            builder.assert_equal(&y, &c);
+        }
+        (builder, x)
+    };
+    let (mut a, x) = build(8, false);
+    let (b, _) = build(8, true);
+    let (c, _) = build(9, false);
+    assert_eq!(a.fingerprint(), b.fingerprint());
+    assert_ne!(a.fingerprint(), c.fingerprint());
+
+    let before = a.fingerprint();
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 3);
+    a.fill_nodes(inputs);
+    assert_eq!(a.fingerprint(), before);
+}
+
+#[test]
+fn test_expr_string() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let x_squared = builder.mul(&x, &x);
+    let eight = builder.constant(8);
+    let sum = builder.add(&x_squared, &x);
+    let y = builder.add(&sum, &eight);
+    assert_eq!(builder.expr_string(&y), "((x0*x0) + x0) + 8");
+
+    let doubled = builder.add(&x_squared, &x_squared);
+    assert_eq!(builder.expr_string(&doubled), "let n1 = x0*x0 in n1 + n1");
+}
+
+#[test]
+fn test_sexpr_round_trip() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let x_squared = builder.mul(&x, &x);
+    let eight = builder.constant(8);
+    let y = builder.add(&x_squared, &eight);
+    let half = builder.hint_named("half", &[y], |v| v[0] / 2);
+    builder.assert_equal(&half, &eight);
+
+    let text = builder.to_sexpr();
+    assert!(text.contains("(define n2 (const 8))"));
+    assert!(text.contains("(define n4 (hint half n3))"));
+
+    let mut hints = HintRegistry::new();
+    hints.register("half", |v| v[0] / 2);
+    let (read, names) = Builder::from_sexpr(&text, &hints).unwrap();
+    assert_eq!(read.fingerprint(), builder.fingerprint());
+    assert_eq!(read.to_sexpr(), text);
+    assert_eq!(names["x0"], x.id);
+}
+
+#[test]
+fn test_sexpr_nested_expressions_and_errors() {
+    let text = "(graph\n  (input x)\n  (define y (add (mul x x) 8)) ; x^2 + 8\n)";
+    let (mut builder, names) = Builder::from_sexpr(text, &HintRegistry::new()).unwrap();
+    let mut inputs = HashMap::new();
+    inputs.insert(names["x"], 3);
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.nodes[&names["y"]].value, Some(17));
+
+    let err = Builder::from_sexpr("(graph\n  (define y (sqrt 4)))", &HintRegistry::new())
+        .err()
+        .unwrap();
+    assert_eq!(err.line, 2);
+    assert!(err.message.contains("sqrt"));
+}
+
+#[test]
+fn test_sexpr_rejects_deep_nesting() {
+    let hints = HintRegistry::new();
+    let nested = |depth: usize| {
+        format!(
+            "(graph\n  (input x)\n  (define y {}x{}))",
+            "(add 1 ".repeat(depth),
+            ")".repeat(depth)
+        )
+    };
+    let (mut builder, names) = Builder::from_sexpr(&nested(100), &hints).unwrap();
+    let mut inputs = HashMap::new();
+    inputs.insert(names["x"], 1);
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.nodes[&names["y"]].value, Some(101));
+
+    let err = Builder::from_sexpr(&nested(1000), &hints).err().unwrap();
+    assert_eq!(err.line, 3);
+    assert!(err.message.contains("nesting"));
+    let err = Builder::from_sexpr(&"(".repeat(1_000_000), &hints)
+        .err()
+        .unwrap();
+    assert!(err.message.contains("nesting"));
+}
+
+fn pb_varint(mut v: u64, out: &mut Vec<u8>) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+fn pb_bytes(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+    pb_varint(field << 3 | 2, out);
+    pb_varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+fn pb_int(field: u64, v: u64, out: &mut Vec<u8>) {
+    pb_varint(field << 3, out);
+    pb_varint(v, out);
+}
+
+fn onnx_node(op: &str, inputs: &[&str], output: &str, attr: Option<Vec<u8>>) -> Vec<u8> {
+    let mut node = Vec::new();
+    for input in inputs {
+        pb_bytes(1, input.as_bytes(), &mut node);
+    }
+    pb_bytes(2, output.as_bytes(), &mut node);
+    pb_bytes(4, op.as_bytes(), &mut node);
+    if let Some(attr) = attr {
+        pb_bytes(5, &attr, &mut node);
+    }
+    node
+}
+
+fn onnx_model(nodes: &[Vec<u8>]) -> Vec<u8> {
+    let mut graph = Vec::new();
+    for node in nodes {
+        pb_bytes(1, node, &mut graph);
+    }
+    // initializer `c`: INT64 scalar 8 stored in int64_data
+    let mut tensor = Vec::new();
+    pb_int(2, 7, &mut tensor);
+    pb_int(7, 8, &mut tensor);
+    pb_bytes(8, b"c", &mut tensor);
+    pb_bytes(5, &tensor, &mut graph);
+    let mut input = Vec::new();
+    pb_bytes(1, b"x", &mut input);
+    pb_bytes(11, &input, &mut graph);
+    let mut output = Vec::new();
+    pb_bytes(1, b"z", &mut output);
+    pb_bytes(12, &output, &mut graph);
+    let mut model = Vec::new();
+    pb_int(1, 8, &mut model);
+    pb_bytes(7, &graph, &mut model);
+    model
+}
+
+#[test]
+fn test_onnx_import() {
+    // Constant `two`: INT32 tensor of shape [1] stored as raw_data
+    let mut tensor = Vec::new();
+    pb_int(1, 1, &mut tensor);
+    pb_int(2, 6, &mut tensor);
+    pb_bytes(9, &2i32.to_le_bytes(), &mut tensor);
+    let mut attr = Vec::new();
+    pb_bytes(1, b"value", &mut attr);
+    pb_bytes(5, &tensor, &mut attr);
+
+    let model = onnx_model(&[
+        onnx_node("Mul", &["x", "x"], "sq", None),
+        onnx_node("Add", &["sq", "c"], "y", None),
+        onnx_node("Constant", &[], "two", Some(attr)),
+        onnx_node("Mul", &["y", "two"], "z", None),
+    ]);
+    let limits = Limits {
+        max_nodes: Some(3),
+        ..Limits::default()
+    };
+    assert!(Builder::from_onnx_with_limits(&model, limits).is_err());
+    let (mut builder, names) = Builder::from_onnx(&model).unwrap();
+    let mut inputs = HashMap::new();
+    inputs.insert(names["x"], 3);
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.nodes[&names["z"]].value, Some(34));
+}
+
+#[test]
+fn test_onnx_rejects_unsupported_operator() {
+    let model = onnx_model(&[onnx_node("Relu", &["x"], "z", None)]);
+    let err = Builder::from_onnx(&model).err().unwrap();
+    assert!(err.message.contains("Relu"));
+}
+
+fn division_graph() -> (Builder, Node) {
+    let mut builder = Builder::new();
+    let a = builder.init();
+    let one = builder.constant(1);
+    let b = builder.add(&a, &one);
+    let c = builder.hint(&[b], |vals| vals[0] / 8);
+    let eight = builder.constant(8);
+    let c_times_8 = builder.mul(&c, &eight);
+    builder.assert_equal(&b, &c_times_8);
+    (builder, a)
+}
+
+#[test]
+fn test_value_accessors() {
+    let (mut builder, a) = division_graph();
+    let unevaluated = builder.witness();
+    assert_eq!(unevaluated.get(a.id), None);
+    assert_eq!(builder.value(a.id), None);
+
+    builder.fill_nodes(HashMap::from([(a.id, 15)]));
+    let quotient = NodeId(3);
+    assert_eq!(builder.value(quotient), Some(2));
+    assert_eq!(builder.value(NodeId(99)), None);
+    assert_eq!(builder.values_of(&[a]), vec![Some(15)]);
+
+    let witness = builder.witness();
+    assert_eq!(witness.get(quotient), Some(2));
+    assert_eq!(witness.iter().count(), 6);
+    assert_eq!(witness.iter().next(), Some((a.id, 15)));
+}
+
+#[test]
+fn test_node_query_api() {
+    let (mut builder, a) = division_graph();
+    assert_eq!(builder.node_count(), 6);
+    let ids: Vec<NodeId> = builder.nodes().map(|n| n.id).collect();
+    assert_eq!(ids, (0..6).map(NodeId).collect::<Vec<_>>());
+    let inputs: Vec<NodeId> = builder.inputs().map(|n| n.id).collect();
+    assert_eq!(inputs, vec![a.id]);
+    let constants: Vec<Option<u32>> = builder.constants().map(NodeData::constant_value).collect();
+    assert_eq!(constants, vec![Some(1), Some(8)]);
+    assert_eq!(builder.nodes_with_op(OpKind::Hint).count(), 1);
+    assert_eq!(builder.node(NodeId(5)).unwrap().kind(), OpKind::Mul);
+    assert_eq!(
+        builder.node(NodeId(5)).unwrap().operands(),
+        vec![NodeId(3), NodeId(4)]
+    );
+    assert_eq!(
+        builder.constraints().collect::<Vec<_>>(),
+        vec![(NodeId(2), NodeId(5))]
+    );
+
+    builder.fill_nodes(HashMap::from([(a.id, 7)]));
+    assert_eq!(builder.node(NodeId(5)).unwrap().value(), Some(8));
+}
+
+#[test]
+fn test_display_summary() {
+    let (mut builder, a) = division_graph();
+    builder.set_label(&a, "a");
+    builder.fill_nodes(HashMap::from([(a.id, 7)]));
+    let expected = "\
+graph: 6 nodes, 1 constraint
+  input  1 | const  2 | add  1 | mul  1 | hint  1
+inputs:      a = 7
+outputs:     n5 = 8
+constraints:
+  n2 == n5
+";
+    assert_eq!(builder.to_string(), expected);
+}
+
+#[test]
+fn test_witness_save_and_load() {
+    let (mut builder, a) = division_graph();
+    let mut inputs = HashMap::new();
+    inputs.insert(a.id, 7);
+    builder.fill_nodes(inputs);
+    let witness = builder.witness();
+
+    let dir = std::env::temp_dir();
+    for (name, format) in [
+        ("witness_test.json", WitnessFormat::Json),
+        ("witness_test.bin", WitnessFormat::Binary),
+    ] {
+        let path = dir.join(format!("{}-{}", std::process::id(), name));
+        witness.save(&path, format).unwrap();
+        let loaded = Witness::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, witness);
+
+        let (mut fresh, _) = division_graph();
+        fresh.apply_witness(&loaded).unwrap();
+        assert!(fresh.check_constraints());
+    }
+
+    let mut other = Builder::new();
+    other.init();
+    assert!(matches!(
+        other.apply_witness(&witness),
+        Err(WitnessError::GraphMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_binary_graph_round_trip_and_v1_upgrade() {
+    let mut hints = HintRegistry::new();
+    hints.register("half", |v| v[0] / 2);
+
+    let mut builder = Builder::new();
+    let x = builder.init();
+    builder.enter_scope("layer1");
+    let sq = builder.mul(&x, &x);
+    let half = builder.hint_named("half", &[sq], |v| v[0] / 2);
+    builder.exit_scope();
+    builder.assert_equal(&half, &x);
+
+    let bytes = builder.to_bytes();
+    assert_eq!(&bytes[4..6], &GRAPH_FORMAT_VERSION.to_le_bytes());
+    let decoded = Builder::from_bytes_any_version(&bytes, &hints).unwrap();
+    assert_eq!(decoded.fingerprint(), builder.fingerprint());
+    assert_eq!(decoded.scope_of(&sq), Some("layer1"));
+
+    // A version 1 file is the same payload without the trailing scope table
+    // and the version 3 arithmetic tables (policy name and override count).
+    let mut unscoped = Builder::new();
+    let x = unscoped.init();
+    let sq = unscoped.mul(&x, &x);
+    unscoped.assert_equal(&sq, &x);
+    let mut v1 = unscoped.to_bytes();
+    v1[4..6].copy_from_slice(&1u16.to_le_bytes());
+    v1.truncate(v1.len() - 8 - (4 + "inherit".len()) - 8);
+    let upgraded = Builder::from_bytes_any_version(&v1, &hints).unwrap();
+    assert_eq!(upgraded.fingerprint(), unscoped.fingerprint());
+    assert_eq!(upgraded.to_bytes(), unscoped.to_bytes());
+
+    let mut future = unscoped.to_bytes();
+    future[4..6].copy_from_slice(&99u16.to_le_bytes());
+    assert!(Builder::from_bytes_any_version(&future, &hints).is_err());
+}
+
+#[test]
+fn test_graph_diff() {
+    let mut before = Builder::new();
+    let x = before.init();
+    let sq = before.mul(&x, &x);
+    let c = before.constant(8);
+    before.assert_equal(&sq, &c);
+
+    let mut after = Builder::new();
+    let x2 = after.init();
+    let sq2 = after.mul(&x2, &x2);
+    let _c2 = after.constant(9);
+    let sum = after.add(&x2, &sq2);
+    after.assert_equal(&sum, &x2);
+
+    let diff = before.diff(&after);
+    assert_eq!(diff.added_nodes, vec![(sum.id, "add 0 1".to_string())]);
+    assert_eq!(diff.changed_nodes.len(), 1);
+    assert_eq!(diff.changed_nodes[0].before, "const 8");
+    assert_eq!(diff.removed_constraints, vec![(sq.id, c.id)]);
+    assert_eq!(diff.added_constraints, vec![(x2.id, sum.id)]);
+    assert!(diff.to_string().contains("~ node 2: const 8 -> const 9"));
+    assert!(before.diff(&before).is_empty());
+}
+
+#[test]
+fn test_equivalence_strategies() {
+    // x*x + x + 5, built in two different operand orders
+    let mut a = Builder::new();
+    let x = a.init();
+    let sq = a.mul(&x, &x);
+    let five = a.constant(5);
+    let s = a.add(&sq, &x);
+    a.add(&s, &five);
+
+    let mut b = Builder::new();
+    let x = b.init();
+    let five = b.constant(5);
+    let sq = b.mul(&x, &x);
+    let s = b.add(&x, &sq);
+    b.add(&five, &s);
+
+    assert_eq!(
+        a.equivalent_to(&b, EquivalenceStrategy::Structural),
+        Equivalence::Proven
+    );
+
+    // x * (x + 1) + 5 is the same polynomial but not the same structure
+    let mut c = Builder::new();
+    let x = c.init();
+    let one = c.constant(1);
+    let x1 = c.add(&x, &one);
+    let prod = c.mul(&x, &x1);
+    let five = c.constant(5);
+    c.add(&prod, &five);
+
+    assert_eq!(
+        a.equivalent_to(&c, EquivalenceStrategy::Structural),
+        Equivalence::Unknown
+    );
+    let auto = EquivalenceStrategy::Auto {
+        samples: 16,
+        seed: 7,
+    };
+    assert_eq!(
+        a.equivalent_to(&c, auto),
+        Equivalence::Tested { samples: 16 }
+    );
+
+    // x*x + 6 differs everywhere
+    let mut d = Builder::new();
+    let x = d.init();
+    let sq = d.mul(&x, &x);
+    let six = d.constant(6);
+    let s = d.add(&sq, &x);
+    d.add(&s, &six);
+    assert!(matches!(
+        a.equivalent_to(&d, auto),
+        Equivalence::Counterexample { .. }
+    ));
+}
+
+#[test]
+fn test_derive_forward() {
+    // f(x, y) = x*x*y + 3x, df/dx = 2xy + 3, df/dy = x*x
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let sq = builder.mul(&x, &x);
+    let sqy = builder.mul(&sq, &y);
+    let three = builder.constant(3);
+    let three_x = builder.mul(&three, &x);
+    let f = builder.add(&sqy, &three_x);
+
+    let mut point = HashMap::new();
+    point.insert(x.id, 4);
+    point.insert(y.id, 5);
+    let dx = builder.derive_forward(&f, &x, &point).unwrap();
+    assert_eq!(
+        dx,
+        Dual {
+            value: 92,
+            derivative: 43
+        }
+    );
+    assert_eq!(
+        builder.derive_forward(&f, &y, &point).unwrap().derivative,
+        16
+    );
+    assert_eq!(
+        builder.derive_forward(&f, &sq, &point),
+        Err(DiffError::NotAnInput(sq.id))
+    );
+
+    let h = builder.hint(&[f], |v| v[0] / 2);
+    assert_eq!(
+        builder.derive_forward(&h, &x, &point),
+        Err(DiffError::NonDifferentiable(h.id))
+    );
+}
+
+#[test]
+fn test_reverse_mode_gradient() {
+    // f(x, y) = x*x*y + 3x, df/dx = 2xy + 3, df/dy = x*x
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let unused = builder.init();
+    let sq = builder.mul(&x, &x);
+    let sqy = builder.mul(&sq, &y);
+    let three = builder.constant(3);
+    let three_x = builder.mul(&three, &x);
+    let f = builder.add(&sqy, &three_x);
+
+    let mut grad = builder.gradient(&f, &[x, y, unused]).unwrap();
+    let mut inputs = HashMap::new();
+    inputs.insert(grad.node_map[&x.id], 4);
+    inputs.insert(grad.node_map[&y.id], 5);
+    inputs.insert(grad.node_map[&unused.id], 1);
+    grad.graph.fill_nodes(inputs);
+    let values: Vec<Option<u32>> = grad
+        .gradients
+        .iter()
+        .map(|id| grad.graph.nodes[id].value)
+        .collect();
+    assert_eq!(values, vec![Some(43), Some(16), Some(0)]);
+    assert_eq!(grad.graph.nodes[&grad.node_map[&f.id]].value, Some(92));
+
+    // A hint with a registered derivative: g(x) = square(x) + x, dg/dx = 2x + 1
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let sq = builder.hint_named("square", &[x], |v| v[0] * v[0]);
+    let g = builder.add(&sq, &x);
+    assert!(matches!(
+        builder.gradient(&g, &[x]),
+        Err(DiffError::NonDifferentiable(_))
+    ));
+    let mut derivatives = HintDerivatives::new();
+    derivatives.register("square", |b: &mut Builder, args: &[Node], _: &Node| {
+        let two = b.constant(2);
+        vec![b.mul(&two, &args[0])]
+    });
+    let mut grad = builder.gradient_with(&g, &[x], &derivatives).unwrap();
+    let mut inputs = HashMap::new();
+    inputs.insert(grad.node_map[&x.id], 6);
+    grad.graph.fill_nodes(inputs);
+    assert_eq!(grad.graph.nodes[&grad.gradients[0]].value, Some(13));
+}
+
+#[test]
+fn test_symbolic_differentiation() {
+    // f(x, y) = x*x*y + 3x, df/dx = 2xy + 3
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let sq = builder.mul(&x, &x);
+    let sqy = builder.mul(&sq, &y);
+    let three = builder.constant(3);
+    let three_x = builder.mul(&three, &x);
+    let f = builder.add(&sqy, &three_x);
+
+    let dfdx = builder.differentiate(&f, &x).unwrap();
+    let expected = builder.constant(43);
+    builder.assert_equal(&dfdx, &expected);
+    let dfdy = builder.differentiate(&f, &y).unwrap();
+    let dconst = builder.differentiate(&three, &x).unwrap();
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 4);
+    inputs.insert(y.id, 5);
+    builder.fill_nodes(inputs);
+    assert!(builder.check_constraints());
+    assert_eq!(builder.nodes[&dfdy.id].value, Some(16));
+    assert_eq!(builder.nodes[&dconst.id].value, Some(0));
+}
+
+#[test]
+fn test_sensitivity_report() {
+    let (mut builder, a) = division_graph();
+    let mut inputs = HashMap::new();
+    inputs.insert(a.id, 7);
+    builder.fill_nodes(inputs);
+    assert!(builder.check_constraints());
+
+    let report = builder.sensitivity();
+    assert_eq!(report.perturbations.len(), 2);
+    let up = &report.perturbations[0];
+    assert_eq!((up.input, up.delta), (a.id, 1));
+    // a + 1 = 9 is no longer divisible by 8, so the division constraint breaks
+    assert_eq!(up.constraints.len(), 1);
+    assert!(up.constraints[0].held_before && !up.constraints[0].holds_after);
+    // ... while c * 8 still evaluates to 8, so no output changes
+    assert!(up.outputs.is_empty());
+    let down = &report.perturbations[1];
+    assert_eq!(down.outputs[0].difference(), Some(-8));
+    assert!(report.to_string().contains("breaks"));
+    // The builder keeps its original values.
+    assert!(builder.check_constraints());
+}
+
+#[test]
+fn test_critical_path() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let five = builder.constant(5);
+    let x_squared = builder.mul(&x, &x);
+    let sum = builder.add(&x_squared, &x);
+    let y = builder.add(&sum, &five);
+
+    let cp = builder.critical_path(&y);
+    assert_eq!(cp.depth, 3);
+    assert_eq!(cp.path, vec![x.id, x_squared.id, sum.id, y.id]);
+    assert_eq!(cp.level_counts, vec![2, 1, 1, 1]);
+    assert_eq!(builder.critical_path(&x).depth, 0);
+}
+
+#[test]
+fn test_graph_macro() {
+    let (mut builder, names) = graph! {
+        inputs: x, z;
+        let y = x * x + x + 8;
+        let w = 2 * (y + 1);
+        assert y == z;
+        assert (x * 3) == w + 0;
+    };
+    assert_eq!(builder.constraints.len(), 2);
+
+    let mut inputs = HashMap::new();
+    inputs.insert(names["x"], 3);
+    inputs.insert(names["z"], 20);
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.nodes[&names["y"]].value, Some(20));
+    assert_eq!(builder.nodes[&names["w"]].value, Some(42));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_named_inputs() {
+    let mut builder = Builder::new();
+    let price = builder.input("price");
+    let qty = builder.input("qty");
+    let total = builder.mul(&price, &qty);
+    assert_eq!(builder.input_id("qty"), Some(qty.id));
+    assert_eq!(builder.label_of(&price), Some("price"));
+
+    let unknown = builder.fill_nodes_named(HashMap::from([("discount", 3u8)]));
+    assert_eq!(
+        unknown,
+        Err(InputError::UnknownName("discount".to_string()))
+    );
+    assert_eq!(builder.nodes[&price.id].value, None);
+
+    builder
+        .fill_nodes_named(HashMap::from([("price", 12u16), ("qty", 3)]))
+        .unwrap();
+    assert_eq!(builder.nodes[&total.id].value, Some(36));
+}
+
+#[test]
+fn test_source_locations() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let line = line!();
+    let sq = builder.mul(&x, &x);
+    builder.assert_equal(&sq, &x);
+    let node_site = builder.location_of(&sq).unwrap();
+    assert_eq!(node_site.file(), file!());
+    assert_eq!(node_site.line(), line + 1);
+    assert_eq!(
+        builder.constraint_location(sq.id, x.id).unwrap().line(),
+        line + 2
+    );
+
+    // Gadget nodes keep the location inside the gadget body.
+    let square = Gadget::build("square", &["v"], &["s"], |b, ins| {
+        vec![b.mul(&ins[0], &ins[0])]
+    });
+    let body_line = line!() - 2;
+    let s = builder.instantiate(&square, &[x]).remove(0);
+    assert_eq!(builder.location_of(&s).unwrap().line(), body_line);
+}
+
+#[test]
+fn test_declared_outputs() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let sq = builder.mul(&x, &x);
+    let y = builder.add(&sq, &x);
+    let debug = builder.mul(&sq, &sq);
+    let three = builder.constant(3);
+    let _unused = builder.add(&debug, &three);
+    builder.mark_output(&y, "y");
+    assert_eq!(builder.output_id("y"), Some(y.id));
+    assert!(builder.is_output(&y) && !builder.is_output(&debug));
+
+    let values = builder.evaluate_outputs(HashMap::from([(x.id, 4)]));
+    assert_eq!(values, vec![("y".to_string(), Some(20))]);
+    assert_eq!(builder.value(debug.id), None);
+
+    assert_eq!(builder.prune(), 3);
+    assert_eq!(builder.node_count(), 3);
+    let outputs: Vec<(&str, NodeId)> = builder.outputs().map(|(n, node)| (n, node.id)).collect();
+    assert_eq!(outputs, vec![("y", y.id)]);
+
+    let mut out = Vec::new();
+    builder.write_dot(&mut out, &DotOptions::default()).unwrap();
+    let dot = String::from_utf8(out).unwrap();
+    assert_eq!(dot.matches("peripheries=2").count(), 1);
+}
+
+#[test]
+fn test_node_ownership() {
+    let mut a = Builder::new();
+    let x = a.init();
+    let copy = a.clone();
+    let b = Builder::new();
+    assert!(a.owns(&x));
+    assert!(copy.owns(&x));
+    assert!(!b.owns(&x));
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "belongs to a different builder")]
+fn test_foreign_node_panics() {
+    let mut a = Builder::new();
+    let mut b = Builder::new();
+    let x = a.init();
+    let y = b.init();
+    b.add(&x, &y);
+}
+
+#[test]
+fn test_checkpoint_rollback() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let sq = builder.mul(&x, &x);
+    builder.assert_equal(&sq, &x);
+    let cp = builder.checkpoint();
+
+    builder.enter_scope("speculative");
+    let branch = builder.input("extra");
+    let sum = builder.add(&sq, &branch);
+    builder.assert_equal(&sum, &x);
+    builder.mark_output(&sum, "sum");
+    builder.rollback(&cp);
+
+    assert_eq!(builder.node_count(), 2);
+    assert_eq!(builder.constraints().count(), 1);
+    assert!(builder.constraint_location(sq.id, x.id).is_some());
+    assert_eq!(builder.input_id("extra"), None);
+    assert_eq!(builder.outputs().count(), 0);
+    let fresh = builder.add(&x, &x);
+    assert_eq!(fresh.id, branch.id);
+    assert_eq!(builder.scope_of(&fresh), None);
+}
+
+#[test]
+fn test_rollback_undoes_edits() {
+    let mut builder = Builder::new();
+    let x = builder.input("x");
+    let y = builder.input("y");
+    let sum = builder.add(&x, &y);
+    let sq = builder.mul(&sum, &sum);
+    let scaled = builder.mul(&sq, 3);
+    let expected = builder.constant(27);
+    builder.set_label(&sum, "sum");
+    builder.assert_equal(&scaled, &expected);
+    builder.mark_output(&sq, "sq");
+    builder.mark_output(&scaled, "scaled");
+    let fingerprint = builder.fingerprint();
+    let sexpr = builder.to_sexpr();
+    let cp = builder.checkpoint();
+
+    // Rewire `sq` to a node built after it, then cut `scaled` and its
+    // constraint away: older nodes change and disappear.
+    let late = builder.add(&x, 1);
+    builder.replace_node(&sum, &late).unwrap();
+    builder.mark_output(&late, "late");
+    builder.remove_subtree(&scaled).unwrap();
+    assert!(!builder.is_live(&sum));
+    assert_eq!(builder.constraints().count(), 0);
+    assert!(builder.reordered);
+    builder.rollback(&cp);
+
+    assert_eq!(builder.fingerprint(), fingerprint);
+    assert_eq!(builder.to_sexpr(), sexpr);
+    assert!(!builder.reordered);
+    assert!(builder.is_live(&sum) && builder.is_live(&scaled));
+    assert_eq!(builder.node(sq.id).unwrap().operands(), [sum.id, sum.id]);
+    assert_eq!(builder.find_by_label("sum").next().unwrap().id, sum.id);
+    assert_eq!(
+        builder.outputs().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["sq", "scaled"]
+    );
+    assert_eq!(builder.nodes().map(|n| n.id).max(), Some(expected.id));
+    builder.fill_nodes(HashMap::from([(x.id, 2), (y.id, 1)]));
+    assert_eq!(builder.value(scaled.id), Some(27));
+    assert!(builder.check_constraints());
+
+    // A rollback can be repeated, and `commit` keeps the branch for good.
+    let cp = builder.checkpoint();
+    builder.prune();
+    builder.minimize_constraints();
+    builder.rollback(&cp);
+    assert_eq!(builder.fingerprint(), fingerprint);
+    let late = builder.add(&y, 1);
+    builder.replace_node(&sum, &late).unwrap();
+    builder.commit();
+    let rolled_back = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        builder.rollback(&cp);
+    }));
+    assert!(rolled_back.is_err());
+    assert!(!builder.is_live(&sum));
+}
+
+#[test]
+fn test_replace_node_rewires_dependents() {
+    // y = x*x + x, then swap x*x for a cheaper x + x built afterwards.
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let sq = builder.mul(&x, &x);
+    let y = builder.add(&sq, &x);
+    builder.assert_equal(&sq, &y);
+    builder.mark_output(&y, "y");
+    builder.fill_nodes(HashMap::from([(x.id, 3)]));
+    let double = builder.add(&x, &x);
+    assert!(double.id > y.id);
+
+    assert_eq!(
+        builder.replace_node(&x, &double),
+        Err(EditError::Cycle {
+            old: x.id,
+            new: double.id
+        })
+    );
+    builder.replace_node(&sq, &double).unwrap();
+    assert!(builder.node(sq.id).is_none());
+    assert_eq!(builder.value(y.id), None);
+    assert_eq!(
+        builder.constraints().collect::<Vec<_>>(),
+        vec![(double.id, y.id)]
+    );
+
+    builder.fill_nodes(HashMap::from([(x.id, 3)]));
+    assert_eq!(builder.value(y.id), Some(9));
+    let order: Vec<NodeId> = builder.nodes().map(|n| n.id).collect();
+    assert_eq!(order, vec![x.id, double.id, y.id]);
+
+    // The reordered graph survives a serialization round trip.
+    let decoded =
+        Builder::from_bytes_any_version(&builder.to_bytes(), &HintRegistry::new()).unwrap();
+    assert_eq!(decoded.fingerprint(), builder.fingerprint());
+}
+
+#[test]
+fn test_remove_subtree() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let shared = builder.mul(&x, &x);
+    let three = builder.constant(3);
+    let branch = builder.add(&shared, &three);
+    let keep = builder.add(&shared, &x);
+    builder.assert_equal(&branch, &keep);
+
+    assert!(matches!(
+        builder.remove_subtree(&shared),
+        Err(EditError::HasDependents { .. })
+    ));
+    let removed = builder.remove_subtree(&branch).unwrap();
+    assert_eq!(removed, vec![three.id, branch.id]);
+    assert_eq!(builder.constraints().count(), 0);
+    assert_eq!(builder.node_count(), 3);
+}
+
+#[test]
+fn test_substitute_pattern() {
+    // Pattern: v * 8 (operands in either order). Replacement: three doublings of v.
+    let mut pattern = Builder::new();
+    let v = pattern.init();
+    let eight = pattern.constant(8);
+    pattern.mul(&v, &eight);
+    let mut replacement = Builder::new();
+    let w = replacement.init();
+    let two = replacement.add(&w, &w);
+    let four = replacement.add(&two, &two);
+    replacement.add(&four, &four);
+
+    let mut builder = Builder::new();
+    let a = builder.init();
+    let b = builder.init();
+    let eight = builder.constant(8);
+    let a8 = builder.mul(&a, &eight);
+    let b8 = builder.mul(&eight, &b);
+    let ab = builder.mul(&a, &b);
+    let total = builder.add(&a8, &b8);
+    let out = builder.add(&total, &ab);
+    builder.mark_output(&out, "out");
+
+    assert_eq!(builder.substitute(&pattern, &replacement), Ok(2));
+    assert!(builder.node(a8.id).is_none() && builder.node(b8.id).is_none());
+    assert!(builder.node(eight.id).is_none());
+    assert_eq!(builder.nodes_with_op(OpKind::Mul).count(), 1);
+    builder.fill_nodes(HashMap::from([(a.id, 3), (b.id, 5)]));
+    assert_eq!(builder.value(out.id), Some(3 * 8 + 5 * 8 + 15));
+
+    let mut two_outputs = Builder::new();
+    let p = two_outputs.init();
+    two_outputs.add(&p, &p);
+    two_outputs.mul(&p, &p);
+    assert!(matches!(
+        builder.substitute(&two_outputs, &replacement),
+        Err(EditError::InvalidPattern(_))
+    ));
+}
+
+#[test]
+fn test_gadget_instantiation() {
+    // Checked division by 8: returns the quotient and constrains q * 8 == v
+    let div8 = Gadget::build("div8", &["v"], &["q"], |b, ins| {
+        let q = b.hint(&[ins[0]], |vals| vals[0] / 8);
+        let eight = b.constant(8);
+        let back = b.mul(&q, &eight);
+        b.assert_equal(&back, &ins[0]);
+        vec![q]
+    });
+    assert_eq!(div8.input_names(), vec!["v"]);
+
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let qx = builder.instantiate(&div8, &[x]).remove(0);
+    let qy = builder.instantiate(&div8, &[y]).remove(0);
+    let sum = builder.add(&qx, &qy);
+    assert_eq!(builder.scope_of(&qx), Some("div8"));
+    assert_eq!(builder.constraints.len(), 2);
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 16);
+    inputs.insert(y.id, 24);
+    builder.fill_nodes(inputs);
+    assert!(builder.check_constraints());
+    assert_eq!(builder.nodes[&sum.id].value, Some(5));
+}
+
+#[test]
+fn test_inline_gadget_instances() {
+    let double = Gadget::build("double", &["v"], &["d"], |b, ins| {
+        vec![b.add(&ins[0], &ins[0])]
+    });
+    let quad = Gadget::build("quad", &["v"], &["q"], |b, ins| {
+        let d = b.instantiate(&double, &ins[..1]).remove(0);
+        b.instantiate(&double, &[d])
+    });
+
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let q = builder.instantiate(&quad, &[x]).remove(0);
+    assert_eq!(builder.scope_of(&q), Some("quad/double"));
+    let gadgets: Vec<&str> = builder
+        .instances()
+        .iter()
+        .map(|i| i.gadget.as_str())
+        .collect();
+    assert_eq!(gadgets, vec!["double", "double", "quad"]);
+    assert_eq!(builder.instances()[2].inputs, vec![x.id]);
+    assert_eq!(builder.instances()[2].outputs, vec![q.id]);
+
+    let flat = builder.inline(InlineMode::Flatten);
+    assert!(flat.instances().is_empty());
+    assert_eq!(flat.scope_of(&q), None);
+    assert_eq!(flat.fingerprint(), builder.fingerprint());
+
+    let preserved = builder.inline(InlineMode::PreserveBoundaries);
+    assert_eq!(preserved.scope_of(&q), Some("quad[0]/double[1]"));
+    assert_eq!(preserved.instances()[0].scope, "quad[0]/double[0]");
+}
+
+#[test]
+fn test_import_gadget_file() {
+    let divmod = Gadget::build("divmod5", &["a"], &["q", "r"], |b, ins| {
+        let five = b.constant(5);
+        let (q, r) = gadgets::divmod(b, &ins[0], &five, 8);
+        vec![q, r]
+    });
+    let path = std::env::temp_dir().join(format!("{}-divmod5.graph.json", std::process::id()));
+    divmod.save(&path).unwrap();
+
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let offset = builder.constant(100);
+    let qr = builder.import(&path, &[x]).unwrap();
+    let shifted = builder.add(&qr[0], &offset);
+    let arity = builder.import(&path, &[x, x]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        arity,
+        Err(ImportError::Arity {
+            expected: 1,
+            found: 2
+        })
+    ));
+    assert_eq!(builder.scope_of(&qr[0]), Some("divmod5"));
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 23);
+    builder.fill_nodes(inputs);
+    assert!(builder.check_constraints());
+    assert_eq!(builder.nodes[&qr[1].id].value, Some(3));
+    assert_eq!(builder.nodes[&shifted.id].value, Some(104));
+
+    let anonymous = Gadget::build("anon", &["v"], &["h"], |b, ins| {
+        vec![b.hint(ins, |vals| vals[0])]
+    });
+    let err = Gadget::from_json(&anonymous.to_json(), &HintRegistry::new());
+    assert!(matches!(err, Err(ImportError::Format(_))));
+}
+
+#[test]
+fn test_poly_eval_template() {
+    let template = PolyEval { degree: 3 };
+    assert_eq!(template.input_names(), vec!["x", "c0", "c1", "c2", "c3"]);
+
+    let mut builder = Builder::new();
+    let x = builder.init();
+    // 2 + 3x + 0x^2 + x^3
+    let coefficients: Vec<Node> = [2, 3, 0, 1].iter().map(|c| builder.constant(*c)).collect();
+    let mut inputs = vec![x];
+    inputs.extend(coefficients);
+    let y = builder.expand(&template, &inputs).remove(0);
+    let gadget = template.to_gadget();
+    let y2 = builder.instantiate(&gadget, &inputs).remove(0);
+    let total = builder.expand(&Sum { width: 2 }, &[y, y2]).remove(0);
+
+    let mut values = HashMap::new();
+    values.insert(x.id, 4);
+    builder.fill_nodes(values);
+    assert_eq!(builder.nodes[&y.id].value, Some(78));
+    assert_eq!(builder.nodes[&y2.id].value, Some(78));
+    assert_eq!(builder.nodes[&total.id].value, Some(156));
+    assert_eq!(builder.scope_of(&y), Some("poly_eval_3"));
+}
+
+#[test]
+fn test_merge_graphs() {
+    // Team A: y = x * x
+    let mut a = Builder::new();
+    let x = a.init();
+    let y = a.mul(&x, &x);
+
+    // Team B: z = v + 1, asserted equal to w
+    let mut b = Builder::new();
+    let v = b.init();
+    let w = b.init();
+    b.enter_scope("plus_one");
+    let one = b.constant(1);
+    let z = b.add(&v, &one);
+    b.exit_scope();
+    b.assert_equal(&z, &w);
+
+    let map = a.merge(&b, &[(y.id, v.id)]).unwrap();
+    assert_eq!(map[&v.id].id, y.id);
+    assert_eq!(a.scope_of(&map[&z.id]), Some("plus_one"));
+    assert_eq!(a.constraints.len(), 1);
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 3);
+    inputs.insert(map[&w.id].id, 10);
+    a.fill_nodes(inputs);
+    assert!(a.check_constraints());
+
+    assert_eq!(
+        a.merge(&b, &[(y.id, z.id)]).err(),
+        Some(MergeError::NotAnInput(z.id))
+    );
+}
+
+#[test]
+fn test_hierarchical_namespaces() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    builder.set_label(&x, "x");
+    let out = builder.namespace("layer1", |b| {
+        b.namespace("adder", |b| {
+            let one = b.constant(1);
+            let out = b.add(&x, &one);
+            b.set_label(&out, "out");
+            out
+        })
+    });
+    assert_eq!(builder.path_of(&out), "layer1/adder/out");
+    assert_eq!(builder.path_of(&x), "x");
+    assert_eq!(builder.scope_of(&out), Some("layer1/adder"));
+    assert_eq!(builder.describe(out.id), "layer1/adder/out (NodeId(2))");
+
+    let mut dot = Vec::new();
+    builder.write_dot(&mut dot, &DotOptions::default()).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.contains("label=\"out\\nNode2: Add Node0 + Node1\""));
+}
+
+#[test]
+fn test_gadget_library() {
+    let mut builder = Builder::new();
+    let a = builder.init();
+    let d = builder.init();
+    let (q, r) = gadgets::divmod(&mut builder, &a, &d, 8);
+    let s = gadgets::sqrt(&mut builder, &a, 8);
+    let lt = gadgets::less_than(&mut builder, &a, &d, 8);
+    let gt = gadgets::less_than(&mut builder, &d, &a, 8);
+    let max = gadgets::select(&mut builder, &lt, &d, &a);
+    let diff = gadgets::abs_diff(&mut builder, &a, &d, 8);
+    let bits = gadgets::to_bits(&mut builder, &d, 4);
+
+    let mut inputs = HashMap::new();
+    inputs.insert(a.id, 23);
+    inputs.insert(d.id, 5);
+    builder.fill_nodes(inputs);
+    assert!(builder.check_constraints());
+    let value = |n: &Node| builder.nodes[&n.id].value.unwrap();
+    assert_eq!((value(&q), value(&r)), (4, 3));
+    assert_eq!(value(&s), 4);
+    assert_eq!((value(&lt), value(&gt)), (0, 1));
+    assert_eq!(value(&max), 23);
+    assert_eq!(value(&diff), 18);
+    assert_eq!(bits.iter().map(value).collect::<Vec<_>>(), vec![1, 0, 1, 0]);
+
+    // A dishonest prover answering 23 = 3 * 5 + 8 is caught by the r < d check.
+    let mut dishonest = Builder::new();
+    let a = dishonest.init();
+    let d = dishonest.init();
+    let (q, r) = gadgets::divmod(&mut dishonest, &a, &d, 8);
+    dishonest.nodes.get_mut(&q.id).unwrap().value = Some(3);
+    dishonest.nodes.get_mut(&r.id).unwrap().value = Some(8);
+    let mut inputs = HashMap::new();
+    inputs.insert(a.id, 23);
+    inputs.insert(d.id, 5);
+    dishonest.fill_nodes(inputs);
+    assert!(!dishonest.check_constraints());
+}
+
+#[test]
+fn test_repeat_unrolls_iterations() {
+    let mut builder = Builder::new();
+    let zero = builder.constant(0);
+    let one = builder.constant(1);
+    let fib = builder.repeat(10, vec![zero, one], |b, s, _| {
+        vec![s[1], b.add(&s[0], &s[1])]
+    });
+
+    // Running sum of x * i for i in 0..4
+    let x = builder.init();
+    let start = builder.constant(0);
+    let sum = builder.repeat(4, vec![start], |b, s, i| {
+        let k = b.constant(i as u32);
+        let term = b.mul(&x, &k);
+        vec![b.add(&s[0], &term)]
+    });
+
+    let mut inputs = HashMap::new();
+    inputs.insert(x.id, 5);
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.nodes[&fib[0].id].value, Some(55));
+    assert_eq!(builder.nodes[&sum[0].id].value, Some(30));
+}
+
+#[test]
+fn test_cli_eval_check_and_export() {
+    let square = Gadget::build("square_plus_one", &["x"], &["y"], |b, ins| {
+        let sq = b.mul(&ins[0], &ins[0]);
+        let one = b.constant(1);
+        vec![b.add(&sq, &one)]
+    });
+    let dir = std::env::temp_dir();
+    let path = |name: &str| {
+        let path = dir.join(format!("{}-cli.{}.json", std::process::id(), name));
+        path.display().to_string()
+    };
+    let (graph, inputs, witness) = (path("graph"), path("inputs"), path("witness"));
+    square.save(&graph).unwrap();
+    std::fs::write(&inputs, r#"{"x": 4}"#).unwrap();
+    let run = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let mut out = Vec::new();
+        cli::run(&args, &mut out).map(|ok| (ok, String::from_utf8(out).unwrap()))
+    };
+
+    let (ok, out) = run(&["eval", &graph, "--inputs", &inputs, "--witness", &witness]).unwrap();
+    assert!(ok);
+    assert_eq!(out, "{\n  \"y\": 17\n}\n");
+
+    let (ok, out) = run(&["check", &graph, &witness]).unwrap();
+    assert!(ok);
+    assert_eq!(out, "ok: 0 constraints hold\n");
+
+    let (_, out) = run(&["export", &graph, "--format", "mermaid"]).unwrap();
+    assert!(out.starts_with("flowchart TD\n"));
+    let (_, out) = run(&["export", &graph, "--format", "r1cs"]).unwrap();
+    assert!(out.contains("\"n_wires\": 3"));
+    assert!(matches!(
+        run(&["export", &graph, "--format", "svg"]),
+        Err(cli::CliError::Usage(_))
+    ));
+    for file in [graph, inputs, witness] {
+        std::fs::remove_file(file).unwrap();
+    }
+}
+
+#[test]
+fn test_r1cs_export() {
+    // x * x == y + 3, with y = x * 2
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let two = builder.constant(2);
+    let y = builder.mul(&x, &two);
+    let three = builder.constant(3);
+    let rhs = builder.add(&y, &three);
+    let sq = builder.mul(&x, &x);
+    builder.assert_equal(&sq, &rhs);
+    let r1cs = json::Json::parse(&builder.to_r1cs()).unwrap();
+    assert_eq!(r1cs.get("n_wires"), Some(&json::Json::Int(4)));
+    let expected = json::Json::parse(
+        r#"[
+            [{"1": 1}, {"0": 2}, {"2": 1}],
+            [{"1": 1}, {"1": 1}, {"3": 1}],
+            [{"0": 4294967293, "2": 4294967295, "3": 1}, {"0": 1}, {}]
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(r1cs.get("constraints"), Some(&expected));
+}
+
+#[test]
+fn test_graph_service_session() {
+    // y = a * b, constrained to equal c
+    let product = Gadget::build("product", &["a", "b", "c"], &["y"], |b, ins| {
+        let y = b.mul(&ins[0], &ins[1]);
+        b.assert_equal(&y, &ins[2]);
+        vec![y]
+    });
+    let service = service::GraphService::new();
+    let handle = service.upload(&product.to_json()).unwrap();
+    assert_eq!(service.input_names(handle).unwrap(), ["a", "b", "c"]);
+
+    service
+        .submit_inputs(handle, &[("a", 6), ("b", 7)])
+        .unwrap();
+    assert!(matches!(
+        service.evaluate(handle),
+        Err(service::ServiceError::MissingInput(name)) if name == "c"
+    ));
+    assert!(matches!(
+        service.submit_inputs(handle, &[("d", 1)]),
+        Err(service::ServiceError::UnknownInput(_))
+    ));
+    service.submit_inputs(handle, &[("c", 41)]).unwrap();
+    assert_eq!(
+        service.evaluate(handle).unwrap(),
+        vec![("y".to_string(), Some(42))]
+    );
+    let report = service.constraint_report(handle).unwrap();
+    assert_eq!(report.total, 1);
+    assert_eq!(report.failed[0].left_value, Some(42));
+    assert_eq!(report.failed[0].right_value, Some(41));
+
+    service
+        .submit_inputs(handle, &[("b", 8), ("c", 48)])
+        .unwrap();
+    assert_eq!(
+        service.evaluate(handle).unwrap(),
+        vec![("y".to_string(), Some(48))]
+    );
+    assert!(service.constraint_report(handle).unwrap().holds());
+
+    let cancel = std::sync::atomic::AtomicBool::new(false);
+    service
+        .submit_inputs(handle, &[("b", 9), ("c", 54)])
+        .unwrap();
+    assert_eq!(
+        service.evaluate_cancellable(handle, &cancel).unwrap(),
+        vec![("y".to_string(), Some(54))]
+    );
+    cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    service.submit_inputs(handle, &[("b", 10)]).unwrap();
+    assert!(matches!(
+        service.evaluate_cancellable(handle, &cancel),
+        Err(service::ServiceError::Cancelled)
+    ));
+
+    // The service is shared between threads as is.
+    std::thread::scope(|scope| {
+        let service = &service;
+        let b = scope.spawn(move || service.upload(&product.to_json()).unwrap());
+        service
+            .submit_inputs(handle, &[("b", 7), ("c", 42)])
+            .unwrap();
+        assert_ne!(b.join().unwrap(), handle);
+    });
+    service.remove(handle).unwrap();
+    assert!(service.evaluate(handle).is_err());
+}
+
+#[test]
+fn test_graph_service_rejects_hint_arity_mismatch() {
+    // `gadgets::divmod` reads a dividend, a divisor and an output index.
+    let short = Gadget::build("short", &["a"], &["q"], |b, ins| {
+        vec![b.hint_named("gadgets::divmod", &[ins[0]], |v| v[0])]
+    });
+    let service = service::GraphService::new();
+    match service.upload(&short.to_json()) {
+        Err(service::ServiceError::Graph(e)) => {
+            assert!(e
+                .to_string()
+                .contains("`gadgets::divmod` with 1 operands, but it takes 3"))
+        }
+        other => panic!("expected a graph error, got {:?}", other),
+    }
+
+    // A zero modulus gives no inverse instead of dividing by zero.
+    let inverse = Gadget::build("inverse", &["z", "p"], &["inv"], |b, ins| {
+        vec![b.hint_named("gadgets::field_inv", &[ins[0], ins[1]], |v| v[0])]
+    });
+    let handle = service.upload(&inverse.to_json()).unwrap();
+    service
+        .submit_inputs(handle, &[("z", 3), ("p", 0)])
+        .unwrap();
+    assert_eq!(
+        service.evaluate(handle).unwrap(),
+        vec![("inv".to_string(), Some(0))]
+    );
+    service.submit_inputs(handle, &[("p", 7)]).unwrap();
+    assert_eq!(
+        service.evaluate(handle).unwrap(),
+        vec![("inv".to_string(), Some(5))]
+    );
+}
+
+#[test]
+fn test_random_graphs_evaluate_and_round_trip() {
+    use crate::testing::{random_graph, RandomGraphConfig};
+
+    let mut hints = HintRegistry::new();
+    testing::register_hints(&mut hints);
+    for seed in 0..25 {
+        let config = RandomGraphConfig {
+            nodes: 40,
+            constraint_density: 0.3,
+            seed,
+            ..RandomGraphConfig::default()
+        };
+        let case = random_graph(&config);
+        assert_eq!(
+            case.builder.node_count(),
+            random_graph(&config).builder.node_count()
+        );
+
+        let mut builder = case.builder.clone();
+        builder.fill_nodes(case.inputs.clone());
+        assert!(builder.check_constraints(), "seed {}", seed);
+        assert!(builder.nodes().all(|n| n.value.is_some()), "seed {}", seed);
+
+        let (parsed, _) = Builder::from_sexpr(&case.builder.to_sexpr(), &hints).unwrap();
+        assert_eq!(
+            parsed.fingerprint(),
+            case.builder.fingerprint(),
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn test_check_property_shrinks_failures() {
+    use crate::testing::{check_property, OpMix, RandomGraphConfig};
+
+    let config = RandomGraphConfig {
+        nodes: 30,
+        ..RandomGraphConfig::default()
+    };
+    // Pruning to the declared output must not change its value.
+    let pruned_agrees = check_property(&config, 20, |case| {
+        let mut original = case.builder.clone();
+        let Some(last) = original.nodes().map(|n| n.id).max() else {
+            return true;
+        };
+        let last = original.handle(last).unwrap();
+        original.mark_output(&last, "out");
+        let mut pruned = original.clone();
+        pruned.prune();
+        original.evaluate_outputs(case.inputs.clone())
+            == pruned.evaluate_outputs(case.inputs.clone())
+    });
+    assert!(pruned_agrees.is_ok());
+
+    // A false property fails on the smallest graph with a multiplication.
+    let only_mul = RandomGraphConfig {
+        op_mix: OpMix {
+            constant: 0,
+            add: 1,
+            mul: 1,
+            hint: 0,
+        },
+        ..config
+    };
+    let failure = check_property(&only_mul, 20, |case| {
+        case.builder.nodes_with_op(OpKind::Mul).next().is_none()
+    })
+    .err()
+    .unwrap();
+    assert_eq!(failure.case.builder.nodes_with_op(OpKind::Mul).count(), 1);
+    assert!(failure.config.nodes < 30);
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_graphs {
+    use crate::testing::{RandomGraph, RandomGraphConfig};
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn pruning_keeps_values(case: RandomGraph) {
+            let mut pruned = case.builder.clone();
+            pruned.prune();
+            prop_assert_eq!(
+                pruned.evaluate_outputs(case.inputs.clone()),
+                case.builder.clone().evaluate_outputs(case.inputs.clone())
+            );
+        }
+
+        #[test]
+        fn generated_inputs_satisfy_constraints(
+            case in any_with::<RandomGraph>(RandomGraphConfig {
+                inputs: 5,
+                nodes: 60,
+                constraint_density: 0.5,
+                ..RandomGraphConfig::default()
+            })
+        ) {
+            prop_assert!(case.builder.input_ids().len() <= 5);
+            let mut graph = case.builder.clone();
+            graph.fill_nodes(case.inputs.clone());
+            prop_assert!(graph.check_constraints(), "{:?}", case);
+        }
+    }
+}
+
+#[test]
+fn test_find_violation() {
+    // q = x / 3 as a hint, constrained only by q * 3 == x: wrong whenever 3 ∤ x.
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let three = builder.constant(3);
+    let q = builder.hint(&[x, three], |v| v[0] / v[1]);
+    let back = builder.mul(&q, &three);
+    builder.assert_equal(&back, &x);
+    let domains = HashMap::from([(x.id, 0..=10)]);
+    let violation = builder.find_violation(&domains).unwrap();
+    assert_eq!(violation.inputs[&x.id], 1);
+    assert_eq!(violation.failed, vec![(back.id, x.id)]);
+    let multiples = HashMap::from([(x.id, 9..=9)]);
+    assert_eq!(builder.find_violation(&multiples), None);
+
+    // divmod is sound on small operands.
+    let mut sound = Builder::new();
+    let a = sound.init();
+    let d = sound.init();
+    gadgets::divmod(&mut sound, &a, &d, 8);
+    let small = HashMap::from([(a.id, 0..=40), (d.id, 1..=40)]);
+    assert_eq!(sound.find_violation(&small), None);
+
+    // A saturating hint disagrees with wrapping addition only at u32::MAX,
+    // which the boundary samples reach without an exhaustive search.
+    let mut edge = Builder::new();
+    let x = edge.init();
+    let one = edge.constant(1);
+    let wrapped = edge.add(&x, &one);
+    let saturated = edge.hint(&[x], |v| v[0].saturating_add(1));
+    edge.assert_equal(&wrapped, &saturated);
+    let violation = edge.find_violation(&HashMap::new()).unwrap();
+    assert_eq!(violation.inputs[&x.id], u32::MAX);
+}
+
+#[test]
+fn test_verify_exhaustive() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let product = builder.mul(&x, &y);
+    let claimed = builder.hint(&[x, y], |v| v[0] * v[1]);
+    builder.assert_equal(&product, &claimed);
+    assert_eq!(
+        builder.verify_exhaustive(),
+        Exhaustion::Unbounded(vec![x.id, y.id])
+    );
+    builder.declare_range(&x, 0..=20);
+    builder.declare_range(&y, 5..=30);
+    assert_eq!(
+        builder.verify_exhaustive(),
+        Exhaustion::Verified {
+            assignments: 21 * 26
+        }
+    );
+
+    // The same graph with a hint that is wrong on two assignments.
+    let mut faulty = Builder::new();
+    let x = faulty.init();
+    let y = faulty.init();
+    let product = faulty.mul(&x, &y);
+    let claimed = faulty.hint(&[x, y], |v| match (v[0], v[1]) {
+        (7, 13) | (2, 29) => 0,
+        (a, b) => a * b,
+    });
+    faulty.assert_equal(&product, &claimed);
+    faulty.declare_range(&x, 0..=20);
+    faulty.declare_range(&y, 5..=30);
+    for threads in [1, 3, 8] {
+        let Exhaustion::Failed(violation) = faulty.verify_exhaustive_with(threads) else {
+            panic!("no violation found on {} threads", threads);
+        };
+        assert_eq!((violation.inputs[&x.id], violation.inputs[&y.id]), (7, 13));
+    }
+}
+
+#[test]
+fn test_lint_underconstrained_nodes() {
+    let mut builder = Builder::new();
+    let x = builder.input("x");
+    let unused = builder.input("unused");
+    let three = builder.constant(3);
+    let q = builder.hint_named("div3", &[x, three], |v| v[0] / v[1]);
+    let r = builder.hint_named("rem3", &[x, three], |v| v[0] % v[1]);
+    let qd = builder.mul(&q, &three);
+    let _ = builder.add(&qd, &unused);
+
+    let lints = builder.lint();
+    let kinds: Vec<_> = lints.iter().map(|l| (l.kind, l.node)).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (LintKind::UnconstrainedInput, x.id),
+            (LintKind::UnconstrainedInput, unused.id),
+            (LintKind::UnconstrainedHint, q.id),
+            (LintKind::UnconstrainedHint, r.id),
+        ]
+    );
+    let message = lints[0].to_string();
+    assert!(message.starts_with(
+        "x (NodeId(0)): input does not affect any constraint (created at src/tests.rs:"
+    ));
+
+    let recomposed = builder.add(&qd, &r);
+    builder.assert_equal(&recomposed, &x);
+    let remaining: Vec<_> = builder.lint().iter().map(|l| l.node).collect();
+    assert_eq!(remaining, vec![unused.id]);
+}
+
+#[test]
+fn test_topological_eval_order_is_reproducible() {
+    use std::sync::Mutex;
+    static CALLS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+    fn record(v: &[u32]) -> u32 {
+        CALLS.lock().unwrap().push(v[0]);
+        v[0] * 2
+    }
+
+    let mut builder = Builder::new();
+    assert_eq!(builder.eval_order(), EvalOrder::Worklist);
+    builder.set_eval_order(EvalOrder::Topological);
+    let x = builder.init();
+    let mut hints = vec![];
+    for i in 0..20 {
+        let k = builder.constant(i);
+        let sum = builder.add(&x, &k);
+        hints.push(builder.hint(&[sum], record));
+    }
+    let chained = builder.hint(&[hints[3]], record);
+
+    let mut worklist = builder.clone();
+    worklist.set_eval_order(EvalOrder::Worklist);
+    worklist.fill_nodes(HashMap::from([(x.id, 100)]));
+    CALLS.lock().unwrap().clear();
+
+    builder.fill_nodes(HashMap::from([(x.id, 100)]));
+    let expected: Vec<u32> = (100..120).chain([206]).collect();
+    assert_eq!(*CALLS.lock().unwrap(), expected);
+    assert_eq!(builder.value(chained.id), Some(412));
+    for node in builder.nodes() {
+        assert_eq!(node.value, worklist.value(node.id));
+    }
+}
+
+#[test]
+fn test_fill_nodes_summary() {
+    for order in [EvalOrder::Worklist, EvalOrder::Topological] {
+        let mut builder = Builder::new();
+        builder.set_eval_order(order);
+        let x = builder.init();
+        let y = builder.init();
+        let two = builder.constant(2);
+        let doubled = builder.mul(&x, &two);
+        let half = builder.hint(&[doubled], |v| v[0] / 2);
+        let blocked = builder.add(&half, &y);
+
+        let summary = builder.fill_nodes(HashMap::from([(x.id, 5)]));
+        assert_eq!(summary.evaluated, 3, "{:?}", order);
+        assert_eq!(summary.hint_calls, 1);
+        assert_eq!(summary.skipped_already_set, 0);
+        assert_eq!(summary.unresolved, vec![y.id, blocked.id]);
+        assert!(!summary.is_complete());
+
+        let summary = builder.fill_nodes(HashMap::from([(y.id, 1)]));
+        assert_eq!(
+            summary,
+            EvalSummary {
+                evaluated: 1,
+                skipped_already_set: 3,
+                unresolved: vec![],
+                hint_calls: 0,
+            }
+        );
+        assert_eq!(builder.value(blocked.id), Some(6));
+    }
+}
+
+#[test]
+fn test_try_fill_nodes_validates_inputs() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let sum = builder.add(&x, &y);
+
+    assert_eq!(
+        builder.try_fill_nodes(HashMap::from([(x.id, 1), (NodeId(99), 2)])),
+        Err(InputError::UnknownNode(NodeId(99)))
+    );
+    assert_eq!(
+        builder.try_fill_nodes(HashMap::from([(x.id, 1), (sum.id, 2)])),
+        Err(InputError::NotAnInput(sum.id))
+    );
+    assert_eq!(
+        builder.try_fill_nodes(HashMap::from([(x.id, 1)])),
+        Err(InputError::MissingInputs(vec![y.id]))
+    );
+    assert_eq!(builder.value(x.id), None, "nothing is set on error");
+
+    let summary = builder
+        .try_fill_nodes(HashMap::from([(x.id, 1), (y.id, 2)]))
+        .unwrap();
+    assert!(summary.is_complete());
+    assert_eq!(builder.value(sum.id), Some(3));
+    // Inputs set by an earlier call are not missing.
+    assert!(builder.validate_inputs(&HashMap::new()).is_ok());
+}
+
+#[test]
+fn test_changed_inputs_reevaluate_downstream() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let xx = builder.mul(&x, &x);
+    let sum = builder.add(&xx, &y);
+    builder.fill_nodes(HashMap::from([(x.id, 3), (y.id, 1)]));
+    assert_eq!(builder.value(sum.id), Some(10));
+
+    // Repeating a value is a no-op; changing one recomputes its cone only.
+    let summary = builder.fill_nodes(HashMap::from([(x.id, 3), (y.id, 5)]));
+    assert_eq!((summary.evaluated, summary.skipped_already_set), (1, 1));
+    assert_eq!(builder.value(sum.id), Some(14));
+    builder.fill_nodes(HashMap::from([(x.id, 4)]));
+    assert_eq!(builder.values_of(&[xx, sum]), vec![Some(16), Some(21)]);
+
+    assert_eq!(
+        builder.try_fill_nodes(HashMap::from([(x.id, 4), (y.id, 6)])),
+        Err(InputError::ConflictingInput {
+            node: y.id,
+            previous: 5,
+            new: 6
+        })
+    );
+    assert_eq!(builder.value(sum.id), Some(21));
+}
+
+#[test]
+fn test_vector_nodes() {
+    let mut builder = Builder::new();
+    let features = builder.input_vector(5);
+    let weights = builder.constant_vector(&[1, 2, 3, 4, 5]);
+    let bias = builder.constant_vector(&[10, 10, 10, 10, 10]);
+    let scaled = builder.vmul(&features, &weights);
+    let shifted = builder.vadd(&scaled, &bias);
+    let total = builder.reduce_sum(&shifted);
+    let score = builder.dot(&features, &weights);
+    let expected = builder.constant(35);
+    builder.assert_equal(&score, &expected);
+
+    let mut inputs = HashMap::new();
+    inputs.extend(features.assign(&[5, 4, 3, 2, 1]));
+    assert!(builder.fill_nodes(inputs).is_complete());
+    assert_eq!(
+        builder.vector_value(&shifted),
+        Some(vec![15, 18, 19, 18, 15])
+    );
+    assert_eq!(builder.value(total.id), Some(85));
+    assert!(builder.check_constraints());
+    // mul and add per lane, then 3 levels of pairwise additions for 5 lanes.
+    assert_eq!(builder.critical_path(&total).depth, 5);
+}
+
+#[test]
+fn test_matmul() {
+    // A linear layer: y = W x + b with W 2x3, x 3x1.
+    let mut builder = Builder::new();
+    let w = builder.constant_vector(&[1, 2, 3, 4, 5, 6]);
+    let x = builder.input_vector(3);
+    let b = builder.constant_vector(&[100, 200]);
+    let wx = builder.matmul(&w, &x, (2, 3, 1));
+    let y = builder.vadd(&wx, &b);
+
+    // A 2x2 product with itself: [[1, 2], [3, 4]]^2 = [[7, 10], [15, 22]].
+    let square = builder.constant_vector(&[1, 2, 3, 4]);
+    let squared = builder.matmul(&square, &square, (2, 2, 2));
+
+    let mut inputs = HashMap::new();
+    inputs.extend(x.assign(&[1, 0, 2]));
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.vector_value(&y), Some(vec![107, 216]));
+    assert_eq!(builder.vector_value(&squared), Some(vec![7, 10, 15, 22]));
+}
+
+#[test]
+fn test_vmap_round_trips() {
+    fn relu(v: &[u32]) -> u32 {
+        // Values above 2^31 are negative in two's complement.
+        if v[0] > i32::MAX as u32 {
+            0
+        } else {
+            v[0]
+        }
+    }
+    let mut builder = Builder::new();
+    let x = builder.input_vector(4);
+    let activated = builder.vmap(&x, "relu", relu);
+    let total = builder.reduce_sum(&activated);
+    builder.mark_output(&total, "total");
+
+    let mut hints = HintRegistry::new();
+    hints.register("relu", relu);
+    let (mut parsed, _) = Builder::from_sexpr(&builder.to_sexpr(), &hints).unwrap();
+    let values = [3, u32::MAX, 7, 0u32.wrapping_sub(5)];
+    let mut inputs = HashMap::new();
+    inputs.extend(x.assign(&values));
+    builder.fill_nodes(inputs.clone());
+    parsed.fill_nodes(inputs);
+    assert_eq!(builder.vector_value(&activated), Some(vec![3, 0, 7, 0]));
+    assert_eq!(parsed.value(total.id), Some(10));
+}
+
+#[test]
+fn test_complex_nodes() {
+    // z^2 + conj(z) for z = 3 + 2i is (5 + 12i) + (3 - 2i) = 8 + 10i.
+    let mut builder = Builder::new();
+    let z = builder.complex_input();
+    let squared = builder.complex_mul(&z, &z);
+    let conj = builder.complex_conj(&z);
+    let result = builder.complex_add(&squared, &conj);
+    let expected = builder.complex_constant(8, 10);
+    builder.assert_complex_equal(&result, &expected);
+    // i * i = -1
+    let i = builder.complex_constant(0, 1);
+    let minus_one = builder.complex_mul(&i, &i);
+
+    builder.fill_nodes(HashMap::from([(z.re.id, 3), (z.im.id, 2)]));
+    assert_eq!(builder.complex_value(&result), Some((8, 10)));
+    assert_eq!(builder.complex_value(&minus_one), Some((u32::MAX, 0)));
+    assert!(builder.check_constraints());
+
+    // A wrong imaginary part alone violates the constraints.
+    builder.fill_nodes(HashMap::from([(z.im.id, 1)]));
+    assert_eq!(builder.complex_value(&result), Some((11, 5)));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_wide_arithmetic() {
+    let mut builder = Builder::new();
+    let a = builder.wide_input(Width::U64);
+    let b = builder.wide_input(Width::U64);
+    let sum = builder.wide_add(&a, &b);
+    let product = builder.wide_mul(&a, &b);
+    let x = builder.wide_input(Width::U128);
+    let y = builder.wide_constant(Width::U128, u128::MAX - 5);
+    let wrapped = builder.wide_add(&x, &y);
+    let squared = builder.wide_mul(&x, &x);
+
+    let (va, vb) = (0xdead_beef_1234_5678u64, 0x0fed_cba9_8765_4321u64);
+    let vx = 0x1234_5678_9abc_def0_1122_3344_5566_7788u128;
+    let mut inputs = HashMap::new();
+    inputs.extend(a.assign(va.into()));
+    inputs.extend(b.assign(vb.into()));
+    inputs.extend(x.assign(vx));
+    builder.fill_nodes(inputs);
+    assert_eq!(builder.wide_value(&sum), Some(va.wrapping_add(vb).into()));
+    assert_eq!(
+        builder.wide_value(&product),
+        Some(va.wrapping_mul(vb).into())
+    );
+    assert_eq!(builder.wide_value(&wrapped), Some(vx.wrapping_sub(6)));
+    assert_eq!(builder.wide_value(&squared), Some(vx.wrapping_mul(vx)));
+    assert!(builder.check_constraints());
+
+    // A limb outside 16 bits is rejected by its range check.
+    builder.fill_nodes(HashMap::from([(a.limbs()[0].id, 1 << 16)]));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_modulus_arithmetic() {
+    let mut builder = Builder::with_modulus(7);
+    assert_eq!(builder.modulus(), Some(7));
+    let x = builder.init();
+    let sq = builder.mul(&x, &x);
+    let three = builder.constant(3);
+    let y = builder.add(&sq, &three);
+    let big = builder.constant(u32::MAX);
+    let no_overflow = builder.mul(&big, &big);
+    let expected = builder.constant(4);
+    builder.assert_equal(&y, &expected);
+
+    // 6 * 6 + 3 = 39 = 4 (mod 7); (2^32 - 1)^2 mod 7 = 3^2 mod 7 = 2.
+    builder.fill_nodes(HashMap::from([(x.id, 6)]));
+    assert_eq!(builder.value(y.id), Some(4));
+    assert_eq!(builder.value(no_overflow.id), Some(2));
+    assert!(builder.check_constraints());
+    assert_eq!(Builder::new().modulus(), None);
+    assert_eq!(Modulus::new(0), None);
+}
+
+#[test]
+fn test_barrett_reduction_matches_remainder() {
+    let samples = [0u64, 1, 2, 0xffff_ffff, 1 << 32, u64::MAX - 1, u64::MAX];
+    for m in [1u32, 2, 3, 7, 65_537, 4_294_967_291, u32::MAX] {
+        let modulus = Modulus::new(m).unwrap();
+        let mut rng = 0x2545_f491_4f6c_dd1du64;
+        let random = core::iter::from_fn(|| {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            Some(rng)
+        });
+        for x in samples.into_iter().chain(random.take(1000)) {
+            assert_eq!(
+                u64::from(modulus.reduce(x)),
+                x % u64::from(m),
+                "{} mod {}",
+                x,
+                m
+            );
+        }
+        let (a, b) = (u32::MAX, u32::MAX - 1);
+        let product = u64::from(a) * u64::from(b) % u64::from(m);
+        assert_eq!(u64::from(modulus.mul(a, b)), product);
+    }
+}
+
+#[test]
+fn test_sha256_gadget() {
+    use crate::gadgets::sha256;
+
+    let vectors: [(&[u8], [u32; 8]); 2] = [
+        (
+            b"abc",
+            [
+                0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+                0xf20015ad,
+            ],
+        ),
+        // 56 bytes: the length no longer fits in the first block.
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            [
+                0x248d6a61, 0xd20638b8, 0xe5c02693, 0x0c3e6039, 0xa33ce459, 0x64ff2167, 0xf6ecedd4,
+                0x19db06c1,
+            ],
+        ),
+    ];
+    for (text, expected) in vectors {
+        let mut builder = Builder::new();
+        builder.set_eval_order(EvalOrder::Topological);
+        let message: Vec<Node> = text.iter().map(|_| builder.init()).collect();
+        let digest = sha256::hash(&mut builder, &message);
+        let inputs = message
+            .iter()
+            .map(|n| n.id)
+            .zip(text.iter().map(|&c| c.into()));
+        builder.fill_nodes(inputs.collect());
+        assert_eq!(builder.values_of(&digest), expected.map(Some));
+        assert!(builder.check_constraints());
+    }
+
+    // 2 = 0b10, so the bits XOR to 1.
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let bits = gadgets::to_bits(&mut builder, &x, 2);
+    let y = gadgets::bit_xor(&mut builder, &bits[0], &bits[1]);
+    let not_y = gadgets::bit_not(&mut builder, &y);
+    builder.fill_nodes(HashMap::from([(x.id, 2)]));
+    assert_eq!(builder.values_of(&[y, not_y]), [Some(1), Some(0)]);
+}
+
+#[test]
+fn test_merkle_verify() {
+    fn hash(l: u32, r: u32) -> u32 {
+        let x = l.wrapping_mul(31).wrapping_add(r).wrapping_add(7);
+        x.wrapping_mul(x).wrapping_add(l)
+    }
+    fn hasher(b: &mut Builder, l: &Node, r: &Node) -> Node {
+        let k = b.constant(31);
+        let seven = b.constant(7);
+        let lk = b.mul(l, &k);
+        let x = b.add(&lk, r);
+        let x = b.add(&x, &seven);
+        let sq = b.mul(&x, &x);
+        b.add(&sq, l)
+    }
+    let leaves = [10, 20, 30, 40];
+    let (h01, h23) = (hash(leaves[0], leaves[1]), hash(leaves[2], leaves[3]));
+    let root_value = hash(h01, h23);
+
+    let mut builder = Builder::new();
+    let leaf = builder.init();
+    let path: Vec<(Node, Node)> = (0..2).map(|_| (builder.init(), builder.init())).collect();
+    let root = builder.init();
+    let computed = gadgets::merkle_verify(&mut builder, &leaf, &path, &root, hasher);
+
+    // Leaf 2 is a left child, and its parent is a right child.
+    let mut inputs = HashMap::from([
+        (leaf.id, leaves[2]),
+        (path[0].0.id, leaves[3]),
+        (path[0].1.id, 0),
+        (path[1].0.id, h01),
+        (path[1].1.id, 1),
+        (root.id, root_value),
+    ]);
+    builder.fill_nodes(inputs.clone());
+    assert_eq!(builder.value(computed.id), Some(root_value));
+    assert!(builder.check_constraints());
+
+    // A wrong direction or sibling no longer reaches the root.
+    inputs.insert(path[1].1.id, 0);
+    builder.fill_nodes(inputs.clone());
+    assert!(!builder.check_constraints());
+    inputs.insert(path[1].1.id, 1);
+    inputs.insert(path[0].0.id, leaves[1]);
+    builder.fill_nodes(inputs);
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_elliptic_curve_gadgets() {
+    use crate::gadgets::ec::{self, Curve};
+
+    // y^2 = x^3 + 3x + 53 over F_65521 has prime order 65029.
+    let curve = Curve { a: 3, b: 53 };
+    let mut builder = Builder::with_modulus(65521);
+    builder.set_eval_order(EvalOrder::Topological);
+    let (gx, gy) = (builder.init(), builder.init());
+    ec::assert_on_curve(&mut builder, &curve, &gx, &gy);
+    let g = ec::point(&mut builder, &gx, &gy);
+    let k = builder.init();
+    let kg = ec::scalar_mul(&mut builder, &curve, &k, 15, 4, &g);
+    let (x, y) = ec::to_affine(&mut builder, &kg);
+
+    // Doubling and adding the inverse need no special cases.
+    let two_g = ec::double(&mut builder, &curve, &g);
+    let three_g = ec::add(&mut builder, &curve, &two_g, &g);
+    let minus_one = builder.constant(65520);
+    let neg_gy = builder.mul(&gy, &minus_one);
+    let neg_g = ec::point(&mut builder, &gx, &neg_gy);
+    let zero = ec::add(&mut builder, &curve, &g, &neg_g);
+    let identity = ec::identity(&mut builder);
+    ec::assert_point_equal(&mut builder, &zero, &identity);
+
+    builder.fill_nodes(HashMap::from([(gx.id, 1), (gy.id, 8223), (k.id, 12345)]));
+    assert_eq!(builder.values_of(&[x, y]), [Some(59610), Some(62258)]);
+    let (x3, y3) = ec::to_affine(&mut builder, &three_g);
+    builder.fill_nodes(HashMap::new());
+    assert_eq!(builder.values_of(&[x3, y3]), [Some(19169), Some(52158)]);
+    assert!(builder.check_constraints());
+
+    // A point off the curve is rejected.
+    builder.fill_nodes(HashMap::from([(gy.id, 8224)]));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_clamp_and_saturating_ops() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    let clamped = gadgets::clamp(&mut builder, &x, 10, 100, 8);
+    let sum = gadgets::saturating_add(&mut builder, &x, &y, 8);
+    let product = gadgets::saturating_mul(&mut builder, &x, &y, 8);
+
+    for (vx, vy) in [(5, 3), (50, 4), (200, 100), (255, 255), (0, 0), (100, 2)] {
+        builder.fill_nodes(HashMap::from([(x.id, vx), (y.id, vy)]));
+        assert_eq!(builder.value(clamped.id), Some(vx.clamp(10, 100)));
+        assert_eq!(
+            builder.value(sum.id),
+            Some((vx as u8).saturating_add(vy as u8).into())
+        );
+        assert_eq!(
+            builder.value(product.id),
+            Some((vx as u8).saturating_mul(vy as u8).into())
+        );
+        assert!(builder.check_constraints());
+    }
+
+    // Operands outside the declared width are rejected.
+    builder.fill_nodes(HashMap::from([(x.id, 256)]));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_per_node_arithmetic_override() {
+    let mut builder = Builder::new();
+    builder.set_arithmetic(Arithmetic::Checked);
+    let x = builder.init();
+    let big = builder.constant(u32::MAX);
+    let checked = builder.add(&x, &big);
+    let wrapped = builder.add_with(&x, &big, Arithmetic::Wrapping);
+    let saturated = builder.mul_with(&x, &big, Arithmetic::Saturating);
+    let modular = builder.mul_with(&x, &big, Arithmetic::Modular(Modulus::new(10).unwrap()));
+    assert_eq!(builder.arithmetic_of(&checked), None);
+    assert_eq!(builder.arithmetic_of(&wrapped), Some(Arithmetic::Wrapping));
+
+    let summary = builder.fill_nodes(HashMap::from([(x.id, 2)]));
+    assert_eq!(summary.unresolved, vec![checked.id]);
+    assert_eq!(
+        builder.values_of(&[checked, wrapped, saturated]),
+        [None, Some(1), Some(u32::MAX)]
+    );
+    assert_eq!(builder.value(modular.id), Some(0));
+
+    // The overrides survive every format.
+    let hints = HintRegistry::new();
+    let decoded = Builder::from_bytes_any_version(&builder.to_bytes(), &hints).unwrap();
+    assert_eq!(decoded.arithmetic(), Arithmetic::Checked);
+    assert_eq!(decoded.to_bytes(), builder.to_bytes());
+    let text = builder.to_sexpr();
+    assert!(text.contains("(add x0 n1 wrapping)"));
+    assert!(text.contains("(mul x0 n1 mod:10)"));
+    let (parsed, _) = Builder::from_sexpr(&text, &hints).unwrap();
+    assert_eq!(parsed.fingerprint(), builder.fingerprint());
+    let gadget = Gadget::build("overrides", &["x"], &["y"], |b, ins| {
+        let one = b.constant(1);
+        vec![b.add_with(&ins[0], &one, Arithmetic::Saturating)]
+    });
+    let loaded = Gadget::from_json(&gadget.to_json(), &hints).unwrap();
+    assert_eq!(loaded.to_json(), gadget.to_json());
+    assert!(gadget.to_json().contains("\"mode\": \"saturating\""));
+    assert!(builder.to_mermaid().contains("+ wrapping"));
+
+    let mut plain = Builder::new();
+    let x = plain.init();
+    let mut overridden = plain.clone();
+    plain.add(&x, &x);
+    overridden.add_with(&x, &x, Arithmetic::Checked);
+    assert_ne!(plain.fingerprint(), overridden.fingerprint());
+    assert_eq!(plain.diff(&overridden).changed_nodes.len(), 1);
+}
+
+#[test]
+fn test_one_hot() {
+    let mut builder = Builder::new();
+    let index = builder.init();
+    let flags = gadgets::one_hot(&mut builder, &index, 5);
+
+    builder.fill_nodes(HashMap::from([(index.id, 3)]));
+    let values: Vec<Option<u32>> = builder.values_of(&flags);
+    assert_eq!(values, [0, 0, 0, 1, 0].map(Some));
+    assert!(builder.check_constraints());
+
+    // Out-of-range indexes have no valid encoding.
+    builder.fill_nodes(HashMap::from([(index.id, 5)]));
+    assert!(!builder.check_constraints());
+
+    // Neither does a vector with two flags set.
+    let mut forged = Builder::new();
+    let index = forged.init();
+    let flags = gadgets::one_hot(&mut forged, &index, 4);
+    for (flag, value) in flags.iter().zip([0, 1, 1, 0]) {
+        forged.nodes.get_mut(&flag.id).unwrap().value = Some(value);
+    }
+    forged.fill_nodes(HashMap::from([(index.id, 3)]));
+    assert!(!forged.check_constraints());
+}
+
+#[test]
+fn test_sorting_network() {
+    let mut builder = Builder::new();
+    builder.set_eval_order(EvalOrder::Topological);
+    let inputs: Vec<Node> = (0..6).map(|_| builder.init()).collect();
+    let sorted = gadgets::sort(&mut builder, &inputs, 8);
+
+    // By the 0-1 principle, sorting every 0/1 input proves the network sorts.
+    for mask in 0u32..1 << inputs.len() {
+        let values: Vec<u32> = (0..inputs.len()).map(|i| (mask >> i) & 1).collect();
+        builder.fill_nodes(inputs.iter().map(|n| n.id).zip(values.clone()).collect());
+        let mut expected = values;
+        expected.sort();
+        assert_eq!(
+            builder.values_of(&sorted),
+            expected.into_iter().map(Some).collect::<Vec<_>>()
+        );
+    }
+    let values = [200, 3, 77, 3, 255, 12];
+    builder.fill_nodes(inputs.iter().map(|n| n.id).zip(values).collect());
+    assert_eq!(
+        builder.values_of(&sorted),
+        [3, 3, 12, 77, 200, 255].map(Some)
+    );
+    assert!(builder.check_constraints());
+}
+
+#[test]
+fn test_assert_permutation() {
+    use crate::gadgets::PermutationCheck;
+
+    let cases: [([u32; 4], [u32; 4], bool); 3] = [
+        ([5, 9, 5, 1], [1, 5, 9, 5], true),
+        ([5, 9, 5, 1], [1, 5, 9, 9], false),
+        ([2, 2, 3, 3], [2, 3, 3, 3], false),
+    ];
+    for (xs_values, ys_values, holds) in cases {
+        let mut sorted = Builder::new();
+        let mut product = Builder::with_modulus(65521);
+        let challenge = product.init();
+        let checks = [
+            (&mut sorted, PermutationCheck::Sorted { bits: 8 }, None),
+            (
+                &mut product,
+                PermutationCheck::GrandProduct { challenge },
+                Some((challenge.id, 31337)),
+            ),
+        ];
+        for (builder, check, challenge_value) in checks {
+            let xs: Vec<Node> = (0..4).map(|_| builder.init()).collect();
+            let ys: Vec<Node> = (0..4).map(|_| builder.init()).collect();
+            gadgets::assert_permutation(builder, &xs, &ys, &check);
+            let mut inputs: HashMap<NodeId, u32> = challenge_value.into_iter().collect();
+            inputs.extend(xs.iter().map(|n| n.id).zip(xs_values));
+            inputs.extend(ys.iter().map(|n| n.id).zip(ys_values));
+            builder.fill_nodes(inputs);
+            assert_eq!(
+                builder.check_constraints(),
+                holds,
+                "{:?} vs {:?}",
+                xs_values,
+                ys_values
+            );
+        }
+    }
+}
+
+#[test]
+fn test_rom_reads() {
+    let squares: Vec<u32> = (0..10).map(|i| i * i).collect();
+    let mut builder = Builder::new();
+    let rom = gadgets::Rom::new(&mut builder, &squares);
+    assert_eq!(rom.len(), 10);
+    let i = builder.init();
+    let j = builder.init();
+    let a = rom.read(&mut builder, &i);
+    let b = rom.read(&mut builder, &j);
+    let sum = builder.add(&a, &b);
+
+    builder.fill_nodes(HashMap::from([(i.id, 3), (j.id, 7)]));
+    assert_eq!(
+        builder.values_of(&[a, b, sum]),
+        [Some(9), Some(49), Some(58)]
+    );
+    assert!(builder.check_constraints());
+
+    builder.fill_nodes(HashMap::from([(j.id, 10)]));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_state_machine_trace() {
+    // (count, acc) -> (count + 1, acc + count), with count kept below 16
+    let step = Gadget::build("step", &["count", "acc"], &["count", "acc"], |b, s| {
+        let one = b.constant(1);
+        let count = b.add(&s[0], &one);
+        let acc = b.add(&s[1], &s[0]);
+        let limit = b.constant(16);
+        let ok = gadgets::less_than(b, &s[0], &limit, 8);
+        let yes = b.constant(1);
+        b.assert_equal(&ok, &yes);
+        vec![count, acc]
+    });
+    let mut builder = Builder::new();
+    let start = builder.init();
+    let zero = builder.constant(0);
+    let trace = builder.state_machine(&step, 4, &[start, zero]);
+    assert_eq!(trace.len(), 5);
+    assert_eq!(builder.instances().len(), 4);
+
+    builder.fill_nodes(HashMap::from([(start.id, 3)]));
+    let counts: Vec<_> = trace.iter().map(|s| builder.value(s[0].id)).collect();
+    let accs: Vec<_> = trace.iter().map(|s| builder.value(s[1].id)).collect();
+    assert_eq!(counts, [Some(3), Some(4), Some(5), Some(6), Some(7)]);
+    assert_eq!(accs, [Some(0), Some(3), Some(7), Some(12), Some(18)]);
+    assert!(builder.check_constraints());
+
+    // The guard fails once the count reaches 16.
+    builder.fill_nodes(HashMap::from([(start.id, 14)]));
+    assert!(!builder.check_constraints());
+}
+
+#[test]
+fn test_eval_plan_and_cache() {
+    let build = || {
+        let mut b = Builder::new();
+        let x = b.init();
+        let y = b.init();
+        let sum = b.add(&x, &y);
+        let h = b.hint(&[sum], |v| v[0] * 2);
+        let out = b.mul(&h, &x);
+        (b, x, y, out)
+    };
+    let (mut graph, x, y, out) = build();
+    let plan = graph.eval_plan();
+    assert_eq!(plan.len(), 5);
+    assert_eq!(plan.order()[0], x.id);
+    assert_eq!(plan.dependents(x.id).len(), 2);
+
+    for (a, b) in [(1, 2), (3, 4), (5, 0)] {
+        let summary = graph
+            .fill_with_plan(&plan, &HashMap::from([(x.id, a), (y.id, b)]))
+            .unwrap();
+        assert!(summary.is_complete());
+        assert_eq!(summary.hint_calls, 1);
+        assert_eq!(graph.value(out.id), Some((a + b) * 2 * a));
+    }
+    let summary = graph
+        .fill_with_plan(&plan, &HashMap::from([(x.id, 1)]))
+        .unwrap();
+    assert_eq!(summary.unresolved.len(), 4);
+
+    let mut other = Builder::new();
+    other.init();
+    assert!(matches!(
+        other.fill_with_plan(&plan, &HashMap::new()),
+        Err(PlanError::Mismatch { plan: 5, graph: 1 })
+    ));
+    // Same node count, different structure: refused in release builds too.
+    let mut same_size = Builder::new();
+    for _ in 0..5 {
+        same_size.init();
+    }
+    assert!(matches!(
+        same_size.fill_with_plan(&plan, &HashMap::new()),
+        Err(PlanError::Fingerprint { .. })
+    ));
+    assert!(same_size.evaluate_batch(&plan, &[HashMap::new()]).is_err());
+
+    let mut cache = PlanCache::new(2);
+    let (twin, _, _, _) = build();
+    assert_eq!(cache.plan_for(&graph).fingerprint(), graph.fingerprint());
+    cache.plan_for(&twin);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+    cache.plan_for(&other);
+    let mut third = Builder::new();
+    third.constant(7);
+    cache.plan_for(&third);
+    assert_eq!(cache.len(), 2);
+    assert!(!cache.contains(graph.fingerprint()));
+    assert!(cache.contains(other.fingerprint()));
+}
+
+#[test]
+fn test_cancellable_evaluation() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    static CANCEL: AtomicBool = AtomicBool::new(false);
+
+    let mut builder = Builder::new();
+    builder.set_eval_order(EvalOrder::Topological);
+    let x = builder.init();
+    let tripwire = builder.hint(&[x], |v| {
+        CANCEL.store(true, Ordering::Relaxed);
+        v[0] + 1
+    });
+    let y = builder.mul(&tripwire, &x);
+    let z = builder.add(&y, &x);
+
+    let err = builder
+        .fill_nodes_cancellable(HashMap::from([(x.id, 3)]), &CANCEL)
+        .unwrap_err();
+    assert_eq!(err.summary.evaluated, 1);
+    assert_eq!(err.summary.unresolved, [y.id, z.id]);
+    assert_eq!(builder.value(tripwire.id), Some(4));
+    assert_eq!(builder.witness().get(z.id), None);
+
+    CANCEL.store(false, Ordering::Relaxed);
+    let summary = builder
+        .fill_nodes_cancellable(HashMap::from([(x.id, 3)]), &AtomicBool::new(false))
+        .unwrap();
+    assert!(summary.is_complete());
+    assert_eq!(builder.value(z.id), Some(15));
+}
+
+#[test]
+fn test_profiled_evaluation() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let slow = builder.namespace("solver", |b| {
+        let h = b.hint(&[x], |v| {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            v[0] * 3
+        });
+        b.set_label(&h, "sqrt");
+        h
+    });
+    let y = builder.add(&slow, &x);
+    builder.fill_nodes(HashMap::from([(x.id, 1)]));
+
+    let (summary, profile) = builder.fill_nodes_profiled(HashMap::from([(x.id, 2)]));
+    assert!(summary.is_complete());
+    assert_eq!(builder.value(y.id), Some(8));
+    assert_eq!(profile.timings().len(), 2);
+    assert_eq!(profile.slowest(1)[0].node, slow.id);
+    assert!(profile.slowest(1)[0].time >= std::time::Duration::from_millis(2));
+    assert_eq!(profile.by_kind()[0].0, OpKind::Hint);
+    assert!(profile.report().lines().any(|l| l.contains("solver/sqrt")));
+    let stacks = profile.collapsed_stacks();
+    assert!(stacks.lines().next().unwrap().starts_with("solver;sqrt "));
+    assert!(stacks
+        .lines()
+        .nth(1)
+        .unwrap()
+        .starts_with(&format!("n{} ", y.id.0)));
+}
+
+#[test]
+fn test_copy_node_handles() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let y = builder.init();
+    // Handles are copied into hint parents without cloning any node state.
+    let h = builder.hint(&[x, y, x], |v| v[0] + v[1] + v[2]);
+    let copy = h;
+    assert_eq!(copy, h);
+    assert_eq!(builder.handle(h.id), Some(h));
+    assert_eq!(builder.handle(NodeId(99)), None);
+    assert_eq!(builder.node(h.id).unwrap().operands(), [x.id, y.id, x.id]);
+
+    builder.fill_nodes(HashMap::from([(x.id, 2), (y.id, 5)]));
+    assert_eq!(builder.node(h.id).unwrap().value(), Some(9));
+}
+
+#[test]
+fn test_stale_handles_are_detected() {
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let checkpoint = builder.checkpoint();
+    let old = builder.add(&x, &x);
+    builder.rollback(&checkpoint);
+    assert!(!builder.is_live(&old));
+
+    // The freed id is reused, but the old handle does not alias the new node.
+    let new = builder.mul(&x, &x);
+    assert_eq!(new.id, old.id);
+    assert_ne!(new, old);
+    assert!(builder.is_live(&new) && !builder.is_live(&old));
+    assert_eq!(builder.handle(new.id), Some(new));
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        builder.add(&old, &x);
+    }));
+    assert!(result.is_err());
+
+    let y = builder.add(&new, &x);
+    builder.remove_subtree(&y).unwrap();
+    assert!(!builder.is_live(&y));
+    assert!(matches!(
+        builder.replace_node(&y, &x),
+        Err(EditError::UnknownNode(_))
+    ));
+}
+
+#[test]
+fn test_evaluate_batch() {
+    let mut builder = Builder::with_modulus(97);
+    let x = builder.init();
+    let y = builder.init();
+    let product = builder.mul(&x, &y);
+    let wrapped = builder.add_with(&product, &x, Arithmetic::Wrapping);
+    let half = builder.hint_named("half", &[wrapped], |v| v[0] / 2);
+    let plan = builder.eval_plan();
+
+    let batch = vec![
+        HashMap::from([(x.id, 10), (y.id, 20)]),
+        HashMap::from([(x.id, 3), (y.id, 4)]),
+        HashMap::from([(x.id, 3)]),
+    ];
+    let results = builder.evaluate_batch(&plan, &batch).unwrap();
+    let at = |values: &Vec<Option<u32>>, node: &Node| values[plan.index_of(node.id).unwrap()];
+    // 200 mod 97 = 6, then a wrapping add of 10.
+    assert_eq!(at(&results[0], &wrapped), Some(16));
+    assert_eq!(at(&results[0], &half), Some(8));
+    assert_eq!(at(&results[1], &half), Some(7));
+    assert_eq!(at(&results[2], &product), None);
+    assert_eq!(at(&results[2], &half), None);
+    // The graph's own values are untouched.
+    assert_eq!(builder.value(half.id), None);
+}
+
+#[test]
+fn test_watcher_reports_output_deltas() {
+    let mut builder = Builder::new();
+    let price = builder.input("price");
+    let qty = builder.input("qty");
+    let discount = builder.input("discount");
+    let total = builder.mul(&price, &qty);
+    let rebate = builder.mul(&discount, &qty);
+    builder.mark_output(&total, "total");
+    builder.mark_output(&rebate, "rebate");
+
+    let mut watcher = Watcher::new(builder);
+    let changes = watcher
+        .update(HashMap::from([
+            (price.id, 5),
+            (qty.id, 2),
+            (discount.id, 1),
+        ]))
+        .unwrap();
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].name.as_deref(), Some("total"));
+    assert_eq!((changes[0].before, changes[0].after), (None, Some(10)));
+
+    // Only `total` depends on the price.
+    let changes = watcher.update(HashMap::from([(price.id, 7)])).unwrap();
+    assert_eq!(
+        changes,
+        [OutputChange {
+            node: total.id,
+            name: Some("total".to_string()),
+            before: Some(10),
+            after: Some(14),
+        }]
+    );
+    assert!(watcher
+        .update(HashMap::from([(price.id, 7)]))
+        .unwrap()
+        .is_empty());
+    assert_eq!(watcher.value(rebate.id), Some(2));
+
+    assert_eq!(
+        watcher.update(HashMap::from([(total.id, 1)])),
+        Err(InputError::NotAnInput(total.id))
+    );
+}
+
+#[test]
+fn test_on_value_observers_fire_during_evaluation() {
+    use std::sync::{Arc, Mutex};
+
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.add(&x, &x);
+    let z = b.mul(&y, &y);
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    for node in [&y, &z] {
+        let seen = seen.clone();
+        b.on_value(node, move |id, v| seen.lock().unwrap().push((id, v)));
+    }
+    b.on_value(&x, |_, _| panic!("inputs do not fire observers"));
+
+    b.fill_nodes(HashMap::from([(x.id, 3)]));
+    assert_eq!(*seen.lock().unwrap(), vec![(y.id, 6), (z.id, 36)]);
+
+    seen.lock().unwrap().clear();
+    let plan = b.eval_plan();
+    b.fill_with_plan(&plan, &HashMap::from([(x.id, 1)]))
+        .unwrap();
+    assert_eq!(*seen.lock().unwrap(), vec![(y.id, 2), (z.id, 4)]);
+
+    seen.lock().unwrap().clear();
+    b.clear_observers(&z);
+    b.fill_nodes(HashMap::from([(x.id, 2)]));
+    assert_eq!(*seen.lock().unwrap(), vec![(y.id, 4)]);
+}
+
+#[test]
+fn test_validate_reports_structural_problems() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let sum = b.add(&x, &y);
+    b.assert_equal(&sum, &x);
+    assert!(b.validate().is_empty());
+    assert!(b.is_valid());
+
+    let unused = b.constant(7);
+    b.assert_equal(&x, &sum);
+    let diagnostics = b.validate();
+    let kinds: Vec<_> = diagnostics.iter().map(|d| d.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            DiagnosticKind::UnreachableConstant { node: unused.id },
+            DiagnosticKind::DuplicateConstraint { a: x.id, b: sum.id },
+        ]
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    assert!(b.is_valid());
+
+    // Corrupt the graph the way a bad import or edit could.
+    b.nodes.get_mut(&sum.id).unwrap().op = Some(Op::Add(sum.id, NodeId(99), Arithmetic::Inherit));
+    b.constraints.push((y.id, NodeId(98)));
+    let kinds: Vec<_> = b.validate().into_iter().map(|d| d.kind).collect();
+    assert!(kinds.contains(&DiagnosticKind::SelfReference { node: sum.id }));
+    assert!(kinds.contains(&DiagnosticKind::DanglingOperand {
+        node: sum.id,
+        operand: NodeId(99)
+    }));
+    assert!(kinds.contains(&DiagnosticKind::DanglingConstraint {
+        a: y.id,
+        b: NodeId(98),
+        missing: NodeId(98)
+    }));
+    assert!(!b.is_valid());
+    assert!(b.validate()[0].to_string().starts_with("error: "));
+}
+
+#[test]
+fn test_resource_limits() {
+    let mut b = Builder::with_limits(Limits {
+        max_nodes: Some(4),
+        max_constraints: Some(1),
+        max_hint_parents: Some(2),
+    });
+    let x = b.try_init().unwrap();
+    let y = b.try_init().unwrap();
+    assert_eq!(
+        b.try_hint(&[x, y, x], |v| v[0]),
+        Err(LimitError::TooManyHintParents {
+            limit: 2,
+            parents: 3
+        })
+    );
+    let s = b.try_add(&x, &y).unwrap();
+    b.try_assert_equal(&s, &x).unwrap();
+    assert_eq!(
+        b.try_assert_equal(&s, &y),
+        Err(LimitError::TooManyConstraints { limit: 1 })
+    );
+    b.try_hint(&[x, y], |v| v[0]).unwrap();
+    assert_eq!(
+        b.try_constant(1),
+        Err(LimitError::TooManyNodes { limit: 4 })
+    );
+    assert_eq!(b.nodes().count(), 4);
+
+    let full = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| b.mul(&x, &y)));
+    assert!(full.is_err());
+
+    let mut b = Builder::new();
+    assert_eq!(b.limits(), Limits::default());
+    b.init();
+    b.set_limits(Limits {
+        max_nodes: Some(1),
+        ..Limits::default()
+    });
+    assert!(b.try_init().is_err());
+}
+
+#[test]
+fn test_decoders_enforce_limits() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let sum = b.add(&x, &y);
+    let root = b.hint_named("gadgets::isqrt", &[sum], |v| v[0]);
+    b.assert_equal(&root, &x);
+    let mut hints = HintRegistry::new();
+    gadgets::register_hints(&mut hints);
+    let nodes = Limits {
+        max_nodes: Some(3),
+        ..Limits::default()
+    };
+    let constraints = Limits {
+        max_constraints: Some(0),
+        ..Limits::default()
+    };
+    let parents = Limits {
+        max_hint_parents: Some(0),
+        ..Limits::default()
+    };
+
+    let bytes = b.to_bytes();
+    #[cfg(feature = "cbor")]
+    let cbor = b.to_cbor();
+    #[cfg(feature = "protobuf")]
+    let protobuf = b.to_protobuf();
+    for limits in [nodes, constraints, parents] {
+        assert!(Builder::from_bytes_any_version_with_limits(&bytes, &hints, limits).is_err());
+        #[cfg(feature = "cbor")]
+        assert!(Builder::from_cbor_with_limits(&cbor, &hints, limits).is_err());
+        #[cfg(feature = "protobuf")]
+        assert!(Builder::from_protobuf_with_limits(&protobuf, &hints, limits).is_err());
+        assert!(Builder::from_sexpr_with_limits(&b.to_sexpr(), &hints, limits).is_err());
+    }
+    let decoded = Builder::from_bytes_any_version_with_limits(&bytes, &hints, nodes);
+    assert_eq!(
+        decoded.err().unwrap().message,
+        "graph already has the maximum of 3 nodes"
+    );
+    let limits = Limits {
+        max_nodes: Some(4),
+        max_constraints: Some(1),
+        max_hint_parents: Some(1),
+    };
+    let decoded = Builder::from_bytes_any_version_with_limits(&bytes, &hints, limits).unwrap();
+    assert_eq!(decoded.limits(), limits);
+    assert_eq!(decoded.nodes().count(), 4);
+
+    let gadget = Gadget::build("root", &["a", "b"], &["r"], |b, ins| {
+        let sum = b.add(&ins[0], &ins[1]);
+        vec![b.hint_named("gadgets::isqrt", &[sum], |v| v[0])]
+    });
+    assert!(matches!(
+        Gadget::from_json_with_limits(&gadget.to_json(), &hints, nodes),
+        Err(ImportError::Limit(LimitError::TooManyNodes { limit: 3 }))
+    ));
+    assert!(Gadget::from_json_with_limits(&gadget.to_json(), &hints, limits).is_ok());
+
+    let toml = "inputs = [\"x\", \"y\"]\nconstraints = [\"x * y == 6\"]\n";
+    let err = Builder::from_toml_with_limits(toml, &hints, constraints)
+        .err()
+        .unwrap();
+    assert_eq!(err.line, 2);
+    assert!(Builder::from_toml_with_limits(toml, &hints, nodes).is_err());
+    assert!(Builder::from_toml_with_limits(toml, &hints, limits).is_ok());
+    let yaml = "inputs: [x, y]\nexpressions:\n  r: isqrt(x + y)\n";
+    hints.register("isqrt", |v| v[0]);
+    let err = Builder::from_yaml_with_limits(yaml, &hints, parents)
+        .err()
+        .unwrap();
+    assert_eq!(err.message, "hint has 1 parents but at most 0 are allowed");
+    assert!(Builder::from_yaml_with_limits(yaml, &hints, limits).is_ok());
+
+    let mut service = service::GraphService::new();
+    assert_eq!(service.limits(), service::DEFAULT_SERVICE_LIMITS);
+    service.set_limits(nodes);
+    assert!(matches!(
+        service.upload(&gadget.to_json()),
+        Err(service::ServiceError::Graph(ImportError::Limit(_)))
+    ));
+    service.set_limits(limits);
+    service.upload(&gadget.to_json()).unwrap();
+}
+
+#[test]
+fn test_air_export() {
+    // Fibonacci: (a, b) -> (b, a + b)
+    let fib = Gadget::build("fib", &["a", "b"], &["a", "b"], |b, s| {
+        vec![s[1], b.add(&s[0], &s[1])]
+    });
+    let mut builder = Builder::new();
+    let init = [builder.constant(0), builder.constant(1)];
+    let trace = builder.state_machine(&fib, 5, &init);
+    let eight = builder.constant(8);
+    builder.assert_equal(&trace[5][1], &eight);
+
+    let air = json::Json::parse(&builder.to_air(&trace).unwrap()).unwrap();
+    assert_eq!(air.get("width"), Some(&json::Json::Int(2)));
+    assert_eq!(air.get("length"), Some(&json::Json::Int(6)));
+    let expected = json::Json::parse(
+        r#"[
+            {"degree": 1, "terms": [[4294967295, [0, 1, 0, 0]], [1, [0, 0, 1, 0]]]},
+            {"degree": 1, "terms": [[4294967295, [1, 0, 0, 0]], [4294967295, [0, 1, 0, 0]], [1, [0, 0, 0, 1]]]}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(air.get("transitions"), Some(&expected));
+    let expected = json::Json::parse(
+        r#"[
+            {"row": 0, "column": 0, "value": 0},
+            {"row": 0, "column": 1, "value": 1},
+            {"row": 5, "column": 1, "value": 8}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(air.get("boundary"), Some(&expected));
+
+    // Squaring after an increment is not the same step twice.
+    let mut b = Builder::new();
+    let x = b.init();
+    let one = b.constant(1);
+    let y = b.add(&x, &one);
+    let z = b.mul(&y, &y);
+    assert_eq!(
+        b.to_air(&[vec![x], vec![y], vec![z]]),
+        Err(AirError::NotUniform { step: 1 })
+    );
+    let h = b.hint(&[z], |v| v[0] / 2);
+    assert_eq!(b.to_air(&[vec![z], vec![h]]), Err(AirError::Hint(h.id)));
+}
+
+#[test]
+fn test_constraint_stats() {
+    // x * x == y + 3, with y = x * 2, as in the R1CS export test
+    let mut builder = Builder::new();
+    let x = builder.init();
+    let two = builder.constant(2);
+    let y = builder.mul(&x, &two);
+    let three = builder.constant(3);
+    let rhs = builder.add(&y, &three);
+    let sq = builder.mul(&x, &x);
+    builder.assert_equal(&sq, &rhs);
+    let stats = builder.constraint_stats();
+    assert_eq!(
+        stats,
+        ConstraintStats {
+            mul_gates: 2,
+            equalities: 1,
+            constraints: 3,
+            max_degree: 2,
+            witness_size: 4,
+        }
+    );
+    let r1cs = json::Json::parse(&builder.to_r1cs()).unwrap();
+    assert_eq!(
+        r1cs.get("n_wires"),
+        Some(&json::Json::Int(stats.witness_size as i128))
+    );
+
+    let cube = builder.mul(&sq, &x);
+    let h = builder.hint(&[x], |v| v[0]);
+    builder.assert_equal(&cube, &h);
+    let stats = builder.constraint_stats();
+    assert_eq!(stats.max_degree, 3);
+    assert_eq!(stats.constraints, 5);
+    assert_eq!(stats.witness_size, 6);
+    assert_eq!(Builder::new().constraint_stats().witness_size, 1);
+}
+
+#[test]
+fn test_witness_verify_against() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let h = b.hint(&[x], |v| v[0] + 1);
+    let sum = b.add(&x, &h);
+    let sq = b.mul(&sum, &sum);
+    b.fill_nodes(HashMap::from([(x.id, 3)]));
+    let witness = b.witness();
+    assert_eq!(witness.verify_against(&b).unwrap(), vec![]);
+
+    // A hand-edited file: the sum is wrong and the square is missing.
+    let edited = format!(
+        r#"{{"fingerprint": "{:016x}", "values": [[0, 3], [1, 4], [2, 8]]}}"#,
+        witness.fingerprint()
+    );
+    let edited = Witness::from_json(&edited).unwrap();
+    assert_eq!(
+        edited.verify_against(&b).unwrap(),
+        vec![
+            WitnessMismatch {
+                node: sum.id,
+                recorded: Some(8),
+                computed: Some(7),
+            },
+            WitnessMismatch {
+                node: sq.id,
+                recorded: None,
+                computed: Some(49),
+            },
+        ]
+    );
+
+    let other = Builder::new();
+    assert!(matches!(
+        witness.verify_against(&other),
+        Err(WitnessError::GraphMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_check_hints_reports_failure_rate() {
+    let divmod = Gadget::build("divmod", &["a", "d"], &["q", "r"], |b, ins| {
+        let (q, r) = gadgets::divmod(b, &ins[0], &ins[1], 8);
+        vec![q, r]
+    });
+    let check = divmod.check_hints(&[("a", 0..=255), ("d", 1..=255)], 200, 7);
+    assert!(check.holds());
+    assert_eq!((check.samples, check.failures), (200, 0));
+    assert_eq!(check.counterexample, None);
+
+    // A quotient hint that rounds up is only right for exact divisions.
+    let rounding_up = Gadget::build("div_up", &["a", "d"], &["q"], |b, ins| {
+        let q = b.hint(ins, |v| v[0].div_ceil(v[1]));
+        let r = b.hint(ins, |v| v[0] % v[1]);
+        let qd = b.mul(&q, &ins[1]);
+        let recomposed = b.add(&qd, &r);
+        b.assert_equal(&recomposed, &ins[0]);
+        vec![q]
+    });
+    let check = rounding_up.check_hints(&[("a", 0..=255), ("d", 1..=255)], 200, 7);
+    assert!(!check.holds());
+    assert!(check.failure_rate() > 0.5 && check.failure_rate() < 1.0);
+    let counterexample = check.counterexample.unwrap();
+    assert_eq!(counterexample.failed.len(), 1);
+    let (a, d) = (
+        counterexample.inputs[&NodeId(0)],
+        counterexample.inputs[&NodeId(1)],
+    );
+    assert_ne!(a % d, 0);
+}
+
+#[test]
+fn test_minimize_constraints() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let z = b.init();
+    let s1 = b.add(&x, &y);
+    let s2 = b.add(&y, &x);
+    let five = b.constant(5);
+    let also_five = b.constant(5);
+    b.assert_equal(&s1, &five);
+    b.assert_equal(&five, &s1); // duplicate in the other order
+    b.assert_equal(&s2, &also_five); // same structure on both sides
+    b.assert_equal(&x, &x); // trivial
+    b.assert_equal(&x, &y);
+    b.assert_equal(&y, &z);
+    b.assert_equal(&x, &z); // follows by transitivity
+
+    assert_eq!(b.minimize_constraints(), 4);
+    assert_eq!(
+        b.constraints().collect::<Vec<_>>(),
+        vec![(s1.id, five.id), (x.id, y.id), (y.id, z.id)]
+    );
+    assert!(b.constraint_location(x.id, z.id).is_none());
+    assert!(b.constraint_location(y.id, z.id).is_some());
+    assert_eq!(b.minimize_constraints(), 0);
+}
+
+#[test]
+fn test_bounded_equivalence_proof() {
+    let build = |distribute: bool, scale_y: bool| {
+        let mut b = Builder::new();
+        let x = b.init();
+        let y = b.init();
+        let two = b.constant(2);
+        if distribute {
+            let x2 = b.mul(&x, &two);
+            let y2 = if scale_y { b.mul(&y, &two) } else { y };
+            b.add(&x2, &y2);
+        } else {
+            let sum = b.add(&x, &y);
+            b.mul(&sum, &two);
+        }
+        b
+    };
+    let factored = build(false, true);
+    let distributed = build(true, true);
+    assert_eq!(
+        factored.equivalent_to(&distributed, EquivalenceStrategy::Structural),
+        Equivalence::Unknown
+    );
+    let bounded = |bits| EquivalenceStrategy::Bounded { bits };
+    let proof = factored.equivalent_to(&distributed, bounded(6));
+    assert_eq!(proof, Equivalence::ProvenBounded { bits: 6 });
+    assert!(proof.holds());
+
+    assert_eq!(
+        factored.equivalent_to(&build(true, false), bounded(6)),
+        Equivalence::Counterexample { inputs: vec![0, 1] }
+    );
+    // 2^32 assignments is past the enumeration budget.
+    assert_eq!(
+        factored.equivalent_to(&distributed, bounded(16)),
+        Equivalence::Unknown
+    );
+}
+
+#[test]
+#[cfg(feature = "z3")]
+fn test_smt_equivalence_proof() {
+    let scale = |factored: bool, mode: Arithmetic| {
+        let mut b = Builder::new();
+        b.set_arithmetic(mode);
+        let x = b.init();
+        let y = b.init();
+        let three = b.constant(3);
+        if factored {
+            let sum = b.add(&x, &y);
+            b.mul(&sum, &three);
+        } else {
+            let x3 = b.mul(&x, &three);
+            let y3 = b.mul(&y, &three);
+            b.add(&x3, &y3);
+        }
+        b
+    };
+    // Distributing holds for the full 32-bit domain, far past what `Bounded`
+    // can enumerate.
+    let wrapping = scale(true, Arithmetic::Wrapping);
+    let proof = wrapping.prove_equivalent(&scale(false, Arithmetic::Wrapping), 32);
+    assert_eq!(proof, Equivalence::ProvenBounded { bits: 32 });
+
+    // Checked arithmetic leaves overflowing results undefined where wrapping
+    // does not, which the solver shows with a concrete assignment.
+    let checked = scale(true, Arithmetic::Checked);
+    let Equivalence::Counterexample { inputs } = checked.prove_equivalent(&wrapping, 32) else {
+        panic!("expected a counterexample");
+    };
+    let outputs = (checked.output_ids(), wrapping.output_ids());
+    assert_ne!(
+        checked.evaluate_with(&inputs, &outputs.0),
+        wrapping.evaluate_with(&inputs, &outputs.1)
+    );
+    // Below 2^29 nothing overflows.
+    assert!(checked.prove_equivalent(&wrapping, 29).holds());
+
+    // Named hints are compared as functions of their arguments.
+    let hinted = |swap: bool| {
+        let mut b = Builder::new();
+        let x = b.init();
+        let y = b.init();
+        let sum = if swap { b.add(&y, &x) } else { b.add(&x, &y) };
+        b.hint_named("square", &[sum], |v| v[0].wrapping_mul(v[0]));
+        b
+    };
+    assert!(hinted(false).prove_equivalent(&hinted(true), 32).holds());
+}
+
+#[cfg(feature = "cbor")]
+#[test]
+fn test_cbor_round_trip() {
+    let mut hints = HintRegistry::new();
+    hints.register("half", |v| v[0] / 2);
+    let mut builder = Builder::new();
+    let x = builder.init();
+    builder.enter_scope("layer1");
+    let sq = builder.mul_with(&x, &x, Arithmetic::Checked);
+    let half = builder.hint_named("half", &[sq], |v| v[0] / 2);
+    builder.exit_scope();
+    let k = builder.constant(70_000);
+    builder.assert_equal(&half, &k);
+
+    let bytes = builder.to_cbor();
+    assert!(bytes.len() < builder.to_bytes().len());
+    // Definite-length items with the shortest heads: a six-entry map whose
+    // first key is "version" and value 3.
+    assert_eq!(&bytes[..10], b"\xa6\x67version\x03");
+    let decoded = Builder::from_cbor(&bytes, &hints).unwrap();
+    assert_eq!(decoded.fingerprint(), builder.fingerprint());
+    assert_eq!(decoded.scope_of(&sq), Some("layer1"));
+    assert_eq!(decoded.arithmetic_of(&sq), Some(Arithmetic::Checked));
+    assert_eq!(decoded.to_bytes(), builder.to_bytes());
+    assert!(Builder::from_cbor(&bytes[..bytes.len() - 1], &hints).is_err());
+    assert!(Builder::from_cbor(&bytes, &HintRegistry::new()).is_err());
+    // Nesting under an unknown key is bounded too.
+    let mut deep = b"\xa1\x61x".to_vec();
+    deep.extend([0x81; 64]);
+    deep.push(0);
+    let e = Builder::from_cbor(&deep, &hints).err().unwrap();
+    assert_eq!(e.message, "CBOR nesting is too deep");
+
+    builder.fill_nodes(HashMap::from([(x.id, 20)]));
+    let witness = builder.witness();
+    assert_eq!(Witness::from_cbor(&witness.to_cbor()).unwrap(), witness);
+    let path = std::env::temp_dir().join("witness_test.cbor");
+    witness.save(&path, WitnessFormat::Cbor).unwrap();
+    assert_eq!(Witness::load(&path).unwrap(), witness);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "protobuf")]
+#[test]
+fn test_protobuf_round_trip() {
+    use prost::Message;
+    use protobuf::proto;
+
+    let mut hints = HintRegistry::new();
+    hints.register("half", |v| v[0] / 2);
+    let mut builder = Builder::new();
+    let x = builder.init();
+    builder.enter_scope("layer1");
+    let sq = builder.mul_with(&x, &x, Arithmetic::Checked);
+    let half = builder.hint_named("half", &[sq], |v| v[0] / 2);
+    builder.exit_scope();
+    let zero = builder.constant(0);
+    let sum = builder.add(&half, &zero);
+    builder.assert_equal(&sum, &half);
+
+    let bytes = builder.to_protobuf();
+    let message = proto::Graph::decode(bytes.as_slice()).unwrap();
+    assert_eq!(message.nodes.len(), 5);
+    assert_eq!(message.arithmetic, "inherit");
+    let decoded = Builder::from_protobuf(&bytes, &hints).unwrap();
+    assert_eq!(decoded.fingerprint(), builder.fingerprint());
+    assert_eq!(decoded.scope_of(&sq), Some("layer1"));
+    assert_eq!(decoded.arithmetic_of(&sq), Some(Arithmetic::Checked));
+    assert_eq!(decoded.to_bytes(), builder.to_bytes());
+    assert!(Builder::from_protobuf(&bytes[..bytes.len() - 1], &hints).is_err());
+    assert!(Builder::from_protobuf(&bytes, &HintRegistry::new()).is_err());
+
+    // Unknown fields are skipped, as generated code from newer schemas may
+    // send them.
+    let mut extended = bytes.clone();
+    extended.extend_from_slice(&[0xa8, 0x06, 0x01]); // field 101 = 1
+    assert!(Builder::from_protobuf(&extended, &hints).is_ok());
+
+    builder.fill_nodes(HashMap::from([(x.id, 20)]));
+    let witness = builder.witness();
+    assert_eq!(
+        Witness::from_protobuf(&witness.to_protobuf()).unwrap(),
+        witness
+    );
+}
+
+#[test]
+fn test_declarative_definitions() {
+    let mut hints = HintRegistry::new();
+    hints.register("half", |v| v[0] / 2);
+    let toml = r#"
+        # Pythagorean check
+        inputs = ["x", "y"]
+        constraints = [
+            "total == 25",  # 3*3 + 4*4
+        ]
+        outputs = ["total", "mid"]
+
+        [expressions]
+        sq = "x * x"
+        total = "sq + y * y"
+        mid = "half(total + 1)"
+    "#;
+    let yaml = "
+inputs: [x, y]
+expressions:
+  sq: x * x
+  total: 'sq + y * y'
+  mid: half(total + 1)
+constraints:
+  - total == 25   # 3*3 + 4*4
+outputs: [total, mid]
+";
+    for (mut builder, names) in [
+        Builder::from_toml(toml, &hints).unwrap(),
+        Builder::from_yaml(yaml, &hints).unwrap(),
+    ] {
+        builder.fill_nodes(HashMap::from([(names["x"], 3), (names["y"], 4)]));
+        assert!(builder.check_constraints());
+        assert_eq!(
+            builder.output_values(),
+            [
+                ("total".to_string(), Some(25)),
+                ("mid".to_string(), Some(13))
+            ]
+        );
+        assert_eq!(
+            builder.label_of(&builder.handle_of(names["sq"])),
+            Some("sq")
+        );
+    }
+
+    let text = "inputs = [\"x\"]\n[expressions]\ny = \"x + z\"";
+    let Err(err) = Builder::from_toml(text, &hints) else {
+        panic!("`z` is not defined");
+    };
+    assert_eq!(err.line, 3);
+    assert!(err.message.contains("`z`"));
+    assert!(Builder::from_yaml("inputs: [x, x]", &hints).is_err());
+    assert!(Builder::from_yaml("colors: [red]", &hints).is_err());
+
+    let path = std::env::temp_dir().join("definition_test.yml");
+    std::fs::write(&path, yaml).unwrap();
+    assert!(Builder::load_definition(&path, &hints).is_ok());
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        Builder::load_definition("graph.ini", &hints),
+        Err(DefinitionError::UnknownFormat(_))
+    ));
+}
+
+#[test]
+fn test_import_circom_r1cs() {
+    // out = a * b + 3, as circom compiles it: a * b = m and 0 = m + 3 - out.
+    // Wires: 0 = one, 1 = out (public output), 2 = a (public input),
+    // 3 = b (private input), 4 = m.
+    fn encode(prime: &[u8], minus_one: &[u8]) -> Vec<u8> {
+        let n8 = prime.len();
+        let elem = |v: u64| {
+            let mut e = v.to_le_bytes().to_vec();
+            e.resize(n8, 0);
+            e
+        };
+        let mut header = (n8 as u32).to_le_bytes().to_vec();
+        header.extend_from_slice(prime);
+        for v in [5u32, 1, 1, 1] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+        header.extend_from_slice(&5u64.to_le_bytes());
+        header.extend_from_slice(&2u32.to_le_bytes());
+        let mut body = Vec::new();
+        let mut lc = |terms: &[(u32, Vec<u8>)]| {
+            body.extend_from_slice(&(terms.len() as u32).to_le_bytes());
+            for (wire, coeff) in terms {
+                body.extend_from_slice(&wire.to_le_bytes());
+                body.extend_from_slice(coeff);
+            }
+        };
+        lc(&[(2, elem(1))]);
+        lc(&[(3, elem(1))]);
+        lc(&[(4, elem(1))]);
+        lc(&[]);
+        lc(&[]);
+        lc(&[(1, minus_one.to_vec()), (4, elem(1)), (0, elem(3))]);
+        let mut file = b"r1cs".to_vec();
+        file.extend_from_slice(&1u32.to_le_bytes());
+        file.extend_from_slice(&2u32.to_le_bytes());
+        // Constraints before the header, which circom also allows.
+        for (kind, section) in [(2u32, body), (1, header)] {
+            file.extend_from_slice(&kind.to_le_bytes());
+            file.extend_from_slice(&(section.len() as u64).to_le_bytes());
+            file.extend_from_slice(&section);
+        }
+        file
+    }
+
+    let bn254: [u8; 32] = [
+        1, 0, 0, 240, 147, 245, 225, 67, 145, 112, 185, 121, 72, 232, 51, 40, 93, 88, 129, 129,
+        182, 69, 80, 184, 41, 160, 49, 225, 114, 78, 100, 48,
+    ];
+    let mut bn254_minus_one = bn254;
+    bn254_minus_one[0] = 0;
+    let mut small_prime = [0u8; 8];
+    small_prime[0] = 97;
+    let mut small_minus_one = [0u8; 8];
+    small_minus_one[0] = 96;
+
+    for (bytes, modulus) in [
+        (encode(&bn254, &bn254_minus_one), None),
+        (encode(&small_prime, &small_minus_one), Some(97)),
+    ] {
+        let path = std::env::temp_dir().join("import_test.r1cs");
+        std::fs::write(&path, &bytes).unwrap();
+        let mut builder = import::r1cs(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(builder.modulus(), modulus);
+        assert!(builder.output_id("w1").is_some());
+        let wires = builder.input_ids();
+        assert_eq!(wires.len(), 4);
+        let label =
+            |builder: &Builder, id| builder.label_of(&builder.handle_of(id)).map(str::to_string);
+        assert_eq!(label(&builder, wires[3]).as_deref(), Some("w4"));
+
+        let assign = |out| {
+            HashMap::from([
+                (wires[0], out),
+                (wires[1], 6),
+                (wires[2], 7),
+                (wires[3], 42),
+            ])
+        };
+        builder.fill_nodes(assign(45));
+        assert!(builder.check_constraints());
+        builder.fill_nodes(assign(46));
+        assert!(!builder.check_constraints());
+
+        assert!(Builder::from_circom_r1cs(&bytes[..bytes.len() - 1]).is_err());
+        let limits = Limits {
+            max_nodes: Some(4),
+            ..Limits::default()
+        };
+        assert!(matches!(
+            Builder::from_circom_r1cs_with_limits(&bytes, limits),
+            Err(ImportError::Limit(LimitError::TooManyNodes { limit: 4 }))
+        ));
+    }
+    assert!(Builder::from_circom_r1cs(b"r1cx").is_err());
+}
+
+#[test]
+fn test_witness_diff() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let sq = b.mul(&x, &x);
+    let sum = b.add(&sq, &y);
+    b.fill_nodes(HashMap::from([(x.id, 3), (y.id, 1)]));
+    let first = b.witness();
+    assert_eq!(first.diff(&first).unwrap(), vec![]);
+
+    b.fill_nodes(HashMap::from([(x.id, 3), (y.id, 2)]));
+    let second = b.witness();
+    let diffs = first.diff(&second).unwrap();
+    assert_eq!(
+        diffs,
+        vec![
+            WitnessDiff {
+                node: y.id,
+                left: Some(1),
+                right: Some(2),
+            },
+            WitnessDiff {
+                node: sum.id,
+                left: Some(10),
+                right: Some(11),
+            },
+        ]
+    );
+    assert_eq!(diffs[0].to_string(), format!("{:?}: 1 vs 2", y.id));
+
+    let partial = format!(
+        r#"{{"fingerprint": "{:016x}", "values": [[0, 3], [1, 1], [2, 9]]}}"#,
+        first.fingerprint()
+    );
+    let partial = Witness::from_json(&partial).unwrap();
+    assert_eq!(
+        first.diff(&partial).unwrap(),
+        vec![WitnessDiff {
+            node: sum.id,
+            left: Some(10),
+            right: None,
+        }]
+    );
+
+    let mut other = Builder::new();
+    other.init();
+    assert!(matches!(
+        first.diff(&other.witness()),
+        Err(WitnessError::GraphMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_constant_interning() {
+    let mut b = Builder::new();
+    assert!(b.intern_constants());
+    let x = b.init();
+    let mut acc = x;
+    for _ in 0..100 {
+        let eight = b.constant(8);
+        acc = b.add(&acc, &eight);
+    }
+    // One input, one shared constant and the hundred sums.
+    assert_eq!(b.nodes.len(), 102);
+    assert_eq!(b.constant(8), b.constant(8));
+    assert_ne!(b.constant(8), b.constant(9));
+    b.fill_nodes(HashMap::from([(x.id, 1)]));
+    assert_eq!(b.value(acc.id), Some(801));
+
+    // A constant that was edited away is not handed out again.
+    let seven = b.constant(7);
+    let nine = b.constant(9);
+    b.replace_node(&seven, &nine).unwrap();
+    assert_ne!(b.constant(7), seven);
+
+    // Full graphs still hand out interned constants.
+    let mut limited = Builder::with_limits(Limits {
+        max_nodes: Some(1),
+        ..Limits::default()
+    });
+    let one = limited.constant(1);
+    assert_eq!(limited.try_constant(1), Ok(one));
+    assert!(limited.try_constant(2).is_err());
+
+    b.set_intern_constants(false);
+    assert_ne!(b.constant(8), b.constant(8));
+}
+
+#[test]
+fn test_scalar_operands() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let plus = b.add(&x, 5);
+    let times = b.mul(3, &plus);
+    let sq = b.mul(&x, &x);
+    let nested = b.add(&sq, 5);
+    // Both literals 5 share one interned constant.
+    assert_eq!(b.nodes.len(), 7);
+    assert_eq!(Operand::from(7), Operand::Const(7));
+    assert_eq!(Operand::from(&x), Operand::Node(x));
+
+    b.fill_nodes(HashMap::from([(x.id, 2)]));
+    assert_eq!(b.value(times.id), Some(21));
+    assert_eq!(b.value(nested.id), Some(9));
+}
+
+#[test]
+fn test_typed_nodes() {
+    let mut b = Builder::new();
+    let (x, y, c) = (b.init(), b.init(), b.init());
+    let x8 = b.constrain_range(&x, 8);
+    let y8 = b.constrain_range(&y, 8);
+    let cond = b.constrain_bool(&c);
+    b.assert_le(&x8, &y8);
+    let lt = b.less_than(&x8, &y8);
+    let picked = b.select(&cond, &x8, 100);
+    let yes = b.bool_constant(true);
+    let both = b.bool_and(&lt, &yes);
+    let either = b.bool_xor(&both, &cond);
+    assert_eq!(RangeCheckedNode::from(cond).bits(), 1);
+    assert_eq!(FieldNode::from(x).node(), x);
+
+    let mut run = |xv, yv, cv| {
+        b.fill_nodes(HashMap::from([(x.id, xv), (y.id, yv), (c.id, cv)]));
+        (
+            b.check_constraints(),
+            b.value(lt.node().id),
+            b.value(picked.id),
+            b.value(either.node().id),
+        )
+    };
+    assert_eq!(run(3, 200, 1), (true, Some(1), Some(3), Some(0)));
+    assert_eq!(run(7, 7, 0), (true, Some(0), Some(100), Some(0)));
+    // x > y, x out of range and a non-boolean condition all fail.
+    assert!(!run(9, 7, 0).0);
+    assert!(!run(300, 400, 0).0);
+    assert!(!run(3, 4, 2).0);
+}
+
+#[test]
+fn test_visitor_traversals() {
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl Visitor for Trace {
+        fn pre_input(&mut self, node: &NodeData) {
+            self.0.push(format!("x{}", node.id.0));
+        }
+        fn pre_add(&mut self, node: &NodeData, _: NodeId, _: NodeId) {
+            self.0.push(format!("+{}", node.id.0));
+        }
+        fn post_add(&mut self, node: &NodeData, _: NodeId, _: NodeId) {
+            self.0.push(format!("/+{}", node.id.0));
+        }
+        fn pre_mul(&mut self, node: &NodeData, a: NodeId, b: NodeId) {
+            self.0.push(format!("*{}({},{})", node.id.0, a.0, b.0));
+        }
+        fn pre_hint(&mut self, node: &NodeData, name: Option<&str>, parents: &[NodeId]) {
+            self.0.push(format!(
+                "{}{}:{}",
+                name.unwrap_or("?"),
+                node.id.0,
+                parents.len()
+            ));
+        }
+    }
+
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let sum = b.add(&x, &y);
+    let prod = b.mul(&sum, &x);
+    b.hint_named("h", &[y], |v| v[0]);
+
+    let trace = |traversal, roots: Option<&[NodeId]>| {
+        let mut trace = Trace::default();
+        match roots {
+            Some(roots) => b.walk_from(roots, traversal, &mut trace),
+            None => b.walk(traversal, &mut trace),
+        }
+        trace.0.join(" ")
+    };
+    assert_eq!(
+        trace(Traversal::DepthFirst, None),
+        "*3(2,0) +2 x0 x1 /+2 h4:1"
+    );
+    assert_eq!(
+        trace(Traversal::BreadthFirst, None),
+        "*3(2,0) h4:1 +2 /+2 x0 x1"
+    );
+    assert_eq!(
+        trace(Traversal::Topological, None),
+        "x0 x1 +2 /+2 *3(2,0) h4:1"
+    );
+    assert_eq!(
+        trace(Traversal::Topological, Some(&[sum.id, NodeId(99)])),
+        "x0 x1 +2 /+2"
+    );
+    // A trait object works as well.
+    let mut trace = Trace::default();
+    let visitor: &mut dyn Visitor = &mut trace;
+    b.walk_from(&[prod.id], Traversal::DepthFirst, visitor);
+    assert_eq!(trace.0.len(), 5);
+}
+
+#[test]
+fn test_reachability() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let z = b.init();
+    let sum = b.add(&x, &y);
+    let prod = b.mul(&sum, &x);
+    let other = b.add(&z, 1);
+
+    assert_eq!(b.ancestors(&prod), vec![x.id, y.id, sum.id]);
+    assert_eq!(b.ancestors(&x), vec![]);
+    assert_eq!(b.descendants(&x), vec![sum.id, prod.id]);
+    assert_eq!(b.descendants(&prod), vec![]);
+    assert!(b.depends_on(&prod, &y));
+    assert!(!b.depends_on(&y, &prod));
+    assert!(!b.depends_on(&prod, &prod));
+    assert!(!b.depends_on(&prod, &z));
+
+    // Rewiring `sum` onto a newer node breaks the id order the search
+    // normally prunes with.
+    b.replace_node(&y, &other).unwrap();
+    assert!(b.depends_on(&prod, &other));
+    assert!(b.depends_on(&prod, &z));
+    assert!(!b.depends_on(&prod, &y));
+    assert_eq!(b.descendants(&z), vec![other.id, sum.id, prod.id]);
+}
+
+#[test]
+fn test_extract_subgraph() {
+    let mut b = Builder::new();
+    let x = b.input("x");
+    let y = b.init();
+    b.set_label(&y, "y");
+    let sum = b.add(&x, &y);
+    let doubled = b.mul(&sum, 2);
+    let other = b.add(&x, 7);
+    b.mark_output(&doubled, "doubled");
+    b.mark_output(&other, "other");
+    b.assert_equal(&doubled, &sum);
+    b.assert_equal(&other, &x);
+
+    // `sum` and `doubled` alone: x and y are cut off and become inputs.
+    let two = NodeId(doubled.id.0 - 1);
+    let (region, map) = b.extract(&[doubled.id, sum.id, two]).unwrap();
+    assert_eq!(region.node_count(), 5);
+    assert_eq!(region.inputs().count(), 2);
+    assert_eq!(region.label_of(&map[&y.id]), Some("y"));
+    assert_eq!(region.input_id("x"), None);
+    assert_eq!(region.constraints().count(), 1);
+    assert_eq!(
+        region.outputs().map(|(name, _)| name).collect::<Vec<_>>(),
+        vec!["doubled"]
+    );
+
+    let mut region = region;
+    region.fill_nodes(HashMap::from([(map[&x.id].id, 3), (map[&y.id].id, 4)]));
+    assert_eq!(region.value(map[&doubled.id].id), Some(14));
+    assert!(!region.check_constraints());
+
+    // The cone of `other` is self-contained and keeps the named input.
+    let (cone, map) = b.extract_cone(&[other.id]).unwrap();
+    assert_eq!(cone.node_count(), 3);
+    assert_eq!(cone.input_id("x"), Some(map[&x.id].id));
+    assert_eq!(cone.constraints().count(), 1);
+
+    assert_eq!(
+        b.extract(&[NodeId(99)]).err(),
+        Some(ExtractError::UnknownNode(NodeId(99)))
+    );
+}
+
+#[test]
+fn test_topo_iter() {
+    let mut b = Builder::new();
+    let x = b.init();
+    let y = b.init();
+    let sum = b.add(&x, &y);
+    let prod = b.mul(&sum, &x);
+    let late = b.add(&x, 1);
+    let ids = |iter: &mut dyn Iterator<Item = &NodeData>| iter.map(|n| n.id.0).collect::<Vec<_>>();
+    assert_eq!(ids(&mut b.topo_iter()), ids(&mut b.nodes()));
+
+    // `sum` now reads the newer `late`, so dependency order departs from
+    // id order.
+    b.replace_node(&y, &late).unwrap();
+    let order = ids(&mut b.topo_iter());
+    assert_eq!(order, vec![0, 4, 5, 2, 3]);
+    let position = |id: NodeId| order.iter().position(|&i| i == id.0).unwrap();
+    assert!(position(late.id) < position(sum.id));
+    assert!(position(sum.id) < position(prod.id));
+    assert_eq!(order, ids(&mut b.topo_iter()));
+}
+
+#[test]
+fn test_find_nodes() {
+    let mut b = Builder::new();
+    let price = b.input("price");
+    let qty = b.input("qty");
+    let subtotal = b.mul(&price, &qty);
+    let fee = b.add(&subtotal, 3);
+    b.set_label(&fee, "fee_total");
+    b.set_metadata(&price, "unit", "cents");
+    b.set_metadata(&fee, "unit", "cents");
+    b.set_metadata(&fee, "owner", "billing");
+
+    let ids = |nodes: Vec<&NodeData>| nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+    assert_eq!(ids(b.find_by_label("fee_total").collect()), vec![fee.id]);
+    assert_eq!(ids(b.find_by_label("missing").collect()), vec![]);
+    assert_eq!(
+        b.find_by_metadata("unit")
+            .map(|(n, v)| (n.id, v))
+            .collect::<Vec<_>>(),
+        vec![(price.id, "cents"), (fee.id, "cents")]
+    );
+    assert_eq!(
+        ids(b
+            .find(|n| n.kind() == OpKind::Add || n.constant_value() == Some(3))
+            .collect()),
+        vec![NodeId(fee.id.0 - 1), fee.id]
+    );
+
+    // The indexes follow relabelling, metadata removal and node removal.
+    b.set_label(&fee, "fee");
+    assert_eq!(b.find_by_label("fee_total").count(), 0);
+    assert_eq!(ids(b.find_by_label("fee").collect()), vec![fee.id]);
+    assert_eq!(b.remove_metadata(&price, "unit"), Some("cents".to_string()));
+    assert_eq!(b.remove_metadata(&price, "unit"), None);
+    assert_eq!(b.find_by_metadata("unit").count(), 1);
+    b.remove_subtree(&fee).unwrap();
+    assert_eq!(b.find_by_label("fee").count(), 0);
+    assert_eq!(b.find_by_metadata("owner").count(), 0);
+    assert_eq!(ids(b.find_by_label("qty").collect()), vec![qty.id]);
+}
+
+#[test]
+fn test_decoders_reject_huge_node_ids() {
+    // Slot storage grows to the largest id, so these would otherwise try to
+    // allocate terabytes.
+    let json = r#"{"gadget": "g", "inputs": [], "outputs": [],
+        "nodes": [{"id": 100000000000, "op": "input"}], "constraints": []}"#;
+    let Err(e) = Gadget::from_json(json, &HintRegistry::new()) else {
+        panic!("huge id accepted");
+    };
+    assert!(e.to_string().contains("out of range"));
+    let service = service::GraphService::new();
+    assert!(service.upload(json).is_err());
+
+    #[cfg(feature = "protobuf")]
+    {
+        use crate::protobuf::proto;
+        use prost::Message;
+        let graph = proto::Graph {
+            version: u32::from(GRAPH_FORMAT_VERSION),
+            nodes: vec![proto::Node {
+                id: 1 << 40,
+                op: Some(proto::node::Op::Input(proto::Input {})),
+            }],
+            ..proto::Graph::default()
+        };
+        let Err(e) = Builder::from_protobuf(&graph.encode_to_vec(), &HintRegistry::new()) else {
+            panic!("huge id accepted");
+        };
+        assert!(e.to_string().contains("out of range"));
+    }
+
+    // Gaps left by removed nodes still decode.
+    let mut b = Builder::new();
+    let x = b.init();
+    let dead = b.add(&x, &x);
+    let live = b.mul(&x, &x);
+    b.remove_subtree(&dead).unwrap();
+    let decoded = Builder::from_bytes_any_version(&b.to_bytes(), &HintRegistry::new()).unwrap();
+    assert!(decoded.node(live.id).is_some());
+}
+
+#[test]
+#[cfg(feature = "wasm")]
+fn test_wasm_facade() {
+    use crate::wasm::WasmGraph;
+
+    let mut graph = WasmGraph::new();
+    let x = graph.input("x");
+    let sq = graph.mul(x, x).unwrap();
+    let eight = graph.constant(8);
+    let y = graph.add(sq, eight).unwrap();
+    graph.mark_output(y, "y").unwrap();
+    assert!(graph.add(x, 99).is_err());
+    assert_eq!(
+        graph.evaluate(r#"{"x": 3}"#).unwrap(),
+        "{\n  \"y\": 17\n}\n"
+    );
+    assert!(graph.evaluate(r#"{"z": 3}"#).is_err());
+    assert!(graph.check());
+    assert!(graph.to_dot().contains("= 17"));
+
+    let text = graph.builder().to_sexpr();
+    let mut parsed = WasmGraph::from_sexpr(&text).unwrap();
+    assert!(parsed.evaluate(r#"{"x0": 2}"#).unwrap().contains(": 12"));
+}
+
+#[test]
+#[cfg(feature = "uniffi")]
+fn test_uniffi_facade() {
+    use crate::uniffi::{MobileError, MobileGraph};
+
+    fn shareable<T: Send + Sync>(_: &T) {}
+
+    let graph = MobileGraph::new();
+    shareable(&graph);
+    let x = graph.input("x").unwrap();
+    let sq = graph.mul(x, x).unwrap();
+    let eight = graph.constant(8);
+    let y = graph.add(sq, eight).unwrap();
+    graph.mark_output(y, "y").unwrap();
+    assert_eq!(graph.add(x, 99), Err(MobileError::UnknownNode { id: 99 }));
+    assert_eq!(
+        graph.input("x"),
+        Err(MobileError::DuplicateName { name: "x".into() })
+    );
+
+    let outputs = graph
+        .evaluate(HashMap::from([("x".to_string(), 3)]))
+        .unwrap();
+    assert_eq!(outputs, HashMap::from([("y".to_string(), Some(17))]));
+    assert!(graph
+        .evaluate(HashMap::from([("z".to_string(), 3)]))
+        .is_err());
+    assert!(graph.check());
+    assert!(graph.to_dot().contains("= 17"));
+
+    let gadget = Gadget::from_json(&graph.to_json(), &HintRegistry::new()).unwrap();
+    assert_eq!(gadget.input_names(), vec!["x"]);
+
+    let parsed = MobileGraph::from_sexpr(&graph.to_sexpr()).unwrap();
+    assert_eq!(parsed.node_count(), 4);
+    let outputs = parsed
+        .evaluate(HashMap::from([("x0".to_string(), 2)]))
+        .unwrap();
+    assert_eq!(outputs.into_values().collect::<Vec<_>>(), vec![Some(12)]);
+    assert!(matches!(
+        MobileGraph::from_sexpr("(graph"),
+        Err(MobileError::Parse { .. })
+    ));
+
+    // The generated scaffolding exports the object through the C ABI that
+    // the Swift and Kotlin bindings call. Each method call consumes a
+    // reference, which the caller takes with the clone function first.
+    use crate::uniffi::{
+        uniffi_succint_graph_fn_clone_mobilegraph as clone_ref,
+        uniffi_succint_graph_fn_constructor_mobilegraph_new as new_graph,
+        uniffi_succint_graph_fn_free_mobilegraph as free_ref,
+        uniffi_succint_graph_fn_method_mobilegraph_constant as constant,
+        uniffi_succint_graph_fn_method_mobilegraph_node_count as node_count,
+    };
+    let mut status = ::uniffi::RustCallStatus::default();
+    let handle = new_graph(&mut status);
+    unsafe {
+        constant(clone_ref(handle.clone(), &mut status), 5, &mut status);
+        assert_eq!(
+            node_count(clone_ref(handle.clone(), &mut status), &mut status),
+            1
+        );
+        free_ref(handle, &mut status);
+    }
+}
+
+#[test]
+#[cfg(feature = "capi")]
+fn test_c_api() {
+    use crate::capi::*;
+    use std::ffi::CStr;
+
+    unsafe {
+        let b = sg_builder_new();
+        let x = sg_input(b);
+        let sq = sg_mul(b, x, x);
+        let five = sg_constant(b, 5);
+        let y = sg_add(b, sq, five);
+        assert_eq!(sg_add(b, y, 999), SG_INVALID_NODE);
+        assert_eq!(sg_assert_equal(b, y, five), 0);
+
+        let (ids, values) = ([x], [2u32]);
+        assert_eq!(sg_fill(b, ids.as_ptr(), values.as_ptr(), 1), 0);
+        let mut out = 0;
+        assert_eq!(sg_value(b, y, &mut out), 1);
+        assert_eq!(out, 9);
+        assert_eq!(sg_check_constraints(b), 0);
+
+        let dot = sg_to_dot(b);
+        assert!(CStr::from_ptr(dot).to_str().unwrap().contains("= 9"));
+        sg_string_free(dot);
+        sg_builder_free(b);
+        assert_eq!(sg_check_constraints(std::ptr::null()), -1);
+    }
+
+    // Every exported function is declared in the C header.
+    let header = include_str!("../include/succint_graph.h");
+    let source = include_str!("capi.rs");
+    for line in source.lines() {
+        if let Some(rest) = line.split("extern \"C\" fn ").nth(1) {
+            let name = &rest[..rest.find('(').unwrap()];
+            assert!(
+                header.contains(&format!("{}(", name)),
+                "{} missing from header",
+                name
+            );
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "server")]
+fn test_http_server() {
+    use crate::server::{route, serve};
+    use crate::service::GraphService;
+    use std::io::{Read, Write};
+
+    let gadget = Gadget::build("square", &["x"], &["y"], |b, ins| {
+        vec![b.mul(&ins[0], &ins[0])]
+    });
+    let service = GraphService::new();
+    let created = route(&service, "POST", "/graphs", &gadget.to_json());
+    assert_eq!(created.status, 201);
+    assert!(created.body.contains("\"id\": 0"));
+    let evaluated = route(&service, "POST", "/graphs/0/evaluate", r#"{"x": 5}"#);
+    assert_eq!(evaluated.status, 200);
+    assert!(evaluated.body.contains("\"y\": 25"));
+    assert!(evaluated.body.contains("\"failed\": []"));
+    assert_eq!(
+        route(&service, "POST", "/graphs/7/evaluate", "{}").status,
+        404
+    );
+    assert_eq!(
+        route(&service, "POST", "/graphs/0/evaluate", "[]").status,
+        400
+    );
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    runtime.spawn(serve(listener, service));
+    let request = |head: &str, body: &str| {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            head,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+    let response = request("POST /graphs/0/evaluate HTTP/1.1", r#"{"x": 6}"#);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("\"y\": 36"));
+
+    // An oversized body is refused from its Content-Length alone.
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "POST /graphs HTTP/1.1\r\nConnection: close\r\nContent-Length: 99999999999999\r\n\r\n{{"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+
+    // The server keeps answering afterwards.
+    let response = request("DELETE /graphs/0 HTTP/1.1", "");
+    assert!(response.starts_with("HTTP/1.1 204"));
+}
+
+#[test]
+#[cfg(feature = "grpc")]
+fn test_grpc_server() {
+    use crate::grpc::proto::graph_engine_client::GraphEngineClient;
+    use crate::grpc::proto::*;
+    use crate::grpc::serve;
+    use crate::service::GraphService;
+
+    let gadget = Gadget::build("square", &["x"], &["y"], |b, ins| {
+        let y = b.mul(&ins[0], &ins[0]);
+        let limit = b.constant(49);
+        b.assert_equal(&y, &limit);
+        vec![y]
+    });
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    runtime.spawn(serve(listener, GraphService::new()));
+
+    runtime.block_on(async {
+        let mut client = GraphEngineClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        let uploaded = client
+            .upload_graph(UploadGraphRequest {
+                graph_json: gadget.to_json(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(uploaded.input_names, vec!["x".to_string()]);
+        let handle = uploaded.handle;
+
+        let status = client
+            .evaluate(EvaluateRequest { handle })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+        let status = client
+            .submit_inputs(SubmitInputsRequest {
+                handle,
+                values: [("z".to_string(), 1)].into(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        client
+            .submit_inputs(SubmitInputsRequest {
+                handle,
+                values: [("x".to_string(), 6)].into(),
+            })
+            .await
+            .unwrap();
+        let outputs = client
+            .evaluate(EvaluateRequest { handle })
+            .await
+            .unwrap()
+            .into_inner()
+            .outputs;
+        assert_eq!(
+            outputs,
+            vec![OutputValue {
+                name: "y".to_string(),
+                value: Some(36)
+            }]
+        );
+        let report = client
+            .constraint_report(ConstraintReportRequest { handle })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(report.total, 1);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].left_value, Some(36));
+        assert_eq!(report.failed[0].right_value, Some(49));
+
+        client
+            .remove_graph(RemoveGraphRequest { handle })
+            .await
+            .unwrap();
+        let status = client
+            .evaluate(EvaluateRequest { handle })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    });
+}
@@ -0,0 +1,168 @@
+//! Node wrappers that carry what the constraints already prove about a value,
+//! so gadgets can demand a boolean or range-checked operand at the type level
+//! instead of re-constraining it on every call.
+//!
+//! Wrappers are only created by builder methods that add the constraint (or,
+//! for constants, where it holds by construction), so holding one is proof
+//! that the graph enforces its property.
+
+use crate::{gadgets, Builder, Node, Operand};
+
+/// A node with no static knowledge beyond being a value of the graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FieldNode(Node);
+
+/// A node constrained to be 0 or 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoolNode(Node);
+
+/// A node constrained to be below `2^bits`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RangeCheckedNode {
+    node: Node,
+    bits: u32,
+}
+
+impl FieldNode {
+    pub fn node(&self) -> Node {
+        self.0
+    }
+}
+
+impl From<Node> for FieldNode {
+    fn from(node: Node) -> Self {
+        FieldNode(node)
+    }
+}
+
+impl BoolNode {
+    pub fn node(&self) -> Node {
+        self.0
+    }
+}
+
+impl RangeCheckedNode {
+    pub fn node(&self) -> Node {
+        self.node
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+}
+
+/// A boolean is a 1-bit value.
+impl From<BoolNode> for RangeCheckedNode {
+    fn from(b: BoolNode) -> Self {
+        RangeCheckedNode { node: b.0, bits: 1 }
+    }
+}
+
+impl From<&FieldNode> for Operand {
+    fn from(node: &FieldNode) -> Self {
+        Operand::Node(node.0)
+    }
+}
+
+impl From<&BoolNode> for Operand {
+    fn from(node: &BoolNode) -> Self {
+        Operand::Node(node.0)
+    }
+}
+
+impl From<&RangeCheckedNode> for Operand {
+    fn from(node: &RangeCheckedNode) -> Self {
+        Operand::Node(node.node)
+    }
+}
+
+/// Comparisons need a spare bit below 2^32 for their offset.
+fn comparison_bits(x: &RangeCheckedNode, y: &RangeCheckedNode) -> u32 {
+    let bits = x.bits.max(y.bits);
+    assert!(bits <= 31, "comparison operands must fit in 31 bits");
+    bits
+}
+
+impl Builder {
+    /// Constrains `x` to be 0 or 1.
+    #[track_caller]
+    pub fn constrain_bool(&mut self, x: &Node) -> BoolNode {
+        gadgets::assert_bool(self, x);
+        BoolNode(*x)
+    }
+
+    /// A constant 0 or 1, boolean without any constraint.
+    #[track_caller]
+    pub fn bool_constant(&mut self, value: bool) -> BoolNode {
+        BoolNode(self.constant(u32::from(value)))
+    }
+
+    /// Constrains `x < 2^bits` by bit decomposition.
+    ///
+    /// # Panics
+    /// If `bits` is 0 or larger than 32.
+    #[track_caller]
+    pub fn constrain_range(&mut self, x: &Node, bits: u32) -> RangeCheckedNode {
+        gadgets::range_check(self, x, bits);
+        RangeCheckedNode { node: *x, bits }
+    }
+
+    #[track_caller]
+    pub fn bool_and(&mut self, x: &BoolNode, y: &BoolNode) -> BoolNode {
+        BoolNode(gadgets::bit_and(self, &x.0, &y.0))
+    }
+
+    #[track_caller]
+    pub fn bool_xor(&mut self, x: &BoolNode, y: &BoolNode) -> BoolNode {
+        BoolNode(gadgets::bit_xor(self, &x.0, &y.0))
+    }
+
+    #[track_caller]
+    pub fn bool_not(&mut self, x: &BoolNode) -> BoolNode {
+        BoolNode(gadgets::bit_not(self, &x.0))
+    }
+
+    /// `if cond { x } else { y }`. Unlike `gadgets::select`, `cond` is
+    /// already known to be boolean, so no constraint is added for it.
+    #[track_caller]
+    pub fn select(
+        &mut self,
+        cond: &BoolNode,
+        x: impl Into<Operand>,
+        y: impl Into<Operand>,
+    ) -> Node {
+        let x = self.operand(x);
+        let y = self.operand(y);
+        let delta = gadgets::sub(self, &x, &y);
+        let picked = self.mul(&cond.0, &delta);
+        self.add(&y, &picked)
+    }
+
+    /// Constrains `x <= y` by range checking `y - x` to the wider of the two
+    /// widths: with both operands below `2^bits`, the wrapped difference only
+    /// fits when `x <= y`.
+    ///
+    /// # Panics
+    /// If either operand is wider than 31 bits.
+    #[track_caller]
+    pub fn assert_le(&mut self, x: &RangeCheckedNode, y: &RangeCheckedNode) {
+        let bits = comparison_bits(x, y);
+        let diff = gadgets::sub(self, &y.node, &x.node);
+        gadgets::range_check(self, &diff, bits);
+    }
+
+    /// A boolean that is 1 iff `x < y`. Unlike `gadgets::less_than`, the
+    /// operands are not range checked again.
+    ///
+    /// # Panics
+    /// If either operand is wider than 31 bits.
+    #[track_caller]
+    pub fn less_than(&mut self, x: &RangeCheckedNode, y: &RangeCheckedNode) -> BoolNode {
+        let bits = comparison_bits(x, y);
+        // x - y + 2^bits lies in [1, 2^(bits+1)); its top bit is set iff x >= y.
+        let shifted = self.add(&x.node, 1u32 << bits);
+        let diff = gadgets::sub(self, &shifted, &y.node);
+        let diff_bits = gadgets::to_bits(self, &diff, bits + 1);
+        self.bool_not(&BoolNode(diff_bits[bits as usize]))
+    }
+}
@@ -0,0 +1,230 @@
+//! A facade shaped for UniFFI, so Swift and Kotlin apps can build and
+//! evaluate graphs on the device (feature `uniffi`).
+//!
+//! UniFFI shares objects behind an `Arc` and calls them through `&self` from
+//! any thread, so `MobileGraph` keeps its graph behind a `Mutex`. Arguments
+//! and results are integers, bools, strings, string-keyed maps and the
+//! `MobileError` enum, all of which UniFFI maps to native types. The interface
+//! is declared in `uniffi/succint_graph.udl`, from which `build.rs` generates
+//! the scaffolding included here, so the crate built as a `cdylib` or
+//! `staticlib` is the native library the Swift and Kotlin bindings load.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{gadgets, Builder, DotOptions, Gadget, HintRegistry, Node, NodeId};
+
+// The generated code names its `UniFfiTag` through the crate root, where
+// lib.rs re-exports it.
+::uniffi::include_scaffolding!("succint_graph");
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MobileError {
+    /// No node has this id.
+    UnknownNode { id: u32 },
+    /// No input is declared under this name.
+    UnknownInput { name: String },
+    /// An input or output with this name already exists.
+    DuplicateName { name: String },
+    /// The graph text could not be parsed.
+    Parse { message: String },
+}
+
+impl fmt::Display for MobileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MobileError::UnknownNode { id } => write!(f, "unknown node {}", id),
+            MobileError::UnknownInput { name } => write!(f, "unknown input `{}`", name),
+            MobileError::DuplicateName { name } => write!(f, "`{}` is already declared", name),
+            MobileError::Parse { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for MobileError {}
+
+struct State {
+    builder: Builder,
+    /// Input names, including those bound by `from_sexpr`.
+    inputs: HashMap<String, NodeId>,
+}
+
+/// A graph that can be shared with Swift or Kotlin code.
+pub struct MobileGraph {
+    state: Mutex<State>,
+}
+
+impl Default for MobileGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn id_of(node: Node) -> u32 {
+    node.id.0 as u32
+}
+
+impl State {
+    fn node(&self, id: u32) -> Result<Node, MobileError> {
+        self.builder
+            .handle(NodeId(id as usize))
+            .ok_or(MobileError::UnknownNode { id })
+    }
+}
+
+impl MobileGraph {
+    pub fn new() -> Self {
+        Self::from_builder(Builder::new(), HashMap::new())
+    }
+
+    fn from_builder(builder: Builder, inputs: HashMap<String, NodeId>) -> Self {
+        MobileGraph {
+            state: Mutex::new(State { builder, inputs }),
+        }
+    }
+
+    /// Parses a graph in the S-expression format. Hints from the `gadgets`
+    /// library are available, and every named input can be fed by name.
+    pub fn from_sexpr(text: &str) -> Result<Self, MobileError> {
+        let mut hints = HintRegistry::new();
+        gadgets::register_hints(&mut hints);
+        let (builder, names) =
+            Builder::from_sexpr(text, &hints).map_err(|e| MobileError::Parse {
+                message: e.to_string(),
+            })?;
+        let inputs = names
+            .into_iter()
+            .filter(|(_, id)| builder.node(*id).is_some_and(|n| n.op.is_none()))
+            .collect();
+        Ok(Self::from_builder(builder, inputs))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock leaves the graph itself consistent.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Creates an input fed by `name` in `evaluate`.
+    pub fn input(&self, name: &str) -> Result<u32, MobileError> {
+        let mut state = self.lock();
+        if state.inputs.contains_key(name) {
+            return Err(MobileError::DuplicateName { name: name.into() });
+        }
+        let node = state.builder.input(name);
+        state.inputs.insert(name.to_string(), node.id);
+        Ok(id_of(node))
+    }
+
+    pub fn constant(&self, value: u32) -> u32 {
+        id_of(self.lock().builder.constant(value))
+    }
+
+    pub fn add(&self, a: u32, b: u32) -> Result<u32, MobileError> {
+        let mut state = self.lock();
+        let (a, b) = (state.node(a)?, state.node(b)?);
+        Ok(id_of(state.builder.add(&a, &b)))
+    }
+
+    pub fn mul(&self, a: u32, b: u32) -> Result<u32, MobileError> {
+        let mut state = self.lock();
+        let (a, b) = (state.node(a)?, state.node(b)?);
+        Ok(id_of(state.builder.mul(&a, &b)))
+    }
+
+    pub fn assert_equal(&self, a: u32, b: u32) -> Result<(), MobileError> {
+        let mut state = self.lock();
+        let (a, b) = (state.node(a)?, state.node(b)?);
+        state.builder.assert_equal(&a, &b);
+        Ok(())
+    }
+
+    /// Declares node `id` as an output called `name`.
+    pub fn mark_output(&self, id: u32, name: &str) -> Result<(), MobileError> {
+        let mut state = self.lock();
+        let node = state.node(id)?;
+        if state.builder.output_id(name).is_some() {
+            return Err(MobileError::DuplicateName { name: name.into() });
+        }
+        state.builder.mark_output(&node, name);
+        Ok(())
+    }
+
+    /// Evaluates the graph on input values keyed by name and returns the
+    /// outputs keyed by name: the declared ones, or every sink as `n<id>`
+    /// when none are declared. Outputs that could not be computed are
+    /// `None`.
+    pub fn evaluate(
+        &self,
+        inputs: HashMap<String, u32>,
+    ) -> Result<HashMap<String, Option<u32>>, MobileError> {
+        let mut state = self.lock();
+        let mut values = HashMap::new();
+        for (name, value) in inputs {
+            let id = *state
+                .inputs
+                .get(&name)
+                .ok_or(MobileError::UnknownInput { name })?;
+            values.insert(id, value);
+        }
+        state.builder.fill_nodes(values);
+
+        let builder = &state.builder;
+        let named: Vec<(String, NodeId)> = if builder.outputs.is_empty() {
+            builder
+                .output_ids()
+                .into_iter()
+                .map(|id| (format!("n{}", id.0), id))
+                .collect()
+        } else {
+            builder.outputs.clone()
+        };
+        Ok(named
+            .into_iter()
+            .map(|(name, id)| (name, builder.value(id)))
+            .collect())
+    }
+
+    /// Whether every constraint holds after the last `evaluate`.
+    pub fn check(&self) -> bool {
+        self.lock().builder.check_constraints()
+    }
+
+    /// The graph as gadget JSON, with its named inputs and declared outputs
+    /// as ports, loadable with `Gadget::from_json` or the `graphengine` CLI.
+    pub fn to_json(&self) -> String {
+        let state = self.lock();
+        let mut inputs: Vec<(String, NodeId)> = state
+            .inputs
+            .iter()
+            .map(|(n, id)| (n.clone(), *id))
+            .collect();
+        inputs.sort_by_key(|(_, id)| *id);
+        Gadget {
+            name: "graph".to_string(),
+            graph: state.builder.clone(),
+            inputs,
+            outputs: state.builder.outputs.clone(),
+        }
+        .to_json()
+    }
+
+    /// The graph in the S-expression format read by `from_sexpr`.
+    pub fn to_sexpr(&self) -> String {
+        self.lock().builder.to_sexpr()
+    }
+
+    /// The graph in Graphviz DOT format, with values and constraints.
+    pub fn to_dot(&self) -> String {
+        let mut out = Vec::new();
+        self.lock()
+            .builder
+            .write_dot(&mut out, &DotOptions::annotated())
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(out).expect("DOT output is UTF-8")
+    }
+
+    pub fn node_count(&self) -> u32 {
+        self.lock().builder.node_count() as u32
+    }
+}
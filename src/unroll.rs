@@ -0,0 +1,70 @@
+//! Unrolling iterative computations into the graph.
+
+use crate::prelude::*;
+use crate::{Builder, Gadget, Node};
+
+impl Builder {
+    /// Unrolls `n` iterations of `step`, starting from `init`, and returns the
+    /// final state. `step` receives the builder, the current state and the
+    /// iteration index (`0..n`) and must return a state of the same width.
+    ///
+    /// ```ignore
+    /// // Fibonacci: (a, b) -> (b, a + b)
+    /// let fib = builder.repeat(10, vec![zero, one], |b, s, _| vec![s[1], b.add(&s[0], &s[1])]);
+    /// ```
+    ///
+    /// # Panics
+    /// If `step` returns a state with a different number of nodes than `init`.
+    pub fn repeat(
+        &mut self,
+        n: usize,
+        init: Vec<Node>,
+        mut step: impl FnMut(&mut Builder, &[Node], usize) -> Vec<Node>,
+    ) -> Vec<Node> {
+        let width = init.len();
+        let mut state = init;
+        for i in 0..n {
+            state = step(self, &state, i);
+            assert_eq!(
+                state.len(),
+                width,
+                "repeat: iteration {} returned {} state nodes, expected {}",
+                i,
+                state.len(),
+                width
+            );
+        }
+        state
+    }
+
+    /// Unrolls a state machine whose transition is the gadget `transition`
+    /// (one input port and one output port per state element) for `steps`
+    /// steps from `init`. Returns the trace: `steps + 1` states, starting with
+    /// `init`. Each step is a separate instance of the gadget, so constraints
+    /// inside it (transition guards, range checks) hold at every step.
+    ///
+    /// # Panics
+    /// If the gadget's input and output port counts differ from `init.len()`.
+    pub fn state_machine(
+        &mut self,
+        transition: &Gadget,
+        steps: usize,
+        init: &[Node],
+    ) -> Vec<Vec<Node>> {
+        assert!(
+            transition.inputs.len() == init.len() && transition.outputs.len() == init.len(),
+            "state_machine: `{}` maps {} to {} state nodes, but the initial state has {}",
+            transition.name,
+            transition.inputs.len(),
+            transition.outputs.len(),
+            init.len()
+        );
+        let mut trace = Vec::with_capacity(steps + 1);
+        trace.push(init.to_vec());
+        for i in 0..steps {
+            let next = self.instantiate(transition, &trace[i]);
+            trace.push(next);
+        }
+        trace
+    }
+}
@@ -0,0 +1,167 @@
+//! Structural validation: references, duplicates and dead constants that
+//! would otherwise surface as confusing failures during evaluation or export.
+
+use core::fmt;
+use core::panic::Location;
+
+use crate::collections::HashSet;
+use crate::prelude::*;
+use crate::{Builder, NodeId, Op};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The graph cannot be evaluated or exported as intended.
+    Error,
+    /// The graph works, but probably not as its author meant.
+    Warning,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// An op reads a node that is not in the graph.
+    DanglingOperand { node: NodeId, operand: NodeId },
+    /// A constraint names a node that is not in the graph.
+    DanglingConstraint {
+        a: NodeId,
+        b: NodeId,
+        missing: NodeId,
+    },
+    /// An op reads its own value.
+    SelfReference { node: NodeId },
+    /// The constraint `a == b` (in either order) was added before; the
+    /// location is that of the first one.
+    DuplicateConstraint { a: NodeId, b: NodeId },
+    /// A constant that no node reads and no constraint or declared output
+    /// mentions.
+    UnreachableConstant { node: NodeId },
+}
+
+/// One finding of `Builder::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub severity: Severity,
+    /// The offending node's scope path and label, or its id; for constraint
+    /// findings, both sides.
+    pub name: String,
+    /// Where the node or constraint was created.
+    pub location: Option<&'static Location<'static>>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: ", severity, self.name)?;
+        match &self.kind {
+            DiagnosticKind::DanglingOperand { operand, .. } => {
+                write!(f, "reads {:?}, which is not in the graph", operand)?
+            }
+            DiagnosticKind::DanglingConstraint { missing, .. } => write!(
+                f,
+                "constraint names {:?}, which is not in the graph",
+                missing
+            )?,
+            DiagnosticKind::SelfReference { .. } => write!(f, "op reads its own value")?,
+            DiagnosticKind::DuplicateConstraint { .. } => write!(f, "constraint is a duplicate")?,
+            DiagnosticKind::UnreachableConstant { .. } => write!(f, "constant is never used")?,
+        }
+        if let Some(location) = self.location {
+            write!(f, " (created at {})", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl Builder {
+    /// Checks the graph's structure: ops and constraints that refer to
+    /// missing nodes, ops that read themselves, repeated constraints and
+    /// unused constants. Node findings come first, in id order, then
+    /// constraint findings in the order the constraints were added. An empty
+    /// list means the graph is well formed.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut read = HashSet::new();
+        for node in self.nodes.values() {
+            let Some(op) = &node.op else { continue };
+            for operand in op.operands() {
+                read.insert(operand);
+                let kind = if operand == node.id {
+                    DiagnosticKind::SelfReference { node: node.id }
+                } else if !self.nodes.contains_key(&operand) {
+                    DiagnosticKind::DanglingOperand {
+                        node: node.id,
+                        operand,
+                    }
+                } else {
+                    continue;
+                };
+                if !diagnostics.iter().any(|d: &Diagnostic| d.kind == kind) {
+                    diagnostics.push(self.node_diagnostic(kind, Severity::Error, node.id));
+                }
+            }
+        }
+
+        let mentioned: HashSet<NodeId> = self
+            .constraints
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .chain(self.outputs.iter().map(|(_, id)| *id))
+            .collect();
+        for node in self.nodes.values() {
+            if matches!(node.op, Some(Op::Const(_)))
+                && !read.contains(&node.id)
+                && !mentioned.contains(&node.id)
+            {
+                diagnostics.push(self.node_diagnostic(
+                    DiagnosticKind::UnreachableConstant { node: node.id },
+                    Severity::Warning,
+                    node.id,
+                ));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        for &(a, b) in &self.constraints {
+            let (kind, severity) =
+                if let Some(missing) = [a, b].into_iter().find(|id| !self.nodes.contains_key(id)) {
+                    (
+                        DiagnosticKind::DanglingConstraint { a, b, missing },
+                        Severity::Error,
+                    )
+                } else if !seen.insert((a.min(b), a.max(b))) {
+                    (
+                        DiagnosticKind::DuplicateConstraint { a, b },
+                        Severity::Warning,
+                    )
+                } else {
+                    continue;
+                };
+            diagnostics.push(Diagnostic {
+                kind,
+                severity,
+                name: format!("{} == {}", self.describe(a), self.describe(b)),
+                location: self.constraint_locations.get(&(a, b)).copied(),
+            });
+        }
+        diagnostics
+    }
+
+    /// Whether `validate` finds no errors (warnings are allowed).
+    pub fn is_valid(&self) -> bool {
+        self.validate()
+            .iter()
+            .all(|d| d.severity != Severity::Error)
+    }
+
+    fn node_diagnostic(&self, kind: DiagnosticKind, severity: Severity, id: NodeId) -> Diagnostic {
+        Diagnostic {
+            kind,
+            severity,
+            name: self.describe(id),
+            location: self.locations.get(&id).copied(),
+        }
+    }
+}
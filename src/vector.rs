@@ -0,0 +1,213 @@
+//! Fixed-length vectors of values, so batch features and small tensors can be
+//! handled as one value with elementwise operations and reductions.
+//!
+//! A `VecNode` is a handle on one scalar node per lane: every vector
+//! operation builds its lanes with the ordinary `add`/`mul`/`hint` nodes, so
+//! vectors evaluate, serialize and export like any other part of the graph.
+
+use crate::prelude::*;
+use crate::{Builder, Node, NodeId};
+
+/// A vector value: one node per lane.
+#[derive(Clone, Debug)]
+pub struct VecNode {
+    lanes: Vec<Node>,
+}
+
+impl VecNode {
+    pub fn len(&self) -> usize {
+        self.lanes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+    }
+
+    /// The node holding lane `i`.
+    ///
+    /// # Panics
+    /// If `i` is out of bounds.
+    pub fn lane(&self, i: usize) -> &Node {
+        &self.lanes[i]
+    }
+
+    pub fn lanes(&self) -> &[Node] {
+        &self.lanes
+    }
+
+    pub fn ids(&self) -> Vec<NodeId> {
+        self.lanes.iter().map(|n| n.id).collect()
+    }
+
+    /// Input assignments giving lane `i` the value `values[i]`, to extend the
+    /// map passed to `fill_nodes`.
+    ///
+    /// # Panics
+    /// If the lengths differ.
+    pub fn assign(&self, values: &[u32]) -> Vec<(NodeId, u32)> {
+        assert_eq!(self.len(), values.len(), "expected {} values", self.len());
+        self.ids().into_iter().zip(values.iter().copied()).collect()
+    }
+}
+
+fn assert_same_len(a: &VecNode, b: &VecNode) {
+    assert_eq!(
+        a.len(),
+        b.len(),
+        "vector lengths differ: {} vs {}",
+        a.len(),
+        b.len()
+    );
+}
+
+impl Builder {
+    /// Groups existing nodes into a vector.
+    pub fn vector(&mut self, lanes: &[Node]) -> VecNode {
+        VecNode {
+            lanes: lanes.to_vec(),
+        }
+    }
+
+    /// A vector of `len` fresh input nodes.
+    #[track_caller]
+    pub fn input_vector(&mut self, len: usize) -> VecNode {
+        VecNode {
+            lanes: (0..len).map(|_| self.init()).collect(),
+        }
+    }
+
+    #[track_caller]
+    pub fn constant_vector(&mut self, values: &[u32]) -> VecNode {
+        VecNode {
+            lanes: values.iter().map(|v| self.constant(*v)).collect(),
+        }
+    }
+
+    /// Lane-wise `a + b`.
+    ///
+    /// # Panics
+    /// If the vectors have different lengths.
+    #[track_caller]
+    pub fn vadd(&mut self, a: &VecNode, b: &VecNode) -> VecNode {
+        assert_same_len(a, b);
+        VecNode {
+            lanes: a
+                .lanes
+                .iter()
+                .zip(&b.lanes)
+                .map(|(x, y)| self.add(x, y))
+                .collect(),
+        }
+    }
+
+    /// Lane-wise `a * b`.
+    ///
+    /// # Panics
+    /// If the vectors have different lengths.
+    #[track_caller]
+    pub fn vmul(&mut self, a: &VecNode, b: &VecNode) -> VecNode {
+        assert_same_len(a, b);
+        VecNode {
+            lanes: a
+                .lanes
+                .iter()
+                .zip(&b.lanes)
+                .map(|(x, y)| self.mul(x, y))
+                .collect(),
+        }
+    }
+
+    /// Every lane of `a` multiplied by the scalar `k`.
+    #[track_caller]
+    pub fn vscale(&mut self, a: &VecNode, k: &Node) -> VecNode {
+        VecNode {
+            lanes: a.lanes.iter().map(|x| self.mul(x, k)).collect(),
+        }
+    }
+
+    /// Applies the scalar function `func` to every lane. Each lane becomes a
+    /// hint named `name`, so the mapping survives serialization: register
+    /// `func` under `name` in the `HintRegistry` used to read the graph back.
+    #[track_caller]
+    pub fn vmap(&mut self, a: &VecNode, name: &str, func: fn(&[u32]) -> u32) -> VecNode {
+        VecNode {
+            lanes: a
+                .lanes
+                .iter()
+                .map(|x| self.hint_named(name, &[*x], func))
+                .collect(),
+        }
+    }
+
+    /// Sum of all lanes, added pairwise so the depth is logarithmic in the
+    /// length. The sum of an empty vector is the constant 0.
+    #[track_caller]
+    pub fn reduce_sum(&mut self, a: &VecNode) -> Node {
+        let mut level = a.lanes.clone();
+        if level.is_empty() {
+            return self.constant(0);
+        }
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            for pair in level.chunks(2) {
+                next.push(match pair {
+                    [x, y] => self.add(x, y),
+                    [x] => *x,
+                    _ => unreachable!(),
+                });
+            }
+            level = next;
+        }
+        level.pop().unwrap()
+    }
+
+    /// Inner product `sum(a[i] * b[i])`.
+    ///
+    /// # Panics
+    /// If the vectors have different lengths.
+    #[track_caller]
+    pub fn dot(&mut self, a: &VecNode, b: &VecNode) -> Node {
+        let products = self.vmul(a, b);
+        self.reduce_sum(&products)
+    }
+
+    /// Matrix product of the row-major `m x k` matrix `a` and the row-major
+    /// `k x n` matrix `b`, as a row-major `m x n` vector. Each entry is a
+    /// multiply-accumulate over `k` products, summed pairwise.
+    ///
+    /// # Panics
+    /// If the lengths of `a` or `b` do not match `dims = (m, k, n)`.
+    #[track_caller]
+    pub fn matmul(&mut self, a: &VecNode, b: &VecNode, dims: (usize, usize, usize)) -> VecNode {
+        let (m, k, n) = dims;
+        assert_eq!(a.len(), m * k, "left matrix must have {}x{} entries", m, k);
+        assert_eq!(b.len(), k * n, "right matrix must have {}x{} entries", k, n);
+        let mut lanes = Vec::with_capacity(m * n);
+        for i in 0..m {
+            for j in 0..n {
+                let products = (0..k)
+                    .map(|t| self.mul(&a.lanes[i * k + t], &b.lanes[t * n + j]))
+                    .collect();
+                lanes.push(self.reduce_sum(&VecNode { lanes: products }));
+            }
+        }
+        VecNode { lanes }
+    }
+
+    /// Constrains `a` and `b` to be equal lane by lane.
+    ///
+    /// # Panics
+    /// If the vectors have different lengths.
+    #[track_caller]
+    pub fn assert_vec_equal(&mut self, a: &VecNode, b: &VecNode) {
+        assert_same_len(a, b);
+        for (x, y) in a.lanes.iter().zip(&b.lanes) {
+            self.assert_equal(x, y);
+        }
+    }
+
+    /// The values of all lanes, once every lane is evaluated.
+    pub fn vector_value(&self, vector: &VecNode) -> Option<Vec<u32>> {
+        vector.lanes.iter().map(|n| self.value(n.id)).collect()
+    }
+}
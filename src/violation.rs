@@ -0,0 +1,287 @@
+//! Searching for input assignments that break a graph's constraints, so
+//! underconstrained or wrongly constrained hints are caught before a circuit
+//! ships.
+
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use crate::rng::SplitMix64;
+use crate::{Builder, Gadget, NodeData, NodeId};
+
+/// Budget of `Builder::find_violation_with`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ViolationSearch {
+    /// Input spaces with at most this many assignments are searched exhaustively.
+    pub exhaustive_limit: u64,
+    /// Assignments tried otherwise. The first half combines domain boundaries
+    /// and small values (0, 1, `lo`, `lo + 1`, `hi - 1`, `hi`), the rest is
+    /// uniform.
+    pub samples: usize,
+    pub seed: u64,
+}
+
+impl Default for ViolationSearch {
+    fn default() -> Self {
+        ViolationSearch {
+            exhaustive_limit: 1 << 16,
+            samples: 10_000,
+            seed: 0,
+        }
+    }
+}
+
+/// Inputs under which some constraints do not hold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub inputs: HashMap<NodeId, u32>,
+    /// The failing constraints, in declaration order.
+    pub failed: Vec<(NodeId, NodeId)>,
+}
+
+/// Outcome of `Builder::check_hints`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HintCheck {
+    pub samples: usize,
+    /// Samples under which some constraint failed.
+    pub failures: usize,
+    /// The first failing sample.
+    pub counterexample: Option<Violation>,
+}
+
+impl HintCheck {
+    /// Whether every sample satisfied every constraint.
+    pub fn holds(&self) -> bool {
+        self.failures == 0
+    }
+
+    /// Fraction of samples that failed, 0 without samples.
+    pub fn failure_rate(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.samples as f64
+        }
+    }
+}
+
+/// The graph's nodes in evaluation order, evaluated without logging.
+pub(crate) struct Evaluator<'a> {
+    graph: &'a Builder,
+    order: Vec<&'a NodeData>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub(crate) fn new(graph: &'a Builder) -> Self {
+        Evaluator {
+            graph,
+            order: graph.sorted_nodes(),
+        }
+    }
+
+    pub(crate) fn values(&self, inputs: &HashMap<NodeId, u32>) -> HashMap<NodeId, u32> {
+        let mut values = HashMap::with_capacity(self.order.len());
+        for node in &self.order {
+            let value = match &node.op {
+                None => inputs.get(&node.id).copied(),
+                Some(op) => op.apply(self.graph.arithmetic, |id| values.get(&id).copied()),
+            };
+            if let Some(value) = value {
+                values.insert(node.id, value);
+            }
+        }
+        values
+    }
+
+    /// The constraints that fail under `inputs`.
+    pub(crate) fn failed(&self, inputs: &HashMap<NodeId, u32>) -> Vec<(NodeId, NodeId)> {
+        let values = self.values(inputs);
+        self.graph
+            .constraints
+            .iter()
+            .filter(|(a, b)| values.get(a) != values.get(b))
+            .copied()
+            .collect()
+    }
+
+    pub(crate) fn violation(&self, inputs: HashMap<NodeId, u32>) -> Option<Violation> {
+        let failed = self.failed(&inputs);
+        (!failed.is_empty()).then_some(Violation { inputs, failed })
+    }
+}
+
+/// Number of assignments over `inputs`, if it fits in a `u64`.
+pub(crate) fn space_size(inputs: &[(NodeId, RangeInclusive<u32>)]) -> Option<u64> {
+    inputs.iter().try_fold(1u64, |acc, (_, domain)| {
+        acc.checked_mul(u64::from(domain.end() - domain.start()) + 1)
+    })
+}
+
+/// Checks every assignment over `inputs`, the first input varying fastest,
+/// and returns the first violation. Gives up with `None` once `stop` says so.
+pub(crate) fn search_exhaustively(
+    evaluator: &Evaluator,
+    inputs: &[(NodeId, RangeInclusive<u32>)],
+    stop: impl Fn() -> bool,
+) -> Option<Violation> {
+    let mut current: Vec<u32> = inputs.iter().map(|(_, d)| *d.start()).collect();
+    loop {
+        let assignment = inputs
+            .iter()
+            .map(|(id, _)| *id)
+            .zip(current.iter().copied());
+        if let Some(violation) = evaluator.violation(assignment.collect()) {
+            return Some(violation);
+        }
+        if stop() {
+            return None;
+        }
+        // Advance like an odometer over the domains; running off the end
+        // means every assignment was checked.
+        let mut position = 0;
+        loop {
+            let (_, domain) = inputs.get(position)?;
+            if current[position] < *domain.end() {
+                current[position] += 1;
+                break;
+            }
+            current[position] = *domain.start();
+            position += 1;
+        }
+    }
+}
+
+fn pick(rng: &mut SplitMix64, domain: &RangeInclusive<u32>) -> u32 {
+    let span = u64::from(domain.end() - domain.start()) + 1;
+    domain.start() + (rng.next_u64() % span) as u32
+}
+
+fn boundary(rng: &mut SplitMix64, domain: &RangeInclusive<u32>) -> u32 {
+    let (lo, hi) = (*domain.start(), *domain.end());
+    let candidates = [0, 1, lo, lo.saturating_add(1), hi.saturating_sub(1), hi];
+    let value = candidates[(rng.next_u64() % candidates.len() as u64) as usize];
+    value.clamp(lo, hi)
+}
+
+impl Builder {
+    /// Looks for inputs within `domains` that violate a constraint, using the
+    /// default `ViolationSearch` budget. Inputs missing from `domains` range
+    /// over their declared range, or all of `u32`.
+    pub fn find_violation(
+        &self,
+        domains: &HashMap<NodeId, RangeInclusive<u32>>,
+    ) -> Option<Violation> {
+        self.find_violation_with(domains, &ViolationSearch::default())
+    }
+
+    /// Like `find_violation`, with an explicit search budget. Returns `None`
+    /// when no violation was found, which only proves the constraints when
+    /// the input space fits in `search.exhaustive_limit`.
+    pub fn find_violation_with(
+        &self,
+        domains: &HashMap<NodeId, RangeInclusive<u32>>,
+        search: &ViolationSearch,
+    ) -> Option<Violation> {
+        let evaluator = Evaluator::new(self);
+        let inputs: Vec<(NodeId, RangeInclusive<u32>)> = self
+            .input_ids()
+            .into_iter()
+            .map(|id| {
+                let domain = domains.get(&id).or(self.input_ranges.get(&id));
+                (id, domain.cloned().unwrap_or(0..=u32::MAX))
+            })
+            .filter(|(_, domain)| !domain.is_empty())
+            .collect();
+        if space_size(&inputs).is_some_and(|size| size <= search.exhaustive_limit) {
+            return search_exhaustively(&evaluator, &inputs, || false);
+        }
+
+        let mut rng = SplitMix64::new(search.seed);
+        for sample in 0..search.samples {
+            let assignment = inputs
+                .iter()
+                .map(|(id, domain)| {
+                    let value = if sample < search.samples / 2 {
+                        boundary(&mut rng, domain)
+                    } else {
+                        pick(&mut rng, domain)
+                    };
+                    (*id, value)
+                })
+                .collect();
+            if let Some(violation) = evaluator.violation(assignment) {
+                return Some(violation);
+            }
+        }
+        None
+    }
+
+    /// Smoke-tests the graph's hints against its constraints: evaluates
+    /// `samples` uniformly random input assignments drawn from `domains`
+    /// (inputs missing from it range over their declared range, or all of
+    /// `u32`), running every hint, and counts those under which a constraint
+    /// fails. Unlike `find_violation` it does not stop at the first failure,
+    /// so the failure rate tells a hint that is slightly off from one that is
+    /// plain wrong.
+    pub fn check_hints(
+        &self,
+        domains: &HashMap<NodeId, RangeInclusive<u32>>,
+        samples: usize,
+        seed: u64,
+    ) -> HintCheck {
+        let evaluator = Evaluator::new(self);
+        let inputs: Vec<(NodeId, RangeInclusive<u32>)> = self
+            .input_ids()
+            .into_iter()
+            .map(|id| {
+                let domain = domains.get(&id).or(self.input_ranges.get(&id));
+                (id, domain.cloned().unwrap_or(0..=u32::MAX))
+            })
+            .filter(|(_, domain)| !domain.is_empty())
+            .collect();
+        let mut rng = SplitMix64::new(seed);
+        let mut check = HintCheck {
+            samples,
+            failures: 0,
+            counterexample: None,
+        };
+        for _ in 0..samples {
+            let assignment = inputs
+                .iter()
+                .map(|(id, domain)| (*id, pick(&mut rng, domain)))
+                .collect();
+            if let Some(violation) = evaluator.violation(assignment) {
+                check.failures += 1;
+                check.counterexample.get_or_insert(violation);
+            }
+        }
+        check
+    }
+}
+
+impl Gadget {
+    /// `Builder::check_hints` on the gadget's body, with input domains given
+    /// by port name.
+    ///
+    /// # Panics
+    /// If a name in `domains` is not an input port.
+    pub fn check_hints(
+        &self,
+        domains: &[(&str, RangeInclusive<u32>)],
+        samples: usize,
+        seed: u64,
+    ) -> HintCheck {
+        let domains = domains
+            .iter()
+            .map(|(name, domain)| {
+                let id = self
+                    .inputs
+                    .iter()
+                    .find(|(port, _)| port == name)
+                    .unwrap_or_else(|| panic!("`{}` has no input port `{}`", self.name, name))
+                    .1;
+                (id, domain.clone())
+            })
+            .collect();
+        self.graph.check_hints(&domains, samples, seed)
+    }
+}
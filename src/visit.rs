@@ -0,0 +1,149 @@
+//! A visitor with pre- and post-order hooks per op kind, and the drivers that
+//! walk a graph depth first, breadth first or in topological order, so
+//! analyses and exporters share one traversal instead of each re-deriving it.
+
+use alloc::collections::VecDeque;
+
+use crate::collections::HashSet;
+use crate::prelude::*;
+use crate::{Builder, NodeData, NodeId, Op};
+
+/// The order in which `Builder::walk` visits nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Traversal {
+    /// From each root down through operands, first operand first. A node's
+    /// post hook runs after the hooks of every node below it, so post hooks
+    /// alone see operands before readers.
+    DepthFirst,
+    /// From the roots towards the inputs, one level of operands at a time.
+    /// Each node's post hook follows its pre hook directly.
+    BreadthFirst,
+    /// Operands before readers, in evaluation order. Each node's post hook
+    /// follows its pre hook directly.
+    Topological,
+}
+
+/// Hooks called by `Builder::walk` for every node it reaches, once each. All
+/// hooks default to doing nothing, so a visitor implements only the kinds it
+/// cares about.
+pub trait Visitor {
+    fn pre_input(&mut self, _node: &NodeData) {}
+    fn post_input(&mut self, _node: &NodeData) {}
+
+    fn pre_const(&mut self, _node: &NodeData, _value: u32) {}
+    fn post_const(&mut self, _node: &NodeData, _value: u32) {}
+
+    fn pre_add(&mut self, _node: &NodeData, _a: NodeId, _b: NodeId) {}
+    fn post_add(&mut self, _node: &NodeData, _a: NodeId, _b: NodeId) {}
+
+    fn pre_mul(&mut self, _node: &NodeData, _a: NodeId, _b: NodeId) {}
+    fn post_mul(&mut self, _node: &NodeData, _a: NodeId, _b: NodeId) {}
+
+    /// `name` is `None` for hints created with `hint` rather than
+    /// `hint_named`.
+    fn pre_hint(&mut self, _node: &NodeData, _name: Option<&str>, _parents: &[NodeId]) {}
+    fn post_hint(&mut self, _node: &NodeData, _name: Option<&str>, _parents: &[NodeId]) {}
+}
+
+fn pre<V: Visitor + ?Sized>(visitor: &mut V, node: &NodeData) {
+    match &node.op {
+        None => visitor.pre_input(node),
+        Some(Op::Const(v)) => visitor.pre_const(node, *v),
+        Some(Op::Add(a, b, _)) => visitor.pre_add(node, *a, *b),
+        Some(Op::Mul(a, b, _)) => visitor.pre_mul(node, *a, *b),
+        Some(Op::Hint(parents, f)) => visitor.pre_hint(node, f.name(), parents),
+    }
+}
+
+fn post<V: Visitor + ?Sized>(visitor: &mut V, node: &NodeData) {
+    match &node.op {
+        None => visitor.post_input(node),
+        Some(Op::Const(v)) => visitor.post_const(node, *v),
+        Some(Op::Add(a, b, _)) => visitor.post_add(node, *a, *b),
+        Some(Op::Mul(a, b, _)) => visitor.post_mul(node, *a, *b),
+        Some(Op::Hint(parents, f)) => visitor.post_hint(node, f.name(), parents),
+    }
+}
+
+impl Builder {
+    /// Visits every node, starting from the sinks (the nodes nothing reads),
+    /// which reach the whole graph.
+    pub fn walk<V: Visitor + ?Sized>(&self, traversal: Traversal, visitor: &mut V) {
+        self.walk_from(&self.sink_ids(), traversal, visitor);
+    }
+
+    /// Visits `roots` and every node they depend on. Ids that are not in the
+    /// graph are skipped.
+    pub fn walk_from<V: Visitor + ?Sized>(
+        &self,
+        roots: &[NodeId],
+        traversal: Traversal,
+        visitor: &mut V,
+    ) {
+        match traversal {
+            Traversal::DepthFirst => {
+                // Explicit stack: generated graphs are far deeper than the
+                // call stack.
+                let mut seen = HashSet::new();
+                for &root in roots {
+                    let mut stack = vec![(root, false)];
+                    while let Some((id, done)) = stack.pop() {
+                        let Some(node) = self.nodes.get(&id) else {
+                            continue;
+                        };
+                        if done {
+                            post(visitor, node);
+                            continue;
+                        }
+                        if !seen.insert(id) {
+                            continue;
+                        }
+                        pre(visitor, node);
+                        stack.push((id, true));
+                        let operands = node.op.as_ref().map(Op::operands).unwrap_or_default();
+                        stack.extend(operands.into_iter().rev().map(|id| (id, false)));
+                    }
+                }
+            }
+            Traversal::BreadthFirst => {
+                let mut seen = HashSet::new();
+                let mut queue: VecDeque<NodeId> = roots
+                    .iter()
+                    .copied()
+                    .filter(|id| seen.insert(*id))
+                    .collect();
+                while let Some(id) = queue.pop_front() {
+                    let Some(node) = self.nodes.get(&id) else {
+                        continue;
+                    };
+                    pre(visitor, node);
+                    post(visitor, node);
+                    for operand in node.op.as_ref().map(Op::operands).unwrap_or_default() {
+                        if seen.insert(operand) {
+                            queue.push_back(operand);
+                        }
+                    }
+                }
+            }
+            Traversal::Topological => {
+                let mut seen = HashSet::new();
+                let mut stack = roots.to_vec();
+                while let Some(id) = stack.pop() {
+                    let Some(node) = self.nodes.get(&id) else {
+                        continue;
+                    };
+                    if seen.insert(id) {
+                        stack.extend(node.op.as_ref().map(Op::operands).unwrap_or_default());
+                    }
+                }
+                let mut ids: Vec<NodeId> = seen.into_iter().collect();
+                self.sort_topologically(&mut ids);
+                for id in ids {
+                    let node = &self.nodes[&id];
+                    pre(visitor, node);
+                    post(visitor, node);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! JavaScript bindings for running the engine in the browser (feature
+//! `wasm`).
+//!
+//! `WasmGraph` is exported with `#[wasm_bindgen]` as the JavaScript class
+//! `Graph`, with camelCase method names. Everything crossing the boundary is a
+//! number, a bool or a string, and errors are thrown as message strings.
+
+use std::collections::HashMap;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::json::Json;
+use crate::{gadgets, Builder, DotOptions, HintRegistry, Node, NodeId};
+
+/// A graph plus the names its nodes are known by in JavaScript.
+#[wasm_bindgen(js_name = Graph)]
+pub struct WasmGraph {
+    builder: Builder,
+    names: HashMap<String, NodeId>,
+}
+
+impl Default for WasmGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(js_class = Graph)]
+impl WasmGraph {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        WasmGraph {
+            builder: Builder::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Parses a graph in the S-expression format. Hints from the `gadgets`
+    /// library are available.
+    #[wasm_bindgen(js_name = fromSexpr)]
+    pub fn from_sexpr(text: &str) -> Result<WasmGraph, String> {
+        let mut hints = HintRegistry::new();
+        gadgets::register_hints(&mut hints);
+        let (builder, names) = Builder::from_sexpr(text, &hints).map_err(|e| e.to_string())?;
+        Ok(WasmGraph { builder, names })
+    }
+
+    pub fn input(&mut self, name: &str) -> u32 {
+        let node = self.builder.init();
+        self.named(name, &node);
+        node.id.0 as u32
+    }
+
+    pub fn constant(&mut self, value: u32) -> u32 {
+        self.builder.constant(value).id.0 as u32
+    }
+
+    pub fn add(&mut self, a: u32, b: u32) -> Result<u32, String> {
+        let (a, b) = (self.node(a)?, self.node(b)?);
+        Ok(self.builder.add(&a, &b).id.0 as u32)
+    }
+
+    pub fn mul(&mut self, a: u32, b: u32) -> Result<u32, String> {
+        let (a, b) = (self.node(a)?, self.node(b)?);
+        Ok(self.builder.mul(&a, &b).id.0 as u32)
+    }
+
+    #[wasm_bindgen(js_name = assertEqual)]
+    pub fn assert_equal(&mut self, a: u32, b: u32) -> Result<(), String> {
+        let (a, b) = (self.node(a)?, self.node(b)?);
+        self.builder.assert_equal(&a, &b);
+        Ok(())
+    }
+
+    /// Declares node `id` as an output called `name`.
+    #[wasm_bindgen(js_name = markOutput)]
+    pub fn mark_output(&mut self, id: u32, name: &str) -> Result<(), String> {
+        let node = self.node(id)?;
+        self.builder.mark_output(&node, name);
+        self.named(name, &node);
+        Ok(())
+    }
+
+    /// Evaluates the graph on a JSON object of input values keyed by name,
+    /// e.g. `{"x": 3}`, and returns the outputs (declared ones, or every sink)
+    /// as a JSON object keyed by name. Unevaluated outputs are `null`.
+    pub fn evaluate(&mut self, inputs: &str) -> Result<String, String> {
+        let json = Json::parse(inputs).map_err(|e| e.to_string())?;
+        let Json::Object(fields) = json else {
+            return Err("inputs must be a JSON object".into());
+        };
+        let mut values = HashMap::new();
+        for (name, value) in fields {
+            let id = *self
+                .names
+                .get(&name)
+                .ok_or_else(|| format!("unknown input `{}`", name))?;
+            let value = value
+                .as_int()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| format!("input `{}` must be a u32", name))?;
+            values.insert(id, value);
+        }
+        self.builder.fill_nodes(values);
+
+        let name_of: HashMap<NodeId, &str> =
+            self.names.iter().map(|(n, id)| (*id, n.as_str())).collect();
+        let outputs = self
+            .builder
+            .output_ids()
+            .into_iter()
+            .map(|id| {
+                let name = match name_of.get(&id) {
+                    Some(name) => name.to_string(),
+                    None => format!("n{}", id.0),
+                };
+                let value = match self.builder.value(id) {
+                    Some(v) => Json::Int(i128::from(v)),
+                    None => Json::Null,
+                };
+                (name, value)
+            })
+            .collect();
+        Ok(Json::Object(outputs).to_pretty_string())
+    }
+
+    pub fn check(&self) -> bool {
+        self.builder.check_constraints()
+    }
+
+    /// The graph in Graphviz DOT format, with values and constraints.
+    #[wasm_bindgen(js_name = toDot)]
+    pub fn to_dot(&self) -> String {
+        let mut out = Vec::new();
+        self.builder
+            .write_dot(&mut out, &DotOptions::annotated())
+            .expect("writing to a Vec cannot fail");
+        String::from_utf8(out).expect("DOT output is UTF-8")
+    }
+}
+
+impl WasmGraph {
+    fn node(&self, id: u32) -> Result<Node, String> {
+        self.builder
+            .handle(NodeId(id as usize))
+            .ok_or_else(|| format!("unknown node {}", id))
+    }
+
+    fn named(&mut self, name: &str, node: &Node) {
+        if !name.is_empty() {
+            self.names.insert(name.to_string(), node.id);
+        }
+    }
+
+    pub fn builder(&self) -> &Builder {
+        &self.builder
+    }
+}
@@ -0,0 +1,97 @@
+//! Reactive evaluation: keep a graph evaluated, feed it input changes and
+//! learn which outputs moved, spreadsheet style.
+
+use crate::collections::HashMap;
+
+use crate::prelude::*;
+use crate::{Builder, InputError, NodeId};
+
+/// An output whose value differs after an update.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputChange {
+    pub node: NodeId,
+    /// The name given with `mark_output`, if the output was declared.
+    pub name: Option<String>,
+    pub before: Option<u32>,
+    pub after: Option<u32>,
+}
+
+/// Owns a graph and its current values. Each `update` re-evaluates only the
+/// nodes downstream of the inputs that changed and reports the outputs
+/// (declared ones, or every sink when none are declared) whose values
+/// changed.
+#[derive(Clone)]
+pub struct Watcher {
+    graph: Builder,
+    outputs: Vec<NodeId>,
+}
+
+impl Watcher {
+    /// Watches `graph` with its current values; inputs without a value stay
+    /// unset until an update provides them.
+    pub fn new(mut graph: Builder) -> Self {
+        graph.fill_nodes(HashMap::new());
+        Watcher {
+            outputs: graph.output_ids(),
+            graph,
+        }
+    }
+
+    /// The watched graph, with the values of the last update.
+    pub fn graph(&self) -> &Builder {
+        &self.graph
+    }
+
+    /// Gives up watching and returns the graph.
+    pub fn into_graph(self) -> Builder {
+        self.graph
+    }
+
+    /// Current value of `node`.
+    pub fn value(&self, node: NodeId) -> Option<u32> {
+        self.graph.value(node)
+    }
+
+    /// Sets new input values and recomputes what depends on them. Returns
+    /// the changed outputs in output order; nothing is changed if any id is
+    /// not an input.
+    pub fn update(
+        &mut self,
+        inputs: HashMap<NodeId, u32>,
+    ) -> Result<Vec<OutputChange>, InputError> {
+        let mut ids: Vec<NodeId> = inputs.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            match self.graph.nodes.get(&id) {
+                None => return Err(InputError::UnknownNode(id)),
+                Some(node) if node.op.is_some() => return Err(InputError::NotAnInput(id)),
+                Some(_) => {}
+            }
+        }
+        let before: Vec<Option<u32>> = self
+            .outputs
+            .iter()
+            .map(|id| self.graph.value(*id))
+            .collect();
+        self.graph.fill_nodes(inputs);
+        Ok(self
+            .outputs
+            .iter()
+            .zip(before)
+            .filter_map(|(id, before)| {
+                let after = self.graph.value(*id);
+                (after != before).then(|| OutputChange {
+                    node: *id,
+                    name: self
+                        .graph
+                        .outputs
+                        .iter()
+                        .find(|(_, o)| o == id)
+                        .map(|(name, _)| name.clone()),
+                    before,
+                    after,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,164 @@
+//! Unsigned integers wider than a node: 64- and 128-bit values held as
+//! 16-bit limbs, with carries propagated by constrained gadgets so sums and
+//! products wrap modulo 2^64 or 2^128 instead of 2^32.
+//!
+//! 16-bit limbs keep every intermediate below 2^32: a limb product plus a
+//! partial sum and a carry is at most `(2^16 - 1)^2 + 2 * (2^16 - 1)`, which
+//! is `2^32 - 1`.
+
+use crate::prelude::*;
+use crate::{gadgets, Builder, Node, NodeId};
+
+/// Bit width of a `WideNode`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    U64,
+    U128,
+}
+
+impl Width {
+    pub fn bits(self) -> u32 {
+        match self {
+            Width::U64 => 64,
+            Width::U128 => 128,
+        }
+    }
+
+    fn limbs(self) -> usize {
+        self.bits() as usize / 16
+    }
+
+    fn mask(self) -> u128 {
+        match self {
+            Width::U64 => u64::MAX as u128,
+            Width::U128 => u128::MAX,
+        }
+    }
+}
+
+/// A wide unsigned value: 16-bit limb nodes, least significant first.
+#[derive(Clone, Debug)]
+pub struct WideNode {
+    width: Width,
+    limbs: Vec<Node>,
+}
+
+impl WideNode {
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    pub fn limbs(&self) -> &[Node] {
+        &self.limbs
+    }
+
+    /// Input assignments giving this value `value` (truncated to the width),
+    /// to extend the map passed to `fill_nodes`.
+    pub fn assign(&self, value: u128) -> Vec<(NodeId, u32)> {
+        let value = value & self.width.mask();
+        self.limbs
+            .iter()
+            .enumerate()
+            .map(|(i, limb)| (limb.id, ((value >> (16 * i)) & 0xffff) as u32))
+            .collect()
+    }
+}
+
+fn assert_same_width(a: &WideNode, b: &WideNode) {
+    assert_eq!(a.width, b.width, "operands have different widths");
+}
+
+impl Builder {
+    /// A wide input. Each limb is range checked to 16 bits.
+    #[track_caller]
+    pub fn wide_input(&mut self, width: Width) -> WideNode {
+        let limbs: Vec<Node> = (0..width.limbs()).map(|_| self.init()).collect();
+        for limb in &limbs {
+            gadgets::range_check(self, limb, 16);
+        }
+        WideNode { width, limbs }
+    }
+
+    /// A wide constant, truncated to `width`.
+    #[track_caller]
+    pub fn wide_constant(&mut self, width: Width, value: u128) -> WideNode {
+        let value = value & width.mask();
+        WideNode {
+            width,
+            limbs: (0..width.limbs())
+                .map(|i| self.constant(((value >> (16 * i)) & 0xffff) as u32))
+                .collect(),
+        }
+    }
+
+    /// `a + b` modulo 2^width.
+    ///
+    /// # Panics
+    /// If the operands have different widths.
+    pub fn wide_add(&mut self, a: &WideNode, b: &WideNode) -> WideNode {
+        assert_same_width(a, b);
+        let mut carry: Option<Node> = None;
+        let mut limbs = Vec::with_capacity(a.limbs.len());
+        for (x, y) in a.limbs.iter().zip(&b.limbs) {
+            let mut sum = self.add(x, y);
+            if let Some(carry) = &carry {
+                sum = self.add(&sum, carry);
+            }
+            let (lo, hi) = gadgets::split16(self, &sum);
+            limbs.push(lo);
+            carry = Some(hi);
+        }
+        WideNode {
+            width: a.width,
+            limbs,
+        }
+    }
+
+    /// `a * b` modulo 2^width, by schoolbook multiplication of the limbs.
+    ///
+    /// # Panics
+    /// If the operands have different widths.
+    pub fn wide_mul(&mut self, a: &WideNode, b: &WideNode) -> WideNode {
+        assert_same_width(a, b);
+        let n = a.limbs.len();
+        let mut result: Vec<Option<Node>> = vec![None; n];
+        for i in 0..n {
+            let mut carry: Option<Node> = None;
+            for j in 0..n - i {
+                let mut t = self.mul(&a.limbs[i], &b.limbs[j]);
+                for extra in [&result[i + j], &carry].into_iter().flatten() {
+                    t = self.add(&t, extra);
+                }
+                let (lo, hi) = gadgets::split16(self, &t);
+                result[i + j] = Some(lo);
+                carry = Some(hi);
+            }
+        }
+        WideNode {
+            width: a.width,
+            limbs: result.into_iter().map(|limb| limb.unwrap()).collect(),
+        }
+    }
+
+    /// Constrains `a` and `b` to be equal limb by limb.
+    ///
+    /// # Panics
+    /// If the operands have different widths.
+    #[track_caller]
+    pub fn assert_wide_equal(&mut self, a: &WideNode, b: &WideNode) {
+        assert_same_width(a, b);
+        for (x, y) in a.limbs.iter().zip(&b.limbs) {
+            self.assert_equal(x, y);
+        }
+    }
+
+    /// The value of `a`, once every limb is evaluated.
+    pub fn wide_value(&self, a: &WideNode) -> Option<u128> {
+        a.limbs
+            .iter()
+            .enumerate()
+            .try_fold(0u128, |acc, (i, limb)| {
+                Some(acc | u128::from(self.value(limb.id)?) << (16 * i))
+            })
+    }
+}
@@ -0,0 +1,460 @@
+//! Witnesses: the values of a filled graph, detached from the builder so they can
+//! be archived, shared and re-checked without re-running hints.
+//!
+//! Two on-disk encodings are supported. JSON is human readable:
+//!
+//! ```text
+//! {
+//!   "fingerprint": "9b4c0e1d6f2a7c35",
+//!   "values": [[0, 3], [1, 9]]
+//! }
+//! ```
+//!
+//! The binary encoding is `GWIT`, a version byte, the graph fingerprint (u64 LE),
+//! the entry count (u64 LE) and then `(node id: u64 LE, value: u32 LE)` pairs.
+//!
+//! The CBOR encoding is a map with the same fields as the JSON one, except that
+//! the fingerprint is an unsigned integer. The protobuf encoding (feature
+//! `protobuf`) is the `Witness` message of `proto/graph.proto`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[cfg(feature = "cbor")]
+use crate::cbor;
+use crate::json::Json;
+#[cfg(feature = "protobuf")]
+use crate::protobuf::{node_id, proto};
+use crate::{Builder, NodeId, Op};
+
+const MAGIC: &[u8; 4] = b"GWIT";
+const BINARY_VERSION: u8 = 1;
+
+/// Encoding used by `Witness::save`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WitnessFormat {
+    Json,
+    Binary,
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+#[derive(Debug)]
+pub enum WitnessError {
+    Io(io::Error),
+    /// The file or buffer is not a valid witness encoding.
+    Format(String),
+    /// The witness was produced for a graph with a different fingerprint.
+    GraphMismatch {
+        expected: u64,
+        found: u64,
+    },
+    /// The witness assigns a value to a node the graph does not contain.
+    UnknownNode(NodeId),
+}
+
+impl fmt::Display for WitnessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessError::Io(e) => write!(f, "witness I/O error: {}", e),
+            WitnessError::Format(msg) => write!(f, "invalid witness: {}", msg),
+            WitnessError::GraphMismatch { expected, found } => write!(
+                f,
+                "witness belongs to graph {:016x}, not {:016x}",
+                found, expected
+            ),
+            WitnessError::UnknownNode(id) => {
+                write!(f, "witness assigns unknown node {:?}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WitnessError {}
+
+impl From<io::Error> for WitnessError {
+    fn from(e: io::Error) -> Self {
+        WitnessError::Io(e)
+    }
+}
+
+/// A witness entry that disagrees with what the graph computes from the
+/// witness's inputs and hint values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WitnessMismatch {
+    pub node: NodeId,
+    /// The value in the witness, `None` if it has none.
+    pub recorded: Option<u32>,
+    /// The value recomputed from the graph, `None` if it cannot be computed.
+    pub computed: Option<u32>,
+}
+
+impl fmt::Display for WitnessMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: Option<u32>| v.map_or("nothing".to_string(), |v| v.to_string());
+        write!(
+            f,
+            "{:?}: witness has {}, graph computes {}",
+            self.node,
+            show(self.recorded),
+            show(self.computed)
+        )
+    }
+}
+
+/// A node whose value differs between two witnesses of the same graph.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WitnessDiff {
+    pub node: NodeId,
+    /// The value in the witness `diff` was called on, `None` if it has none.
+    pub left: Option<u32>,
+    /// The value in the other witness, `None` if it has none.
+    pub right: Option<u32>,
+}
+
+impl fmt::Display for WitnessDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: Option<u32>| v.map_or("nothing".to_string(), |v| v.to_string());
+        write!(
+            f,
+            "{:?}: {} vs {}",
+            self.node,
+            show(self.left),
+            show(self.right)
+        )
+    }
+}
+
+/// The computed values of a graph, tagged with the fingerprint of that graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Witness {
+    fingerprint: u64,
+    values: BTreeMap<NodeId, u32>,
+}
+
+/// The CBOR map of a witness: the JSON fields, with the fingerprint as an
+/// integer.
+#[cfg(feature = "cbor")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CborWitness {
+    fingerprint: u64,
+    values: Vec<(u64, u32)>,
+}
+
+impl Witness {
+    /// Fingerprint of the graph this witness was extracted from.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Value recorded for `node`, if it was evaluated.
+    pub fn get(&self, node: NodeId) -> Option<u32> {
+        self.values.get(&node).copied()
+    }
+
+    /// All recorded `(node, value)` pairs in ascending node order.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, u32)> + '_ {
+        self.values.iter().map(|(id, v)| (*id, *v))
+    }
+
+    /// Recomputes every constant, `add` and `mul` of `graph` from the
+    /// witness's input and hint values and returns the entries that disagree,
+    /// in evaluation order. Hint values are taken as recorded, since they are
+    /// advice the constraints check. Catches corrupted or hand-edited witness
+    /// files before they reach a prover; an empty list means the witness is
+    /// consistent.
+    pub fn verify_against(&self, graph: &Builder) -> Result<Vec<WitnessMismatch>, WitnessError> {
+        let expected = graph.fingerprint();
+        if self.fingerprint != expected {
+            return Err(WitnessError::GraphMismatch {
+                expected,
+                found: self.fingerprint,
+            });
+        }
+        if let Some(id) = self.values.keys().find(|id| !graph.nodes.contains_key(id)) {
+            return Err(WitnessError::UnknownNode(*id));
+        }
+        let mut computed: HashMap<NodeId, Option<u32>> = HashMap::new();
+        let mut mismatches = Vec::new();
+        for node in graph.sorted_nodes() {
+            let recorded = self.get(node.id);
+            let value = match &node.op {
+                None | Some(Op::Hint(..)) => recorded,
+                Some(op) => op.apply(graph.arithmetic, |id| computed.get(&id).copied().flatten()),
+            };
+            if value != recorded {
+                mismatches.push(WitnessMismatch {
+                    node: node.id,
+                    recorded,
+                    computed: value,
+                });
+            }
+            computed.insert(node.id, value);
+        }
+        Ok(mismatches)
+    }
+
+    /// Compares two witnesses of the same graph node by node, including nodes
+    /// only one of them has a value for. Differences are in id order. Operands
+    /// normally have smaller ids than the nodes reading them, making the first
+    /// difference the point where two runs started to diverge, but after
+    /// `replace_node` wires in a newer node that no longer holds; sort by the
+    /// graph's `topo_iter()` order to find the divergence then.
+    pub fn diff(&self, other: &Witness) -> Result<Vec<WitnessDiff>, WitnessError> {
+        if self.fingerprint != other.fingerprint {
+            return Err(WitnessError::GraphMismatch {
+                expected: self.fingerprint,
+                found: other.fingerprint,
+            });
+        }
+        let nodes: BTreeSet<NodeId> = self
+            .values
+            .keys()
+            .chain(other.values.keys())
+            .copied()
+            .collect();
+        Ok(nodes
+            .into_iter()
+            .map(|node| WitnessDiff {
+                node,
+                left: self.get(node),
+                right: other.get(node),
+            })
+            .filter(|d| d.left != d.right)
+            .collect())
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_json_value().to_pretty_string()
+    }
+
+    pub(crate) fn to_json_value(&self) -> Json {
+        let values = self
+            .values
+            .iter()
+            .map(|(id, v)| Json::Array(vec![Json::Int(id.0 as i128), Json::Int(i128::from(*v))]))
+            .collect();
+        Json::Object(vec![
+            (
+                "fingerprint".to_string(),
+                Json::Str(format!("{:016x}", self.fingerprint)),
+            ),
+            ("values".to_string(), Json::Array(values)),
+        ])
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, WitnessError> {
+        let json = Json::parse(text).map_err(|e| WitnessError::Format(e.to_string()))?;
+        let fingerprint = json
+            .get("fingerprint")
+            .and_then(Json::as_str)
+            .and_then(|s| u64::from_str_radix(s, 16).ok())
+            .ok_or_else(|| WitnessError::Format("missing or invalid `fingerprint`".into()))?;
+        let entries = json
+            .get("values")
+            .and_then(Json::as_array)
+            .ok_or_else(|| WitnessError::Format("missing `values` array".into()))?;
+        let mut values = BTreeMap::new();
+        for entry in entries {
+            let pair = entry.as_array().unwrap_or(&[]);
+            let (id, value) = match pair {
+                [id, value] => (id.as_int(), value.as_int()),
+                _ => (None, None),
+            };
+            let id = id.and_then(|id| usize::try_from(id).ok());
+            let value = value.and_then(|v| u32::try_from(v).ok());
+            match (id, value) {
+                (Some(id), Some(value)) => {
+                    values.insert(NodeId(id), value);
+                }
+                _ => {
+                    return Err(WitnessError::Format(
+                        "each value must be a [node id, u32] pair".into(),
+                    ))
+                }
+            }
+        }
+        Ok(Witness {
+            fingerprint,
+            values,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(21 + self.values.len() * 12);
+        out.extend_from_slice(MAGIC);
+        out.push(BINARY_VERSION);
+        out.extend_from_slice(&self.fingerprint.to_le_bytes());
+        out.extend_from_slice(&(self.values.len() as u64).to_le_bytes());
+        for (id, value) in &self.values {
+            out.extend_from_slice(&(id.0 as u64).to_le_bytes());
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WitnessError> {
+        let truncated = || WitnessError::Format("truncated binary witness".into());
+        if bytes.len() < 21 || &bytes[..4] != MAGIC {
+            return Err(WitnessError::Format("missing GWIT header".into()));
+        }
+        if bytes[4] != BINARY_VERSION {
+            return Err(WitnessError::Format(format!(
+                "unsupported binary witness version {}",
+                bytes[4]
+            )));
+        }
+        let u64_at = |pos: usize| -> Result<u64, WitnessError> {
+            let slice = bytes.get(pos..pos + 8).ok_or_else(truncated)?;
+            Ok(u64::from_le_bytes(slice.try_into().unwrap()))
+        };
+        let fingerprint = u64_at(5)?;
+        let count = u64_at(13)? as usize;
+        let mut values = BTreeMap::new();
+        let mut pos = 21;
+        for _ in 0..count {
+            let id = u64_at(pos)? as usize;
+            let slice = bytes.get(pos + 8..pos + 12).ok_or_else(truncated)?;
+            values.insert(NodeId(id), u32::from_le_bytes(slice.try_into().unwrap()));
+            pos += 12;
+        }
+        if pos != bytes.len() {
+            return Err(WitnessError::Format("trailing bytes after witness".into()));
+        }
+        Ok(Witness {
+            fingerprint,
+            values,
+        })
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn to_cbor(&self) -> Vec<u8> {
+        cbor::to_vec(&CborWitness {
+            fingerprint: self.fingerprint,
+            values: self
+                .values
+                .iter()
+                .map(|(id, v)| (id.0 as u64, *v))
+                .collect(),
+        })
+    }
+
+    #[cfg(feature = "cbor")]
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, WitnessError> {
+        let format = |e: crate::DecodeError| WitnessError::Format(e.message);
+        let witness: CborWitness = cbor::from_slice(bytes).map_err(format)?;
+        let values = witness
+            .values
+            .into_iter()
+            .map(|(id, value)| Ok((cbor::node_id(id)?, value)))
+            .collect::<Result<_, _>>()
+            .map_err(format)?;
+        Ok(Witness {
+            fingerprint: witness.fingerprint,
+            values,
+        })
+    }
+
+    /// Encodes the witness as the `Witness` message of `proto/graph.proto`.
+    #[cfg(feature = "protobuf")]
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        use prost::Message;
+        proto::Witness {
+            fingerprint: self.fingerprint,
+            values: self
+                .values
+                .iter()
+                .map(|(id, value)| proto::Value {
+                    node: id.0 as u64,
+                    value: *value,
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+
+    #[cfg(feature = "protobuf")]
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Self, WitnessError> {
+        use prost::Message;
+        let format = |e: crate::DecodeError| WitnessError::Format(e.message);
+        let witness = proto::Witness::decode(bytes).map_err(|e| format(e.into()))?;
+        let values = witness
+            .values
+            .into_iter()
+            .map(|value| Ok((node_id(value.node)?, value.value)))
+            .collect::<Result<_, _>>()
+            .map_err(format)?;
+        Ok(Witness {
+            fingerprint: witness.fingerprint,
+            values,
+        })
+    }
+
+    /// Writes the witness to `path` in the given format.
+    pub fn save(&self, path: impl AsRef<Path>, format: WitnessFormat) -> Result<(), WitnessError> {
+        match format {
+            WitnessFormat::Json => fs::write(path, self.to_json())?,
+            WitnessFormat::Binary => fs::write(path, self.to_bytes())?,
+            #[cfg(feature = "cbor")]
+            WitnessFormat::Cbor => fs::write(path, self.to_cbor())?,
+        }
+        Ok(())
+    }
+
+    /// Reads a witness written by `save`; the format is detected from the contents.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WitnessError> {
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(MAGIC) {
+            return Self::from_bytes(&bytes);
+        }
+        // A two-entry CBOR map; never the first byte of UTF-8 text.
+        #[cfg(feature = "cbor")]
+        if bytes.first() == Some(&0xa2) {
+            return Self::from_cbor(&bytes);
+        }
+        let text = String::from_utf8(bytes)
+            .map_err(|_| WitnessError::Format("neither binary, CBOR nor UTF-8 JSON".into()))?;
+        Self::from_json(&text)
+    }
+}
+
+impl Builder {
+    /// Captures the value of every evaluated node.
+    pub fn witness(&self) -> Witness {
+        Witness {
+            fingerprint: self.fingerprint(),
+            values: self
+                .nodes
+                .values()
+                .filter_map(|n| n.value.map(|v| (n.id, v)))
+                .collect(),
+        }
+    }
+
+    /// Replaces all node values with those stored in `witness`, without running
+    /// any evaluation, so `check_constraints` can re-check an archived run.
+    /// Fails if the witness was produced for a structurally different graph.
+    pub fn apply_witness(&mut self, witness: &Witness) -> Result<(), WitnessError> {
+        let expected = self.fingerprint();
+        if witness.fingerprint != expected {
+            return Err(WitnessError::GraphMismatch {
+                expected,
+                found: witness.fingerprint,
+            });
+        }
+        if let Some(id) = witness
+            .values
+            .keys()
+            .find(|id| !self.nodes.contains_key(id))
+        {
+            return Err(WitnessError::UnknownNode(*id));
+        }
+        for node in self.nodes.values_mut() {
+            node.value = witness.values.get(&node.id).copied();
+        }
+        Ok(())
+    }
+}
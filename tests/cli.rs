@@ -0,0 +1,38 @@
+//! Runs the `graphengine` binary, to check what reaches stdout and stderr
+//! when the process, not a writer, is the boundary.
+
+use std::process::Command;
+
+use succint_graph::Gadget;
+
+#[test]
+fn eval_prints_only_json_to_stdout() {
+    let gadget = Gadget::build("square_plus_one", &["x"], &["y"], |b, ins| {
+        let sq = b.mul(&ins[0], &ins[0]);
+        let one = b.constant(1);
+        vec![b.add(&sq, &one)]
+    });
+    let dir = std::env::temp_dir();
+    let graph = dir.join(format!("{}-cli-bin.graph.json", std::process::id()));
+    let inputs = dir.join(format!("{}-cli-bin.inputs.json", std::process::id()));
+    gadget.save(&graph).unwrap();
+    std::fs::write(&inputs, r#"{"x": 4}"#).unwrap();
+
+    for log in [false, true] {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_graphengine"));
+        command.arg("eval").arg(&graph).arg("--inputs").arg(&inputs);
+        if log {
+            command.env("GRAPHENGINE_LOG", "1");
+        } else {
+            command.env_remove("GRAPHENGINE_LOG");
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        let stdout: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(stdout, serde_json::json!({"y": 17}));
+        assert_eq!(output.stderr.is_empty(), !log);
+    }
+
+    std::fs::remove_file(graph).unwrap();
+    std::fs::remove_file(inputs).unwrap();
+}